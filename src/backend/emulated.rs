@@ -0,0 +1,655 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+use crate::backend::{ContentObject, ContentObjectInfo, ContentObjectIterator, ContentType, DeviceInfo, DeviceOperate, FileWriter, FunctionalCategory, PortableDeviceBackend};
+use crate::common::file_reader::FileReader;
+use crate::common::timestamp::{TimeZone, Timestamp};
+use crate::copy_operate::file_info::FileInfo;
+use crate::copy_operate::local_file_reader::LocalFileReader;
+use crate::copy_operate::local_folder_imp::set_file_times;
+
+// 模拟设备后端，把本地文件夹当作设备的存储
+//
+// manifest 示例:
+// {
+//   "devices": [
+//     { "name": "Phone", "storages": [ { "name": "Internal storage", "path": "phone/internal" } ] }
+//   ]
+// }
+//
+// 存储路径是相对于 manifest 所在的文件夹。
+// 每个存储的根目录下可以放一个 sidecar 文件 (.mtp_attributes.json)，
+// 用来覆盖文件的隐藏、系统、可删除标志和时间，key 是以 '/' 分隔的相对路径:
+// { "DCIM/.thumbnails": { "hidden": true, "can_delete": false } }
+// 时间可以是 Unix 秒数或日期 (如 "2024-01-02 03:04:05")，没有时区的日期按设备的时区解释
+// 重命名和移动时 sidecar 中的属性跟着对象移动，并写回 sidecar 文件
+
+pub const SIDECAR_FILE_NAME: &str = ".mtp_attributes.json";
+
+const ROOT_OBJECT_ID: &str = "";
+const DEVICE_OBJECT_ID: &str = "DEVICE";
+const BUFFER_SIZE: u32 = 32768;
+
+#[derive(Debug, Deserialize)]
+struct Manifest {
+    devices: Vec<ManifestDevice>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestDevice {
+    name: String,
+    #[serde(default)]
+    storages: Vec<ManifestStorage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestStorage {
+    name: String,
+    path: PathBuf,
+}
+
+/// Attributes that override the file attributes
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+struct SidecarAttributes {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hidden: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    can_delete: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_created: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time_modified: Option<String>,
+}
+
+struct EmulatedStorage {
+    name: String,
+    root: PathBuf,
+}
+
+struct EmulatedDeviceConfig {
+    name: String,
+    storages: Vec<EmulatedStorage>,
+}
+
+/// A backend that presents local folders as storages of portable devices.
+pub struct EmulatedBackend {
+    devices: Vec<EmulatedDeviceConfig>,
+    time_zone: TimeZone,
+}
+
+impl EmulatedBackend {
+    /// Loads the manifest file.
+    pub fn from_manifest(manifest_path: &Path) -> Result<EmulatedBackend, Box<dyn std::error::Error>> {
+        let text = std::fs::read_to_string(manifest_path)
+            .map_err(|err| format!("failed to read the manifest {}: {}", manifest_path.display(), err))?;
+        let manifest: Manifest = serde_json::from_str(&text)
+            .map_err(|err| format!("invalid manifest {}: {}", manifest_path.display(), err))?;
+        let base_dir = manifest_path.parent().unwrap_or(Path::new(""));
+
+        let mut devices = Vec::<EmulatedDeviceConfig>::new();
+        for device in manifest.devices {
+            let mut storages = Vec::<EmulatedStorage>::new();
+            for storage in device.storages {
+                let root = base_dir.join(&storage.path);
+                if !root.is_dir() {
+                    return Err(format!("storage folder was not found: {}", root.display()).into());
+                }
+                storages.push(EmulatedStorage {
+                    name: storage.name,
+                    root,
+                });
+            }
+            devices.push(EmulatedDeviceConfig {
+                name: device.name,
+                storages,
+            });
+        }
+        Ok(EmulatedBackend { devices, time_zone: TimeZone::UTC })
+    }
+
+    /// Sets the time zone of the sidecar dates without an offset.
+    pub fn set_time_zone(&mut self, time_zone: TimeZone) {
+        self.time_zone = time_zone;
+    }
+}
+
+impl PortableDeviceBackend for EmulatedBackend {
+    fn list_devices(&self) -> Result<Vec<DeviceInfo>, Box<dyn std::error::Error>> {
+        Ok(self
+            .devices
+            .iter()
+            .enumerate()
+            .map(|(index, device)| DeviceInfo {
+                id: format!("emulated:{}", index),
+                name: device.name.clone(),
+            })
+            .collect())
+    }
+
+    fn open_device(&self, info: &DeviceInfo) -> Result<Box<dyn DeviceOperate>, Box<dyn std::error::Error>> {
+        let config = info
+            .id
+            .strip_prefix("emulated:")
+            .and_then(|s| s.parse::<usize>().ok())
+            .and_then(|index| self.devices.get(index))
+            .ok_or_else(|| format!("failed to open device: {}", &info.name))?;
+
+        let mut storages = Vec::<EmulatedStorageState>::new();
+        for storage in config.storages.iter() {
+            storages.push(EmulatedStorageState {
+                name: storage.name.clone(),
+                root: storage.root.clone(),
+                sidecar: RefCell::new(load_sidecar(&storage.root)?),
+            });
+        }
+        Ok(Box::new(EmulatedDevice {
+            name: config.name.clone(),
+            storages,
+            time_zone: self.time_zone,
+        }))
+    }
+}
+
+fn load_sidecar(root: &Path) -> Result<HashMap<String, SidecarAttributes>, Box<dyn std::error::Error>> {
+    let sidecar_path = root.join(SIDECAR_FILE_NAME);
+    if !sidecar_path.exists() {
+        return Ok(HashMap::new());
+    }
+    let text = std::fs::read_to_string(&sidecar_path)?;
+    let sidecar = serde_json::from_str(&text)
+        .map_err(|err| format!("invalid sidecar file {}: {}", sidecar_path.display(), err))?;
+    Ok(sidecar)
+}
+
+struct EmulatedStorageState {
+    name: String,
+    root: PathBuf,
+    sidecar: RefCell<HashMap<String, SidecarAttributes>>,
+}
+
+impl EmulatedStorageState {
+    // 把相对路径 from 和它下面的对象的属性移到 to 下，有变化时保存 sidecar 文件
+    fn move_attributes(&self, from: &str, to: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut sidecar = self.sidecar.borrow_mut();
+        let prefix = format!("{}/", from);
+        let keys: Vec<String> = sidecar.keys().filter(|key| *key == from || key.starts_with(&prefix)).cloned().collect();
+        if keys.is_empty() {
+            return Ok(());
+        }
+        for key in keys {
+            let attributes = sidecar.remove(&key).unwrap();
+            sidecar.insert(format!("{}{}", to, &key[from.len()..]), attributes);
+        }
+        // 按路径排序写入，方便查看
+        let sorted: BTreeMap<&String, &SidecarAttributes> = sidecar.iter().collect();
+        std::fs::write(self.root.join(SIDECAR_FILE_NAME), serde_json::to_string_pretty(&sorted)?)?;
+        Ok(())
+    }
+}
+
+pub struct EmulatedDevice {
+    name: String,
+    storages: Vec<EmulatedStorageState>,
+    time_zone: TimeZone,
+}
+
+// 对象id: 根对象 ""，设备对象 "DEVICE"，存储和存储下的对象 "<存储序号>:<相对路径>"
+enum ObjectLocation<'a> {
+    Root,
+    Device,
+    Storage(usize, &'a EmulatedStorageState),
+    Entry(usize, &'a EmulatedStorageState, String),
+}
+
+fn make_object_id(storage_index: usize, relative_path: &str) -> ContentObject {
+    ContentObject {
+        id: format!("{}:{}", storage_index, relative_path),
+    }
+}
+
+fn join_relative_path(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+impl EmulatedDevice {
+    // sidecar 中的时间，格式错误时返回错误
+    fn sidecar_time(&self, time: &Option<String>) -> Result<Option<Timestamp>, Box<dyn std::error::Error>> {
+        match time {
+            Some(time) => match Timestamp::parse(time, self.time_zone) {
+                Some(timestamp) => Ok(Some(timestamp)),
+                None => Err(format!("invalid time in the sidecar file: {:?}", time).into()),
+            },
+            None => Ok(None),
+        }
+    }
+
+    fn locate(&self, object: &ContentObject) -> Result<ObjectLocation<'_>, Box<dyn std::error::Error>> {
+        match object.id.as_str() {
+            ROOT_OBJECT_ID => Ok(ObjectLocation::Root),
+            DEVICE_OBJECT_ID => Ok(ObjectLocation::Device),
+            id => {
+                let (index_str, relative_path) = id
+                    .split_once(':')
+                    .ok_or_else(|| format!("invalid object id: {}", id))?;
+                let index = index_str
+                    .parse::<usize>()
+                    .map_err(|_| format!("invalid object id: {}", id))?;
+                let storage = self
+                    .storages
+                    .get(index)
+                    .ok_or_else(|| format!("invalid object id: {}", id))?;
+                if relative_path.is_empty() {
+                    Ok(ObjectLocation::Storage(index, storage))
+                } else {
+                    Ok(ObjectLocation::Entry(index, storage, relative_path.to_string()))
+                }
+            }
+        }
+    }
+
+    // 获取文件夹对象的本地路径和相对路径
+    fn local_folder(&self, object: &ContentObject) -> Result<(usize, PathBuf, String), Box<dyn std::error::Error>> {
+        match self.locate(object)? {
+            ObjectLocation::Storage(index, storage) => Ok((index, storage.root.clone(), String::new())),
+            ObjectLocation::Entry(index, storage, relative_path) => {
+                let path = storage.root.join(&relative_path);
+                if !path.is_dir() {
+                    return Err(format!("not a folder: {}", &relative_path).into());
+                }
+                Ok((index, path, relative_path))
+            }
+            _ => Err(format!("cannot create objects in: {:?}", &object.id).into()),
+        }
+    }
+}
+
+impl DeviceOperate for EmulatedDevice {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_root_object(&self) -> ContentObject {
+        ContentObject::new(ROOT_OBJECT_ID)
+    }
+
+    fn get_object_iterator(&self, parent: &ContentObject) -> Result<Box<dyn ContentObjectIterator + '_>, Box<dyn std::error::Error>> {
+        let mut object_ids = match self.locate(parent)? {
+            ObjectLocation::Root => vec![ContentObject::new(DEVICE_OBJECT_ID)],
+            ObjectLocation::Device => (0..self.storages.len())
+                .map(|index| make_object_id(index, ""))
+                .collect(),
+            ObjectLocation::Storage(index, storage) => list_folder(index, &storage.root, "")?,
+            ObjectLocation::Entry(index, storage, relative_path) => {
+                list_folder(index, &storage.root.join(&relative_path), &relative_path)?
+            }
+        };
+        object_ids.reverse(); // for moving item out by pop()
+        Ok(Box::new(EmulatedObjectIterator { object_ids }))
+    }
+
+    fn get_object_info(&self, object: ContentObject) -> Result<ContentObjectInfo, Box<dyn std::error::Error>> {
+        let functional_object_info = |name: &str, functional_object_category: FunctionalCategory, parent_id: Option<&str>| ContentObjectInfo {
+            content_object: object.clone(),
+            name: name.to_string(),
+            content_type: ContentType::FunctionalObject,
+            functional_object_category,
+            parent_id: parent_id.map(|id| id.to_string()),
+            persistent_id: None,
+            format: None,
+            data_size: 0,
+            is_hidden: false,
+            is_system: false,
+            can_delete: false,
+            time_created: None,
+            time_modified: None,
+            time_authored: None,
+        };
+        match self.locate(&object)? {
+            ObjectLocation::Root => Ok(functional_object_info("", FunctionalCategory::Other, None)),
+            ObjectLocation::Device => Ok(functional_object_info(&self.name, FunctionalCategory::Device, Some(ROOT_OBJECT_ID))),
+            ObjectLocation::Storage(_, storage) => Ok(functional_object_info(&storage.name, FunctionalCategory::Storage, Some(DEVICE_OBJECT_ID))),
+            ObjectLocation::Entry(index, storage, relative_path) => {
+                let name = relative_path.rsplit('/').next().unwrap_or("").to_string();
+                let parent_relative_path = relative_path.rsplit_once('/').map_or("", |(parent, _)| parent);
+                let metadata = storage.root.join(&relative_path).metadata()?;
+                let file_info = FileInfo::from_metadata(&metadata, &name)?;
+                let attributes = storage.sidecar.borrow().get(&relative_path).cloned().unwrap_or_default();
+                Ok(ContentObjectInfo {
+                    content_object: object,
+                    name,
+                    content_type: if file_info.is_folder { ContentType::Folder } else { ContentType::GenericFile },
+                    functional_object_category: FunctionalCategory::None,
+                    parent_id: Some(make_object_id(index, parent_relative_path).id),
+                    persistent_id: None,
+                    format: None,
+                    data_size: file_info.data_size,
+                    is_hidden: attributes.hidden.unwrap_or(file_info.is_hidden),
+                    is_system: attributes.system.unwrap_or(file_info.is_system),
+                    can_delete: attributes.can_delete.unwrap_or(file_info.can_delete),
+                    time_created: self.sidecar_time(&attributes.time_created)?.or(file_info.time_created),
+                    time_modified: self.sidecar_time(&attributes.time_modified)?.or(file_info.time_modified),
+                    time_authored: None,
+                })
+            }
+        }
+    }
+
+    fn get_resoure(&self, object: &ContentObject) -> Result<Box<dyn FileReader + '_>, Box<dyn std::error::Error>> {
+        match self.locate(object)? {
+            ObjectLocation::Entry(_, storage, relative_path) => {
+                let file = File::open(storage.root.join(&relative_path))?;
+                Ok(Box::new(LocalFileReader::new(file)))
+            }
+            _ => Err(format!("object has no data: {:?}", &object.id).into()),
+        }
+    }
+
+    fn create_file(
+        &self,
+        parent: &ContentObject,
+        name: &str,
+        size: u64,
+        created: &Option<Timestamp>,
+        modified: &Option<Timestamp>,
+    ) -> Result<Box<dyn FileWriter + '_>, Box<dyn std::error::Error>> {
+        let (index, folder_path, relative_path) = self.local_folder(parent)?;
+        let path = folder_path.join(name);
+        let file = OpenOptions::new().write(true).create_new(true).open(&path)?;
+        Ok(Box::new(EmulatedFileWriter {
+            file: Some(file),
+            path,
+            content_object: make_object_id(index, &join_relative_path(&relative_path, name)),
+            size,
+            written: 0,
+            created: created.map(|time| time.to_system_time()),
+            modified: modified.map(|time| time.to_system_time()),
+        }))
+    }
+
+    fn create_folder(&self, parent: &ContentObject, name: &str) -> Result<ContentObject, Box<dyn std::error::Error>> {
+        let (index, folder_path, relative_path) = self.local_folder(parent)?;
+        std::fs::create_dir(folder_path.join(name))?;
+        Ok(make_object_id(index, &join_relative_path(&relative_path, name)))
+    }
+
+    fn delete(&self, object: &ContentObject) -> Result<(), Box<dyn std::error::Error>> {
+        match self.locate(object)? {
+            ObjectLocation::Entry(_, storage, relative_path) => {
+                if storage.sidecar.borrow().get(&relative_path).and_then(|a| a.can_delete) == Some(false) {
+                    return Err(format!("object cannot be deleted: {}", &relative_path).into());
+                }
+                let path = storage.root.join(&relative_path);
+                if path.is_dir() {
+                    std::fs::remove_dir_all(path)?;
+                } else {
+                    std::fs::remove_file(path)?;
+                }
+                Ok(())
+            }
+            _ => Err(format!("object cannot be deleted: {:?}", &object.id).into()),
+        }
+    }
+
+    fn rename(&self, object: &ContentObject, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match self.locate(object)? {
+            ObjectLocation::Entry(_, storage, relative_path) => {
+                let path = storage.root.join(&relative_path);
+                rename_entry(&path, &path.with_file_name(name))?;
+                let new_relative_path = match relative_path.rsplit_once('/') {
+                    Some((parent, _)) => join_relative_path(parent, name),
+                    None => name.to_string(),
+                };
+                storage.move_attributes(&relative_path, &new_relative_path)
+            }
+            _ => Err(format!("object cannot be renamed: {:?}", &object.id).into()),
+        }
+    }
+
+    // 只能在同一个存储内移动
+    fn move_object(&self, object: &ContentObject, parent: &ContentObject) -> Result<bool, Box<dyn std::error::Error>> {
+        let (index, storage, relative_path) = match self.locate(object)? {
+            ObjectLocation::Entry(index, storage, relative_path) => (index, storage, relative_path),
+            _ => return Err(format!("object cannot be moved: {:?}", &object.id).into()),
+        };
+        let (parent_index, folder_path, parent_relative_path) = self.local_folder(parent)?;
+        if parent_index != index {
+            return Ok(false);
+        }
+        if parent_relative_path == relative_path || parent_relative_path.starts_with(&format!("{}/", relative_path)) {
+            return Err(format!("cannot move a folder into itself: {}", &relative_path).into());
+        }
+        let name = relative_path.rsplit('/').next().unwrap_or("");
+        rename_entry(&storage.root.join(&relative_path), &folder_path.join(name))?;
+        storage.move_attributes(&relative_path, &join_relative_path(&parent_relative_path, name))?;
+        Ok(true)
+    }
+}
+
+// 重命名或移动本地文件，不覆盖已有的文件
+fn rename_entry(from: &Path, to: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    if to.symlink_metadata().is_ok() {
+        return Err(format!("already exists: {}", to.display()).into());
+    }
+    std::fs::rename(from, to)?;
+    Ok(())
+}
+
+// 列出文件夹下的对象，按名称排序，跳过 sidecar 文件
+fn list_folder(storage_index: usize, folder_path: &Path, relative_path: &str) -> Result<Vec<ContentObject>, Box<dyn std::error::Error>> {
+    let mut names = Vec::<String>::new();
+    for entry_result in folder_path.read_dir()? {
+        let entry = entry_result?;
+        if let Some(name) = entry.file_name().to_str() {
+            if relative_path.is_empty() && name == SIDECAR_FILE_NAME {
+                continue;
+            }
+            names.push(name.to_string());
+        }
+    }
+    names.sort();
+    Ok(names
+        .iter()
+        .map(|name| make_object_id(storage_index, &join_relative_path(relative_path, name)))
+        .collect())
+}
+
+pub struct EmulatedObjectIterator {
+    object_ids: Vec<ContentObject>,
+}
+
+impl ContentObjectIterator for EmulatedObjectIterator {
+    fn next(&mut self) -> Result<Option<ContentObject>, Box<dyn std::error::Error>> {
+        Ok(self.object_ids.pop())
+    }
+}
+
+pub struct EmulatedFileWriter {
+    file: Option<File>,
+    path: PathBuf,
+    content_object: ContentObject,
+    size: u64,
+    written: u64,
+    // 提交后设置的时间
+    created: Option<SystemTime>,
+    modified: Option<SystemTime>,
+}
+
+impl FileWriter for EmulatedFileWriter {
+    fn get_buffer_size(&self) -> u32 {
+        BUFFER_SIZE
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let file = self.file.as_mut().ok_or("the file has already been committed.")?;
+        file.write_all(data)?;
+        self.written += data.len() as u64;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<ContentObject, Box<dyn std::error::Error>> {
+        let file = self.file.take().ok_or("the file has already been committed.")?;
+        drop(file);
+        if self.written != self.size {
+            let _ = std::fs::remove_file(&self.path);
+            return Err(format!(
+                "size mismatch: {} bytes were written, {} bytes were expected",
+                self.written, self.size
+            )
+            .into());
+        }
+        set_file_times(&self.path, &self.created, &self.modified)?;
+        Ok(self.content_object.clone())
+    }
+}
+
+impl Drop for EmulatedFileWriter {
+    fn drop(&mut self) {
+        // 没有提交的文件不会留在设备上
+        if self.file.take().is_some() {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::copy::copy;
+    use crate::find::find_file_or_folder;
+    use crate::path::DeviceStoragePath;
+    use crate::copy_operate::CopyOptions;
+
+    fn create_manifest(dir: &Path) -> PathBuf {
+        std::fs::create_dir_all(dir.join("phone/internal/DCIM/.thumbnails")).unwrap();
+        std::fs::create_dir_all(dir.join("phone/sd")).unwrap();
+        std::fs::write(dir.join("phone/internal/DCIM/a.jpg"), "aaa").unwrap();
+        std::fs::write(dir.join("phone/internal/DCIM/b.jpg"), "bb").unwrap();
+        std::fs::write(
+            dir.join("phone/internal").join(SIDECAR_FILE_NAME),
+            r#"{ "DCIM/b.jpg": { "system": true, "can_delete": false, "time_modified": "1600000000" } }"#,
+        ).unwrap();
+        let manifest_path = dir.join("manifest.json");
+        std::fs::write(
+            &manifest_path,
+            r#"{ "devices": [ { "name": "Phone", "storages": [
+                { "name": "Internal storage", "path": "phone/internal" },
+                { "name": "SD card", "path": "phone/sd" } ] } ] }"#,
+        ).unwrap();
+        manifest_path
+    }
+
+    fn find(backend: &EmulatedBackend, path: &str) -> Option<ContentObjectInfo> {
+        let storage_path = DeviceStoragePath::from(path).unwrap();
+        find_file_or_folder(backend, &storage_path).unwrap().map(|(_, _, info)| info)
+    }
+
+    #[test]
+    fn test_invalid_manifest() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let manifest_path = tempdir.path().join("manifest.json");
+        assert!(EmulatedBackend::from_manifest(&manifest_path).is_err());
+        std::fs::write(&manifest_path, r#"{ "devices": [ { "name": "Phone", "storages": [ { "name": "a", "path": "missing" } ] } ] }"#).unwrap();
+        assert!(EmulatedBackend::from_manifest(&manifest_path).is_err());
+    }
+
+    #[test]
+    fn test_find_file_or_folder() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let backend = EmulatedBackend::from_manifest(&create_manifest(tempdir.path())).unwrap();
+
+        let storage = find(&backend, "Phone:SD card:").unwrap();
+        assert!(storage.is_storage());
+        let folder = find(&backend, "Phone:Internal storage:/DCIM").unwrap();
+        assert!(folder.is_folder());
+        let file = find(&backend, "Phone:Internal storage:/DCIM/a.jpg").unwrap();
+        assert!(file.is_file());
+        assert_eq!(file.data_size, 3);
+        assert!(find(&backend, "Phone:Internal storage:/DCIM/c.jpg").is_none());
+        assert!(find(&backend, &format!("Phone:Internal storage:/{}", SIDECAR_FILE_NAME)).is_none());
+    }
+
+    #[test]
+    fn test_attributes() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let backend = EmulatedBackend::from_manifest(&create_manifest(tempdir.path())).unwrap();
+
+        let a = find(&backend, "Phone:Internal storage:/DCIM/a.jpg").unwrap();
+        assert!(!a.is_hidden && !a.is_system && a.can_delete);
+        assert!(a.time_modified.is_some());
+
+        let b = find(&backend, "Phone:Internal storage:/DCIM/b.jpg").unwrap();
+        assert!(!b.is_hidden && b.is_system && !b.can_delete);
+        assert_eq!(b.time_modified, Some(Timestamp::from_unix_seconds(1600000000)));
+    }
+
+    #[test]
+    fn test_copy_through_emulated_device() -> Result<(), Box<dyn std::error::Error>> {
+        let tempdir = tempfile::tempdir()?;
+        let backend = EmulatedBackend::from_manifest(&create_manifest(tempdir.path()))?;
+
+        // device -> device: 系统文件和隐藏文件不会被复制
+        copy(&backend, "Phone:Internal storage:/DCIM", "Phone:SD card:/", &CopyOptions { recursive: true, ..Default::default() })?;
+        assert_eq!(std::fs::read(tempdir.path().join("phone/sd/DCIM/a.jpg"))?, b"aaa");
+        assert!(!tempdir.path().join("phone/sd/DCIM/b.jpg").exists());
+
+        // local -> device
+        let src = tempdir.path().join("local.txt");
+        std::fs::write(&src, "local")?;
+        copy(&backend, src.to_str().unwrap(), "Phone:SD card:/DCIM/local.txt", &CopyOptions::default())?;
+        assert_eq!(std::fs::read(tempdir.path().join("phone/sd/DCIM/local.txt"))?, b"local");
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_refused_by_sidecar() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let backend = EmulatedBackend::from_manifest(&create_manifest(tempdir.path())).unwrap();
+        let device_info = backend.list_devices().unwrap().remove(0);
+        let device = backend.open_device(&device_info).unwrap();
+
+        let b = find(&backend, "Phone:Internal storage:/DCIM/b.jpg").unwrap();
+        assert!(device.delete(&b.content_object).is_err());
+        let a = find(&backend, "Phone:Internal storage:/DCIM/a.jpg").unwrap();
+        device.delete(&a.content_object).unwrap();
+        assert!(!tempdir.path().join("phone/internal/DCIM/a.jpg").exists());
+    }
+
+    #[test]
+    fn test_rename_and_move_keep_attributes() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let backend = EmulatedBackend::from_manifest(&create_manifest(tempdir.path())).unwrap();
+        std::fs::create_dir(tempdir.path().join("phone/internal/Pictures")).unwrap();
+        let device_info = backend.list_devices().unwrap().remove(0);
+        let device = backend.open_device(&device_info).unwrap();
+
+        let b = find(&backend, "Phone:Internal storage:/DCIM/b.jpg").unwrap();
+        device.rename(&b.content_object, "c.jpg").unwrap();
+        let folder = find(&backend, "Phone:Internal storage:/DCIM").unwrap();
+        let parent = find(&backend, "Phone:Internal storage:/Pictures").unwrap();
+        assert!(device.move_object(&folder.content_object, &parent.content_object).unwrap());
+
+        // 重新打开设备，属性从 sidecar 文件读取
+        let c = find(&backend, "Phone:Internal storage:/Pictures/DCIM/c.jpg").unwrap();
+        assert!(c.is_system && !c.can_delete);
+        assert_eq!(c.time_modified, Some(Timestamp::from_unix_seconds(1600000000)));
+        let sidecar = std::fs::read_to_string(tempdir.path().join("phone/internal").join(SIDECAR_FILE_NAME)).unwrap();
+        let sidecar: HashMap<String, SidecarAttributes> = serde_json::from_str(&sidecar).unwrap();
+        assert_eq!(sidecar.keys().collect::<Vec<_>>(), vec!["Pictures/DCIM/c.jpg"]);
+
+        // 移动回去
+        let folder = find(&backend, "Phone:Internal storage:/Pictures/DCIM").unwrap();
+        let storage = find(&backend, "Phone:Internal storage:").unwrap();
+        assert!(device.move_object(&folder.content_object, &storage.content_object).unwrap());
+        assert!(!find(&backend, "Phone:Internal storage:/DCIM/c.jpg").unwrap().can_delete);
+    }
+}
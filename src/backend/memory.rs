@@ -0,0 +1,647 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Arc, Mutex, MutexGuard};
+use crate::backend::{ContentObject, ContentObjectInfo, ContentObjectIterator, ContentType, DeviceInfo, DeviceOperate, FileWriter, FunctionalCategory, PortableDeviceBackend};
+use crate::common::file_reader::FileReader;
+use crate::common::timestamp::Timestamp;
+use crate::error::MtpError;
+use crate::path::{DeviceStoragePath, SEPARATORS};
+
+// 内存设备后端，所有的设备、存储、文件夹和文件都保存在内存中，用于测试
+
+const ROOT_OBJECT_ID: &str = "";
+const DEVICE_OBJECT_ID: &str = "DEVICE";
+const BUFFER_SIZE: u32 = 4096;
+
+/// An object stored in a memory device
+#[derive(Debug, Clone)]
+pub struct MemoryObject {
+    /// Name to display
+    pub name: String,
+    pub content_type: ContentType,
+    pub functional_object_category: FunctionalCategory,
+    /// Id of the parent object
+    pub parent_id: String,
+    /// File content
+    pub data: Vec<u8>,
+    /// Hidden flag
+    pub is_hidden: bool,
+    /// System flag
+    pub is_system: bool,
+    /// Whether the object can be deleted
+    pub can_delete: bool,
+    /// Time created (or None if not provided)
+    pub time_created: Option<Timestamp>,
+    /// Time modified (or None if not provided)
+    pub time_modified: Option<Timestamp>,
+    children: Vec<String>,
+}
+
+impl MemoryObject {
+    fn new(name: &str, content_type: ContentType, functional_object_category: FunctionalCategory, parent_id: &str) -> MemoryObject {
+        MemoryObject {
+            name: name.to_string(),
+            content_type,
+            functional_object_category,
+            parent_id: parent_id.to_string(),
+            data: Vec::new(),
+            is_hidden: false,
+            is_system: false,
+            can_delete: true,
+            time_created: None,
+            time_modified: None,
+            children: Vec::new(),
+        }
+    }
+}
+
+struct MemoryDeviceState {
+    name: String,
+    objects: HashMap<String, MemoryObject>,
+}
+
+struct MemoryState {
+    devices: Vec<MemoryDeviceState>,
+    next_id: u64,
+    // 为 true 时写文件失败，模拟设备断开
+    disconnected: bool,
+    // 为 true 时 move_object 返回 false，模拟不支持移动的设备
+    move_disabled: bool,
+}
+
+impl MemoryState {
+    fn new_object_id(&mut self) -> String {
+        self.next_id += 1;
+        format!("o{}", self.next_id)
+    }
+
+    fn device_index(&self, device_name: &str) -> Option<usize> {
+        self.devices.iter().position(|d| d.name == device_name)
+    }
+
+    fn insert_object(&mut self, device_index: usize, object: MemoryObject) -> String {
+        let id = self.new_object_id();
+        let device = &mut self.devices[device_index];
+        if let Some(parent) = device.objects.get_mut(&object.parent_id) {
+            parent.children.push(id.clone());
+        }
+        device.objects.insert(id.clone(), object);
+        id
+    }
+
+    fn remove_object(&mut self, device_index: usize, id: &str) {
+        let device = &mut self.devices[device_index];
+        if let Some(object) = device.objects.remove(id) {
+            if let Some(parent) = device.objects.get_mut(&object.parent_id) {
+                parent.children.retain(|child_id| child_id != id);
+            }
+            for child_id in object.children {
+                self.remove_object(device_index, &child_id);
+            }
+        }
+    }
+
+    fn move_object(&mut self, device_index: usize, id: &str, parent_id: &str) {
+        let device = &mut self.devices[device_index];
+        let old_parent_id = std::mem::replace(&mut device.objects.get_mut(id).unwrap().parent_id, parent_id.to_string());
+        if let Some(old_parent) = device.objects.get_mut(&old_parent_id) {
+            old_parent.children.retain(|child_id| child_id != id);
+        }
+        if let Some(parent) = device.objects.get_mut(parent_id) {
+            parent.children.push(id.to_string());
+        }
+    }
+
+    fn find_child(&self, device_index: usize, parent_id: &str, name: &str) -> Option<String> {
+        let objects = &self.devices[device_index].objects;
+        objects.get(parent_id)?
+            .children
+            .iter()
+            .find(|id| objects.get(*id).is_some_and(|o| o.name == name))
+            .cloned()
+    }
+
+    // 解析 "设备名:存储名:路径"，返回设备序号和对象id
+    fn resolve(&self, path: &str) -> Result<Option<(usize, String)>, Box<dyn std::error::Error>> {
+        let storage_path = DeviceStoragePath::from(path)?;
+        let device_index = match self.device_index(&storage_path.device_name) {
+            Some(index) => index,
+            None => return Ok(None),
+        };
+        let mut id = match self.find_child(device_index, DEVICE_OBJECT_ID, &storage_path.storage_name) {
+            Some(id) => id,
+            None => return Ok(None),
+        };
+        for name in storage_path.path.split(SEPARATORS).filter(|s| !s.is_empty()) {
+            id = match self.find_child(device_index, &id, name) {
+                Some(id) => id,
+                None => return Ok(None),
+            };
+        }
+        Ok(Some((device_index, id)))
+    }
+}
+
+/// A backend that keeps devices, storages, folders and files in memory.
+///
+/// Cloning the backend shares the same devices.
+#[derive(Clone)]
+pub struct MemoryBackend {
+    state: Arc<Mutex<MemoryState>>,
+}
+
+impl Default for MemoryBackend {
+    fn default() -> Self {
+        MemoryBackend::new()
+    }
+}
+
+impl MemoryBackend {
+    pub fn new() -> MemoryBackend {
+        MemoryBackend {
+            state: Arc::new(Mutex::new(MemoryState {
+                devices: Vec::new(),
+                next_id: 0,
+                disconnected: false,
+                move_disabled: false,
+            })),
+        }
+    }
+
+    /// Creates a backend with one device that has one storage, the fixture of the tests.
+    #[cfg(test)]
+    pub fn with_storage(device_name: &str, storage_name: &str) -> MemoryBackend {
+        let backend = MemoryBackend::new();
+        backend.add_device(device_name);
+        backend.add_storage(device_name, storage_name).unwrap();
+        backend
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MemoryState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Adds a device that has no storages.
+    pub fn add_device(&self, name: &str) {
+        let mut objects = HashMap::<String, MemoryObject>::new();
+        let mut root = MemoryObject::new("", ContentType::FunctionalObject, FunctionalCategory::Other, ROOT_OBJECT_ID);
+        root.children.push(DEVICE_OBJECT_ID.to_string());
+        objects.insert(ROOT_OBJECT_ID.to_string(), root);
+        objects.insert(
+            DEVICE_OBJECT_ID.to_string(),
+            MemoryObject::new(name, ContentType::FunctionalObject, FunctionalCategory::Device, ROOT_OBJECT_ID),
+        );
+        self.lock().devices.push(MemoryDeviceState {
+            name: name.to_string(),
+            objects,
+        });
+    }
+
+    /// Adds a storage to the device.
+    pub fn add_storage(&self, device_name: &str, storage_name: &str) -> Result<ContentObject, Box<dyn std::error::Error>> {
+        let mut state = self.lock();
+        let device_index = state
+            .device_index(device_name)
+            .ok_or_else(|| format!("device was not found: {}", device_name))?;
+        let mut storage = MemoryObject::new(storage_name, ContentType::FunctionalObject, FunctionalCategory::Storage, DEVICE_OBJECT_ID);
+        storage.can_delete = false;
+        let id = state.insert_object(device_index, storage);
+        Ok(ContentObject { id })
+    }
+
+    /// Adds a folder at the path "device:storage:path", creating missing parent folders.
+    pub fn add_folder(&self, path: &str) -> Result<ContentObject, Box<dyn std::error::Error>> {
+        let storage_path = DeviceStoragePath::from(path)?;
+        let mut state = self.lock();
+        let (device_index, storage_id) = state
+            .resolve(&format!("{}:{}:", &storage_path.device_name, &storage_path.storage_name))?
+            .ok_or_else(|| format!("storage was not found: {}", path))?;
+        let mut id = storage_id;
+        for name in storage_path.path.split(SEPARATORS).filter(|s| !s.is_empty()) {
+            id = match state.find_child(device_index, &id, name) {
+                Some(child_id) => child_id,
+                None => state.insert_object(
+                    device_index,
+                    MemoryObject::new(name, ContentType::Folder, FunctionalCategory::None, &id),
+                ),
+            };
+        }
+        Ok(ContentObject { id })
+    }
+
+    /// Adds a file at the path "device:storage:path", creating missing parent folders.
+    pub fn add_file(&self, path: &str, data: &[u8]) -> Result<ContentObject, Box<dyn std::error::Error>> {
+        let storage_path = DeviceStoragePath::from(path)?;
+        let name = storage_path.file_name().ok_or_else(|| format!("invalid file path: {}", path))?;
+        let parent = self.add_folder(&storage_path.parent().unwrap().full_path())?;
+        let mut state = self.lock();
+        let device_index = state.device_index(&storage_path.device_name).unwrap();
+        let mut object = MemoryObject::new(name, ContentType::GenericFile, FunctionalCategory::None, &parent.id);
+        object.data = data.to_vec();
+        let id = state.insert_object(device_index, object);
+        Ok(ContentObject { id })
+    }
+
+    /// Makes the following file writes fail as if the devices were unplugged.
+    pub fn disconnect(&self) {
+        self.lock().disconnected = true;
+    }
+
+    /// Makes the devices report that they cannot move objects.
+    pub fn disable_move(&self) {
+        self.lock().move_disabled = true;
+    }
+
+    /// Returns a copy of the object at the path "device:storage:path".
+    pub fn get_object(&self, path: &str) -> Option<MemoryObject> {
+        let state = self.lock();
+        let (device_index, id) = state.resolve(path).ok()??;
+        state.devices[device_index].objects.get(&id).cloned()
+    }
+
+    /// Modifies the object at the path "device:storage:path".
+    pub fn update_object<F>(&self, path: &str, f: F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&mut MemoryObject),
+    {
+        let mut state = self.lock();
+        let (device_index, id) = state
+            .resolve(path)?
+            .ok_or_else(|| format!("object was not found: {}", path))?;
+        f(state.devices[device_index].objects.get_mut(&id).unwrap());
+        Ok(())
+    }
+
+    /// Returns the sorted names of the children of the folder at the path "device:storage:path".
+    pub fn list_names(&self, path: &str) -> Option<Vec<String>> {
+        let state = self.lock();
+        let (device_index, id) = state.resolve(path).ok()??;
+        let objects = &state.devices[device_index].objects;
+        let mut names: Vec<String> = objects
+            .get(&id)?
+            .children
+            .iter()
+            .filter_map(|child_id| objects.get(child_id).map(|o| o.name.clone()))
+            .collect();
+        names.sort();
+        Some(names)
+    }
+}
+
+impl PortableDeviceBackend for MemoryBackend {
+    fn list_devices(&self) -> Result<Vec<DeviceInfo>, Box<dyn std::error::Error>> {
+        Ok(self
+            .lock()
+            .devices
+            .iter()
+            .enumerate()
+            .map(|(index, device)| DeviceInfo {
+                id: format!("memory:{}", index),
+                name: device.name.clone(),
+            })
+            .collect())
+    }
+
+    fn open_device(&self, info: &DeviceInfo) -> Result<Box<dyn DeviceOperate>, Box<dyn std::error::Error>> {
+        let index = info
+            .id
+            .strip_prefix("memory:")
+            .and_then(|s| s.parse::<usize>().ok())
+            .filter(|index| *index < self.lock().devices.len())
+            .ok_or_else(|| format!("failed to open device: {}", &info.name))?;
+        Ok(Box::new(MemoryDevice {
+            state: self.state.clone(),
+            index,
+            name: info.name.clone(),
+        }))
+    }
+}
+
+pub struct MemoryDevice {
+    state: Arc<Mutex<MemoryState>>,
+    index: usize,
+    name: String,
+}
+
+impl MemoryDevice {
+    fn lock(&self) -> MutexGuard<'_, MemoryState> {
+        self.state.lock().unwrap()
+    }
+
+    fn get_object(&self, object: &ContentObject) -> Result<MemoryObject, Box<dyn std::error::Error>> {
+        self.lock().devices[self.index]
+            .objects
+            .get(&object.id)
+            .cloned()
+            .ok_or_else(|| format!("object was not found: {}", &object.id).into())
+    }
+}
+
+impl DeviceOperate for MemoryDevice {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_root_object(&self) -> ContentObject {
+        ContentObject::new(ROOT_OBJECT_ID)
+    }
+
+    fn get_object_iterator(&self, parent: &ContentObject) -> Result<Box<dyn ContentObjectIterator + '_>, Box<dyn std::error::Error>> {
+        let mut object_ids = self.get_object(parent)?.children;
+        object_ids.reverse(); // for moving item out by pop()
+        Ok(Box::new(MemoryObjectIterator { object_ids }))
+    }
+
+    fn get_object_info(&self, object: ContentObject) -> Result<ContentObjectInfo, Box<dyn std::error::Error>> {
+        let memory_object = self.get_object(&object)?;
+        let parent_id = if object.id == ROOT_OBJECT_ID { None } else { Some(memory_object.parent_id) };
+        Ok(ContentObjectInfo {
+            content_object: object,
+            name: memory_object.name,
+            content_type: memory_object.content_type,
+            functional_object_category: memory_object.functional_object_category,
+            parent_id,
+            persistent_id: None,
+            format: None,
+            data_size: memory_object.data.len() as u64,
+            is_hidden: memory_object.is_hidden,
+            is_system: memory_object.is_system,
+            can_delete: memory_object.can_delete,
+            time_created: memory_object.time_created,
+            time_modified: memory_object.time_modified,
+            time_authored: None,
+        })
+    }
+
+    fn get_resoure(&self, object: &ContentObject) -> Result<Box<dyn FileReader + '_>, Box<dyn std::error::Error>> {
+        let memory_object = self.get_object(object)?;
+        if memory_object.content_type == ContentType::Folder || memory_object.content_type == ContentType::FunctionalObject {
+            return Err(format!("object has no data: {}", &memory_object.name).into());
+        }
+        Ok(Box::new(MemoryFileReader {
+            data: memory_object.data,
+            offset: 0,
+        }))
+    }
+
+    fn create_file(
+        &self,
+        parent: &ContentObject,
+        name: &str,
+        size: u64,
+        created: &Option<Timestamp>,
+        modified: &Option<Timestamp>,
+    ) -> Result<Box<dyn FileWriter + '_>, Box<dyn std::error::Error>> {
+        self.get_object(parent)?;
+        let mut object = MemoryObject::new(name, ContentType::GenericFile, FunctionalCategory::None, &parent.id);
+        object.time_created = *created;
+        object.time_modified = *modified;
+        Ok(Box::new(MemoryFileWriter {
+            device: self,
+            object: Some(object),
+            size,
+        }))
+    }
+
+    fn create_folder(&self, parent: &ContentObject, name: &str) -> Result<ContentObject, Box<dyn std::error::Error>> {
+        self.get_object(parent)?;
+        let id = self.lock().insert_object(
+            self.index,
+            MemoryObject::new(name, ContentType::Folder, FunctionalCategory::None, &parent.id),
+        );
+        Ok(ContentObject { id })
+    }
+
+    fn delete(&self, object: &ContentObject) -> Result<(), Box<dyn std::error::Error>> {
+        let memory_object = self.get_object(object)?;
+        if !memory_object.can_delete {
+            return Err(format!("object cannot be deleted: {}", &memory_object.name).into());
+        }
+        self.lock().remove_object(self.index, &object.id);
+        Ok(())
+    }
+
+    fn rename(&self, object: &ContentObject, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let memory_object = self.get_object(object)?;
+        if memory_object.content_type == ContentType::FunctionalObject {
+            return Err(format!("object cannot be renamed: {}", &memory_object.name).into());
+        }
+        let mut state = self.lock();
+        state.devices[self.index].objects.get_mut(&object.id).unwrap().name = name.to_string();
+        Ok(())
+    }
+
+    fn move_object(&self, object: &ContentObject, parent: &ContentObject) -> Result<bool, Box<dyn std::error::Error>> {
+        let memory_object = self.get_object(object)?;
+        if memory_object.content_type == ContentType::FunctionalObject {
+            return Err(format!("object cannot be moved: {}", &memory_object.name).into());
+        }
+        let parent_object = self.get_object(parent)?;
+        if parent_object.content_type == ContentType::GenericFile || parent.id == ROOT_OBJECT_ID || parent.id == DEVICE_OBJECT_ID {
+            return Err(format!("cannot move objects to: {}", &parent_object.name).into());
+        }
+        let mut state = self.lock();
+        if state.move_disabled {
+            return Ok(false);
+        }
+        // 不能移动到自己或自己的子文件夹下
+        let mut ancestor_id = parent.id.clone();
+        while ancestor_id != ROOT_OBJECT_ID {
+            if ancestor_id == object.id {
+                return Err(format!("cannot move a folder into itself: {}", &memory_object.name).into());
+            }
+            ancestor_id = state.devices[self.index].objects[&ancestor_id].parent_id.clone();
+        }
+        state.move_object(self.index, &object.id, &parent.id);
+        Ok(true)
+    }
+}
+
+pub struct MemoryObjectIterator {
+    object_ids: Vec<String>,
+}
+
+impl ContentObjectIterator for MemoryObjectIterator {
+    fn next(&mut self) -> Result<Option<ContentObject>, Box<dyn std::error::Error>> {
+        Ok(self.object_ids.pop().map(|id| ContentObject { id }))
+    }
+}
+
+pub struct MemoryFileReader {
+    data: Vec<u8>,
+    offset: usize,
+}
+
+impl Read for MemoryFileReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = (&self.data[self.offset..]).read(buf)?;
+        self.offset += len;
+        Ok(len)
+    }
+}
+
+impl FileReader for MemoryFileReader {
+    fn buffer_size(&self) -> u32 {
+        BUFFER_SIZE
+    }
+}
+
+pub struct MemoryFileWriter<'d> {
+    device: &'d MemoryDevice,
+    object: Option<MemoryObject>,
+    size: u64,
+}
+
+impl<'d> FileWriter for MemoryFileWriter<'d> {
+    fn get_buffer_size(&self) -> u32 {
+        BUFFER_SIZE
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        if self.device.lock().disconnected {
+            return Err(MtpError::Disconnected(self.device.name.clone()).into());
+        }
+        match self.object.as_mut() {
+            Some(object) => {
+                object.data.extend_from_slice(data);
+                Ok(())
+            }
+            None => Err("the file has already been committed.".into()),
+        }
+    }
+
+    fn commit(&mut self) -> Result<ContentObject, Box<dyn std::error::Error>> {
+        let object = self.object.take().ok_or("the file has already been committed.")?;
+        if object.data.len() as u64 != self.size {
+            return Err(format!(
+                "size mismatch: {} bytes were written, {} bytes were expected",
+                object.data.len(),
+                self.size
+            )
+            .into());
+        }
+        let id = self.device.lock().insert_object(self.device.index, object);
+        Ok(ContentObject { id })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_backend() -> MemoryBackend {
+        let backend = MemoryBackend::with_storage("Phone", "Internal");
+        backend.add_file("Phone:Internal:/DCIM/Camera/a.jpg", b"abc").unwrap();
+        backend
+    }
+
+    fn open_device(backend: &MemoryBackend) -> Box<dyn DeviceOperate> {
+        let device_info = backend.list_devices().unwrap().into_iter().next().unwrap();
+        backend.open_device(&device_info).unwrap()
+    }
+
+    #[test]
+    fn test_add_file_creates_parent_folders() {
+        let backend = create_backend();
+        assert_eq!(backend.list_names("Phone:Internal:/"), Some(vec!["DCIM".to_string()]));
+        assert_eq!(backend.list_names("Phone:Internal:/DCIM"), Some(vec!["Camera".to_string()]));
+        let object = backend.get_object("Phone:Internal:/DCIM/Camera/a.jpg").unwrap();
+        assert_eq!(object.data, b"abc");
+        assert_eq!(object.content_type, ContentType::GenericFile);
+    }
+
+    #[test]
+    fn test_device_object_hierarchy() {
+        let backend = create_backend();
+        let device = open_device(&backend);
+
+        let mut iter = device.get_object_iterator(&device.get_root_object()).unwrap();
+        let device_object = device.get_object_info(iter.next().unwrap().unwrap()).unwrap();
+        assert!(device_object.is_device());
+        assert!(iter.next().unwrap().is_none());
+
+        let mut iter = device.get_object_iterator(&device_object.content_object).unwrap();
+        let storage_object = device.get_object_info(iter.next().unwrap().unwrap()).unwrap();
+        assert!(storage_object.is_storage());
+        assert_eq!(storage_object.name, "Internal");
+    }
+
+    #[test]
+    fn test_create_file_and_read_back() {
+        let backend = create_backend();
+        let device = open_device(&backend);
+        let parent = ContentObject::new(&backend_object_id(&backend, "Phone:Internal:/DCIM"));
+
+        let mut writer = device.create_file(&parent, "b.txt", 5, &None, &None).unwrap();
+        writer.write(b"hel").unwrap();
+        writer.write(b"lo").unwrap();
+        let object = writer.commit().unwrap();
+        drop(writer);
+
+        let mut reader = device.get_resoure(&object).unwrap();
+        let mut buf = [0u8; 3];
+        assert_eq!(reader.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf, b"hel");
+        assert_eq!(reader.read(&mut buf).unwrap(), 2);
+        assert_eq!(&buf[..2], b"lo");
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_object_writer_with_io_copy() {
+        let backend = create_backend();
+        let device = open_device(&backend);
+        let parent = ContentObject::new(&backend_object_id(&backend, "Phone:Internal:/DCIM"));
+
+        let mut writer = crate::backend::ObjectWriter::new(device.create_file(&parent, "c.txt", 11, &None, &None).unwrap());
+        std::io::copy(&mut &b"hello world"[..], &mut writer).unwrap();
+        let object = writer.commit().unwrap();
+
+        let mut data = String::new();
+        std::io::BufReader::new(device.get_resoure(&object).unwrap()).read_to_string(&mut data).unwrap();
+        assert_eq!(data, "hello world");
+    }
+
+    #[test]
+    fn test_write_after_disconnect_fails() {
+        let backend = create_backend();
+        let device = open_device(&backend);
+        let parent = ContentObject::new(&backend_object_id(&backend, "Phone:Internal:/DCIM"));
+
+        let mut writer = crate::backend::ObjectWriter::new(device.create_file(&parent, "c.txt", 11, &None, &None).unwrap());
+        backend.disconnect();
+        let err = std::io::copy(&mut &b"hello world"[..], &mut writer).unwrap_err();
+        assert_eq!(crate::error::exit_code(&err), MtpError::Disconnected(String::new()).exit_code());
+        let err: Box<dyn std::error::Error> = err.into();
+        assert_eq!(crate::error::exit_code(err.as_ref()), MtpError::Disconnected(String::new()).exit_code());
+    }
+
+    #[test]
+    fn test_commit_with_wrong_size_fails() {
+        let backend = create_backend();
+        let device = open_device(&backend);
+        let parent = ContentObject::new(&backend_object_id(&backend, "Phone:Internal:/DCIM"));
+
+        let mut writer = device.create_file(&parent, "b.txt", 5, &None, &None).unwrap();
+        writer.write(b"hel").unwrap();
+        assert!(writer.commit().is_err());
+        drop(writer);
+        assert!(backend.get_object("Phone:Internal:/DCIM/b.txt").is_none());
+    }
+
+    #[test]
+    fn test_delete_is_recursive() {
+        let backend = create_backend();
+        let device = open_device(&backend);
+        let folder = ContentObject::new(&backend_object_id(&backend, "Phone:Internal:/DCIM"));
+
+        device.delete(&folder).unwrap();
+        assert_eq!(backend.list_names("Phone:Internal:/"), Some(vec![]));
+        assert!(backend.get_object("Phone:Internal:/DCIM/Camera/a.jpg").is_none());
+    }
+
+    fn backend_object_id(backend: &MemoryBackend, path: &str) -> String {
+        let state = backend.lock();
+        state.resolve(path).unwrap().unwrap().1
+    }
+}
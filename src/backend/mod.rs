@@ -0,0 +1,217 @@
+use crate::common::file_reader::FileReader;
+use crate::common::timestamp::Timestamp;
+use crate::error::MtpError;
+
+pub mod emulated;
+pub mod memory;
+
+// 设备后端接口
+// WPD (Windows) 和内存设备都实现这些接口，查找、列出和复制逻辑只依赖于这些接口
+
+/// Identifier of an object on a device (device object, storage, folder or file).
+///
+/// The empty id is the virtual root whose only child is the device object.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ContentObject {
+    pub id: String,
+}
+
+impl ContentObject {
+    pub fn new(id: &str) -> ContentObject {
+        ContentObject { id: id.to_string() }
+    }
+}
+
+/// Content type of an object
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ContentType {
+    FunctionalObject,
+    Folder,
+    GenericFile,
+    Other,
+}
+
+impl ContentType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContentType::FunctionalObject => "functional object",
+            ContentType::Folder => "folder",
+            ContentType::GenericFile => "file",
+            ContentType::Other => "other",
+        }
+    }
+}
+
+/// Functional category of a functional object
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FunctionalCategory {
+    /// 不是功能对象（文件、文件夹）
+    None,
+    Device,
+    Storage,
+    Other,
+}
+
+impl FunctionalCategory {
+    /// The name of the category, `None` for files and folders.
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            FunctionalCategory::None => None,
+            FunctionalCategory::Device => Some("device"),
+            FunctionalCategory::Storage => Some("storage"),
+            FunctionalCategory::Other => Some("other"),
+        }
+    }
+}
+
+// 对象详情信息
+#[derive(Debug, Clone)]
+pub struct ContentObjectInfo {
+    pub content_object: ContentObject,
+    /// Name to display
+    pub name: String,
+    /// Content type
+    pub content_type: ContentType,
+    /// 如果是文件或文件夹，则为 None
+    pub functional_object_category: FunctionalCategory,
+    /// Id of the parent object (or None for the root object or if not provided)
+    pub parent_id: Option<String>,
+    /// Id that stays the same across connections (or None if not provided)
+    pub persistent_id: Option<String>,
+    /// MTP object format code (or None if not provided)
+    pub format: Option<u16>,
+    /// Size of the resource data
+    pub data_size: u64,
+    /// Hidden flag
+    pub is_hidden: bool,
+    /// System flag
+    pub is_system: bool,
+    /// Whether the object can be deleted
+    pub can_delete: bool,
+    /// Time created (or None if not provided)
+    pub time_created: Option<Timestamp>,
+    /// Time modified (or None if not provided)
+    pub time_modified: Option<Timestamp>,
+    /// Time authored (or None if not provided)
+    pub time_authored: Option<Timestamp>,
+}
+
+impl ContentObjectInfo {
+    pub fn is_functional_object(&self) -> bool {
+        self.content_type == ContentType::FunctionalObject
+    }
+
+    pub fn is_device(&self) -> bool {
+        self.functional_object_category == FunctionalCategory::Device
+    }
+
+    pub fn is_storage(&self) -> bool {
+        self.functional_object_category == FunctionalCategory::Storage
+    }
+
+    pub fn is_folder(&self) -> bool {
+        self.content_type == ContentType::Folder
+    }
+
+    pub fn is_file(&self) -> bool {
+        !self.is_functional_object() && !self.is_folder()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceInfo {
+    pub id: String,
+    pub name: String,
+}
+
+// 设备管理接口，枚举设备并打开设备
+pub trait PortableDeviceBackend {
+    // 列出所有设备
+    fn list_devices(&self) -> Result<Vec<DeviceInfo>, Box<dyn std::error::Error>>;
+    // 打开设备
+    fn open_device(&self, info: &DeviceInfo) -> Result<Box<dyn DeviceOperate>, Box<dyn std::error::Error>>;
+}
+
+// 设备操作接口
+pub trait DeviceOperate {
+    // 设备名称
+    fn name(&self) -> &str;
+    // 获取根对象，根对象下面是设备对象
+    fn get_root_object(&self) -> ContentObject;
+    // 获取parent对象下的所有对象的迭代器
+    fn get_object_iterator(&self, parent: &ContentObject) -> Result<Box<dyn ContentObjectIterator + '_>, Box<dyn std::error::Error>>;
+    // 获取对象信息，对象包括是device、storages、文件夹、文件。
+    fn get_object_info(&self, object: ContentObject) -> Result<ContentObjectInfo, Box<dyn std::error::Error>>;
+    // 打开文件的数据流
+    fn get_resoure(&self, object: &ContentObject) -> Result<Box<dyn FileReader + '_>, Box<dyn std::error::Error>>;
+    // 创建文件,parent为父文件夹对象，name为文件名称，size为文件大小，created为创建时间，modified为修改时间
+    fn create_file(
+        &self,
+        parent: &ContentObject,
+        name: &str,
+        size: u64,
+        created: &Option<Timestamp>,
+        modified: &Option<Timestamp>,
+    ) -> Result<Box<dyn FileWriter + '_>, Box<dyn std::error::Error>>;
+    // 创建文件夹,parent为父文件夹对象，name为文件夹名称
+    fn create_folder(&self, parent: &ContentObject, name: &str) -> Result<ContentObject, Box<dyn std::error::Error>>;
+    // 删除对象（文件夹会递归删除）
+    fn delete(&self, object: &ContentObject) -> Result<(), Box<dyn std::error::Error>>;
+    // 重命名文件或文件夹，不检查同名对象
+    fn rename(&self, object: &ContentObject, name: &str) -> Result<(), Box<dyn std::error::Error>>;
+    // 把文件或文件夹移动到 parent 下，设备不支持时返回 false (由调用方复制后删除)
+    fn move_object(&self, object: &ContentObject, parent: &ContentObject) -> Result<bool, Box<dyn std::error::Error>>;
+}
+
+// 子对象迭代器
+pub trait ContentObjectIterator {
+    fn next(&mut self) -> Result<Option<ContentObject>, Box<dyn std::error::Error>>;
+}
+
+// 文件数据写入器，commit 后文件才会出现在设备上
+pub trait FileWriter {
+    fn get_buffer_size(&self) -> u32;
+    fn write(&mut self, data: &[u8]) -> Result<(), Box<dyn std::error::Error>>;
+    fn commit(&mut self) -> Result<ContentObject, Box<dyn std::error::Error>>;
+}
+
+/// Adapts a [`FileWriter`] to [`std::io::Write`].
+///
+/// The file appears on the device only after [`ObjectWriter::commit`] is called.
+pub struct ObjectWriter<'a> {
+    writer: Box<dyn FileWriter + 'a>,
+}
+
+impl<'a> ObjectWriter<'a> {
+    pub fn new(writer: Box<dyn FileWriter + 'a>) -> ObjectWriter<'a> {
+        ObjectWriter { writer }
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.writer.get_buffer_size() as usize
+    }
+
+    pub fn commit(mut self) -> Result<ContentObject, Box<dyn std::error::Error>> {
+        self.writer.commit()
+    }
+}
+
+impl std::io::Write for ObjectWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.writer.write(buf) {
+            Ok(()) => Ok(buf.len()),
+            // io::Error 原样返回，其他错误包装为 io::Error，保留分类以便决定退出码
+            Err(err) => match err.downcast::<std::io::Error>() {
+                Ok(err) => Err(*err),
+                Err(err) => match MtpError::classify(err.as_ref()) {
+                    Some(err) => Err(std::io::Error::other(err)),
+                    None => Err(std::io::Error::other(err.to_string())),
+                },
+            },
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
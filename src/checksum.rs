@@ -0,0 +1,165 @@
+use std::io::Read;
+use std::str::FromStr;
+
+// 文件校验和: sha256、crc32 和 blake3
+// 复制时边读取源文件边计算校验和，复制完成后读回目标文件比较
+
+/// Checksum algorithms for `copy --verify`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ChecksumAlgorithm {
+    #[default]
+    Sha256,
+    Crc32,
+    Blake3,
+}
+
+impl ChecksumAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Crc32 => "crc32",
+            ChecksumAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    pub fn hasher(&self) -> Box<dyn Checksum> {
+        match self {
+            ChecksumAlgorithm::Sha256 => Box::new(<sha2::Sha256 as sha2::Digest>::new()),
+            ChecksumAlgorithm::Crc32 => Box::new(crc32fast::Hasher::new()),
+            ChecksumAlgorithm::Blake3 => Box::new(blake3::Hasher::new()),
+        }
+    }
+
+    // 读取 reader 的全部数据并计算校验和
+    pub fn checksum(&self, reader: &mut dyn Read) -> std::io::Result<Vec<u8>> {
+        let mut reader = HashingReader::new(reader, *self);
+        std::io::copy(&mut reader, &mut std::io::sink())?;
+        Ok(reader.finish())
+    }
+}
+
+impl FromStr for ChecksumAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "sha256" => Ok(ChecksumAlgorithm::Sha256),
+            "crc32" => Ok(ChecksumAlgorithm::Crc32),
+            "blake3" => Ok(ChecksumAlgorithm::Blake3),
+            _ => Err(format!("unknown checksum algorithm: {} (sha256, crc32 or blake3)", s)),
+        }
+    }
+}
+
+pub trait Checksum {
+    fn update(&mut self, data: &[u8]);
+    fn finish(&self) -> Vec<u8>;
+}
+
+/// Computes the checksum of the data read through it.
+pub struct HashingReader<R: Read> {
+    reader: R,
+    hasher: Box<dyn Checksum>,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub fn new(reader: R, algorithm: ChecksumAlgorithm) -> HashingReader<R> {
+        HashingReader {
+            reader,
+            hasher: algorithm.hasher(),
+        }
+    }
+
+    pub fn finish(&self) -> Vec<u8> {
+        self.hasher.finish()
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        self.hasher.update(&buf[..len]);
+        Ok(len)
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// 校验和算法的实现来自 crc32fast、sha2 和 blake3
+
+impl Checksum for crc32fast::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        crc32fast::Hasher::update(self, data);
+    }
+
+    fn finish(&self) -> Vec<u8> {
+        self.clone().finalize().to_be_bytes().to_vec()
+    }
+}
+
+impl Checksum for sha2::Sha256 {
+    fn update(&mut self, data: &[u8]) {
+        sha2::Digest::update(self, data);
+    }
+
+    fn finish(&self) -> Vec<u8> {
+        sha2::Digest::finalize(self.clone()).to_vec()
+    }
+}
+
+impl Checksum for blake3::Hasher {
+    fn update(&mut self, data: &[u8]) {
+        blake3::Hasher::update(self, data);
+    }
+
+    fn finish(&self) -> Vec<u8> {
+        self.finalize().as_bytes().to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn digest(algorithm: ChecksumAlgorithm, data: &[u8]) -> String {
+        to_hex(&algorithm.checksum(&mut &data[..]).unwrap())
+    }
+
+    // 第 i 个字节为 i % 251
+    fn test_input(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test_case(ChecksumAlgorithm::Crc32, b"", "00000000")]
+    #[test_case(ChecksumAlgorithm::Crc32, b"123456789", "cbf43926")]
+    #[test_case(ChecksumAlgorithm::Sha256, b"", "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")]
+    #[test_case(ChecksumAlgorithm::Sha256, b"abc", "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")]
+    #[test_case(ChecksumAlgorithm::Sha256, b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq", "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1")]
+    #[test_case(ChecksumAlgorithm::Blake3, b"", "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262")]
+    #[test_case(ChecksumAlgorithm::Blake3, b"abc", "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85")]
+    fn test_known_digests(algorithm: ChecksumAlgorithm, data: &[u8], expected: &str) {
+        assert_eq!(digest(algorithm, data), expected);
+    }
+
+    #[test_case(ChecksumAlgorithm::Sha256)]
+    #[test_case(ChecksumAlgorithm::Crc32)]
+    #[test_case(ChecksumAlgorithm::Blake3)]
+    fn test_incremental_update(algorithm: ChecksumAlgorithm) {
+        let data = test_input(5000);
+        let mut hasher = algorithm.hasher();
+        for chunk in data.chunks(77) {
+            hasher.update(chunk);
+        }
+        assert_eq!(to_hex(&hasher.finish()), digest(algorithm, &data));
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("SHA256".parse::<ChecksumAlgorithm>(), Ok(ChecksumAlgorithm::Sha256));
+        assert_eq!("blake3".parse::<ChecksumAlgorithm>(), Ok(ChecksumAlgorithm::Blake3));
+        assert!("md5".parse::<ChecksumAlgorithm>().is_err());
+    }
+}
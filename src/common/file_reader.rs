@@ -1,4 +1,22 @@
-pub trait FileReader {
+use std::io::{ErrorKind, Read, Write};
+
+// 文件数据读取器，buffer_size 为设备建议的每次读取的字节数
+pub trait FileReader: Read {
     fn buffer_size(&self) -> u32;
-    fn seek(&mut self, max_size: u32) -> Result<Option<&[u8]>, Box<dyn std::error::Error>>;
+}
+
+// 按照指定的缓冲区大小把 reader 的数据全部写入 writer，返回写入的字节数
+pub fn copy_with_buffer(reader: &mut dyn Read, writer: &mut dyn Write, buffer_size: usize) -> std::io::Result<u64> {
+    let mut buffer = vec![0u8; buffer_size.max(1)];
+    let mut total = 0u64;
+    loop {
+        let len = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(total),
+            Ok(len) => len,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        writer.write_all(&buffer[..len])?;
+        total += len as u64;
+    }
 }
@@ -32,6 +32,7 @@ impl FileNamePattern {
     }
 }
 
+#[allow(clippy::len_zero)]
 fn matches_seq(mut seq: &[char], mut pattern: &[char]) -> bool {
     while pattern.len() > 0 {
         let pat = pattern[0];
@@ -81,6 +82,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_matches_seq() {
         assert_eq!(true, call_matches_seq("", ""));
         assert_eq!(true, call_matches_seq("", "*"));
@@ -118,6 +120,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_file_name_pattern() {
         let pat = FileNamePattern::new("a?c*c");
         assert_eq!(false, pat.matches(""));
@@ -1,4 +1,5 @@
 pub mod path_matcher;
 pub mod filename;
 pub mod file_reader;
-pub mod time_transfer;
\ No newline at end of file
+pub mod timestamp;
+pub mod text_width;
\ No newline at end of file
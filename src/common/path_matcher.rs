@@ -17,8 +17,9 @@ pub enum PathMatchingState {
 /// Creates linked matchers that match the given path pattern.
 ///
 /// * `pattern` - path pattern.  
-///     Each component can contain wildcard characters ('*' and '?').  
-///     `**` matches zero or more any directories.
+///   Each component can contain wildcard characters ('*' and '?').  
+///   `**` matches zero or more any directories.
+#[allow(clippy::len_zero, clippy::comparison_to_empty)]
 pub fn create_path_pattern_matcher(pattern: &str) -> Result<RootPathMatcher, Box<dyn std::error::Error>> {
     if pattern.len() == 0 {
         return Err("path is empty.".into());
@@ -77,7 +78,7 @@ impl RootPathMatcher {
     pub fn matches_root(&self) -> (PathMatchingState, Option<&PathMatcher>) {
         match &self.next {
             None => (PathMatchingState::Completed, None),
-            Some(m) => (PathMatchingState::Accepted, Some(&m)),
+            Some(m) => (PathMatchingState::Accepted, Some(m)),
         }
     }
 
@@ -86,13 +87,14 @@ impl RootPathMatcher {
     fn next_matcher(&self) -> Option<&PathMatcher> {
         match &self.next {
             None => None,
-            Some(m) => Some(&m),
+            Some(m) => Some(m),
         }
     }
 }
 
 /// Other matchers
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum PathMatcher {
     ExactNameMatcher {
         name: String,
@@ -124,7 +126,7 @@ impl PathMatcher {
                 if (!*must_be_dir || is_dir) && name == m_name {
                     match next {
                         None => (PathMatchingState::Completed, None),
-                        Some(m) => (PathMatchingState::Accepted, Some(&m)),
+                        Some(m) => (PathMatchingState::Accepted, Some(m)),
                     }
                 } else {
                     (PathMatchingState::Rejected, None)
@@ -139,7 +141,7 @@ impl PathMatcher {
                 if (!*must_be_dir || is_dir) && pattern.matches(name) {
                     match next {
                         None => (PathMatchingState::Completed, None),
-                        Some(m) => (PathMatchingState::Accepted, Some(&m)),
+                        Some(m) => (PathMatchingState::Accepted, Some(m)),
                     }
                 } else {
                     (PathMatchingState::Rejected, None)
@@ -174,7 +176,7 @@ impl PathMatcher {
                 next,
             } => match next {
                 None => None,
-                Some(m) => Some(&m),
+                Some(m) => Some(m),
             },
 
             PathMatcher::FileNamePatternMatcher {
@@ -183,7 +185,7 @@ impl PathMatcher {
                 next,
             } => match next {
                 None => None,
-                Some(m) => Some(&m),
+                Some(m) => Some(m),
             },
 
             PathMatcher::AnyDirectoriesMatcher { next } => Some(next),
@@ -348,6 +350,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::redundant_pattern_matching)]
     fn test_create_path_pattern_matcher_errors() {
         assert!(matches!(create_path_pattern_matcher(""), Err(_)));
         assert!(matches!(create_path_pattern_matcher("a/./a"), Err(_)));
@@ -0,0 +1,85 @@
+// 终端显示宽度，中日韩文字和全角字符占两列，组合字符占零列
+// 只覆盖常见的范围，不依赖 unicode-width
+
+// 东亚宽字符 (East Asian Wide / Fullwidth) 的范围
+const WIDE_RANGES: [(u32, u32); 15] = [
+    (0x1100, 0x115F),
+    (0x2E80, 0x303E),
+    (0x3041, 0x33FF),
+    (0x3400, 0x4DBF),
+    (0x4E00, 0x9FFF),
+    (0xA000, 0xA4CF),
+    (0xAC00, 0xD7A3),
+    (0xF900, 0xFAFF),
+    (0xFE30, 0xFE4F),
+    (0xFF00, 0xFF60),
+    (0xFFE0, 0xFFE6),
+    (0x1F300, 0x1F64F),
+    (0x1F900, 0x1F9FF),
+    (0x20000, 0x2FFFD),
+    (0x30000, 0x3FFFD),
+];
+
+// 不占宽度的组合字符、零宽字符和变体选择符
+const ZERO_WIDTH_RANGES: [(u32, u32); 5] = [
+    (0x0300, 0x036F),
+    (0x200B, 0x200F),
+    (0x20D0, 0x20FF),
+    (0xFE00, 0xFE0F),
+    (0xFE20, 0xFE2F),
+];
+
+fn in_ranges(c: char, ranges: &[(u32, u32)]) -> bool {
+    let code = c as u32;
+    ranges.iter().any(|&(start, end)| start <= code && code <= end)
+}
+
+/// The number of terminal columns `c` takes.
+pub fn char_width(c: char) -> usize {
+    if c.is_control() || in_ranges(c, &ZERO_WIDTH_RANGES) {
+        0
+    } else if in_ranges(c, &WIDE_RANGES) {
+        2
+    } else {
+        1
+    }
+}
+
+/// The number of terminal columns `s` takes.
+pub fn display_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+/// Pads `s` with spaces on the right to `width` columns.
+pub fn pad_right(s: &str, width: usize) -> String {
+    format!("{}{}", s, " ".repeat(width.saturating_sub(display_width(s))))
+}
+
+/// Pads `s` with spaces on the left to `width` columns.
+pub fn pad_left(s: &str, width: usize) -> String {
+    format!("{}{}", " ".repeat(width.saturating_sub(display_width(s))), s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("DCIM", 4)]
+    #[test_case("内部存储设备", 12)]
+    #[test_case("Redmi K70:内部存储设备:", 23)]
+    #[test_case("\u{ff76}\u{ff9e}", 2 ; "halfwidth katakana")]
+    #[test_case("\u{ff21}\u{ff22}", 4 ; "fullwidth latin")]
+    #[test_case("e\u{301}", 1 ; "combining accent")]
+    #[test_case("", 0)]
+    fn test_display_width(s: &str, width: usize) {
+        assert_eq!(display_width(s), width);
+    }
+
+    #[test]
+    fn test_pad() {
+        assert_eq!(pad_right("内部", 6), "内部  ");
+        assert_eq!(pad_left("12", 4), "  12");
+        assert_eq!(pad_right("内部存储设备", 4), "内部存储设备");
+    }
+}
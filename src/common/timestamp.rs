@@ -0,0 +1,477 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+
+// 时间戳和设备时间格式之间的转换
+// WPD: "YYYY/MM/DD:HH:MM:SS.mmm"，MTP: "YYYYMMDDThhmmss[.s][Z|+hhmm|-hhmm]"
+// 这两种格式通常没有时区 (设备的本地时间)，按指定的 TimeZone 解释
+
+const MILLIS_PER_DAY: i64 = 86_400_000;
+// 1970-01-01 的 OLE 自动化日期
+const OLE_AUTOMATION_DATE_EPOCH: f64 = 25569.0;
+
+/// A fixed offset from UTC, used for the device times that carry no offset.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct TimeZone {
+    offset_seconds: i32,
+}
+
+impl TimeZone {
+    pub const UTC: TimeZone = TimeZone { offset_seconds: 0 };
+
+    /// Creates a time zone `offset_seconds` east of UTC, the offset must be less than 24 hours.
+    pub fn from_offset_seconds(offset_seconds: i32) -> Option<TimeZone> {
+        if offset_seconds.abs() < 86_400 {
+            Some(TimeZone { offset_seconds })
+        } else {
+            None
+        }
+    }
+
+    pub fn offset_seconds(&self) -> i32 {
+        self.offset_seconds
+    }
+
+    // "+hhmm"、"+hh:mm" 或 "+hh"
+    fn parse_offset(s: &str) -> Option<TimeZone> {
+        let sign = match s.get(0..1)? {
+            "+" => 1,
+            "-" => -1,
+            _ => return None,
+        };
+        let digits: String = s[1..].chars().filter(|c| *c != ':').collect();
+        if !digits.bytes().all(|b| b.is_ascii_digit()) || (s[1..].contains(':') && s.len() != 6) {
+            return None;
+        }
+        let (hours, minutes) = match digits.len() {
+            2 => (digits.parse::<i32>().ok()?, 0),
+            4 => (digits[0..2].parse::<i32>().ok()?, digits[2..4].parse::<i32>().ok()?),
+            _ => return None,
+        };
+        if hours > 23 || minutes > 59 {
+            return None;
+        }
+        TimeZone::from_offset_seconds(sign * (hours * 3600 + minutes * 60))
+    }
+}
+
+impl FromStr for TimeZone {
+    type Err = String;
+
+    /// Parses "UTC", "Z" or an offset like "+08:00", "+0800" and "-05".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("utc") || s.eq_ignore_ascii_case("z") {
+            return Ok(TimeZone::UTC);
+        }
+        TimeZone::parse_offset(s).ok_or_else(|| format!("invalid time zone: {} (UTC or an offset like +08:00)", s))
+    }
+}
+
+impl fmt::Display for TimeZone {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.offset_seconds == 0 {
+            return write!(f, "UTC");
+        }
+        let sign = if self.offset_seconds < 0 { '-' } else { '+' };
+        let offset = self.offset_seconds.abs();
+        write!(f, "{}{:02}:{:02}", sign, offset / 3600, offset % 3600 / 60)
+    }
+}
+
+/// A point in time with millisecond precision.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Timestamp {
+    // 1970-01-01T00:00:00Z 以来的毫秒数
+    millis: i64,
+}
+
+// 日期和时间的各个字段
+#[derive(Debug, PartialEq, Eq)]
+struct DateTimeFields {
+    year: i64,
+    month: i64,
+    day: i64,
+    hour: i64,
+    minute: i64,
+    second: i64,
+    millis: i64,
+}
+
+impl Timestamp {
+    pub fn from_unix_seconds(seconds: i64) -> Timestamp {
+        Timestamp { millis: seconds * 1000 }
+    }
+
+    pub fn from_unix_millis(millis: i64) -> Timestamp {
+        Timestamp { millis }
+    }
+
+    pub fn unix_seconds(&self) -> i64 {
+        self.millis.div_euclid(1000)
+    }
+
+    pub fn unix_millis(&self) -> i64 {
+        self.millis
+    }
+
+    pub fn from_system_time(time: SystemTime) -> Timestamp {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(duration) => Timestamp::from_unix_millis(duration.as_millis() as i64),
+            Err(err) => Timestamp::from_unix_millis(-(err.duration().as_millis() as i64)),
+        }
+    }
+
+    pub fn to_system_time(&self) -> SystemTime {
+        if self.millis >= 0 {
+            UNIX_EPOCH + Duration::from_millis(self.millis as u64)
+        } else {
+            UNIX_EPOCH - Duration::from_millis(self.millis.unsigned_abs())
+        }
+    }
+
+    /// Parses a WPD date "YYYY/MM/DD:HH:MM:SS[.mmm]" given in `time_zone`.
+    pub fn parse_wpd(s: &str, time_zone: TimeZone) -> Option<Timestamp> {
+        let mut scanner = Scanner::new(s);
+        let year = scanner.number(4)?;
+        scanner.expect("/")?;
+        let month = scanner.number(2)?;
+        scanner.expect("/")?;
+        let day = scanner.number(2)?;
+        scanner.expect(":")?;
+        let hour = scanner.number(2)?;
+        scanner.expect(":")?;
+        let minute = scanner.number(2)?;
+        scanner.expect(":")?;
+        let second = scanner.number(2)?;
+        let millis = scanner.fraction()?;
+        if !scanner.rest().is_empty() {
+            return None;
+        }
+        Timestamp::from_fields(&DateTimeFields { year, month, day, hour, minute, second, millis }, time_zone)
+    }
+
+    /// Formats as a WPD date "YYYY/MM/DD:HH:MM:SS.mmm" in `time_zone`.
+    pub fn to_wpd(&self, time_zone: TimeZone) -> String {
+        let f = self.fields(time_zone);
+        format!(
+            "{:04}/{:02}/{:02}:{:02}:{:02}:{:02}.{:03}",
+            f.year, f.month, f.day, f.hour, f.minute, f.second, f.millis
+        )
+    }
+
+    /// Parses an MTP date "YYYYMMDDThhmmss[.s][Z|+hhmm|-hhmm]", a date without an offset is in `time_zone`.
+    pub fn parse_mtp(s: &str, time_zone: TimeZone) -> Option<Timestamp> {
+        let mut scanner = Scanner::new(s);
+        let year = scanner.number(4)?;
+        let month = scanner.number(2)?;
+        let day = scanner.number(2)?;
+        scanner.expect("T")?;
+        let hour = scanner.number(2)?;
+        let minute = scanner.number(2)?;
+        let second = scanner.number(2)?;
+        let millis = scanner.fraction()?;
+        let time_zone = match scanner.rest() {
+            "" => time_zone,
+            "Z" => TimeZone::UTC,
+            offset if offset.len() == 5 => TimeZone::parse_offset(offset)?,
+            _ => return None,
+        };
+        Timestamp::from_fields(&DateTimeFields { year, month, day, hour, minute, second, millis }, time_zone)
+    }
+
+    /// Formats as an MTP date in `time_zone`, with "Z" for UTC or the offset.
+    pub fn to_mtp(&self, time_zone: TimeZone) -> String {
+        let f = self.fields(time_zone);
+        let mut s = format!(
+            "{:04}{:02}{:02}T{:02}{:02}{:02}",
+            f.year, f.month, f.day, f.hour, f.minute, f.second
+        );
+        // MTP 只有十分之一秒
+        if f.millis / 100 != 0 {
+            s.push_str(&format!(".{}", f.millis / 100));
+        }
+        if time_zone == TimeZone::UTC {
+            s.push('Z');
+        } else {
+            s.push_str(&time_zone.to_string().replace(':', ""));
+        }
+        s
+    }
+
+    /// Converts to an OLE automation date in `time_zone`, the days since 1899-12-30, used by the WPD date properties.
+    pub fn to_ole_automation_date(&self, time_zone: TimeZone) -> f64 {
+        let local = self.millis + time_zone.offset_seconds as i64 * 1000;
+        local as f64 / MILLIS_PER_DAY as f64 + OLE_AUTOMATION_DATE_EPOCH
+    }
+
+    /// Parses unix seconds, an ISO 8601 date "YYYY-MM-DD[T ]HH:MM:SS[.mmm][Z|+hh:mm]", a WPD date or an MTP date.
+    pub fn parse(s: &str, time_zone: TimeZone) -> Option<Timestamp> {
+        let s = s.trim();
+        if let Ok(seconds) = s.parse::<i64>() {
+            return Some(Timestamp::from_unix_seconds(seconds));
+        }
+        Timestamp::parse_iso(s, time_zone)
+            .or_else(|| Timestamp::parse_wpd(s, time_zone))
+            .or_else(|| Timestamp::parse_mtp(s, time_zone))
+    }
+
+    fn parse_iso(s: &str, time_zone: TimeZone) -> Option<Timestamp> {
+        let mut scanner = Scanner::new(s);
+        let year = scanner.number(4)?;
+        scanner.expect("-")?;
+        let month = scanner.number(2)?;
+        scanner.expect("-")?;
+        let day = scanner.number(2)?;
+        scanner.expect("T").or_else(|| scanner.expect(" "))?;
+        let hour = scanner.number(2)?;
+        scanner.expect(":")?;
+        let minute = scanner.number(2)?;
+        scanner.expect(":")?;
+        let second = scanner.number(2)?;
+        let millis = scanner.fraction()?;
+        let time_zone = match scanner.rest() {
+            "" => time_zone,
+            "Z" => TimeZone::UTC,
+            offset => TimeZone::parse_offset(offset)?,
+        };
+        Timestamp::from_fields(&DateTimeFields { year, month, day, hour, minute, second, millis }, time_zone)
+    }
+
+    /// Formats as "YYYY-MM-DD HH:MM:SS" in `time_zone`.
+    pub fn format(&self, time_zone: TimeZone) -> String {
+        let f = self.fields(time_zone);
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            f.year, f.month, f.day, f.hour, f.minute, f.second
+        )
+    }
+
+    /// Formats as ISO 8601 with the offset of `time_zone`, e.g. "2021-08-02T03:31:01+08:00".
+    pub fn to_iso(&self, time_zone: TimeZone) -> String {
+        let f = self.fields(time_zone);
+        let mut s = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            f.year, f.month, f.day, f.hour, f.minute, f.second
+        );
+        if f.millis != 0 {
+            s.push_str(&format!(".{:03}", f.millis));
+        }
+        if time_zone == TimeZone::UTC {
+            s.push('Z');
+        } else {
+            s.push_str(&time_zone.to_string());
+        }
+        s
+    }
+
+    fn from_fields(f: &DateTimeFields, time_zone: TimeZone) -> Option<Timestamp> {
+        let days_in_month = match f.month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if is_leap_year(f.year) => 29,
+            2 => 28,
+            _ => return None,
+        };
+        // 闰秒按 59 秒处理
+        if f.day < 1 || f.day > days_in_month || f.hour > 23 || f.minute > 59 || f.second > 60 {
+            return None;
+        }
+        let seconds = days_from_civil(f.year, f.month, f.day) * 86400
+            + f.hour * 3600
+            + f.minute * 60
+            + f.second.min(59)
+            - time_zone.offset_seconds as i64;
+        Some(Timestamp::from_unix_millis(seconds * 1000 + f.millis))
+    }
+
+    fn fields(&self, time_zone: TimeZone) -> DateTimeFields {
+        let local = self.millis + time_zone.offset_seconds as i64 * 1000;
+        let (year, month, day) = civil_from_days(local.div_euclid(MILLIS_PER_DAY));
+        let millis_of_day = local.rem_euclid(MILLIS_PER_DAY);
+        DateTimeFields {
+            year,
+            month,
+            day,
+            hour: millis_of_day / 3_600_000,
+            minute: millis_of_day % 3_600_000 / 60_000,
+            second: millis_of_day % 60_000 / 1000,
+            millis: millis_of_day % 1000,
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    /// ISO 8601 in UTC, e.g. "2021-08-01T19:31:01Z".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_iso(TimeZone::UTC))
+    }
+}
+
+// 按固定宽度读取数字和分隔符
+struct Scanner<'a> {
+    s: &'a str,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(s: &'a str) -> Scanner<'a> {
+        Scanner { s }
+    }
+
+    fn number(&mut self, width: usize) -> Option<i64> {
+        let digits = self.s.get(..width)?;
+        if !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        self.s = &self.s[width..];
+        digits.parse().ok()
+    }
+
+    fn expect(&mut self, separator: &str) -> Option<()> {
+        self.s = self.s.strip_prefix(separator)?;
+        Some(())
+    }
+
+    // 可选的小数部分，返回毫秒，超过 3 位的部分被截断
+    fn fraction(&mut self) -> Option<i64> {
+        let Some(rest) = self.s.strip_prefix('.') else {
+            return Some(0);
+        };
+        let count = rest.bytes().take_while(|b| b.is_ascii_digit()).count();
+        if count == 0 {
+            return None;
+        }
+        let digits = &rest[..count.min(3)];
+        self.s = &rest[count..];
+        Some(digits.parse::<i64>().ok()? * 10i64.pow(3 - digits.len() as u32))
+    }
+
+    fn rest(&self) -> &'a str {
+        self.s
+    }
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+// 公历日期转换为 1970-01-01 以来的天数 (Howard Hinnant 的 days_from_civil 算法)
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// 1970-01-01 以来的天数转换为公历日期 (civil_from_days 算法)
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tz(s: &str) -> TimeZone {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_time_zone() {
+        assert_eq!(tz("UTC"), TimeZone::UTC);
+        assert_eq!(tz("z"), TimeZone::UTC);
+        assert_eq!(tz("+08:00").offset_seconds(), 8 * 3600);
+        assert_eq!(tz("+0530").offset_seconds(), 5 * 3600 + 30 * 60);
+        assert_eq!(tz("-05").offset_seconds(), -5 * 3600);
+        assert_eq!(tz("-05:30").to_string(), "-05:30");
+        assert_eq!(TimeZone::UTC.to_string(), "UTC");
+        assert!("local".parse::<TimeZone>().is_err());
+        assert!("+24:00".parse::<TimeZone>().is_err());
+        assert!("+8".parse::<TimeZone>().is_err());
+        assert!("+08:0".parse::<TimeZone>().is_err());
+    }
+
+    #[test]
+    fn test_mtp_date() {
+        let utc = TimeZone::UTC;
+        assert_eq!(Timestamp::parse_mtp("19700101T000000Z", utc), Some(Timestamp::from_unix_seconds(0)));
+        assert_eq!(Timestamp::parse_mtp("20210801T193101", utc), Some(Timestamp::from_unix_seconds(1627846261)));
+        assert_eq!(Timestamp::parse_mtp("20210801T193101.5Z", utc), Some(Timestamp::from_unix_millis(1627846261500)));
+        assert_eq!(Timestamp::parse_mtp("20210802T033101+0800", utc), Some(Timestamp::from_unix_seconds(1627846261)));
+        assert_eq!(Timestamp::parse_mtp("20210801T143101-0500", utc), Some(Timestamp::from_unix_seconds(1627846261)));
+        assert_eq!(Timestamp::parse_mtp("20000229T000000", utc), Some(Timestamp::from_unix_seconds(951782400)));
+        // 没有时区的时间按指定的时区解释
+        assert_eq!(Timestamp::parse_mtp("20210802T033101", tz("+08:00")), Some(Timestamp::from_unix_seconds(1627846261)));
+        assert_eq!(Timestamp::parse_mtp("20210802T033101Z", tz("+08:00")), Some(Timestamp::from_unix_seconds(1627875061)));
+        assert_eq!(Timestamp::parse_mtp("", utc), None);
+        assert_eq!(Timestamp::parse_mtp("2021-08-01T19:31:01", utc), None);
+        assert_eq!(Timestamp::parse_mtp("20211301T000000", utc), None);
+        assert_eq!(Timestamp::parse_mtp("20210230T000000", utc), None);
+        assert_eq!(Timestamp::parse_mtp("20210801T193101+08", utc), None);
+        assert_eq!(Timestamp::parse_mtp("20210801T193101.", utc), None);
+
+        let timestamp = Timestamp::from_unix_seconds(1627846261);
+        assert_eq!(timestamp.to_mtp(utc), "20210801T193101Z");
+        assert_eq!(timestamp.to_mtp(tz("+08:00")), "20210802T033101+0800");
+        assert_eq!(Timestamp::from_unix_millis(1627846261500).to_mtp(utc), "20210801T193101.5Z");
+        assert_eq!(Timestamp::from_unix_seconds(951782400).to_mtp(utc), "20000229T000000Z");
+    }
+
+    #[test]
+    fn test_wpd_date() {
+        let timestamp = Timestamp::from_unix_millis(1627846261123);
+        assert_eq!(Timestamp::parse_wpd("2021/08/01:19:31:01.123", TimeZone::UTC), Some(timestamp));
+        assert_eq!(Timestamp::parse_wpd("2021/08/02:03:31:01.123", tz("+08:00")), Some(timestamp));
+        assert_eq!(Timestamp::parse_wpd("2021/08/01:19:31:01", TimeZone::UTC), Some(Timestamp::from_unix_seconds(1627846261)));
+        assert_eq!(Timestamp::parse_wpd("2021/08/01 19:31:01", TimeZone::UTC), None);
+        assert_eq!(Timestamp::parse_wpd("2021/08/01:19:31:01.123Z", TimeZone::UTC), None);
+        assert_eq!(timestamp.to_wpd(TimeZone::UTC), "2021/08/01:19:31:01.123");
+        assert_eq!(timestamp.to_wpd(tz("-05:00")), "2021/08/01:14:31:01.123");
+
+        assert_eq!(Timestamp::from_unix_seconds(0).to_ole_automation_date(TimeZone::UTC), 25569.0);
+        assert_eq!(Timestamp::parse_wpd("2000/01/01:12:00:00", TimeZone::UTC).unwrap().to_ole_automation_date(TimeZone::UTC), 36526.5);
+        assert_eq!(Timestamp::from_unix_seconds(0).to_ole_automation_date(tz("+06:00")), 25569.25);
+    }
+
+    #[test]
+    fn test_parse_and_format() {
+        let utc = TimeZone::UTC;
+        let timestamp = Timestamp::from_unix_seconds(1627846261);
+        assert_eq!(Timestamp::parse("1627846261", utc), Some(timestamp));
+        assert_eq!(Timestamp::parse("2021-08-01T19:31:01Z", utc), Some(timestamp));
+        assert_eq!(Timestamp::parse("2021-08-02 03:31:01+08:00", utc), Some(timestamp));
+        assert_eq!(Timestamp::parse("2021-08-02 03:31:01", tz("+08:00")), Some(timestamp));
+        assert_eq!(Timestamp::parse("2021/08/01:19:31:01.000", utc), Some(timestamp));
+        assert_eq!(Timestamp::parse("20210801T193101", utc), Some(timestamp));
+        assert_eq!(Timestamp::parse("yesterday", utc), None);
+
+        assert_eq!(timestamp.to_string(), "2021-08-01T19:31:01Z");
+        assert_eq!(Timestamp::from_unix_millis(-1).to_string(), "1969-12-31T23:59:59.999Z");
+        assert_eq!(timestamp.format(tz("+08:00")), "2021-08-02 03:31:01");
+        assert_eq!(timestamp.to_iso(tz("+08:00")), "2021-08-02T03:31:01+08:00");
+        assert_eq!(Timestamp::parse(&timestamp.to_iso(tz("-05:30")), utc), Some(timestamp));
+    }
+
+    #[test]
+    fn test_system_time() {
+        let time = UNIX_EPOCH + Duration::from_millis(1627846261123);
+        assert_eq!(Timestamp::from_system_time(time), Timestamp::from_unix_millis(1627846261123));
+        assert_eq!(Timestamp::from_unix_millis(1627846261123).to_system_time(), time);
+        let before_epoch = UNIX_EPOCH - Duration::from_secs(10);
+        assert_eq!(Timestamp::from_system_time(before_epoch).unix_seconds(), -10);
+        assert_eq!(Timestamp::from_unix_seconds(-10).to_system_time(), before_epoch);
+        assert_eq!(Timestamp::from_unix_millis(-1).unix_seconds(), -1);
+    }
+}
@@ -1,83 +1,173 @@
+use std::collections::HashMap;
 use std::path::PathBuf;
 use crate::copy_operate::device_folder_imp::DeviceFolder;
-use crate::copy_operate::{do_copy, get_destination_path_info, has_wildcard, inspect_path};
+use crate::copy_operate::{do_copy, get_destination_path_info, has_wildcard, inspect_path, CopyOptions, CopySummary, MovedSource};
 use crate::copy_operate::local_folder_imp::LocalFolder;
-use crate::find::find_file_or_folder;
+use crate::find::{find_file_or_folder, find_storage};
+use crate::backend::{DeviceOperate, PortableDeviceBackend};
+use crate::error::MtpError;
 use crate::path::{DeviceStoragePath, get_path_type, PathType};
-use crate::Paths;
-use crate::wpd::manager::Manager;
 
 
 
 pub fn copy(
-    paths: &Paths,
-    recursive: bool,
-    mirror: bool,
+    backend: &dyn PortableDeviceBackend,
+    src_path: &str,
+    dest_path: &str,
+    options: &CopyOptions,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    log::trace!("command_copy paths={:?}", paths);
-    let manager = Manager::get_portable_device_manager()?;
+    copy_many(backend, &[src_path], dest_path, options)
+}
+
+// 复制多个源到同一个目标，目标只解析一次
+// 多个源时目标必须是已存在的文件夹
+pub fn copy_many(
+    backend: &dyn PortableDeviceBackend,
+    src_paths: &[&str],
+    dest_path: &str,
+    options: &CopyOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    check_summary(&copy_sources(backend, src_paths, dest_path, options)?)
+}
+
+// 移动：先复制所有的源，复制成功后才删除校验过的源文件
+// 不能删除的源文件保留并报告，文件夹只在变为空时删除
+pub fn move_many(
+    backend: &dyn PortableDeviceBackend,
+    src_paths: &[&str],
+    dest_path: &str,
+    options: &CopyOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let options = CopyOptions {
+        move_sources: true,
+        ..options.clone()
+    };
+    let summary = copy_sources(backend, src_paths, dest_path, &options)?;
+    check_summary(&summary)?;
+    delete_moved_sources(backend, &summary.moved)
+}
 
-    let src_path = paths.src.as_str();
-    let dest_path = paths.dest.as_str();
+fn copy_sources(
+    backend: &dyn PortableDeviceBackend,
+    src_paths: &[&str],
+    dest_path: &str,
+    options: &CopyOptions,
+) -> Result<CopySummary, Box<dyn std::error::Error>> {
+    log::trace!("command_copy src={:?} dest={:?} options={:?}", src_paths, dest_path, options);
 
-    // 1. 获取源路径和目标路径类型
-    let src_path_type = get_path_type(src_path);
+    // 1. 获取目标路径类型
     let dest_path_type = get_path_type(dest_path);
 
-    // 2. 检查路径是否包含通配符，不支持通配符
-    for (path, path_type) in [(src_path, src_path_type), (dest_path, dest_path_type)] {
-        if has_wildcard(path, path_type)? {
-            return Err(format!("Wildcard characters in the {} path are not allowed.",
-                               if path == src_path { "source" } else { "destination" }).into());
-        }
+    // 2. 检查目标路径是否包含通配符，源路径的通配符在复制时展开
+    if has_wildcard(dest_path, dest_path_type)? {
+        return Err("Wildcard characters in the destination path are not allowed.".into());
     }
 
     // 3. 检查目标路径状态
-    let dest_inspection = inspect_path(&manager, dest_path, dest_path_type)?;
+    let dest_inspection = inspect_path(backend, dest_path, dest_path_type)?;
     log::trace!("dest_inspection = {:?}", &dest_inspection);
 
     // 判断目标路径是否是父文件夹
     let (dest_base_path, dest_name) = match get_destination_path_info(&dest_inspection, dest_path)? {
         Some(info) => info,
-        None => return Err("cannot create the destination path.".into()),
+        None => return Err(MtpError::DestinationNotCreatable(dest_path.to_string()).into()),
     };
+    if src_paths.len() > 1 && dest_name.is_some() {
+        return Err(format!("the destination of multiple sources must be an existing folder: {}", dest_path).into());
+    }
 
     // 处理不同路径类型的复制逻辑
-    match dest_path_type {
+    let summary = match dest_path_type {
         // 复制到设备存储
         PathType::DeviceStorage => {
             let storage_path = DeviceStoragePath::from(dest_base_path)?;
-            if let Some((_, device, object_info)) = find_file_or_folder(&manager, &storage_path)? {
-                let mut destination_folder = DeviceFolder::new(&device, object_info)?;
+            if let Some((_, device, object_info)) = find_file_or_folder(backend, &storage_path)? {
+                let destination_folder = DeviceFolder::new(device.as_ref(), object_info)?;
                 do_copy(
-                    &manager,
-                    src_path,
-                    src_path_type,
-                    &mut destination_folder,
-                    dest_name.is_none(),
+                    backend,
+                    src_paths,
+                    destination_folder,
                     dest_name,
-                    recursive,
-                    mirror,
-                )
+                    options,
+                )?
             }else {
-                Err("failed to open source path.".into())
+                return Err(MtpError::PathNotFound(dest_base_path.to_string()).into());
             }
         }
         // 复制到本地
         PathType::Local => {
-            let mut destination_folder = LocalFolder::new(PathBuf::from(dest_base_path));
+            let destination_folder = LocalFolder::new(PathBuf::from(dest_base_path));
             do_copy(
-                &manager,
-                src_path,
-                src_path_type,
-                &mut destination_folder,
-                !dest_name.is_none(),
+                backend,
+                src_paths,
+                destination_folder,
                 dest_name,
-                recursive,
-                mirror,
-            )
+                options,
+            )?
         },
-        PathType::Invalid => Err("invalid destination path.".into()),
+        PathType::Invalid => return Err("invalid destination path.".into()),
+    };
+    Ok(summary)
+}
+
+// 删除移动的源，设备按 "设备名:存储名" 只打开一次
+fn delete_moved_sources(backend: &dyn PortableDeviceBackend, moved: &[MovedSource]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut devices = HashMap::<String, Box<dyn DeviceOperate>>::new();
+    for source in moved {
+        match source {
+            MovedSource::Device { path, object, is_folder, can_delete } => {
+                if !can_delete {
+                    report_kept(path, "cannot be deleted");
+                    continue;
+                }
+                let storage_path = DeviceStoragePath::from(path)?;
+                let key = format!("{}:{}", storage_path.device_name, storage_path.storage_name);
+                if !devices.contains_key(&key) {
+                    match find_storage(backend, &storage_path)? {
+                        Some((_, device, _)) => devices.insert(key.clone(), device),
+                        None => return Err(MtpError::StorageNotFound(key).into()),
+                    };
+                }
+                let device = devices[&key].as_ref();
+                // Device::delete 会递归删除，文件夹只在为空时删除
+                if *is_folder && device.get_object_iterator(object)?.next()?.is_some() {
+                    report_kept(path, "not empty");
+                    continue;
+                }
+                device.delete(object)?;
+                report_removed(path);
+            }
+            MovedSource::Local { path, is_folder } => {
+                if *is_folder {
+                    if std::fs::read_dir(path)?.next().is_some() {
+                        report_kept(&path.display().to_string(), "not empty");
+                        continue;
+                    }
+                    std::fs::remove_dir(path)?;
+                } else {
+                    std::fs::remove_file(path)?;
+                }
+                report_removed(&path.display().to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn report_removed(path: &str) {
+    println!("remove source \"{}\"", path);
+}
+
+fn report_kept(path: &str, reason: &str) {
+    println!("keep source \"{}\" ({})", path, reason);
+}
+
+// 有文件校验失败时返回错误
+fn check_summary(summary: &CopySummary) -> Result<(), Box<dyn std::error::Error>> {
+    if summary.verify_failures.is_empty() {
+        Ok(())
+    } else {
+        Err(MtpError::VerificationFailed(summary.verify_failures.join(", ")).into())
     }
 }
 
@@ -86,31 +176,424 @@ pub fn copy(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::path::PathType;
-    use crate::Paths;
+    use crate::backend::memory::MemoryBackend;
+    use crate::checksum::ChecksumAlgorithm;
+    use crate::common::timestamp::Timestamp;
+    use crate::copy_operate::{ComparePolicy, ConflictPolicy};
+    use crate::copy_operate::filter::FilterRule;
     use std::error::Error;
-    use windows::Win32::System::Com::{COINIT_MULTITHREADED, CoInitializeEx};
+
+    fn create_backend() -> MemoryBackend {
+        let backend = MemoryBackend::with_storage("Phone", "Internal");
+        backend.add_file("Phone:Internal:/test_data/file.txt", b"hello").unwrap();
+        backend.add_file("Phone:Internal:/test_data/sub/a.txt", b"a").unwrap();
+        backend
+    }
 
     #[test]
     fn command_copy_device_to_local() -> Result<(), Box<dyn Error>> {
-        let paths = Paths {
-            src: "device:/test_data/file.txt".to_string(),
-            dest: "dest/test_data/file.txt".to_string(),
-        };
-        let result = copy(&paths, false, false);
-        assert!(result.is_ok());
+        let backend = create_backend();
+        let tempdir = tempfile::tempdir()?;
+        let dest = tempdir.path().join("file.txt");
+        let src = "Phone:Internal:/test_data/file.txt";
+        copy(&backend, src, dest.to_str().unwrap(), &CopyOptions::default())?;
+        assert_eq!(std::fs::read(&dest)?, b"hello");
         Ok(())
     }
 
     #[test]
     fn command_copy_local_to_device() -> Result<(), Box<dyn Error>> {
-        let paths = Paths {
-            src: "C:\\Users\\admin\\java_error_in_gateway64_20100.log".to_string(),
-            dest: "Redmi K70:内部存储设备:/Pictures/file.txt".to_string(),
+        let backend = create_backend();
+        let tempdir = tempfile::tempdir()?;
+        let src = tempdir.path().join("java_error.log");
+        std::fs::write(&src, "error log")?;
+        let src = src.to_str().unwrap();
+        let dest = "Phone:Internal:/test_data/file2.txt";
+        copy(&backend, src, dest, &CopyOptions::default())?;
+        let object = backend.get_object("Phone:Internal:/test_data/file2.txt").unwrap();
+        assert_eq!(object.data, b"error log");
+        Ok(())
+    }
+
+    #[test]
+    fn command_copy_folder_device_to_local_recursive() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        let tempdir = tempfile::tempdir()?;
+        let src = "Phone:Internal:/test_data";
+        let dest = tempdir.path().to_str().unwrap();
+        copy(&backend, src, dest, &CopyOptions { recursive: true, ..Default::default() })?;
+        assert_eq!(std::fs::read(tempdir.path().join("test_data").join("file.txt"))?, b"hello");
+        assert_eq!(std::fs::read(tempdir.path().join("test_data").join("sub").join("a.txt"))?, b"a");
+        Ok(())
+    }
+
+    #[test]
+    fn command_copy_folder_device_to_device_mirror() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        backend.add_file("Phone:Internal:/backup/test_data/stale.txt", b"stale")?;
+        let src = "Phone:Internal:/test_data";
+        let dest = "Phone:Internal:/backup";
+        copy(&backend, src, dest, &CopyOptions { recursive: true, mirror: true, ..Default::default() })?;
+        assert_eq!(
+            backend.list_names("Phone:Internal:/backup/test_data"),
+            Some(vec!["file.txt".to_string(), "sub".to_string()])
+        );
+        assert_eq!(backend.get_object("Phone:Internal:/backup/test_data/sub/a.txt").unwrap().data, b"a");
+        Ok(())
+    }
+
+    #[test]
+    fn command_copy_folder_to_new_name() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        // 目标不存在时以目标的名称创建文件夹，而不是把内容复制到上层文件夹中
+        copy(&backend, "Phone:Internal:/test_data", "Phone:Internal:/renamed", &CopyOptions { recursive: true, ..Default::default() })?;
+        assert_eq!(backend.list_names("Phone:Internal:/renamed"), Some(vec!["file.txt".to_string(), "sub".to_string()]));
+        assert_eq!(backend.list_names("Phone:Internal:/"), Some(vec!["renamed".to_string(), "test_data".to_string()]));
+
+        let tempdir = tempfile::tempdir()?;
+        let dest = tempdir.path().join("renamed");
+        copy(&backend, "Phone:Internal:/test_data", dest.to_str().unwrap(), &CopyOptions { recursive: true, ..Default::default() })?;
+        assert_eq!(std::fs::read(dest.join("sub").join("a.txt"))?, b"a");
+        assert!(!tempdir.path().join("file.txt").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn command_copy_skips_hidden_objects() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        backend.update_object("Phone:Internal:/test_data/sub", |o| o.is_hidden = true)?;
+        let tempdir = tempfile::tempdir()?;
+        let src = "Phone:Internal:/test_data";
+        let dest = tempdir.path().join("out");
+        copy(&backend, src, dest.to_str().unwrap(), &CopyOptions { recursive: true, ..Default::default() })?;
+        assert!(tempdir.path().join("out").join("file.txt").exists());
+        assert!(!tempdir.path().join("out").join("sub").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn command_copy_with_filters() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        backend.add_file("Phone:Internal:/test_data/b.tmp", b"b")?;
+        backend.update_object("Phone:Internal:/test_data/sub", |o| o.is_hidden = true)?;
+        let tempdir = tempfile::tempdir()?;
+        let dest = tempdir.path().join("test_data");
+        std::fs::create_dir(&dest)?;
+        std::fs::write(dest.join("keep.tmp"), b"keep")?;
+        std::fs::write(dest.join("old.txt"), b"old")?;
+        let options = CopyOptions {
+            recursive: true,
+            mirror: true,
+            filters: vec![FilterRule::Include("sub/".to_string()), FilterRule::Exclude("*.tmp".to_string())],
+            include_hidden: true,
+            ..Default::default()
+        };
+        copy(&backend, "Phone:Internal:/test_data", tempdir.path().to_str().unwrap(), &options)?;
+        assert!(dest.join("file.txt").exists());
+        assert!(dest.join("sub").join("a.txt").exists());
+        assert!(!dest.join("b.tmp").exists());
+        // 镜像模式不删除被排除的文件
+        assert_eq!(std::fs::read(dest.join("keep.tmp"))?, b"keep");
+        assert!(!dest.join("old.txt").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn command_copy_wildcard_source() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        let tempdir = tempfile::tempdir()?;
+        let dest = tempdir.path().to_str().unwrap();
+        copy(&backend, "Phone:Internal:/test_data/**/*.txt", dest, &CopyOptions::default())?;
+        assert_eq!(std::fs::read(tempdir.path().join("file.txt"))?, b"hello");
+        assert_eq!(std::fs::read(tempdir.path().join("sub").join("a.txt"))?, b"a");
+
+        let flat = tempdir.path().join("flat");
+        std::fs::create_dir(&flat)?;
+        copy(&backend, "Phone:Internal:/test_data/**/*.txt", flat.to_str().unwrap(), &CopyOptions { flatten: true, ..Default::default() })?;
+        assert_eq!(std::fs::read(flat.join("a.txt"))?, b"a");
+        assert!(!flat.join("sub").exists());
+
+        // 本地通配符复制到设备
+        let music = tempdir.path().join("music");
+        std::fs::create_dir_all(music.join("album"))?;
+        std::fs::write(music.join("album").join("1.flac"), b"1")?;
+        std::fs::write(music.join("cover.jpg"), b"c")?;
+        copy(&backend, &format!("{}/**/*.flac", music.display()), "Phone:Internal:/test_data", &CopyOptions::default())?;
+        assert_eq!(backend.get_object("Phone:Internal:/test_data/album/1.flac").unwrap().data, b"1");
+        assert!(backend.get_object("Phone:Internal:/test_data/cover.jpg").is_none());
+
+        assert!(copy(&backend, "Phone:Internal:/test_data/*.txt", &format!("{}/none", dest), &CopyOptions::default()).is_err());
+        assert!(copy(&backend, "Phone:Internal:/test_data/*.png", dest, &CopyOptions::default()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn command_copy_many_sources() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        let tempdir = tempfile::tempdir()?;
+        let local = tempdir.path().join("local.txt");
+        std::fs::write(&local, b"local")?;
+        let dest = tempdir.path().join("out");
+        std::fs::create_dir(&dest)?;
+        let srcs = ["Phone:Internal:/test_data/file.txt", "Phone:Internal:/test_data/sub", local.to_str().unwrap()];
+        copy_many(&backend, &srcs, dest.to_str().unwrap(), &CopyOptions { recursive: true, ..Default::default() })?;
+        assert_eq!(std::fs::read(dest.join("file.txt"))?, b"hello");
+        assert_eq!(std::fs::read(dest.join("sub").join("a.txt"))?, b"a");
+        assert_eq!(std::fs::read(dest.join("local.txt"))?, b"local");
+
+        // 多个源时目标必须是已存在的文件夹
+        let not_folder = tempdir.path().join("none");
+        assert!(copy_many(&backend, &srcs, not_folder.to_str().unwrap(), &CopyOptions::default()).is_err());
+        assert!(!not_folder.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn command_move() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        backend.add_file("Phone:Internal:/test_data/sub/locked.txt", b"locked")?;
+        backend.update_object("Phone:Internal:/test_data/sub/locked.txt", |o| o.can_delete = false)?;
+        let tempdir = tempfile::tempdir()?;
+        let dest = tempdir.path().to_str().unwrap();
+        let options = CopyOptions { recursive: true, verify: Some(ChecksumAlgorithm::Crc32), ..Default::default() };
+        move_many(&backend, &["Phone:Internal:/test_data"], dest, &options)?;
+        assert_eq!(std::fs::read(tempdir.path().join("test_data").join("sub").join("a.txt"))?, b"a");
+        assert_eq!(std::fs::read(tempdir.path().join("test_data").join("sub").join("locked.txt"))?, b"locked");
+        assert!(backend.get_object("Phone:Internal:/test_data/file.txt").is_none());
+        assert!(backend.get_object("Phone:Internal:/test_data/sub/a.txt").is_none());
+        // 不能删除的文件和它的文件夹保留
+        assert!(backend.get_object("Phone:Internal:/test_data/sub/locked.txt").is_some());
+        assert_eq!(backend.list_names("Phone:Internal:/test_data"), Some(vec!["sub".to_string()]));
+
+        // 移回设备后本地的源被删除
+        let local = tempdir.path().join("test_data");
+        move_many(&backend, &[local.join("file.txt").to_str().unwrap()], "Phone:Internal:/test_data", &options)?;
+        assert_eq!(backend.get_object("Phone:Internal:/test_data/file.txt").unwrap().data, b"hello");
+        assert!(!local.join("file.txt").exists());
+
+        // 复制失败时不删除任何源
+        let sub = local.join("sub");
+        let srcs = [sub.to_str().unwrap(), "Phone:Internal:/no_such_file"];
+        assert!(move_many(&backend, &srcs, "Phone:Internal:/", &options).is_err());
+        assert!(sub.join("a.txt").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn command_move_sources_with_the_same_name() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        backend.add_file("Phone:Internal:/a/IMG.jpg", b"first")?;
+        backend.add_file("Phone:Internal:/b/IMG.jpg", b"other")?;
+        let options = CopyOptions::default();
+        move_many(&backend, &["Phone:Internal:/a/IMG.jpg", "Phone:Internal:/b/IMG.jpg"], "Phone:Internal:/test_data", &options)?;
+        // 第二个源不覆盖第一个源的副本，也不被删除
+        assert_eq!(backend.get_object("Phone:Internal:/test_data/IMG.jpg").unwrap().data, b"first");
+        assert!(backend.get_object("Phone:Internal:/a/IMG.jpg").is_none());
+        assert_eq!(backend.get_object("Phone:Internal:/b/IMG.jpg").unwrap().data, b"other");
+        Ok(())
+    }
+
+    #[test]
+    fn command_copy_with_verification() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        let tempdir = tempfile::tempdir()?;
+        let src = "Phone:Internal:/test_data";
+        let options = CopyOptions {
+            recursive: true,
+            verify: Some(ChecksumAlgorithm::Blake3),
+            ..Default::default()
         };
-        unsafe { CoInitializeEx(Some(std::ptr::null_mut()), COINIT_MULTITHREADED).ok().unwrap(); }
-        let result = copy(&paths, false, false);
-        assert!(result.is_ok());
+        copy(&backend, src, tempdir.path().to_str().unwrap(), &options)?;
+        assert_eq!(std::fs::read(tempdir.path().join("test_data").join("sub").join("a.txt"))?, b"a");
+
+        // 复制回设备
+        let src = tempdir.path().join("test_data");
+        copy(&backend, src.to_str().unwrap(), "Phone:Internal:/backup", &options)?;
+        assert_eq!(backend.get_object("Phone:Internal:/backup/file.txt").unwrap().data, b"hello");
+        Ok(())
+    }
+
+    #[test]
+    fn command_copy_resume_with_journal() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        let tempdir = tempfile::tempdir()?;
+        let journal_path = tempdir.path().join("journal.jsonl");
+        let dest = tempdir.path().join("out");
+        std::fs::create_dir(&dest)?;
+        let src = "Phone:Internal:/test_data";
+        let mut options = CopyOptions {
+            recursive: true,
+            journal: Some(journal_path.clone()),
+            ..Default::default()
+        };
+        copy(&backend, src, dest.to_str().unwrap(), &options)?;
+
+        // 模拟中断: a.txt 开始写入但没有完成
+        let journal = std::fs::read_to_string(&journal_path)?;
+        let started = journal.lines().find(|line| line.contains("a.txt") && line.contains("started")).unwrap();
+        std::fs::write(&journal_path, format!("{}{}\n", journal, started))?;
+        std::fs::write(dest.join("test_data").join("sub").join("a.txt"), b"")?;
+        // 已完成的文件不会再复制
+        std::fs::write(dest.join("test_data").join("file.txt"), b"HELLO")?;
+
+        options.resume = true;
+        copy(&backend, src, dest.to_str().unwrap(), &options)?;
+        assert_eq!(std::fs::read(dest.join("test_data").join("sub").join("a.txt"))?, b"a");
+        assert_eq!(std::fs::read(dest.join("test_data").join("file.txt"))?, b"HELLO");
+
+        // 没有日志文件不能恢复
+        options.journal = None;
+        assert!(copy(&backend, src, dest.to_str().unwrap(), &options).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn command_copy_dry_run_mirror() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        backend.add_file("Phone:Internal:/backup/test_data/file.txt", b"HELLO!")?;
+        backend.add_file("Phone:Internal:/backup/test_data/stale.txt", b"stale")?;
+        let src = "Phone:Internal:/test_data";
+        let dest = "Phone:Internal:/backup";
+        let options = CopyOptions { recursive: true, mirror: true, dry_run: true, ..Default::default() };
+        copy(&backend, src, dest, &options)?;
+        assert_eq!(
+            backend.list_names("Phone:Internal:/backup/test_data"),
+            Some(vec!["file.txt".to_string(), "stale.txt".to_string()])
+        );
+        assert_eq!(backend.get_object("Phone:Internal:/backup/test_data/file.txt").unwrap().data, b"HELLO!");
+        Ok(())
+    }
+
+    #[test]
+    fn command_copy_dry_run_does_not_ask() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        backend.add_file("Phone:Internal:/backup/test_data/file.txt", b"OLD")?;
+        let src = "Phone:Internal:/test_data";
+        let dest = "Phone:Internal:/backup";
+        let options = CopyOptions { recursive: true, dry_run: true, on_conflict: ConflictPolicy::Ask, ..Default::default() };
+        copy(&backend, src, dest, &options)?;
+        assert_eq!(backend.get_object("Phone:Internal:/backup/test_data/file.txt").unwrap().data, b"OLD");
         Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn command_copy_only_changed_files() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        backend.update_object("Phone:Internal:/test_data/file.txt", |o| o.time_modified = Some(Timestamp::from_unix_seconds(1000)))?;
+        backend.update_object("Phone:Internal:/test_data/sub/a.txt", |o| o.time_modified = Some(Timestamp::from_unix_seconds(1000)))?;
+        let tempdir = tempfile::tempdir()?;
+        let src = "Phone:Internal:/test_data";
+        let options = CopyOptions { recursive: true, ..Default::default() };
+        copy(&backend, src, tempdir.path().to_str().unwrap(), &options)?;
+
+        // 大小相同且目标不比源文件旧的文件不会再复制
+        std::fs::write(tempdir.path().join("test_data").join("file.txt"), b"HELLO")?;
+        backend.update_object("Phone:Internal:/test_data/sub/a.txt", |o| o.data = b"b".to_vec())?;
+        backend.update_object("Phone:Internal:/test_data/sub/a.txt", |o| o.time_modified = Some(Timestamp::from_unix_seconds(u32::MAX as i64)))?;
+        copy(&backend, src, tempdir.path().to_str().unwrap(), &options)?;
+        assert_eq!(std::fs::read(tempdir.path().join("test_data").join("file.txt"))?, b"HELLO");
+        assert_eq!(std::fs::read(tempdir.path().join("test_data").join("sub").join("a.txt"))?, b"b");
+
+        copy(&backend, src, tempdir.path().to_str().unwrap(), &CopyOptions { compare: ComparePolicy::Checksum, ..options })?;
+        assert_eq!(std::fs::read(tempdir.path().join("test_data").join("file.txt"))?, b"hello");
+        Ok(())
+    }
+
+    #[test]
+    fn command_copy_preserves_times() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        let created = Some(Timestamp::from_unix_seconds(1500000000));
+        let modified = Some(Timestamp::from_unix_seconds(1600000000));
+        backend.update_object("Phone:Internal:/test_data/file.txt", |o| {
+            o.time_created = created;
+            o.time_modified = modified;
+        })?;
+        let tempdir = tempfile::tempdir()?;
+
+        // 设备到本地
+        let local = tempdir.path().join("file.txt");
+        copy(&backend, "Phone:Internal:/test_data/file.txt", local.to_str().unwrap(), &CopyOptions::default())?;
+        assert_eq!(Some(Timestamp::from_system_time(std::fs::metadata(&local)?.modified()?)), modified);
+
+        // 设备到设备
+        copy(&backend, "Phone:Internal:/test_data/file.txt", "Phone:Internal:/test_data/sub/file.txt", &CopyOptions::default())?;
+        let object = backend.get_object("Phone:Internal:/test_data/sub/file.txt").unwrap();
+        assert_eq!((object.time_created, object.time_modified), (created, modified));
+
+        // 本地到设备
+        let local_modified = Timestamp::from_unix_seconds(1650000000);
+        std::fs::File::options().write(true).open(&local)?.set_modified(local_modified.to_system_time())?;
+        copy(&backend, local.to_str().unwrap(), "Phone:Internal:/test_data/sub/upload.txt", &CopyOptions::default())?;
+        let object = backend.get_object("Phone:Internal:/test_data/sub/upload.txt").unwrap();
+        assert_eq!(object.time_modified, Some(local_modified));
+
+        // 不保留时间
+        let options = CopyOptions { preserve_times: false, ..Default::default() };
+        let local = tempdir.path().join("file2.txt");
+        copy(&backend, "Phone:Internal:/test_data/file.txt", local.to_str().unwrap(), &options)?;
+        assert_ne!(Some(Timestamp::from_system_time(std::fs::metadata(&local)?.modified()?)), modified);
+        copy(&backend, "Phone:Internal:/test_data/file.txt", "Phone:Internal:/test_data/sub/file2.txt", &options)?;
+        let object = backend.get_object("Phone:Internal:/test_data/sub/file2.txt").unwrap();
+        assert_eq!((object.time_created, object.time_modified), (None, None));
+        Ok(())
+    }
+
+    #[test]
+    fn command_copy_on_conflict() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        let tempdir = tempfile::tempdir()?;
+        let dest = tempdir.path().join("test_data");
+        std::fs::create_dir(&dest)?;
+        std::fs::write(dest.join("file.txt"), b"HELLO!")?;
+        let src = "Phone:Internal:/test_data/file.txt";
+        let copy_with = |on_conflict: ConflictPolicy, conflict_overrides: &str| {
+            let options = CopyOptions {
+                on_conflict,
+                conflict_overrides: conflict_overrides.split(',').filter(|s| !s.is_empty()).map(|s| s.parse().unwrap()).collect(),
+                ..Default::default()
+            };
+            copy(&backend, src, dest.join("file.txt").to_str().unwrap(), &options)
+        };
+
+        copy_with(ConflictPolicy::Skip, "")?;
+        copy_with(ConflictPolicy::Larger, "")?;
+        copy_with(ConflictPolicy::Overwrite, "*.db=overwrite,*.txt=skip")?;
+        assert_eq!(std::fs::read(dest.join("file.txt"))?, b"HELLO!");
+
+        copy_with(ConflictPolicy::Rename, "")?;
+        copy_with(ConflictPolicy::Rename, "")?;
+        assert_eq!(std::fs::read(dest.join("file (1).txt"))?, b"hello");
+        assert_eq!(std::fs::read(dest.join("file (2).txt"))?, b"hello");
+
+        // 目标文件比源文件新
+        backend.update_object(src, |o| o.time_modified = Some(Timestamp::from_unix_seconds(1000)))?;
+        copy_with(ConflictPolicy::Newer, "")?;
+        assert_eq!(std::fs::read(dest.join("file.txt"))?, b"HELLO!");
+        backend.update_object(src, |o| o.time_modified = Some(Timestamp::from_unix_seconds(u32::MAX as i64)))?;
+        copy_with(ConflictPolicy::Newer, "")?;
+        assert_eq!(std::fs::read(dest.join("file.txt"))?, b"hello");
+        Ok(())
+    }
+
+    #[test]
+    fn command_copy_source_not_found() {
+        let backend = create_backend();
+        let tempdir = tempfile::tempdir().unwrap();
+        let src = "Phone:Internal:/no_such_file";
+        let dest = tempdir.path().to_str().unwrap();
+        let err = copy(&backend, src, dest, &CopyOptions::default()).err().unwrap();
+        assert_eq!(crate::error::exit_code(err.as_ref()), MtpError::PathNotFound(String::new()).exit_code());
+    }
+
+    #[test]
+    fn command_copy_to_disconnected_device() -> Result<(), Box<dyn Error>> {
+        let backend = create_backend();
+        let tempdir = tempfile::tempdir()?;
+        let src = tempdir.path().join("a.txt");
+        std::fs::write(&src, "hello")?;
+        backend.disconnect();
+        let err = copy(&backend, src.to_str().unwrap(), "Phone:Internal:/test_data/a.txt", &CopyOptions::default()).err().unwrap();
+        assert_eq!(crate::error::exit_code(err.as_ref()), MtpError::Disconnected(String::new()).exit_code());
+        Ok(())
+    }
+}
@@ -1,6 +1,10 @@
-use std::io::{stdout, Write};
+use std::io::{stderr, stdin, stdout, BufRead, Read, Write};
+use crate::checksum::{to_hex, ChecksumAlgorithm, HashingReader};
 use crate::copy_operate::file_info::FileInfo;
 use crate::copy_operate::folder_operate::FolderOperate;
+use crate::copy_operate::journal::JournalEntry;
+use crate::common::timestamp::Timestamp;
+use crate::copy_operate::{ComparePolicy, ConflictPolicy, CopyContext, CopyOptions, VerifyFailureAction};
 
 
 pub trait CopyProcessor {
@@ -9,53 +13,408 @@ pub trait CopyProcessor {
         name: &str,
         dest: &mut impl FolderOperate,
         dest_is_parent_folder: bool,
-        recursive: bool,
-        mirror: bool,
+        options: &CopyOptions,
+        context: &mut CopyContext,
     ) -> Result<(), Box<dyn std::error::Error>>;
 }
 
-pub fn can_skip_copying(src_file_info: &FileInfo, dest_file_info: &FileInfo) -> bool {
-    if src_file_info.data_size == dest_file_info.data_size {
-        // if let Some(src_time) = get_file_time(src_file_info) {
-        //     if let Some(dest_time) = get_file_time(dest_file_info) {
-        //         // use .timestamp() value to ignore the subsecond
-        //         let src_ts = src_time.timestamp();
-        //         let dest_ts = dest_time.timestamp();
-        //         log::debug!(
-        //             "src_time = {:?} ({:?})  dest_time = {:?} ({:?})",
-        //             &src_time,
-        //             &src_ts,
-        //             &dest_time,
-        //             &dest_ts
-        //         );
-        //         return src_ts <= dest_ts;
-        //     }
-        // }
-    }
-    false
-}
-
-// fn get_file_time(file_info: &FileInfo) -> Option<String> {
-//     if let Some(time_created) = file_info.time_created {
-//         if let Some(time_modified) = file_info.time_modified {
-//             Some(std::cmp::max(time_created, time_modified))
-//         } else {
-//             Some(time_created)
-//         }
-//     } else {
-//         if let Some(time_modified) = file_info.time_modified {
-//             Some(time_modified)
-//         } else {
-//             None
-//         }
-//     }
-// }
+// 复制一个文件，src_path 为源文件的完整路径，用于传输日志
+// 日志中已完成且源文件没有变化的文件直接跳过，不再检查目标
+// 日志中开始但未完成的文件是中断时写了一半的文件，删除后重新复制
+// 返回目标中是否有校验过的源文件的副本，只在移动时检查，移动据此决定是否删除源文件
+pub fn copy_file<R, F>(
+    dest: &mut impl FolderOperate,
+    dest_name: &str,
+    src_file_info: &FileInfo,
+    src_path: &str,
+    mut open_source: F,
+    options: &CopyOptions,
+    context: &mut CopyContext,
+) -> Result<bool, Box<dyn std::error::Error>>
+    where
+        R: Read,
+        F: FnMut() -> Result<R, Box<dyn std::error::Error>>,
+{
+    let entry = JournalEntry {
+        source: src_path.to_string(),
+        size: src_file_info.data_size,
+        created: src_file_info.time_created,
+        modified: src_file_info.time_modified,
+        destination: None,
+    };
+    let mut partial = false;
+    if let Some(journal) = context.journal.as_ref() {
+        if journal.is_completed(&entry) {
+            dest.retain(dest_name);
+            return Ok(false);
+        }
+        partial = journal.is_pending(&entry.source);
+    }
+
+    let dest_file_info = dest.get_file_info(dest_name)?;
+
+    // 不覆盖本次复制刚写入的文件，多个源中的同名文件会互相覆盖，移动时会丢失先复制的源
+    if dest_file_info.is_some() && dest.locate(dest_name).is_some_and(|location| context.written.contains(&location)) {
+        if !options.dry_run {
+            report_skip(dest_name, "just written");
+        }
+        dest.retain(dest_name);
+        return Ok(false);
+    }
+
+    // 如果可以跳过复制，则直接返回
+    if let Some(dest_file_info_ref) = dest_file_info.as_ref() {
+        if !partial && is_up_to_date(dest, dest_name, src_file_info, dest_file_info_ref, &mut open_source, options)? {
+            dest.retain(dest_name);
+            // checksum 比较过的文件不需要再次校验
+            let verified = options.compare == ComparePolicy::Checksum;
+            return verify_moved_file(dest, dest_name, src_file_info, &mut open_source, verified, options);
+        }
+    }
+
+    // 目标文件已经存在时按冲突策略处理，写了一半的文件总是删除
+    let mut dest_name = dest_name.to_string();
+    if let Some(dest_file_info_ref) = dest_file_info.as_ref() {
+        let action = if partial {
+            ConflictAction::Overwrite
+        } else {
+            resolve_conflict(dest, &dest_name, src_file_info, dest_file_info_ref, options, context)?
+        };
+        match action {
+            ConflictAction::Overwrite => {
+                if !options.dry_run {
+                    if partial {
+                        report_delete_incomplete_file(&dest_name);
+                    } else {
+                        report_overwrite(&dest_name);
+                    }
+                }
+                dest.delete_file_or_folder(&dest_name)?;
+            }
+            ConflictAction::Skip(reason) => {
+                if !options.dry_run {
+                    report_skip(&dest_name, reason);
+                }
+                dest.retain(&dest_name);
+                return Ok(false);
+            }
+            ConflictAction::Rename(new_name) => {
+                if !options.dry_run {
+                    report_rename(&dest_name, &new_name);
+                }
+                // 保留原有的文件
+                dest.retain(&dest_name);
+                dest_name = new_name;
+            }
+        }
+    }
+    let dest_name = dest_name.as_str();
+
+    if !options.dry_run {
+        report_copying_start(src_file_info);
+    }
+    if let Some(journal) = context.journal.as_mut() {
+        journal.start(&entry)?;
+    }
+
+    // 创建目标文件，不保留时间时由目标决定文件的时间
+    let (created, modified) = if options.preserve_times {
+        (src_file_info.time_created, src_file_info.time_modified)
+    } else {
+        (None, None)
+    };
+    let copied = create_file(
+        dest,
+        dest_name,
+        &mut open_source,
+        src_file_info.data_size,
+        &created,
+        &modified,
+        options,
+    )?;
+    dest.retain(dest_name);
+    if !copied {
+        context.summary.verify_failures.push(dest_name.to_string());
+    }
+    if let Some(location) = dest.locate(dest_name) {
+        context.written.insert(location);
+    }
+
+    // 校验失败的文件不记录为完成，恢复时重新复制
+    if copied {
+        if let Some(journal) = context.journal.as_mut() {
+            journal.complete(&JournalEntry {
+                destination: dest.locate(dest_name),
+                ..entry
+            })?;
+        }
+    }
+    if !options.dry_run {
+        report_copying_end();
+    }
+    if !copied {
+        return Ok(false);
+    }
+    verify_moved_file(dest, dest_name, src_file_info, &mut open_source, options.verify.is_some(), options)
+}
+
+// 移动时确认目标文件和源文件的大小相同，需要校验时比较校验和
+// verified 为复制时已经比较过校验和
+fn verify_moved_file<R, F>(
+    dest: &mut impl FolderOperate,
+    dest_name: &str,
+    src_file_info: &FileInfo,
+    open_source: &mut F,
+    verified: bool,
+    options: &CopyOptions,
+) -> Result<bool, Box<dyn std::error::Error>>
+    where
+        R: Read,
+        F: FnMut() -> Result<R, Box<dyn std::error::Error>>,
+{
+    if !options.move_sources || options.dry_run {
+        return Ok(false);
+    }
+    let size = dest.get_file_info(dest_name)?.map(|info| info.data_size);
+    if size != Some(src_file_info.data_size) {
+        report_keep_source(&src_file_info.name, "size mismatch");
+        return Ok(false);
+    }
+    if let (Some(algorithm), false) = (options.verify, verified) {
+        let expected = algorithm.checksum(&mut open_source()?)?;
+        let actual = algorithm.checksum(dest.open_file(dest_name)?.as_mut())?;
+        if expected != actual {
+            report_keep_source(&src_file_info.name, "checksum mismatch");
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+// 创建文件，open_source 打开源文件，返回文件是否完整，不完整即校验失败
+// 如果需要校验，写入时计算源文件的校验和，写入后从目标读回文件比较
+pub fn create_file<R, F>(
+    dest: &mut impl FolderOperate,
+    dest_name: &str,
+    mut open_source: F,
+    size: u64,
+    created: &Option<Timestamp>,
+    modified: &Option<Timestamp>,
+    options: &CopyOptions,
+) -> Result<bool, Box<dyn std::error::Error>>
+    where
+        R: Read,
+        F: FnMut() -> Result<R, Box<dyn std::error::Error>>,
+{
+    let algorithm = match options.verify {
+        None => {
+            dest.create_file(dest_name, &mut open_source()?, size, created, modified)?;
+            return Ok(true);
+        }
+        Some(algorithm) => algorithm,
+    };
+
+    // retry 时最多复制两次
+    let attempts = if options.on_verify_failure == VerifyFailureAction::Retry { 2 } else { 1 };
+    for attempt in 1..=attempts {
+        let mut reader = HashingReader::new(open_source()?, algorithm);
+        dest.create_file(dest_name, &mut reader, size, created, modified)?;
+        let expected = reader.finish();
+        let actual = algorithm.checksum(dest.open_file(dest_name)?.as_mut())?;
+        if expected == actual {
+            report_verified();
+            return Ok(true);
+        }
+        report_checksum_mismatch(algorithm, &expected, &actual);
+        if attempt < attempts {
+            report_retrying();
+            dest.delete_file_or_folder(dest_name)?;
+        }
+    }
+
+    if options.on_verify_failure == VerifyFailureAction::Delete {
+        dest.delete_file_or_folder(dest_name)?;
+        report_deleted();
+    }
+    Ok(false)
+}
+
+// 目标文件是否已是最新，不需要复制
+// checksum 需要读取源文件和目标文件，其他方式只比较文件信息
+fn is_up_to_date<R, F>(
+    dest: &mut impl FolderOperate,
+    dest_name: &str,
+    src_file_info: &FileInfo,
+    dest_file_info: &FileInfo,
+    open_source: &mut F,
+    options: &CopyOptions,
+) -> Result<bool, Box<dyn std::error::Error>>
+    where
+        R: Read,
+        F: FnMut() -> Result<R, Box<dyn std::error::Error>>,
+{
+    if options.compare != ComparePolicy::Checksum {
+        return Ok(can_skip_copying(src_file_info, dest_file_info, options.compare, options.mtime_tolerance));
+    }
+    if dest_file_info.is_folder || src_file_info.data_size != dest_file_info.data_size {
+        return Ok(false);
+    }
+    let algorithm = options.verify.unwrap_or_default();
+    let src_checksum = algorithm.checksum(&mut open_source()?)?;
+    let dest_checksum = algorithm.checksum(dest.open_file(dest_name)?.as_mut())?;
+    Ok(src_checksum == dest_checksum)
+}
+
+// 根据文件信息判断是否可以跳过复制
+// size-mtime 时源文件不比目标文件新即可跳过，mtime_tolerance 容许 FAT 的 2 秒精度和设备时钟误差
+// 任一方没有修改时间时无法判断，需要复制
+pub fn can_skip_copying(src_file_info: &FileInfo, dest_file_info: &FileInfo, policy: ComparePolicy, mtime_tolerance: u64) -> bool {
+    if dest_file_info.is_folder || src_file_info.data_size != dest_file_info.data_size {
+        return false;
+    }
+    match policy {
+        ComparePolicy::Size => true,
+        ComparePolicy::SizeMtime => match (get_file_time(src_file_info), get_file_time(dest_file_info)) {
+            (Some(src_time), Some(dest_time)) => {
+                log::debug!("src_time = {} dest_time = {}", src_time, dest_time);
+                src_time.unix_millis() <= dest_time.unix_millis().saturating_add(mtime_tolerance.saturating_mul(1000) as i64)
+            }
+            _ => false,
+        },
+        ComparePolicy::Checksum | ComparePolicy::Always => false,
+    }
+}
+
+// 目标文件已存在时的处理
+#[derive(Debug, PartialEq, Eq)]
+enum ConflictAction {
+    Overwrite,
+    // 跳过的原因
+    Skip(&'static str),
+    // 复制到新的名称
+    Rename(String),
+}
+
+// 按目标文件名称的冲突策略决定如何处理已存在的目标文件
+fn resolve_conflict(
+    dest: &mut impl FolderOperate,
+    dest_name: &str,
+    src_file_info: &FileInfo,
+    dest_file_info: &FileInfo,
+    options: &CopyOptions,
+    context: &mut CopyContext,
+) -> Result<ConflictAction, Box<dyn std::error::Error>> {
+    let mut policy = options.conflict_policy(dest_name);
+    if policy == ConflictPolicy::Ask {
+        policy = match context.conflict_answer {
+            Some(answer) => answer,
+            // 演练时不读取输入，保留已存在的文件
+            None if options.dry_run => {
+                println!("would ask \"{}\" (exists)", dest_name);
+                return Ok(ConflictAction::Skip("would ask"));
+            }
+            None => {
+                let (answer, for_all) = ask_conflict_policy(&mut stdin().lock(), dest_name)?;
+                if for_all {
+                    context.conflict_answer = Some(answer);
+                }
+                answer
+            }
+        };
+    }
+    Ok(match policy {
+        ConflictPolicy::Overwrite | ConflictPolicy::Ask => ConflictAction::Overwrite,
+        ConflictPolicy::Skip => ConflictAction::Skip("exists"),
+        ConflictPolicy::Rename => ConflictAction::Rename(find_free_name(dest, dest_name)?),
+        // 不知道哪个更新时不覆盖
+        ConflictPolicy::Newer => match (get_file_time(src_file_info), get_file_time(dest_file_info)) {
+            (Some(src_time), Some(dest_time))
+                if src_time.unix_millis() > dest_time.unix_millis().saturating_add(options.mtime_tolerance.saturating_mul(1000) as i64) =>
+            {
+                ConflictAction::Overwrite
+            }
+            _ => ConflictAction::Skip("not newer"),
+        },
+        ConflictPolicy::Larger => {
+            if src_file_info.data_size > dest_file_info.data_size {
+                ConflictAction::Overwrite
+            } else {
+                ConflictAction::Skip("not larger")
+            }
+        }
+    })
+}
+
+// 询问如何处理已存在的文件，返回选择的策略和是否用于之后所有的冲突
+// 没有输入时跳过
+fn ask_conflict_policy(input: &mut impl BufRead, name: &str) -> Result<(ConflictPolicy, bool), Box<dyn std::error::Error>> {
+    loop {
+        eprint!("\"{}\" exists, [o]verwrite, [s]kip or [r]ename? (upper case for all) ", name);
+        stderr().flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            eprintln!();
+            return Ok((ConflictPolicy::Skip, false));
+        }
+        let answer = line.trim();
+        let policy = match answer.to_lowercase().as_str() {
+            "o" | "overwrite" => ConflictPolicy::Overwrite,
+            "s" | "skip" => ConflictPolicy::Skip,
+            "r" | "rename" => ConflictPolicy::Rename,
+            _ => continue,
+        };
+        let for_all = answer.chars().next().is_some_and(|c| c.is_uppercase());
+        return Ok((policy, for_all));
+    }
+}
+
+// 第一个不存在的 "name (n).ext"
+fn find_free_name(dest: &mut impl FolderOperate, name: &str) -> Result<String, Box<dyn std::error::Error>> {
+    for number in 1.. {
+        let candidate = numbered_name(name, number);
+        if dest.get_file_info(&candidate)?.is_none() {
+            return Ok(candidate);
+        }
+    }
+    unreachable!()
+}
+
+// "IMG_0001.jpg" -> "IMG_0001 (1).jpg"，以 '.' 开头的名称 (如 ".profile") 没有扩展名
+fn numbered_name(name: &str, number: u32) -> String {
+    match name.rfind('.') {
+        Some(index) if index > 0 => format!("{} ({}){}", &name[..index], number, &name[index..]),
+        _ => format!("{} ({})", name, number),
+    }
+}
+
+// 修改时间，没有时使用创建时间
+fn get_file_time(file_info: &FileInfo) -> Option<Timestamp> {
+    file_info.time_modified.or(file_info.time_created)
+}
 
 pub fn report_copying_start(src_file_info: &FileInfo) {
     print!("copying \"{}\" ...", src_file_info.name);
     stdout().flush().unwrap();
 }
 
+pub fn report_verified() {
+    print!(" verified");
+}
+
+pub fn report_checksum_mismatch(algorithm: ChecksumAlgorithm, expected: &[u8], actual: &[u8]) {
+    print!(" {} mismatch (source {}, destination {})", algorithm.name(), to_hex(expected), to_hex(actual));
+}
+
+pub fn report_retrying() {
+    print!(", retrying ...");
+    stdout().flush().unwrap();
+}
+
+pub fn report_deleted() {
+    print!(", deleted");
+}
+
+#[allow(clippy::println_empty_string)]
 pub fn report_copying_end() {
     println!("");
 }
@@ -71,3 +430,192 @@ pub fn report_delete_file(name: &str) {
 pub fn report_delete_folder(name: &str) {
     println!("delete folder \"{}\"", name);
 }
+
+// 镜像时保留被过滤掉的目标文件和文件夹，relative_path 为文件夹的相对路径
+pub fn retain_excluded(dest: &mut impl FolderOperate, relative_path: &str, context: &CopyContext) -> Result<(), Box<dyn std::error::Error>> {
+    for file_info in dest.unretained()? {
+        let child_path = format!("{}/{}", relative_path, file_info.name);
+        if context.filter.is_excluded(&child_path, file_info.is_folder, file_info.is_hidden, file_info.is_system) {
+            dest.retain(&file_info.name);
+        }
+    }
+    Ok(())
+}
+
+pub fn report_delete_incomplete_file(name: &str) {
+    println!("delete incomplete file \"{}\"", name);
+}
+
+pub fn report_overwrite(name: &str) {
+    println!("overwrite \"{}\"", name);
+}
+
+pub fn report_skip(name: &str, reason: &str) {
+    println!("skip \"{}\" ({})", name, reason);
+}
+
+pub fn report_rename(name: &str, new_name: &str) {
+    println!("rename \"{}\" to \"{}\"", name, new_name);
+}
+
+pub fn report_keep_source(name: &str, reason: &str) {
+    println!("keep source \"{}\" ({})", name, reason);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use test_case::test_case;
+    use crate::copy_operate::local_folder_imp::LocalFolder;
+
+    // 前 corrupt_count 次写入时修改第一个字节
+    struct CorruptingFolder {
+        inner: LocalFolder,
+        corrupt_count: u32,
+    }
+
+    impl FolderOperate for CorruptingFolder {
+        fn get_file_info(&mut self, name: &str) -> Result<Option<FileInfo>, Box<dyn std::error::Error>> {
+            self.inner.get_file_info(name)
+        }
+
+        fn create_file(
+            &mut self,
+            name: &str,
+            reader: &mut dyn Read,
+            size: u64,
+            created: &Option<Timestamp>,
+            modified: &Option<Timestamp>,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            let mut data = Vec::<u8>::new();
+            reader.read_to_end(&mut data)?;
+            if self.corrupt_count > 0 {
+                self.corrupt_count -= 1;
+                data[0] ^= 0xFF;
+            }
+            self.inner.create_file(name, &mut data.as_slice(), size, created, modified)
+        }
+
+        fn open_file(&mut self, name: &str) -> Result<Box<dyn Read + '_>, Box<dyn std::error::Error>> {
+            self.inner.open_file(name)
+        }
+
+        fn open_or_create_folder<FBeforeOpen, FBeforeCreate>(
+            &mut self,
+            _name: &str,
+            _before_open: FBeforeOpen,
+            _before_create: FBeforeCreate,
+        ) -> Result<Box<Self>, Box<dyn std::error::Error>>
+            where
+                FBeforeOpen: FnOnce(&str),
+                FBeforeCreate: FnOnce(&str),
+        {
+            Err("not supported".into())
+        }
+
+        fn delete_file_or_folder(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+            self.inner.delete_file_or_folder(name)
+        }
+
+        fn retain(&mut self, name: &str) {
+            self.inner.retain(name)
+        }
+
+        fn locate(&self, name: &str) -> Option<String> {
+            self.inner.locate(name)
+        }
+
+        fn unretained(&mut self) -> Result<Vec<FileInfo>, Box<dyn std::error::Error>> {
+            self.inner.unretained()
+        }
+
+        fn delete_unretained<FBeforeDeleteFile, FBeforeDeleteFolder>(
+            &mut self,
+            before_delete_file: FBeforeDeleteFile,
+            before_delete_folder: FBeforeDeleteFolder,
+        ) -> Result<(), Box<dyn std::error::Error>>
+            where
+                FBeforeDeleteFile: Fn(&str),
+                FBeforeDeleteFolder: Fn(&str),
+        {
+            self.inner.delete_unretained(before_delete_file, before_delete_folder)
+        }
+    }
+
+    fn file_info(size: u64, modified: Option<i64>) -> FileInfo {
+        FileInfo {
+            name: "a.jpg".to_string(),
+            data_size: size,
+            is_folder: false,
+            is_hidden: false,
+            is_system: false,
+            can_delete: true,
+            time_created: None,
+            time_modified: modified.map(Timestamp::from_unix_seconds),
+        }
+    }
+
+    #[test_case(ComparePolicy::Size, 3, Some(1000) => true; "size")]
+    #[test_case(ComparePolicy::Size, 4, Some(1000) => false; "size changed")]
+    #[test_case(ComparePolicy::SizeMtime, 3, Some(1000) => true; "same mtime")]
+    #[test_case(ComparePolicy::SizeMtime, 3, Some(1001) => true; "fat resolution")]
+    #[test_case(ComparePolicy::SizeMtime, 3, Some(1003) => false; "source is newer")]
+    #[test_case(ComparePolicy::SizeMtime, 3, Some(900) => true; "destination is newer")]
+    #[test_case(ComparePolicy::SizeMtime, 3, None => false; "no mtime")]
+    #[test_case(ComparePolicy::SizeMtime, 4, Some(1000) => false; "size and mtime changed")]
+    #[test_case(ComparePolicy::Checksum, 3, Some(1000) => false; "checksum needs the content")]
+    #[test_case(ComparePolicy::Always, 3, Some(1000) => false; "always")]
+    fn test_can_skip_copying(policy: ComparePolicy, src_size: u64, src_modified: Option<i64>) -> bool {
+        let dest = file_info(3, Some(1000));
+        can_skip_copying(&file_info(src_size, src_modified), &dest, policy, 2)
+    }
+
+    #[test_case("IMG_0001.jpg", 1 => "IMG_0001 (1).jpg"; "extension")]
+    #[test_case("archive.tar.gz", 2 => "archive.tar (2).gz"; "last extension")]
+    #[test_case("README", 1 => "README (1)"; "no extension")]
+    #[test_case(".profile", 1 => ".profile (1)"; "dot file")]
+    fn test_numbered_name(name: &str, number: u32) -> String {
+        numbered_name(name, number)
+    }
+
+    #[test_case("o\n" => (ConflictPolicy::Overwrite, false); "overwrite")]
+    #[test_case("S\n" => (ConflictPolicy::Skip, true); "skip all")]
+    #[test_case("x\nrename\n" => (ConflictPolicy::Rename, false); "ask again")]
+    #[test_case("" => (ConflictPolicy::Skip, false); "no input")]
+    fn test_ask_conflict_policy(input: &str) -> (ConflictPolicy, bool) {
+        ask_conflict_policy(&mut input.as_bytes(), "a.jpg").unwrap()
+    }
+
+    #[test_case(b"abc" => true; "same content")]
+    #[test_case(b"abd" => false; "changed content")]
+    fn test_is_up_to_date_with_checksum(src: &'static [u8]) -> bool {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("a.jpg"), b"abc").unwrap();
+        let mut dest = LocalFolder::new(PathBuf::from(tempdir.path()));
+        let options = CopyOptions { compare: ComparePolicy::Checksum, ..Default::default() };
+        let dest_file_info = dest.get_file_info("a.jpg").unwrap().unwrap();
+        is_up_to_date(&mut dest, "a.jpg", &file_info(3, None), &dest_file_info, &mut || Ok(src), &options).unwrap()
+    }
+
+    // 返回值: 校验是否失败，目标文件是否存在
+    #[test_case(VerifyFailureAction::Report, 0 => (false, true); "no corruption")]
+    #[test_case(VerifyFailureAction::Report, 1 => (true, true); "report")]
+    #[test_case(VerifyFailureAction::Retry, 1 => (false, true); "retry succeeds")]
+    #[test_case(VerifyFailureAction::Retry, 2 => (true, true); "retry fails")]
+    #[test_case(VerifyFailureAction::Delete, 1 => (true, false); "delete")]
+    fn test_create_file_with_verification(action: VerifyFailureAction, corrupt_count: u32) -> (bool, bool) {
+        let tempdir = tempfile::tempdir().unwrap();
+        let mut dest = CorruptingFolder {
+            inner: LocalFolder::new(PathBuf::from(tempdir.path())),
+            corrupt_count,
+        };
+        let options = CopyOptions {
+            verify: Some(ChecksumAlgorithm::Crc32),
+            on_verify_failure: action,
+            ..Default::default()
+        };
+        let copied = create_file(&mut dest, "a.txt", || Ok(&b"hello"[..]), 5, &None, &None, &options).unwrap();
+        (!copied, tempdir.path().join("a.txt").exists())
+    }
+}
@@ -1,115 +1,111 @@
-use crate::copy_operate::copy_processor::{can_skip_copying, CopyProcessor, report_copying_end, report_copying_start, report_creating_new_folder, report_delete_file, report_delete_folder};
+use crate::copy_operate::copy_processor::{copy_file as copy_file_to, retain_excluded, CopyProcessor, report_creating_new_folder, report_delete_file, report_delete_folder};
 use crate::copy_operate::folder_operate::FolderOperate;
-use crate::wpd::device::{ContentObjectInfo, Device};
+use crate::copy_operate::{CopyContext, CopyOptions, MovedSource};
+use crate::backend::{ContentObjectInfo, DeviceOperate};
 use super::file_info::FileInfo;
 
 pub struct DeviceCopyProcessor<'d> {
-    device: &'d Device,
+    device: &'d dyn DeviceOperate,
     source_root_object_info: ContentObjectInfo,
+    // 源对象的完整路径
+    source_root_path: String,
 }
 
 impl<'d> DeviceCopyProcessor<'d> {
-    pub fn new(device: &'d Device, source_root_object_info: ContentObjectInfo) -> Self {
+    pub fn new(device: &'d dyn DeviceOperate, source_root_object_info: ContentObjectInfo, source_root_path: String) -> Self {
         Self {
             device,
             source_root_object_info,
+            source_root_path,
         }
     }
 }
 
+// 正在复制的源对象，path 为完整路径，用于传输日志，relative_path 从复制的根对象开始，用于过滤
+struct SourceObject<'a> {
+    object_info: &'a ContentObjectInfo,
+    path: &'a str,
+    relative_path: &'a str,
+}
+
 impl<'d> CopyProcessor for DeviceCopyProcessor<'d> {
     fn copy(
         &self,
         name: &str,
         dest: &mut impl FolderOperate,
         dest_is_parent_folder: bool,
-        recursive: bool,
-        mirror: bool,
+        options: &CopyOptions,
+        context: &mut CopyContext,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        copy_iter(
-            self.device,
-            dest,
-            dest_is_parent_folder,
-            &self.source_root_object_info,
-            name,
-            recursive,
-            mirror,
-        )
+        let source = SourceObject {
+            object_info: &self.source_root_object_info,
+            path: &self.source_root_path,
+            relative_path: &self.source_root_object_info.name,
+        };
+        copy_iter(self.device, dest, dest_is_parent_folder, &source, name, options, context)
     }
 }
 
 fn copy_iter(
-    device: &Device,
+    device: &dyn DeviceOperate,
     dest: &mut impl FolderOperate,
     dest_is_parent_folder: bool,
-    target_object_info: &ContentObjectInfo,
+    source: &SourceObject,
     dest_name: &str,
-    recursive: bool,
-    mirror: bool,
+    options: &CopyOptions,
+    context: &mut CopyContext,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // 过滤系统文件和隐藏文件
-    if target_object_info.is_system || target_object_info.is_hidden {
+    let target_object_info = source.object_info;
+    // 过滤文件，默认不复制系统文件和隐藏文件
+    if context.filter.is_excluded(source.relative_path, target_object_info.is_folder(), target_object_info.is_hidden, target_object_info.is_system) {
         return Ok(());
     }
     // 根据对象类型决定复制逻辑
     if target_object_info.is_file() {
-        copy_file(device, dest, target_object_info, dest_name)?;
+        copy_file(device, dest, target_object_info, source.path, dest_name, options, context)?;
     } else if target_object_info.is_folder() {
-        copy_folder(device, dest, dest_is_parent_folder, target_object_info, dest_name, recursive, mirror)?;
+        copy_folder(device, dest, dest_is_parent_folder, source, dest_name, options, context)?;
     }
     Ok(())
 }
 
 // 复制文件的逻辑
 fn copy_file(
-    device: &Device,
+    device: &dyn DeviceOperate,
     dest: &mut impl FolderOperate,
     target_object_info: &ContentObjectInfo,
-    dest_name: &str
+    src_path: &str,
+    dest_name: &str,
+    options: &CopyOptions,
+    context: &mut CopyContext,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let src_file_info = FileInfo::from_content_object_info(target_object_info)?;
-    let dest_file_info = dest.get_file_info(dest_name)?;
-
-    // 如果可以跳过复制，则直接返回
-    if let Some(dest_file_info_ref) = dest_file_info.as_ref() {
-        if can_skip_copying(&src_file_info, dest_file_info_ref) {
-            dest.retain(dest_name);
-            return Ok(());
-        }
-    }
-
-    // 如果目标文件已经存在，先删除它
-    if dest_file_info.is_some() {
-        dest.delete_file_or_folder(dest_name)?;
-    }
-
-    let mut res_reader = device.get_resoure(&target_object_info.content_object)?;
-    report_copying_start(&src_file_info);
-
-    // 创建目标文件
-    dest.create_file(
+    let verified = copy_file_to(
+        dest,
         dest_name,
-        &mut res_reader,
-        src_file_info.data_size,
-        &target_object_info.time_created,
-        &target_object_info.time_modified,
+        &src_file_info,
+        src_path,
+        || device.get_resoure(&target_object_info.content_object),
+        options,
+        context,
     )?;
-
-    dest.retain(dest_name);
-    report_copying_end();
+    if verified {
+        context.summary.moved.push(moved_source(target_object_info, src_path));
+    }
     Ok(())
 }
 
 // 复制文件夹的逻辑
 fn copy_folder(
-    device: &Device,
+    device: &dyn DeviceOperate,
     dest: &mut impl FolderOperate,
     dest_is_parent_folder: bool,
-    target_object_info: &ContentObjectInfo,
+    source: &SourceObject,
     dest_name: &str,
-    recursive: bool,
-    mirror: bool
+    options: &CopyOptions,
+    context: &mut CopyContext,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let target_object_info = source.object_info;
     let new_dest_ref;
     let mut new_dest;
     // 如果目标是父文件夹，则在目标中创建一个新文件夹
@@ -123,26 +119,48 @@ fn copy_folder(
     }
 
     // 如果是递归复制
-    if recursive {
+    if options.recursive {
         let mut iter = device.get_object_iterator(&target_object_info.content_object)?;
         while let Some(content_object) = iter.next()? {
             let content_object_info = device.get_object_info(content_object)?;
+            let child_path = format!("{}\\{}", source.path.trim_end_matches('\\'), content_object_info.name);
+            let child_relative_path = format!("{}/{}", source.relative_path, content_object_info.name);
+            let child = SourceObject {
+                object_info: &content_object_info,
+                path: &child_path,
+                relative_path: &child_relative_path,
+            };
             copy_iter(
                 device,
                 new_dest_ref,
                 true, // dest_is_parent_folder
-                &content_object_info,
+                &child,
                 &content_object_info.name,
-                recursive,
-                mirror,
+                options,
+                context,
             )?;
         }
 
         // 如果启用了镜像模式，多余的文件和文件夹将被删除,经过上面的递归中会去标记保留的文件和文件夹.
-        if mirror {
+        if options.mirror {
+            retain_excluded(new_dest_ref, source.relative_path, context)?;
             new_dest_ref.delete_unretained(report_delete_file, report_delete_folder)?;
         }
+
+        // 移动时文件夹在其内容之后删除
+        if options.move_sources && !options.dry_run {
+            context.summary.moved.push(moved_source(target_object_info, source.path));
+        }
     }
 
     Ok(())
 }
+
+fn moved_source(object_info: &ContentObjectInfo, src_path: &str) -> MovedSource {
+    MovedSource::Device {
+        path: src_path.to_string(),
+        object: object_info.content_object.clone(),
+        is_folder: object_info.is_folder(),
+        can_delete: object_info.can_delete,
+    }
+}
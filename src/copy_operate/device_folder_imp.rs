@@ -1,13 +1,15 @@
 use std::collections::{HashMap, HashSet};
-use windows::core::Error;
-use crate::common::file_reader::FileReader;
+use std::io::Read;
+use crate::backend::{ContentObjectInfo, DeviceOperate, ObjectWriter};
+use crate::common::file_reader::copy_with_buffer;
+use crate::common::timestamp::Timestamp;
+use crate::error::MtpError;
 use crate::copy_operate::folder_operate::FolderOperate;
-use crate::wpd::device::{ContentObjectInfo, Device};
 use super::file_info::FileInfo;
 
 // 调用resource_stream中的FileReader
 pub struct DeviceFolder<'d> {
-    device: &'d Device,
+    device: &'d dyn DeviceOperate,
     // 文件夹对象信息
     folder_object_info: ContentObjectInfo,
     // 文件夹下所有的文件 key: 文件名/文件夹名，value: 文件信息
@@ -18,7 +20,7 @@ pub struct DeviceFolder<'d> {
 
 impl<'d> DeviceFolder<'d> {
     // 给某个设备的某个文件夹创建一个新的DeviceFolder对象
-    pub fn new(device: &'d Device, folder_object_info: ContentObjectInfo) -> Result<DeviceFolder<'d>, Box<dyn std::error::Error>> {
+    pub fn new(device: &'d dyn DeviceOperate, folder_object_info: ContentObjectInfo) -> Result<DeviceFolder<'d>, Box<dyn std::error::Error>> {
         let mut iter = device.get_object_iterator(&folder_object_info.content_object)?;
         let mut entry_map = HashMap::<String, ContentObjectInfo>::new();
         // 遍历文件夹中的对象
@@ -50,24 +52,23 @@ impl<'d> FolderOperate for DeviceFolder<'d> {
     fn create_file(
         &mut self,
         name: &str,
-        reader: &mut impl FileReader,
+        reader: &mut dyn Read,
         size: u64,
-        created: &Option<String>,
-        modified: &Option<String>,
+        created: &Option<Timestamp>,
+        modified: &Option<Timestamp>,
     ) -> Result<(), Box<dyn std::error::Error>> {
         // 创建文件
-        let mut resource_writer = self.device.create_file(
+        let mut resource_writer = ObjectWriter::new(self.device.create_file(
             &self.folder_object_info.content_object,
             name,
             size,
             created,
             modified,
-        )?;
+        )?);
 
         // 循环读取并写入数据
-        while let Some(bytes) = reader.seek(resource_writer.get_buffer_size())? {
-            resource_writer.write(bytes)?;
-        }
+        let buffer_size = resource_writer.buffer_size();
+        copy_with_buffer(reader, &mut resource_writer, buffer_size)?;
 
         // 提交资源并获取内容对象
         let content_object = resource_writer.commit()?;
@@ -79,6 +80,13 @@ impl<'d> FolderOperate for DeviceFolder<'d> {
         Ok(())
     }
 
+    fn open_file(&mut self, name: &str) -> Result<Box<dyn Read + '_>, Box<dyn std::error::Error>> {
+        match self.entry_map.get(name) {
+            Some(object_info) if object_info.is_file() => Ok(Box::new(self.device.get_resoure(&object_info.content_object)?)),
+            _ => Err(MtpError::PathNotFound(name.to_string()).into()),
+        }
+    }
+
     fn open_or_create_folder<FBeforeOpen, FBeforeCreate>(
         &mut self,
         name: &str,
@@ -115,6 +123,19 @@ impl<'d> FolderOperate for DeviceFolder<'d> {
         self.retained.insert(String::from(name));
     }
 
+    fn locate(&self, name: &str) -> Option<String> {
+        self.entry_map.get(name).map(|object_info| object_info.content_object.id.clone())
+    }
+
+    fn unretained(&mut self) -> Result<Vec<FileInfo>, Box<dyn std::error::Error>> {
+        self.entry_map.iter()
+            .filter(|(name, object_info)| {
+                (object_info.is_file() || object_info.is_folder()) && !self.retained.contains(*name)
+            })
+            .map(|(_, object_info)| FileInfo::from_content_object_info(object_info))
+            .collect()
+    }
+
     fn delete_unretained<FBeforeDeleteFile, FBeforeDeleteFolder>(
         &mut self,
         before_delete_file: FBeforeDeleteFile,
@@ -124,7 +145,7 @@ impl<'d> FolderOperate for DeviceFolder<'d> {
             FBeforeDeleteFile: Fn(&str),
             FBeforeDeleteFolder: Fn(&str),
     {
-        let mut delete_error: Option<Error> = None;
+        let mut delete_error: Option<Box<dyn std::error::Error>> = None;
         let names_to_delete: Vec<String> = self.entry_map.iter()
             .filter_map(|(name, object_info)| {
                 if (object_info.is_file() || object_info.is_folder()) && !self.retained.contains(name) {
@@ -153,6 +174,6 @@ impl<'d> FolderOperate for DeviceFolder<'d> {
         }
 
         // 处理删除错误
-        delete_error.map_or(Ok(()), |err| Err(err.into()))
+        delete_error.map_or(Ok(()), Err)
     }
 }
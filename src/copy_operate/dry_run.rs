@@ -0,0 +1,256 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt;
+use std::io::Read;
+use std::rc::Rc;
+use crate::common::timestamp::Timestamp;
+use crate::copy_operate::file_info::FileInfo;
+use crate::copy_operate::folder_operate::FolderOperate;
+
+// 演练模式，复制流程不变，只记录目标文件夹上的操作，不修改任何文件
+
+/// The actions a dry run would take.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CopyPlan {
+    /// Number of the files to create
+    pub files_created: u64,
+    /// Bytes of the files to create
+    pub bytes_created: u64,
+    /// Number of the existing files to overwrite
+    pub files_overwritten: u64,
+    /// Bytes of the files to overwrite
+    pub bytes_overwritten: u64,
+    /// Number of the files that are up to date
+    pub files_skipped: u64,
+    /// Bytes of the files that are up to date
+    pub bytes_skipped: u64,
+    /// Number of the folders to create
+    pub folders_created: u64,
+    /// Number of the files to delete
+    pub files_deleted: u64,
+    /// Bytes of the files to delete
+    pub bytes_deleted: u64,
+    /// Number of the folders to delete
+    pub folders_deleted: u64,
+}
+
+impl fmt::Display for CopyPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<9} {:>8} files {:>14} bytes", "create", self.files_created, self.bytes_created)?;
+        writeln!(f, "{:<9} {:>8} files {:>14} bytes", "overwrite", self.files_overwritten, self.bytes_overwritten)?;
+        writeln!(f, "{:<9} {:>8} files {:>14} bytes", "skip", self.files_skipped, self.bytes_skipped)?;
+        writeln!(f, "{:<9} {:>8} files {:>14} bytes", "delete", self.files_deleted, self.bytes_deleted)?;
+        writeln!(f, "{:<9} {:>8} folders", "create", self.folders_created)?;
+        write!(f, "{:<9} {:>8} folders", "delete", self.folders_deleted)
+    }
+}
+
+/// A folder that records the changes instead of making them.
+pub struct DryRunFolder<F: FolderOperate> {
+    // 实际的目标文件夹，None 表示文件夹还不存在，将被创建
+    inner: Option<Box<F>>,
+    plan: Rc<RefCell<CopyPlan>>,
+    retained: HashSet<String>,
+    // 将被删除的文件或文件夹
+    deleted: HashSet<String>,
+    // 将被写入的文件
+    written: HashSet<String>,
+}
+
+impl<F: FolderOperate> DryRunFolder<F> {
+    pub fn new(inner: F) -> DryRunFolder<F> {
+        DryRunFolder::with_plan(Some(Box::new(inner)), Rc::new(RefCell::new(CopyPlan::default())))
+    }
+
+    fn with_plan(inner: Option<Box<F>>, plan: Rc<RefCell<CopyPlan>>) -> DryRunFolder<F> {
+        DryRunFolder {
+            inner,
+            plan,
+            retained: HashSet::new(),
+            deleted: HashSet::new(),
+            written: HashSet::new(),
+        }
+    }
+
+    /// The actions recorded so far, in this folder and its sub folders.
+    pub fn plan(&self) -> CopyPlan {
+        self.plan.borrow().clone()
+    }
+
+    // 目标文件夹中现有的文件或文件夹
+    fn existing_file_info(&mut self, name: &str) -> Result<Option<FileInfo>, Box<dyn std::error::Error>> {
+        match self.inner.as_mut() {
+            Some(inner) if !self.deleted.contains(name) => inner.get_file_info(name),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl<F: FolderOperate> FolderOperate for DryRunFolder<F> {
+    fn get_file_info(&mut self, name: &str) -> Result<Option<FileInfo>, Box<dyn std::error::Error>> {
+        self.existing_file_info(name)
+    }
+
+    // 不读取源文件，只记录
+    fn create_file(
+        &mut self,
+        name: &str,
+        _reader: &mut dyn Read,
+        size: u64,
+        _created: &Option<Timestamp>,
+        _modified: &Option<Timestamp>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut plan = self.plan.borrow_mut();
+        if self.deleted.remove(name) {
+            println!("overwrite file \"{}\" ({} bytes)", name, size);
+            plan.files_overwritten += 1;
+            plan.bytes_overwritten += size;
+        } else {
+            println!("create file \"{}\" ({} bytes)", name, size);
+            plan.files_created += 1;
+            plan.bytes_created += size;
+        }
+        self.written.insert(name.to_string());
+        Ok(())
+    }
+
+    // 只能读取现有的文件
+    fn open_file(&mut self, name: &str) -> Result<Box<dyn Read + '_>, Box<dyn std::error::Error>> {
+        match self.inner.as_mut() {
+            Some(inner) if !self.deleted.contains(name) && !self.written.contains(name) => inner.open_file(name),
+            _ => Err(format!("cannot read \"{}\" in a dry run.", name).into()),
+        }
+    }
+
+    fn open_or_create_folder<FBeforeOpen, FBeforeCreate>(
+        &mut self,
+        name: &str,
+        before_open: FBeforeOpen,
+        before_create: FBeforeCreate,
+    ) -> Result<Box<Self>, Box<dyn std::error::Error>>
+        where
+            FBeforeOpen: FnOnce(&str),
+            FBeforeCreate: FnOnce(&str),
+    {
+        let is_folder = self.existing_file_info(name)?.is_some_and(|info| info.is_folder);
+        let inner = match self.inner.as_mut() {
+            // 现有的文件夹可以打开，不会创建
+            Some(inner) if is_folder => Some(inner.open_or_create_folder(name, before_open, |_| {})?),
+            _ => {
+                before_create(name);
+                self.plan.borrow_mut().folders_created += 1;
+                None
+            }
+        };
+        Ok(Box::new(DryRunFolder::with_plan(inner, self.plan.clone())))
+    }
+
+    fn delete_file_or_folder(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        // 复制前删除现有文件，创建时记为覆盖
+        self.deleted.insert(name.to_string());
+        Ok(())
+    }
+
+    fn retain(&mut self, name: &str) {
+        self.retained.insert(name.to_string());
+        if self.written.contains(name) {
+            return;
+        }
+        // 保留现有文件而没有写入，即文件被跳过
+        if let Ok(Some(file_info)) = self.existing_file_info(name) {
+            if !file_info.is_folder {
+                println!("skip file \"{}\"", name);
+                let mut plan = self.plan.borrow_mut();
+                plan.files_skipped += 1;
+                plan.bytes_skipped += file_info.data_size;
+            }
+        }
+    }
+
+    fn locate(&self, name: &str) -> Option<String> {
+        self.inner.as_ref().and_then(|inner| inner.locate(name))
+    }
+
+    fn unretained(&mut self) -> Result<Vec<FileInfo>, Box<dyn std::error::Error>> {
+        let retained = &self.retained;
+        match self.inner.as_mut() {
+            Some(inner) => Ok(inner.unretained()?.into_iter().filter(|info| !retained.contains(&info.name)).collect()),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    fn delete_unretained<FBeforeDeleteFile, FBeforeDeleteFolder>(
+        &mut self,
+        before_delete_file: FBeforeDeleteFile,
+        before_delete_folder: FBeforeDeleteFolder,
+    ) -> Result<(), Box<dyn std::error::Error>>
+        where
+            FBeforeDeleteFile: Fn(&str),
+            FBeforeDeleteFolder: Fn(&str),
+    {
+        for file_info in self.unretained()? {
+            let mut plan = self.plan.borrow_mut();
+            if file_info.is_folder {
+                before_delete_folder(&file_info.name);
+                plan.folders_deleted += 1;
+            } else {
+                before_delete_file(&file_info.name);
+                plan.files_deleted += 1;
+                plan.bytes_deleted += file_info.data_size;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::copy_operate::local_folder_imp::LocalFolder;
+
+    #[test]
+    fn test_dry_run_folder() -> Result<(), Box<dyn std::error::Error>> {
+        let tempdir = tempfile::tempdir()?;
+        std::fs::write(tempdir.path().join("same.txt"), b"same")?;
+        std::fs::write(tempdir.path().join("old.txt"), b"old")?;
+        std::fs::write(tempdir.path().join("stale.txt"), b"stale")?;
+        std::fs::create_dir(tempdir.path().join("stale"))?;
+
+        let mut folder = DryRunFolder::new(LocalFolder::new(PathBuf::from(tempdir.path())));
+        folder.retain("same.txt");
+        folder.delete_file_or_folder("old.txt")?;
+        assert!(folder.get_file_info("old.txt")?.is_none());
+        folder.create_file("old.txt", &mut &b"new"[..], 3, &None, &None)?;
+        folder.retain("old.txt");
+        folder.create_file("new.txt", &mut &b"new!"[..], 4, &None, &None)?;
+        folder.retain("new.txt");
+        let mut sub = folder.open_or_create_folder("sub", |_| {}, |_| {})?;
+        sub.create_file("a.txt", &mut &b"a"[..], 1, &None, &None)?;
+        sub.retain("a.txt");
+        sub.delete_unretained(|_| {}, |_| {})?;
+        folder.retain("sub");
+        folder.delete_unretained(|_| {}, |_| {})?;
+
+        assert_eq!(folder.plan(), CopyPlan {
+            files_created: 2,
+            bytes_created: 5,
+            files_overwritten: 1,
+            bytes_overwritten: 3,
+            files_skipped: 1,
+            bytes_skipped: 4,
+            folders_created: 1,
+            files_deleted: 1,
+            bytes_deleted: 5,
+            folders_deleted: 1,
+        });
+
+        // 没有修改任何文件
+        assert_eq!(std::fs::read(tempdir.path().join("old.txt"))?, b"old");
+        assert!(tempdir.path().join("stale.txt").exists());
+        assert!(tempdir.path().join("stale").exists());
+        assert!(!tempdir.path().join("new.txt").exists());
+        assert!(!tempdir.path().join("sub").exists());
+        Ok(())
+    }
+}
@@ -1,10 +1,7 @@
-use crate::wpd::device::ContentObjectInfo;
+use crate::backend::ContentObjectInfo;
+use crate::common::timestamp::Timestamp;
 
-use std::{
-    fs::Metadata,
-    os::windows::prelude::MetadataExt,
-};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::fs::Metadata;
 
 #[derive(Debug)]
 pub struct FileInfo {
@@ -21,9 +18,9 @@ pub struct FileInfo {
     /// Whether the object can be deleted
     pub can_delete: bool,
     /// Time created (or None if not provided)
-    pub time_created: Option<String>,
+    pub time_created: Option<Timestamp>,
     /// Time modified (or None if not provided)
-    pub time_modified: Option<String>,
+    pub time_modified: Option<Timestamp>,
 }
 
 impl FileInfo {
@@ -35,8 +32,8 @@ impl FileInfo {
             is_hidden: info.is_hidden,
             is_system: info.is_system,
             can_delete: info.can_delete,
-            time_created: info.time_created.clone(),
-            time_modified: info.time_modified.clone(),
+            time_created: info.time_created,
+            time_modified: info.time_modified,
         })
     }
 
@@ -44,39 +41,44 @@ impl FileInfo {
         metadata: &Metadata,
         name: &str,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let created_date_time  = system_time_to_string(metadata.created()?);
-        let modified_date_time =  system_time_to_string(metadata.modified()?);
-        //  Windows 文件属性标志位 2 为隐藏，4 为系统 1 为只读 0x10 为目录。。。
-        //  通过按位或操作，可以组合多个属性
-        let file_attr = metadata.file_attributes();
+        // 有些文件系统不支持创建时间
+        let created_date_time = metadata.created().ok().map(Timestamp::from_system_time);
+        let modified_date_time = Timestamp::from_system_time(metadata.modified()?);
+        let (is_hidden, is_system) = get_file_attributes(metadata, name);
         let data_size = if metadata.is_dir() {
             0
         } else {
-            metadata.file_size()
+            metadata.len()
         };
         Ok(FileInfo {
             name: name.to_string(),
             data_size,
             is_folder: metadata.is_dir(),
-            is_hidden: (file_attr & 2) != 0,
-            is_system: (file_attr & 4) != 0,
+            is_hidden,
+            is_system,
             can_delete: true,
-            time_created: Some(created_date_time),
+            time_created: created_date_time,
             time_modified: Some(modified_date_time),
         })
     }
 }
 
-fn system_time_to_string(time: SystemTime) -> String {
-    match time.duration_since(UNIX_EPOCH) {
-        Ok(duration) => {
-            let timestamp = duration.as_secs();
-            timestamp.to_string()
-        }
-        Err(e) => format!("Error: {:?}", e),
-    }
+// 获取隐藏和系统属性
+//  Windows 文件属性标志位 2 为隐藏，4 为系统 1 为只读 0x10 为目录。。。
+//  通过按位或操作，可以组合多个属性
+#[cfg(windows)]
+pub fn get_file_attributes(metadata: &Metadata, #[allow(unused_variables)] name: &str) -> (bool, bool) {
+    use std::os::windows::prelude::MetadataExt;
+    let file_attr = metadata.file_attributes();
+    ((file_attr & 2) != 0, (file_attr & 4) != 0)
 }
 
+// 获取隐藏和系统属性
+// 其他平台没有隐藏和系统属性，以 '.' 开头的文件视为隐藏文件
+#[cfg(not(windows))]
+pub fn get_file_attributes(#[allow(unused_variables)] metadata: &Metadata, name: &str) -> (bool, bool) {
+    (name.starts_with('.'), false)
+}
 
 #[cfg(test)]
 mod tests {
@@ -84,21 +86,31 @@ mod tests {
     use std::fs::File;
     use std::io::Write;
     use std::fs::metadata;
-    use std::time::{SystemTime, Duration};
-    use windows::Win32::System::Com::{COINIT_MULTITHREADED, CoInitializeEx};
+    use crate::backend::memory::MemoryBackend;
     use crate::find::find_file_or_folder;
     use crate::path;
-    use crate::wpd::manager::Manager;
 
     #[test]
     fn from_content_object_info_creates_correct_file_info() {
-        unsafe { CoInitializeEx(Some(std::ptr::null_mut()), COINIT_MULTITHREADED).ok().unwrap(); }
-        let manager = Manager::get_portable_device_manager().unwrap();
-        let storage_path = path::DeviceStoragePath::from("Redmi K70:内部存储设备:/Pictures").unwrap();
-        let option = find_file_or_folder(&manager, &storage_path).unwrap();
-        let (device_info, device, content_object_info) = option.unwrap();
+        let backend = MemoryBackend::with_storage("Redmi K70", "内部存储设备");
+        backend.add_file("Redmi K70:内部存储设备:/Pictures/a.jpg", b"abc").unwrap();
+        backend.update_object("Redmi K70:内部存储设备:/Pictures/a.jpg", |o| {
+            o.is_hidden = true;
+            o.time_modified = Some(Timestamp::from_unix_seconds(1627846261));
+        }).unwrap();
+        let storage_path = path::DeviceStoragePath::from("Redmi K70:内部存储设备:/Pictures/a.jpg").unwrap();
+        let option = find_file_or_folder(&backend, &storage_path).unwrap();
+        let (_device_info, _device, content_object_info) = option.unwrap();
         let file_info = FileInfo::from_content_object_info(&content_object_info).unwrap();
-        println!("{:?}", file_info);
+
+        assert_eq!(file_info.name, "a.jpg");
+        assert_eq!(file_info.data_size, 3);
+        assert!(!file_info.is_folder);
+        assert!(file_info.is_hidden);
+        assert!(!file_info.is_system);
+        assert!(file_info.can_delete);
+        assert_eq!(file_info.time_created, None);
+        assert_eq!(file_info.time_modified, Some(Timestamp::from_unix_seconds(1627846261)));
     }
 
     #[test]
@@ -140,18 +152,4 @@ mod tests {
         assert!(file_info.time_created.is_some());
         assert!(file_info.time_modified.is_some());
     }
-
-    #[test]
-    fn system_time_to_string_converts_correctly() {
-        let system_time = SystemTime::UNIX_EPOCH + Duration::new(1627846261, 0);
-        let time_string = system_time_to_string(system_time);
-        assert_eq!(time_string, "1627846261");
-    }
-
-    #[test]
-    fn system_time_to_string_handles_error() {
-        let system_time = SystemTime::UNIX_EPOCH - Duration::new(1, 0);
-        let time_string = system_time_to_string(system_time);
-        assert!(time_string.starts_with("Error:"));
-    }
 }
\ No newline at end of file
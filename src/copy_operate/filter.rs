@@ -0,0 +1,131 @@
+use crate::common::path_matcher::{create_path_pattern_matcher, PathMatchingState, RootPathMatcher};
+use crate::path::SEPARATORS;
+
+// 复制时的文件过滤，规则按顺序匹配，第一个匹配的规则决定是否复制 (同 rsync)
+// 规则匹配相对于复制源的父文件夹的路径，例如复制 "DCIM" 时 "DCIM/Camera/a.jpg"
+// 没有分隔符的模式匹配任意层的名称，"/" 开头或含有分隔符的模式从复制源开始匹配
+// 以分隔符结尾的模式只匹配文件夹
+
+/// An include or exclude rule of a copy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FilterRule {
+    /// Copy the entries matching the pattern
+    Include(String),
+    /// Do not copy the entries matching the pattern
+    Exclude(String),
+}
+
+struct CompiledRule {
+    include: bool,
+    matcher: RootPathMatcher,
+    anchored: bool,
+    dir_only: bool,
+}
+
+/// Decides which entries are copied.
+pub struct CopyFilter {
+    rules: Vec<CompiledRule>,
+    include_hidden: bool,
+    include_system: bool,
+}
+
+impl CopyFilter {
+    pub fn new(rules: &[FilterRule], include_hidden: bool, include_system: bool) -> Result<CopyFilter, Box<dyn std::error::Error>> {
+        let mut compiled = Vec::new();
+        for rule in rules {
+            let (include, pattern) = match rule {
+                FilterRule::Include(pattern) => (true, pattern),
+                FilterRule::Exclude(pattern) => (false, pattern),
+            };
+            let dir_only = pattern.ends_with(SEPARATORS);
+            let trimmed = pattern.trim_end_matches(SEPARATORS);
+            let anchored = trimmed.starts_with(SEPARATORS) || trimmed.contains(SEPARATORS);
+            let matcher = create_path_pattern_matcher(trimmed)
+                .map_err(|err| format!("invalid pattern {:?}: {}", pattern, err))?;
+            compiled.push(CompiledRule { include, matcher, anchored, dir_only });
+        }
+        Ok(CopyFilter {
+            rules: compiled,
+            include_hidden,
+            include_system,
+        })
+    }
+
+    /// Whether an entry is not copied, `relative_path` is separated by '/'.
+    pub fn is_excluded(&self, relative_path: &str, is_folder: bool, is_hidden: bool, is_system: bool) -> bool {
+        if (is_hidden && !self.include_hidden) || (is_system && !self.include_system) {
+            return true;
+        }
+        let components: Vec<&str> = relative_path.split('/').filter(|c| !c.is_empty()).collect();
+        self.rules
+            .iter()
+            .find(|rule| {
+                // 不固定位置的模式只匹配名称
+                let components = if rule.anchored { &components[..] } else { &components[components.len().saturating_sub(1)..] };
+                (!rule.dir_only || is_folder) && matches_path(&rule.matcher, components, is_folder)
+            })
+            .is_some_and(|rule| !rule.include)
+    }
+}
+
+// 整个路径匹配模式，中间的部分都是文件夹
+fn matches_path(matcher: &RootPathMatcher, components: &[&str], is_folder: bool) -> bool {
+    let (mut state, mut next) = matcher.matches_root();
+    for (index, component) in components.iter().enumerate() {
+        let current = match (state, next) {
+            (PathMatchingState::Accepted, Some(current)) => current,
+            _ => return false,
+        };
+        let is_last = index + 1 == components.len();
+        (state, next) = current.matches(component, !is_last || is_folder);
+        if is_last {
+            return state == PathMatchingState::Completed;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    fn filter(rules: &[&str]) -> CopyFilter {
+        let rules: Vec<FilterRule> = rules
+            .iter()
+            .map(|rule| match rule.strip_prefix('+') {
+                Some(pattern) => FilterRule::Include(pattern.to_string()),
+                None => FilterRule::Exclude(rule.trim_start_matches('-').to_string()),
+            })
+            .collect();
+        CopyFilter::new(&rules, false, false).unwrap()
+    }
+
+    #[test_case(&["-*.tmp"], "DCIM/a.tmp", false => true; "name at any level")]
+    #[test_case(&["-*.tmp"], "DCIM/a.jpg", false => false; "not matching")]
+    #[test_case(&["-cache/"], "DCIM/cache", true => true; "folder only")]
+    #[test_case(&["-cache/"], "DCIM/cache", false => false; "folder only does not match files")]
+    #[test_case(&["-/DCIM/*.jpg"], "DCIM/a.jpg", false => true; "anchored")]
+    #[test_case(&["-/DCIM/*.jpg"], "DCIM/sub/a.jpg", false => false; "anchored does not match deeper")]
+    #[test_case(&["-DCIM/**/*.jpg"], "DCIM/sub/a.jpg", false => true; "any directories")]
+    #[test_case(&["+*.jpg", "-*"], "DCIM/a.jpg", false => false; "first match includes")]
+    #[test_case(&["+*.jpg", "-*"], "DCIM/a.png", false => true; "first match excludes")]
+    #[test_case(&["-*", "+*.jpg"], "DCIM/a.jpg", false => true; "order matters")]
+    fn test_is_excluded(rules: &[&str], path: &str, is_folder: bool) -> bool {
+        filter(rules).is_excluded(path, is_folder, false, false)
+    }
+
+    #[test]
+    fn test_hidden_and_system() -> Result<(), Box<dyn std::error::Error>> {
+        let rules = [FilterRule::Exclude("*.tmp".to_string())];
+        let default_filter = CopyFilter::new(&rules, false, false)?;
+        assert!(default_filter.is_excluded("DCIM/.thumbnails", true, true, false));
+        assert!(default_filter.is_excluded("DCIM/sys", false, false, true));
+        let filter = CopyFilter::new(&rules, true, true)?;
+        assert!(!filter.is_excluded("DCIM/.thumbnails", true, true, false));
+        assert!(!filter.is_excluded("DCIM/sys", false, false, true));
+        assert!(filter.is_excluded("DCIM/.a.tmp", false, true, false));
+        assert!(CopyFilter::new(&[FilterRule::Include("a/../b".to_string())], false, false).is_err());
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
-use crate::common::file_reader::FileReader;
+use std::io::Read;
+use crate::common::timestamp::Timestamp;
 use super::file_info::FileInfo;
 
 
@@ -10,11 +11,13 @@ pub trait FolderOperate {
     fn create_file(
         &mut self,
         name: &str,
-        reader: &mut impl FileReader,
+        reader: &mut dyn Read,
         size: u64,
-        created: &Option<String>,
-        modified: &Option<String>,
+        created: &Option<Timestamp>,
+        modified: &Option<Timestamp>,
     ) -> Result<(), Box<dyn std::error::Error>>;
+    // 打开文件读取内容,用于复制后校验
+    fn open_file(&mut self, name: &str) -> Result<Box<dyn Read + '_>, Box<dyn std::error::Error>>;
     // 打开或创建文件夹,before_open为打开文件夹前的回调函数,before_create为创建文件夹前的回调函数
     fn open_or_create_folder<FBeforeOpen, FBeforeCreate>(
         &mut self,
@@ -29,6 +32,10 @@ pub trait FolderOperate {
     fn delete_file_or_folder(&mut self, name: &str) -> Result<(), Box<dyn std::error::Error>>;
     // 标记一个文件或文件夹为保留,delete_unretained配合
     fn retain(&mut self, name: &str);
+    // 文件或文件夹在目标中的位置,设备上为对象ID,本地为路径,用于传输日志
+    fn locate(&self, name: &str) -> Option<String>;
+    // 未保留的文件或文件夹,即 delete_unretained 将要删除的文件或文件夹
+    fn unretained(&mut self) -> Result<Vec<FileInfo>, Box<dyn std::error::Error>>;
     // 删除未保留的文件或文件夹,用于镜像文件模式,before_delete_file为删除文件前的回调函数,before_delete_folder为删除文件夹前的回调函数
     fn delete_unretained<FBeforeDeleteFile, FBeforeDeleteFolder>(
         &mut self,
@@ -0,0 +1,213 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::common::timestamp::Timestamp;
+
+// 传输日志，每行一条 JSON 记录
+// 开始写入文件前记录 started，写入(和校验)成功后记录 completed
+// 只有 started 没有 completed 的文件是中断时写了一半的文件，恢复时需要删除后重新复制
+
+/// A file recorded in the transfer journal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// The source path
+    pub source: String,
+    /// Size of the source file
+    pub size: u64,
+    /// Time created of the source file
+    pub created: Option<Timestamp>,
+    /// Time modified of the source file
+    pub modified: Option<Timestamp>,
+    /// The destination object id or local path, set when the copy is completed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+}
+
+impl JournalEntry {
+    // 源文件没有变化
+    fn is_same_source(&self, other: &JournalEntry) -> bool {
+        self.source == other.source
+            && self.size == other.size
+            && self.created == other.created
+            && self.modified == other.modified
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum JournalEvent {
+    Started,
+    Completed,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JournalRecord {
+    event: JournalEvent,
+    #[serde(flatten)]
+    entry: JournalEntry,
+}
+
+/// An append-only log of the files copied, used to resume an interrupted copy.
+pub struct Journal {
+    // None 表示只读
+    file: Option<File>,
+    // key: 源路径
+    completed: HashMap<String, JournalEntry>,
+    // 已开始但未完成的源路径
+    pending: HashSet<String>,
+}
+
+impl Journal {
+    /// Creates a new journal, or loads an existing one when `resume` is set.
+    pub fn open(path: &Path, resume: bool) -> Result<Journal, Box<dyn std::error::Error>> {
+        let mut journal = if resume && path.exists() {
+            Journal::load(path)?
+        } else {
+            Journal {
+                file: None,
+                completed: HashMap::new(),
+                pending: HashSet::new(),
+            }
+        };
+        let mut file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resume)
+            .truncate(!resume)
+            .open(path)?;
+        // 补上不完整的最后一行的换行，避免和新记录连在一起
+        if resume && file.metadata()?.len() > 0 && !std::fs::read(path)?.ends_with(b"\n") {
+            writeln!(file)?;
+        }
+        journal.file = Some(file);
+        Ok(journal)
+    }
+
+    /// Loads an existing journal without writing to it.
+    pub fn load(path: &Path) -> Result<Journal, Box<dyn std::error::Error>> {
+        let mut completed = HashMap::new();
+        let mut pending = HashSet::new();
+        if path.exists() {
+            for line in std::fs::read_to_string(path)?.lines() {
+                // 中断时最后一行可能不完整，忽略无法解析的行
+                let record = match serde_json::from_str::<JournalRecord>(line) {
+                    Ok(record) => record,
+                    Err(err) => {
+                        log::warn!("ignore the journal record {:?}: {}", line, err);
+                        continue;
+                    }
+                };
+                match record.event {
+                    JournalEvent::Started => {
+                        completed.remove(&record.entry.source);
+                        pending.insert(record.entry.source);
+                    }
+                    JournalEvent::Completed => {
+                        pending.remove(&record.entry.source);
+                        completed.insert(record.entry.source.clone(), record.entry);
+                    }
+                }
+            }
+        }
+        Ok(Journal {
+            file: None,
+            completed,
+            pending,
+        })
+    }
+
+    /// Whether the source file was copied completely and has not changed since.
+    pub fn is_completed(&self, entry: &JournalEntry) -> bool {
+        self.completed
+            .get(&entry.source)
+            .is_some_and(|completed| completed.is_same_source(entry))
+    }
+
+    /// Whether copying the source file was started but not completed.
+    pub fn is_pending(&self, source: &str) -> bool {
+        self.pending.contains(source)
+    }
+
+    /// Records that copying `entry` has started.
+    pub fn start(&mut self, entry: &JournalEntry) -> Result<(), Box<dyn std::error::Error>> {
+        self.append(JournalEvent::Started, entry)?;
+        self.completed.remove(&entry.source);
+        self.pending.insert(entry.source.clone());
+        Ok(())
+    }
+
+    /// Records that `entry` was copied completely.
+    pub fn complete(&mut self, entry: &JournalEntry) -> Result<(), Box<dyn std::error::Error>> {
+        self.append(JournalEvent::Completed, entry)?;
+        self.pending.remove(&entry.source);
+        self.completed.insert(entry.source.clone(), entry.clone());
+        Ok(())
+    }
+
+    fn append(&mut self, event: JournalEvent, entry: &JournalEntry) -> Result<(), Box<dyn std::error::Error>> {
+        let record = JournalRecord {
+            event,
+            entry: entry.clone(),
+        };
+        // 每条记录立即写入，进程中断时不丢失
+        if let Some(file) = self.file.as_mut() {
+            writeln!(file, "{}", serde_json::to_string(&record)?)?;
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(source: &str, size: u64) -> JournalEntry {
+        JournalEntry {
+            source: source.to_string(),
+            size,
+            created: None,
+            modified: Some(Timestamp::from_unix_seconds(1700000000)),
+            destination: None,
+        }
+    }
+
+    #[test]
+    fn test_journal_resume() -> Result<(), Box<dyn std::error::Error>> {
+        let tempdir = tempfile::tempdir()?;
+        let path = tempdir.path().join("journal.jsonl");
+        {
+            let mut journal = Journal::open(&path, false)?;
+            journal.start(&entry("a.jpg", 1))?;
+            journal.complete(&JournalEntry { destination: Some("o1".to_string()), ..entry("a.jpg", 1) })?;
+            journal.start(&entry("b.jpg", 2))?;
+        }
+        // 模拟中断时写了一半的记录
+        let mut file = OpenOptions::new().append(true).open(&path)?;
+        write!(file, "{{\"event\":\"comp")?;
+        drop(file);
+
+        let mut journal = Journal::open(&path, true)?;
+        assert!(journal.is_completed(&entry("a.jpg", 1)));
+        // 源文件有变化
+        assert!(!journal.is_completed(&entry("a.jpg", 3)));
+        assert!(!journal.is_completed(&entry("b.jpg", 2)));
+        assert!(journal.is_pending("b.jpg"));
+        assert!(!journal.is_pending("a.jpg"));
+
+        journal.start(&entry("b.jpg", 2))?;
+        journal.complete(&entry("b.jpg", 2))?;
+        drop(journal);
+        let journal = Journal::open(&path, true)?;
+        assert!(journal.is_completed(&entry("b.jpg", 2)));
+        assert!(!journal.is_pending("b.jpg"));
+
+        // 不恢复时清空日志
+        let journal = Journal::open(&path, false)?;
+        assert!(!journal.is_completed(&entry("a.jpg", 1)));
+        assert_eq!(std::fs::read(&path)?.len(), 0);
+        Ok(())
+    }
+}
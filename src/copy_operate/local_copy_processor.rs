@@ -1,9 +1,10 @@
+use std::fs::File;
 use std::path::PathBuf;
-use std::{fs::File, os::windows::prelude::MetadataExt};
-use crate::copy_operate::copy_processor::{can_skip_copying, CopyProcessor, report_copying_end, report_copying_start, report_creating_new_folder, report_delete_file, report_delete_folder};
+use crate::copy_operate::copy_processor::{copy_file as copy_file_to, retain_excluded, CopyProcessor, report_creating_new_folder, report_delete_file, report_delete_folder};
 use crate::copy_operate::folder_operate::FolderOperate;
+use crate::copy_operate::{CopyContext, CopyOptions, MovedSource};
 
-use super::file_info::FileInfo;
+use super::file_info::{get_file_attributes, FileInfo};
 use super::local_file_reader::LocalFileReader;
 
 
@@ -25,16 +26,18 @@ impl CopyProcessor for LocalCopyProcessor {
         name: &str,
         dest: &mut impl FolderOperate,
         dest_is_parent_folder: bool,
-        recursive: bool,
-        mirror: bool,
+        options: &CopyOptions,
+        context: &mut CopyContext,
     ) -> Result<(), Box<dyn std::error::Error>> {
+        let src_name = self.path.file_name().and_then(|n| n.to_str()).unwrap_or("");
         copy_iter(
             &self.path,
             dest,
             dest_is_parent_folder,
+            src_name,
             name,
-            recursive,
-            mirror,
+            options,
+            context,
         )
     }
 }
@@ -43,26 +46,26 @@ fn copy_iter(
     path: &PathBuf,
     dest: &mut impl FolderOperate,
     dest_is_parent_folder: bool,
+    relative_path: &str,
     dest_name: &str,
-    recursive: bool,
-    mirror: bool,
+    options: &CopyOptions,
+    context: &mut CopyContext,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let metadata = path.metadata()?;
-    let file_attr = metadata.file_attributes();
-    let is_hidden = (file_attr & 2) != 0;
-    let is_system = (file_attr & 4) != 0;
+    let src_name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    let (is_hidden, is_system) = get_file_attributes(&metadata, src_name);
 
-    // 跳过隐藏文件或系统文件
-    if is_hidden || is_system {
+    // 过滤文件，默认不复制系统文件和隐藏文件
+    if context.filter.is_excluded(relative_path, metadata.is_dir(), is_hidden, is_system) {
         return Ok(());
     }
 
     if metadata.is_file() {
-        return copy_file(path, dest, dest_name);
+        return copy_file(path, dest, dest_name, options, context);
     }
 
     if metadata.is_dir() {
-        return copy_directory(path, dest, dest_is_parent_folder, dest_name, recursive, mirror);
+        return copy_directory(path, dest, dest_is_parent_folder, relative_path, dest_name, options, context);
     }
 
     Ok(())
@@ -72,30 +75,23 @@ fn copy_file(
     path: &PathBuf,
     dest: &mut impl FolderOperate,
     dest_name: &str,
+    options: &CopyOptions,
+    context: &mut CopyContext,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let metadata = path.metadata()?;
     let src_file_info = FileInfo::from_metadata(&metadata, path.file_name().unwrap().to_str().unwrap())?;
-    let dest_file_info = dest.get_file_info(dest_name)?;
-
-    if let Some(dest_file_info_ref) = dest_file_info.as_ref() {
-        if can_skip_copying(&src_file_info, dest_file_info_ref) {
-            dest.retain(dest_name);
-            return Ok(());
-        }
+    let verified = copy_file_to(
+        dest,
+        dest_name,
+        &src_file_info,
+        &path.display().to_string(),
+        || Ok(LocalFileReader::new(File::open(path)?)),
+        options,
+        context,
+    )?;
+    if verified {
+        context.summary.moved.push(MovedSource::Local { path: path.clone(), is_folder: false });
     }
-
-    if dest_file_info.is_some() {
-        dest.delete_file_or_folder(dest_name)?;
-    }
-
-    let file = File::open(path)?;
-    let mut reader = LocalFileReader::new(file);
-
-    report_copying_start(&src_file_info);
-    dest.create_file(dest_name, &mut reader, src_file_info.data_size, &None, &None)?;
-    dest.retain(dest_name);
-    report_copying_end();
-
     Ok(())
 }
 
@@ -103,9 +99,10 @@ fn copy_directory(
     path: &PathBuf,
     dest: &mut impl FolderOperate,
     dest_is_parent_folder: bool,
+    relative_path: &str,
     dest_name: &str,
-    recursive: bool,
-    mirror: bool,
+    options: &CopyOptions,
+    context: &mut CopyContext,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let new_dest_ref;
     let mut new_dest;
@@ -117,17 +114,24 @@ fn copy_directory(
         new_dest_ref = dest;
     }
 
-    if recursive {
+    if options.recursive {
         for result in std::fs::read_dir(path)? {
             let entry = result?;
             let new_path = entry.path();
             let dest_file_name = new_path.file_name().unwrap().to_str().unwrap();
-            copy_iter(&new_path, new_dest_ref, true, dest_file_name, recursive, mirror)?;
+            let child_path = format!("{}/{}", relative_path, dest_file_name);
+            copy_iter(&new_path, new_dest_ref, true, &child_path, dest_file_name, options, context)?;
         }
 
-        if mirror {
+        if options.mirror {
+            retain_excluded(new_dest_ref, relative_path, context)?;
             new_dest_ref.delete_unretained(report_delete_file, report_delete_folder)?;
         }
+
+        // 移动时文件夹在其内容之后删除
+        if options.move_sources && !options.dry_run {
+            context.summary.moved.push(MovedSource::Local { path: path.clone(), is_folder: true });
+        }
     }
 
     Ok(())
@@ -2,35 +2,28 @@ use std::fs::File;
 use std::io::Read;
 use crate::common::file_reader::FileReader;
 
+// 读取文件时的缓冲区大小
+const BUFFER_SIZE: u32 = 32768;
 
 pub struct LocalFileReader {
     file: File,
-    buf: Vec<u8>,
 }
 
 impl LocalFileReader {
     pub fn new(file: File) -> LocalFileReader {
-        let mut buf = Vec::<u8>::new();
-        buf.resize(32768, 0);
-        LocalFileReader { file, buf }
+        LocalFileReader { file }
+    }
+}
+
+impl Read for LocalFileReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.file.read(buf)
     }
 }
 
 impl FileReader for LocalFileReader {
-    // 读取文件时的缓冲区大小
     fn buffer_size(&self) -> u32 {
-        self.buf.len() as u32
-    }
-    // 读取文件内容 max_size 为读取的最大字节数
-    fn seek(&mut self, max_size: u32) -> Result<Option<&[u8]>, Box<dyn std::error::Error>> {
-        // 重新调整缓冲区大小
-        self.buf.resize(max_size as usize, 0);
-        let len = self.file.read(self.buf.as_mut_slice())?;
-        if len > 0 {
-            Ok(Some(&self.buf.as_slice()[..len]))
-        } else {
-            Ok(None)
-        }
+        BUFFER_SIZE
     }
 }
 
@@ -38,6 +31,8 @@ impl FileReader for LocalFileReader {
 mod tests {
     use super::*;
     use std::fs::File;
+    use std::io::BufRead;
+    use std::io::BufReader;
     use std::io::Write;
     use std::io::Seek;
     use std::io::SeekFrom;
@@ -58,34 +53,41 @@ mod tests {
     }
 
     #[test]
-    fn next_reads_data_into_buffer() {
+    fn read_reads_data_into_buffer() {
         let file = create_temp_file_with_content(b"Hello, world!");
         let mut reader = LocalFileReader::new(file);
-        let result = reader.seek(5).unwrap();
-        assert_eq!(result, Some(&b"Hello"[..]));
+        let mut buf = [0u8; 5];
+        assert_eq!(reader.read(&mut buf).unwrap(), 5);
+        assert_eq!(&buf, b"Hello");
     }
 
     #[test]
-    fn next_returns_none_when_no_more_data() {
+    fn read_returns_zero_when_no_more_data() {
         let file = create_temp_file_with_content(b"");
         let mut reader = LocalFileReader::new(file);
-        let result = reader.seek(5).unwrap();
-        assert_eq!(result, None);
+        let mut buf = [0u8; 5];
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
     }
 
     #[test]
-    fn next_resizes_buffer_correctly() {
-        let file = create_temp_file_with_content(b"Hello, world!");
+    fn read_handles_partial_reads() {
+        let file = create_temp_file_with_content(b"Hello");
         let mut reader = LocalFileReader::new(file);
-        reader.seek(5).unwrap();
-        assert_eq!(reader.buffer_size(), 5);
+        let mut buf = [0u8; 10];
+        assert_eq!(reader.read(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"Hello");
     }
 
     #[test]
-    fn next_handles_partial_reads() {
-        let file = create_temp_file_with_content(b"Hello");
-        let mut reader = LocalFileReader::new(file);
-        let result = reader.seek(10).unwrap();
-        assert_eq!(result, Some(&b"Hello"[..]));
+    fn works_with_std_io_adapters() {
+        let file = create_temp_file_with_content(b"line 1\nline 2\n");
+        let reader = BufReader::new(LocalFileReader::new(file));
+        let lines: Vec<String> = reader.lines().map(|l| l.unwrap()).collect();
+        assert_eq!(lines, vec!["line 1", "line 2"]);
+
+        let file = create_temp_file_with_content(b"Hello, world!");
+        let mut output = Vec::<u8>::new();
+        std::io::copy(&mut LocalFileReader::new(file), &mut output).unwrap();
+        assert_eq!(output, b"Hello, world!");
     }
 }
@@ -1,11 +1,9 @@
 use std::collections::HashSet;
-use std::fs;
-use std::fs::{File, OpenOptions};
-use std::io::Write;
+use std::fs::{File, FileTimes, OpenOptions};
+use std::io::Read;
 use std::path::{Path, PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
-use crate::common::file_reader::FileReader;
-use crate::common::time_transfer::string_to_system_time;
+use std::time::SystemTime;
+use crate::common::timestamp::Timestamp;
 use crate::copy_operate::folder_operate::FolderOperate;
 
 use super::file_info::FileInfo;
@@ -43,10 +41,10 @@ impl FolderOperate for LocalFolder {
     fn create_file(
         &mut self,
         name: &str,
-        reader: &mut impl FileReader,
+        reader: &mut dyn Read,
         #[allow(unused_variables)] size: u64,
-        created: &Option<String>,
-        modified: &Option<String>,
+        created: &Option<Timestamp>,
+        modified: &Option<Timestamp>,
     ) -> Result<(), Box<dyn std::error::Error>> {
         let path_buf = Path::new(&self.folder_path).join(name);
 
@@ -66,24 +64,20 @@ impl FolderOperate for LocalFolder {
             let _ = std::fs::remove_file(&path_buf);
             return Err(err);
         }
-        // created 如果不为空，将创建时间 转换成 SystemTime
-        let created = if let Some(created) = created {
-                Some(string_to_system_time(created)?)
-            } else {
-                None
-            };
-        // modified 如果不为空，将修改时间设置为 modified
-        let modified = if let Some(modified) = modified {
-                Some(string_to_system_time(modified)?)
-            } else {
-                None
-            };
+        // 转换成 SystemTime
+        let created = created.map(|created| created.to_system_time());
+        let modified = modified.map(|modified| modified.to_system_time());
 
         set_file_times(&path_buf, &created, &modified)?;
 
         Ok(())
     }
 
+    fn open_file(&mut self, name: &str) -> Result<Box<dyn Read + '_>, Box<dyn std::error::Error>> {
+        let path_buf = Path::new(&self.folder_path).join(name);
+        Ok(Box::new(File::open(path_buf)?))
+    }
+
     fn open_or_create_folder<FBeforeOpen, FBeforeCreate>(
         &mut self,
         name: &str,
@@ -123,6 +117,27 @@ impl FolderOperate for LocalFolder {
         self.retained.insert(String::from(name));
     }
 
+    fn locate(&self, name: &str) -> Option<String> {
+        Some(self.folder_path.join(name).display().to_string())
+    }
+
+    fn unretained(&mut self) -> Result<Vec<FileInfo>, Box<dyn std::error::Error>> {
+        let mut unretained = Vec::new();
+        // 遍历文件夹中的所有文件和子文件夹
+        for entry_result in self.folder_path.read_dir()? {
+            let entry = entry_result?;
+            if let Some(name) = entry.file_name().to_str() {
+                let metadata = entry.metadata()?;
+                let file_info = FileInfo::from_metadata(&metadata, name)?;
+                // 跳过隐藏文件和系统文件
+                if !file_info.is_hidden && !file_info.is_system && !self.retained.contains(name) {
+                    unretained.push(file_info);
+                }
+            }
+        }
+        Ok(unretained)
+    }
+
     fn delete_unretained<FBeforeDeleteFile, FBeforeDeleteFolder>(
         &mut self,
         before_delete_file: FBeforeDeleteFile,
@@ -132,60 +147,43 @@ impl FolderOperate for LocalFolder {
         FBeforeDeleteFile: Fn(&str),
         FBeforeDeleteFolder: Fn(&str),
     {
-        // 遍历文件夹中的所有文件和子文件夹
-        for entry_result in self.folder_path.read_dir()? {
-            let entry = entry_result?;
-            if let Some(name) = entry.file_name().to_str() {
-                let metadata = entry.metadata()?;
-                let file_info = FileInfo::from_metadata(&metadata, name)?;
-                // 跳过隐藏文件和系统文件
-                if !file_info.is_hidden && !file_info.is_system {
-                    if !self.retained.contains(name) {
-                        if file_info.is_folder {
-                            before_delete_folder(name);
-                        } else {
-                            before_delete_file(name);
-                        }
-
-                        self.delete_file_or_folder(name)?;
-                    }
-                }
+        for file_info in self.unretained()? {
+            if file_info.is_folder {
+                before_delete_folder(&file_info.name);
+            } else {
+                before_delete_file(&file_info.name);
             }
+            self.delete_file_or_folder(&file_info.name)?;
         }
         Ok(())
     }
 }
 
 fn copy_to_file(
-    reader: &mut impl FileReader,
+    reader: &mut dyn Read,
     file: &mut File,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    while let Some(bytes) = reader.seek(reader.buffer_size())? {
-        file.write_all(bytes)?;
-    }
+    std::io::copy(reader, file)?;
     Ok(())
 }
 
-fn set_file_times(path: &Path, created: &Option<SystemTime>, modified: &Option<SystemTime>) -> std::io::Result<()> {
-    // 打开文件以确保它存在
-    let _file = File::open(path)?;
-
-    // 获取当前的文件元数据
-    let mut metadata = fs::metadata(path)?;
-
-    // 设置创建时间
-    if let Some(created_time) = created {
-        // Rust 标准库不直接支持设置创建时间，通常需要使用外部库或系统调用
-        // 例如，在 Windows 上，可以使用 WinAPI
+// 设置文件的创建时间和修改时间，只有 Windows 可以设置创建时间
+pub(crate) fn set_file_times(path: &Path, created: &Option<SystemTime>, modified: &Option<SystemTime>) -> std::io::Result<()> {
+    if created.is_none() && modified.is_none() {
+        return Ok(());
     }
-
-    // 设置修改时间
-    if let Some(modified_time) = modified {
-        // Rust 标准库不直接支持设置修改时间，通常需要使用外部库或系统调用
-        // 例如，在 Windows 上，可以使用 WinAPI
+    // 设置时间需要写权限
+    let file = OpenOptions::new().write(true).open(path)?;
+    let mut times = FileTimes::new();
+    if let Some(modified) = modified {
+        times = times.set_modified(*modified);
     }
-
-    Ok(())
+    #[cfg(windows)]
+    if let Some(created) = created {
+        use std::os::windows::fs::FileTimesExt;
+        times = times.set_created(*created);
+    }
+    file.set_times(times)
 }
 
 // fn naive_date_time_to_file_time(
@@ -232,13 +230,14 @@ mod local_destination_folder_tests {
     use test_case::test_case;
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_get_file_info_folder() -> Result<(), Box<dyn std::error::Error>> {
         let tempdir = tempfile::tempdir()?;
         let path = tempdir.path().join("foo bar");
         std::fs::create_dir(path)?;
 
         let mut ldf = LocalFolder::new(PathBuf::from(tempdir.path()));
-        let file_info_opt = ldf.get_file_info(&"foo bar".to_string())?;
+        let file_info_opt = ldf.get_file_info("foo bar")?;
 
         assert!(file_info_opt.is_some());
         let file_info = file_info_opt.unwrap();
@@ -266,13 +265,14 @@ mod local_destination_folder_tests {
     }
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn test_get_file_info_file() -> Result<(), Box<dyn std::error::Error>> {
         let tempdir = tempfile::tempdir()?;
         let path = tempdir.path().join("foo bar");
         std::fs::write(&path, "abc")?;
 
         let mut ldf = LocalFolder::new(PathBuf::from(tempdir.path()));
-        let file_info_opt = ldf.get_file_info(&"foo bar".to_string())?;
+        let file_info_opt = ldf.get_file_info("foo bar")?;
 
         assert!(file_info_opt.is_some());
         let file_info = file_info_opt.unwrap();
@@ -315,21 +315,19 @@ mod local_destination_folder_tests {
         }
     }
 
-    impl FileReader for TestingFileReader {
-        fn buffer_size(&self) -> u32 {
-            self.buf.len() as u32
-        }
-
-        fn seek(&mut self, _max_size: u32) -> Result<Option<&[u8]>, Box<dyn std::error::Error>> {
+    // 每次最多产生 10 字节，共 3 次
+    impl Read for TestingFileReader {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
             if self.count >= 3 {
-                Ok(None)
+                Ok(0)
             } else {
                 for i in 0..self.buf.len() {
                     self.n = self.n.wrapping_add(1);
                     self.buf[i] = self.n;
                 }
                 self.count += 1;
-                Ok(Some(&self.buf))
+                buf[..self.buf.len()].copy_from_slice(&self.buf);
+                Ok(self.buf.len())
             }
         }
     }
@@ -353,11 +351,11 @@ mod local_destination_folder_tests {
         //     NaiveTime::from_hms_milli(5, 6, 7, 890),
         // ));
 
-        let file_size = path.metadata()?.len();
+        let file_size = 30u64; // TestingFileReader 产生 30 字节
         let mut reader = TestingFileReader::new();
         let mut ldf = LocalFolder::new(PathBuf::from(tempdir.path()));
         ldf.create_file(
-            &"foo bar".to_string(),
+            "foo bar",
             &mut reader,
             file_size,
             &None,
@@ -397,7 +395,7 @@ mod local_destination_folder_tests {
         let mut before_open_called = false;
         let mut before_create_called = false;
         let ldf2 = ldf.open_or_create_folder(
-            &"foo bar".to_string(),
+            "foo bar",
             |_name| before_open_called = true,
             |_name| before_create_called = true,
         )?;
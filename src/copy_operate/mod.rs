@@ -1,22 +1,251 @@
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
-use crate::copy_operate::copy_processor::CopyProcessor;
+use std::str::FromStr;
+use crate::checksum::ChecksumAlgorithm;
+use crate::common::filename::FileNamePattern;
+use crate::copy_operate::copy_processor::{report_creating_new_folder, CopyProcessor};
 use crate::copy_operate::device_copy_processor::DeviceCopyProcessor;
 use crate::copy_operate::file_info::FileInfo;
 use crate::copy_operate::folder_operate::FolderOperate;
+use crate::copy_operate::dry_run::{CopyPlan, DryRunFolder};
+use crate::copy_operate::filter::{CopyFilter, FilterRule};
+use crate::copy_operate::journal::Journal;
 use crate::copy_operate::local_copy_processor::LocalCopyProcessor;
-use crate::find::find_file_or_folder;
-use crate::path::{DeviceStoragePath, PathType, SEPARATORS, WILDCARD_CHARACTERS};
-use crate::wpd::manager::Manager;
+use crate::find::{find_file_or_folder, find_storage, iterate_file_or_folder};
+use crate::backend::{ContentObject, ContentObjectInfo, PortableDeviceBackend};
+use crate::error::MtpError;
+use crate::path::{get_path_type, DeviceStoragePath, PathType, SEPARATORS, WILDCARD_CHARACTERS};
 
-mod folder_operate;
-mod local_file_reader;
-mod file_info;
+pub mod folder_operate;
+pub mod local_file_reader;
+pub mod file_info;
 pub mod device_folder_imp;
 pub mod local_folder_imp;
+pub mod journal;
+pub mod dry_run;
+pub mod filter;
+pub mod wildcard;
 mod device_copy_processor;
 mod local_copy_processor;
 mod copy_processor;
 
+/// Default tolerance of the modification time comparison in seconds, FAT stores times with a 2-second resolution.
+pub const DEFAULT_MTIME_TOLERANCE: u64 = 2;
+
+/// Options of a copy operation.
+#[derive(Debug, Clone)]
+pub struct CopyOptions {
+    /// Copy folders recursively
+    pub recursive: bool,
+    /// Delete the files and folders in the destination that do not exist in the source
+    pub mirror: bool,
+    /// Read the copied files back and compare their checksums with the source
+    pub verify: Option<ChecksumAlgorithm>,
+    /// What to do with a copied file whose checksum does not match
+    pub on_verify_failure: VerifyFailureAction,
+    /// Record the copied files in a journal file
+    pub journal: Option<PathBuf>,
+    /// Skip the files the journal records as copied
+    pub resume: bool,
+    /// Print the actions instead of changing the destination
+    pub dry_run: bool,
+    /// How to decide whether an existing destination file is up to date
+    pub compare: ComparePolicy,
+    /// Seconds the destination may be older than the source and still be up to date
+    pub mtime_tolerance: u64,
+    /// Set the times of the copied files to the times of the source files
+    pub preserve_times: bool,
+    /// What to do when a destination file exists and is not up to date
+    pub on_conflict: ConflictPolicy,
+    /// Conflict policies for the file names matching a pattern, the first match takes precedence over `on_conflict`
+    pub conflict_overrides: Vec<ConflictOverride>,
+    /// Include and exclude rules, the first matching rule decides
+    pub filters: Vec<FilterRule>,
+    /// Copy hidden files and folders
+    pub include_hidden: bool,
+    /// Copy system files and folders
+    pub include_system: bool,
+    /// Copy the matches of a wildcard source directly into the destination folder, without the folders below the first wildcard
+    pub flatten: bool,
+    /// Record the verified sources in the summary, `move` deletes them after the copy
+    pub move_sources: bool,
+}
+
+impl CopyOptions {
+    /// The conflict policy of a destination file name.
+    pub fn conflict_policy(&self, name: &str) -> ConflictPolicy {
+        self.conflict_overrides
+            .iter()
+            .find(|o| FileNamePattern::new(&o.pattern).matches(name))
+            .map_or(self.on_conflict, |o| o.policy)
+    }
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        CopyOptions {
+            recursive: false,
+            mirror: false,
+            verify: None,
+            on_verify_failure: VerifyFailureAction::default(),
+            journal: None,
+            resume: false,
+            dry_run: false,
+            compare: ComparePolicy::default(),
+            mtime_tolerance: DEFAULT_MTIME_TOLERANCE,
+            preserve_times: true,
+            on_conflict: ConflictPolicy::default(),
+            conflict_overrides: Vec::new(),
+            filters: Vec::new(),
+            include_hidden: false,
+            include_system: false,
+            flatten: false,
+            move_sources: false,
+        }
+    }
+}
+
+/// How to decide whether an existing destination file is up to date.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ComparePolicy {
+    /// The sizes are equal
+    Size,
+    /// The sizes are equal and the source is not newer than the destination
+    #[default]
+    SizeMtime,
+    /// The sizes and the checksums are equal
+    Checksum,
+    /// Never up to date, always copy
+    Always,
+}
+
+impl FromStr for ComparePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "size" => Ok(ComparePolicy::Size),
+            "size-mtime" => Ok(ComparePolicy::SizeMtime),
+            "checksum" => Ok(ComparePolicy::Checksum),
+            "always" => Ok(ComparePolicy::Always),
+            _ => Err(format!("unknown policy: {} (size, size-mtime, checksum or always)", s)),
+        }
+    }
+}
+
+/// What to do when the destination file exists and is not up to date.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Replace the destination file
+    #[default]
+    Overwrite,
+    /// Keep the destination file
+    Skip,
+    /// Copy to a new name like "IMG_0001 (1).jpg"
+    Rename,
+    /// Replace the destination file if the source is newer
+    Newer,
+    /// Replace the destination file if the source is larger
+    Larger,
+    /// Ask for each file
+    Ask,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "skip" => Ok(ConflictPolicy::Skip),
+            "rename" => Ok(ConflictPolicy::Rename),
+            "newer" => Ok(ConflictPolicy::Newer),
+            "larger" => Ok(ConflictPolicy::Larger),
+            "ask" => Ok(ConflictPolicy::Ask),
+            _ => Err(format!("unknown policy: {} (overwrite, skip, rename, newer, larger or ask)", s)),
+        }
+    }
+}
+
+/// A conflict policy for the file names matching a pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictOverride {
+    /// File name pattern, may contain '*' and '?'
+    pub pattern: String,
+    pub policy: ConflictPolicy,
+}
+
+impl FromStr for ConflictOverride {
+    type Err = String;
+
+    /// Parses "<pattern>=<policy>", e.g. "*.db=skip".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.rsplit_once('=') {
+            Some((pattern, policy)) if !pattern.is_empty() => Ok(ConflictOverride {
+                pattern: pattern.to_string(),
+                policy: policy.parse()?,
+            }),
+            _ => Err(format!("invalid override: {} (<pattern>=<policy>)", s)),
+        }
+    }
+}
+
+/// What to do with a copied file whose checksum does not match the source.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum VerifyFailureAction {
+    /// Report the file and keep it
+    #[default]
+    Report,
+    /// Copy the file once more
+    Retry,
+    /// Delete the copied file
+    Delete,
+}
+
+impl FromStr for VerifyFailureAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "report" => Ok(VerifyFailureAction::Report),
+            "retry" => Ok(VerifyFailureAction::Retry),
+            "delete" => Ok(VerifyFailureAction::Delete),
+            _ => Err(format!("unknown action: {} (report, retry or delete)", s)),
+        }
+    }
+}
+
+/// Results of a copy operation.
+#[derive(Debug, Clone, Default)]
+pub struct CopySummary {
+    /// The files whose checksum did not match the source
+    pub verify_failures: Vec<String>,
+    /// The actions of a dry run
+    pub plan: Option<CopyPlan>,
+    /// The sources whose copies were verified, folders follow their contents
+    pub moved: Vec<MovedSource>,
+}
+
+/// A source of a move whose copy was verified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovedSource {
+    /// A file or folder on a device, `path` is "<device>:<storage>:<path>"
+    Device { path: String, object: ContentObject, is_folder: bool, can_delete: bool },
+    /// A local file or folder
+    Local { path: PathBuf, is_folder: bool },
+}
+
+// 复制过程中的状态
+pub struct CopyContext {
+    pub summary: CopySummary,
+    pub journal: Option<Journal>,
+    // ask 时选择了 "全部" 的回答，用于后续的冲突
+    pub conflict_answer: Option<ConflictPolicy>,
+    pub filter: CopyFilter,
+    // 本次复制写入的目标文件的位置，不会被后面的源覆盖
+    pub written: HashSet<String>,
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 enum TargetStatus {
     NotExist,
@@ -38,26 +267,98 @@ pub struct TargetInspectionResult {
 }
 
 pub fn do_copy(
-    manager: &Manager,
+    backend: &dyn PortableDeviceBackend,
+    src_paths: &[&str],
+    mut destination_folder: impl FolderOperate,
+    dest_name: Option<&str>,
+    options: &CopyOptions,
+) -> Result<CopySummary, Box<dyn std::error::Error>> {
+    if options.resume && options.journal.is_none() {
+        return Err("resuming a copy requires a journal file.".into());
+    }
+
+    if options.dry_run {
+        // 演练时不校验，日志只读取不写入
+        let journal = match &options.journal {
+            Some(path) if options.resume => Some(Journal::load(path)?),
+            _ => None,
+        };
+        let options = CopyOptions {
+            verify: None,
+            ..options.clone()
+        };
+        let mut dry_run_folder = DryRunFolder::new(destination_folder);
+        let mut summary = copy_into(backend, src_paths, &mut dry_run_folder, dest_name, &options, journal)?;
+        let plan = dry_run_folder.plan();
+        println!("dry run, nothing was changed:\n{}", plan);
+        summary.plan = Some(plan);
+        return Ok(summary);
+    }
+
+    let journal = match &options.journal {
+        Some(path) => Some(Journal::open(path, options.resume)?),
+        None => None,
+    };
+    copy_into(backend, src_paths, &mut destination_folder, dest_name, options, journal)
+}
+
+fn copy_into(
+    backend: &dyn PortableDeviceBackend,
+    src_paths: &[&str],
+    destination_folder: &mut impl FolderOperate,
+    dest_name: Option<&str>,
+    options: &CopyOptions,
+    journal: Option<Journal>,
+) -> Result<CopySummary, Box<dyn std::error::Error>> {
+    let mut context = CopyContext {
+        summary: CopySummary::default(),
+        journal,
+        conflict_answer: None,
+        filter: CopyFilter::new(&options.filters, options.include_hidden, options.include_system)?,
+        written: HashSet::new(),
+    };
+    // 所有源共用目标文件夹和复制状态
+    for src_path in src_paths {
+        copy_source(backend, src_path, destination_folder, dest_name, options, &mut context)?;
+    }
+    Ok(context.summary)
+}
+
+fn copy_source(
+    backend: &dyn PortableDeviceBackend,
     src_path: &str,
-    src_path_type: PathType,
     destination_folder: &mut impl FolderOperate,
-    dest_is_parent_folder: bool,
     dest_name: Option<&str>,
-    recursive: bool,
-    mirror: bool,
+    options: &CopyOptions,
+    context: &mut CopyContext,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let src_path_type = get_path_type(src_path);
+    if has_wildcard(src_path, src_path_type)? {
+        if dest_name.is_some() {
+            return Err("the destination of a wildcard source must be an existing folder.".into());
+        }
+        match src_path_type {
+            PathType::DeviceStorage => copy_device_matches(backend, src_path, destination_folder, options, context)?,
+            PathType::Local => copy_local_matches(src_path, destination_folder, options, context)?,
+            PathType::Invalid => return Err("invalid source path.".into()),
+        }
+        return Ok(());
+    }
+
+    // 目标文件夹总是作为父文件夹，源文件或文件夹以 dest_name 或源的名称复制到它的下面
+    // 目标不存在时 dest_name 是新的名称，目标是已有的文件夹时 dest_name 为空，设备和本地的目标相同
     match src_path_type {
         PathType::DeviceStorage => {
-            copy_to_device_storage(manager, src_path, destination_folder, dest_is_parent_folder, dest_name, recursive, mirror)
+            copy_to_device_storage(backend, src_path, destination_folder, dest_name, options, context)?;
         }
         PathType::Local => {
-            copy_to_local(src_path, destination_folder, dest_is_parent_folder, dest_name, recursive, mirror)
+            copy_to_local(src_path, destination_folder, dest_name, options, context)?;
         }
         PathType::Invalid => {
             return Err("invalid source path.".into());
         }
     }
+    Ok(())
 }
 
 // 获取目标路径信息
@@ -65,9 +366,10 @@ pub fn do_copy(
 // 如果目标路径不存在或是文件，判断父路径是否是文件夹，返回父路径和目标路径名称,复制到父文件夹下
 // 如果目标路径是文件夹，返回目标路径和空的目标路径名称,copy到目标文件夹下
 // dest_is_parent_folder 用来决定目标路径是作为父文件夹还是具体的目标文件夹,true复制到父文件夹下，false复制到具体的目标文件夹下
+#[allow(clippy::type_complexity, clippy::needless_return)]
 pub fn get_destination_path_info<'a>(dest_inspection: &'a TargetInspectionResult, dest_path: &'a str) -> Result<Option<(&'a str, Option<&'a str>)>, Box<dyn std::error::Error>> {
     match dest_inspection.target_status {
-        TargetStatus::Hidden => return Err("destination path is a hidden file or folder.".into()),
+        TargetStatus::Hidden => return Err(MtpError::HiddenDestination(dest_path.to_string()).into()),
         TargetStatus::NotExist | TargetStatus::File => {
             match dest_inspection.parent_status {
                 TargetStatus::Folder => Ok(Some((
@@ -83,37 +385,29 @@ pub fn get_destination_path_info<'a>(dest_inspection: &'a TargetInspectionResult
 
 
 fn copy_to_device_storage(
-    manager: &Manager,
+    backend: &dyn PortableDeviceBackend,
     src_path: &str,
     destination_folder: &mut impl FolderOperate,
-    dest_is_parent_folder: bool,
     dest_name: Option<&str>,
-    recursive: bool,
-    mirror: bool,
+    options: &CopyOptions,
+    context: &mut CopyContext,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let storage_path = DeviceStoragePath::from(src_path)?;
-    if let Some((_device_info, device, content_object)) = find_file_or_folder(manager, &storage_path)? {
-        let processor = DeviceCopyProcessor::new(&device, content_object.clone());
+    if let Some((_device_info, device, content_object)) = find_file_or_folder(backend, &storage_path)? {
+        let processor = DeviceCopyProcessor::new(device.as_ref(), content_object.clone(), storage_path.full_path());
         let real_dest_name = dest_name.unwrap_or(&content_object.name);
-        processor.copy(
-            real_dest_name,
-            destination_folder,
-            dest_is_parent_folder,
-            recursive,
-            mirror,
-        )
+        processor.copy(real_dest_name, destination_folder, true, options, context)
     } else {
-        Err("failed to open source path.".into())
+        Err(MtpError::PathNotFound(src_path.to_string()).into())
     }
 }
 
 fn copy_to_local(
     src_path: &str,
     destination_folder: &mut impl FolderOperate,
-    dest_is_parent_folder: bool,
     dest_name: Option<&str>,
-    recursive: bool,
-    mirror: bool,
+    options: &CopyOptions,
+    context: &mut CopyContext,
 ) -> Result<(), Box<dyn std::error::Error>> {
     // 处理本地路径
     let src_path_buf;
@@ -136,18 +430,85 @@ fn copy_to_local(
     }
 
     let processor = LocalCopyProcessor::new(src_path);
-    processor.copy(
-        real_dest_name,
-        destination_folder,
-        dest_is_parent_folder,
-        recursive,
-        mirror,
-    )
+    processor.copy(real_dest_name, destination_folder, true, options, context)
 }
 
 
 
-// 判断是否包含通配符，含通配符不支持
+// 复制设备上匹配通配符的文件和文件夹
+fn copy_device_matches(
+    backend: &dyn PortableDeviceBackend,
+    src_path: &str,
+    destination_folder: &mut impl FolderOperate,
+    options: &CopyOptions,
+    context: &mut CopyContext,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let storage_path = DeviceStoragePath::from(src_path)?;
+    let (device_info, device, storage_object) = match find_storage(backend, &storage_path)? {
+        Some(found) => found,
+        None => return Err(MtpError::PathNotFound(src_path.to_string()).into()),
+    };
+    let mut matches = Vec::<(ContentObjectInfo, String)>::new();
+    iterate_file_or_folder(device.as_ref(), &device_info, &storage_object, &storage_path.path, false, |object_info, path| {
+        matches.push((object_info.clone(), path.to_string()));
+    })?;
+    if matches.is_empty() {
+        return Err(MtpError::PathNotFound(src_path.to_string()).into());
+    }
+
+    for (object_info, path) in matches {
+        let folders = if options.flatten {
+            Vec::new()
+        } else {
+            wildcard::matched_folders(&storage_path.path, &DeviceStoragePath::from(&path)?.path)
+        };
+        let processor = DeviceCopyProcessor::new(device.as_ref(), object_info.clone(), path);
+        copy_into_folders(&processor, &object_info.name, destination_folder, &folders, options, context)?;
+    }
+    Ok(())
+}
+
+// 复制本地匹配通配符的文件和文件夹
+fn copy_local_matches(
+    src_path: &str,
+    destination_folder: &mut impl FolderOperate,
+    options: &CopyOptions,
+    context: &mut CopyContext,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let matches = wildcard::find_local_matches(src_path)?;
+    if matches.is_empty() {
+        return Err(MtpError::PathNotFound(src_path.to_string()).into());
+    }
+
+    for matched in matches {
+        let name = matched.path.file_name().and_then(|n| n.to_str()).unwrap_or("").to_string();
+        let folders = if options.flatten { Vec::new() } else { matched.folders };
+        let processor = LocalCopyProcessor::new(&matched.path.to_string_lossy());
+        copy_into_folders(&processor, &name, destination_folder, &folders, options, context)?;
+    }
+    Ok(())
+}
+
+// 在目标文件夹下依次打开或创建 folders，再复制到最后的文件夹中
+fn copy_into_folders(
+    processor: &impl CopyProcessor,
+    name: &str,
+    destination_folder: &mut impl FolderOperate,
+    folders: &[String],
+    options: &CopyOptions,
+    context: &mut CopyContext,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match folders.split_first() {
+        Some((folder, rest)) => {
+            let mut sub_folder = destination_folder.open_or_create_folder(folder, |_| {}, report_creating_new_folder)?;
+            destination_folder.retain(folder);
+            copy_into_folders(processor, name, sub_folder.as_mut(), rest, options, context)
+        }
+        None => processor.copy(name, destination_folder, true, options, context),
+    }
+}
+
+// 判断是否包含通配符
 pub fn has_wildcard(path: &str, path_type: PathType) -> Result<bool, Box<dyn std::error::Error>> {
     let path_to_check = match path_type {
         PathType::DeviceStorage => {
@@ -164,31 +525,31 @@ pub fn has_wildcard(path: &str, path_type: PathType) -> Result<bool, Box<dyn std
 }
 
 pub fn inspect_path(
-    manager: &Manager,
+    backend: &dyn PortableDeviceBackend,
     path: &str,
     path_type: PathType,
 ) -> Result<TargetInspectionResult, Box<dyn std::error::Error>> {
     match path_type {
-        PathType::DeviceStorage => inspect_device_path(manager, path),
+        PathType::DeviceStorage => inspect_device_path(backend, path),
         PathType::Local => inspect_local_path(path),
         PathType::Invalid => Err(format!("invalid path: {}", path).into()),
     }
 }
 
 fn inspect_device_path(
-    manager: &Manager,
+    backend: &dyn PortableDeviceBackend,
     path: &str,
 ) -> Result<TargetInspectionResult, Box<dyn std::error::Error>> {
     let storage_path = DeviceStoragePath::from(path)?;
-    let target_name: Option<String> = storage_path.file_name().and_then(|v| Some(String::from(v)));
-    let target_status = inspect_device_path_status(manager, &storage_path)?;
+    let target_name: Option<String> = storage_path.file_name().map(String::from);
+    let target_status = inspect_device_path_status(backend, &storage_path)?;
 
     // 获取父路径状态和名称
     let parent_status: TargetStatus;
     let parent_path: Option<String>;
     match storage_path.parent() {
         Some(p) => {
-            parent_status = inspect_device_path_status(manager, &p)?;
+            parent_status = inspect_device_path_status(backend, &p)?;
             parent_path = Some(p.full_path());
         }
         None => {
@@ -257,10 +618,10 @@ fn inspect_local_path_status(path_obj: &Path) -> Result<TargetStatus, Box<dyn st
 
 //
 fn inspect_device_path_status(
-    manager: &Manager,
+    backend: &dyn PortableDeviceBackend,
     storage_path: &DeviceStoragePath,
 ) -> Result<TargetStatus, Box<dyn std::error::Error>> {
-    if let Some((_, _, content_object_info)) = find_file_or_folder(manager, storage_path)? {
+    if let Some((_, _, content_object_info)) = find_file_or_folder(backend, storage_path)? {
         match (
             content_object_info.is_hidden || content_object_info.is_system,
             content_object_info.is_folder() || content_object_info.is_storage(),
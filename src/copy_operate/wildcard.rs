@@ -0,0 +1,121 @@
+use std::path::{Path, PathBuf};
+use crate::common::path_matcher::{create_path_pattern_matcher, PathMatcher, PathMatchingState};
+use crate::path::{SEPARATORS, WILDCARD_CHARACTERS};
+
+// 通配符源路径的展开
+// 路径在第一个含通配符的组件处分为基础路径和模式，匹配结果保留基础路径之下的文件夹结构
+// 例如 "music/**/*.flac" 匹配的 "music/a/b/c.flac" 复制为 "目标/a/b/c.flac"
+
+/// Splits a path into the part before the first component with a wildcard and the rest,
+/// `None` if the path has no wildcard.
+pub fn split_at_wildcard(path: &str) -> Option<(&str, &str)> {
+    let wildcard_index = path.find(WILDCARD_CHARACTERS)?;
+    match path[..wildcard_index].rfind(SEPARATORS) {
+        Some(0) => Some((&path[..1], &path[1..])),
+        Some(index) => Some((&path[..index], &path[index + 1..])),
+        None => Some(("", path)),
+    }
+}
+
+/// The folders between the base path of a wildcard pattern and a matched path.
+pub fn matched_folders(pattern: &str, matched_path: &str) -> Vec<String> {
+    let base_count = split_at_wildcard(pattern)
+        .map_or(0, |(base, _)| base.split(SEPARATORS).filter(|c| !c.is_empty()).count());
+    let components: Vec<&str> = matched_path.split(SEPARATORS).filter(|c| !c.is_empty()).collect();
+    let end = components.len().saturating_sub(1);
+    components[base_count.min(end)..end].iter().map(|c| c.to_string()).collect()
+}
+
+/// A local file or folder matching a wildcard path.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LocalMatch {
+    pub path: PathBuf,
+    /// Folders between the base path and the matched entry
+    pub folders: Vec<String>,
+}
+
+/// Finds the local files and folders matching a wildcard path, sorted by path.
+pub fn find_local_matches(pattern: &str) -> Result<Vec<LocalMatch>, Box<dyn std::error::Error>> {
+    let (base, rest) = split_at_wildcard(pattern).ok_or_else(|| format!("no wildcard in the path: {}", pattern))?;
+    let base = if base.is_empty() { Path::new(".") } else { Path::new(base) };
+    let root_matcher = create_path_pattern_matcher(rest)?;
+    let mut matches = Vec::new();
+    if let (PathMatchingState::Accepted, Some(matcher)) = root_matcher.matches_root() {
+        find_local_matches_recursive(base, matcher, &mut Vec::new(), &mut matches)?;
+    }
+    Ok(matches)
+}
+
+fn find_local_matches_recursive(
+    dir: &Path,
+    matcher: &PathMatcher,
+    folders: &mut Vec<String>,
+    matches: &mut Vec<LocalMatch>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let name = match entry.file_name().to_str() {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+        let is_dir = path.is_dir();
+        match matcher.matches(&name, is_dir) {
+            (PathMatchingState::Rejected, _) => (),
+            (PathMatchingState::Completed, _) => matches.push(LocalMatch { path, folders: folders.clone() }),
+            (PathMatchingState::Accepted, next) => {
+                if let Some(next) = next {
+                    folders.push(name);
+                    find_local_matches_recursive(&path, next, folders, matches)?;
+                    folders.pop();
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("a/b/*.jpg" => Some(("a/b", "*.jpg")); "file name")]
+    #[test_case("/a/**/c?/d" => Some(("/a", "**/c?/d")); "any directories")]
+    #[test_case("/*.jpg" => Some(("/", "*.jpg")); "root")]
+    #[test_case("*.jpg" => Some(("", "*.jpg")); "no base")]
+    #[test_case("\\DCIM\\Camera\\IMG*" => Some(("\\DCIM\\Camera", "IMG*")); "backslashes")]
+    #[test_case("a/b.jpg" => None; "no wildcard")]
+    fn test_split_at_wildcard(path: &str) -> Option<(&str, &str)> {
+        split_at_wildcard(path)
+    }
+
+    #[test_case("\\DCIM\\*.jpg", "\\DCIM\\a.jpg" => Vec::<String>::new(); "no folders")]
+    #[test_case("\\DCIM\\**\\*.jpg", "\\DCIM\\Camera\\2024\\a.jpg" => vec!["Camera", "2024"]; "any directories")]
+    #[test_case("\\DCIM\\C*\\*.jpg", "\\DCIM\\Camera\\a.jpg" => vec!["Camera"]; "wildcard folder")]
+    fn test_matched_folders(pattern: &str, matched_path: &str) -> Vec<String> {
+        matched_folders(pattern, matched_path)
+    }
+
+    #[test]
+    fn test_find_local_matches() -> Result<(), Box<dyn std::error::Error>> {
+        let tempdir = tempfile::tempdir()?;
+        let music = tempdir.path().join("music");
+        std::fs::create_dir_all(music.join("a").join("b"))?;
+        std::fs::write(music.join("x.flac"), b"x")?;
+        std::fs::write(music.join("a").join("y.mp3"), b"y")?;
+        std::fs::write(music.join("a").join("b").join("z.flac"), b"z")?;
+
+        let pattern = format!("{}/**/*.flac", music.display());
+        let matches = find_local_matches(&pattern)?;
+        assert_eq!(
+            matches,
+            vec![
+                LocalMatch { path: music.join("a").join("b").join("z.flac"), folders: vec!["a".to_string(), "b".to_string()] },
+                LocalMatch { path: music.join("x.flac"), folders: vec![] },
+            ]
+        );
+        Ok(())
+    }
+}
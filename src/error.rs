@@ -0,0 +1,250 @@
+use std::fmt;
+use crate::mtp::codes::*;
+use crate::mtp::session::ResponseError;
+
+// 错误类型，每一类错误对应一个进程退出码，脚本可以根据退出码区分错误
+// 函数仍然返回 Box<dyn std::error::Error>，main 根据错误类型决定退出码
+
+/// Exit code for errors that do not belong to any category (bad arguments etc.).
+pub const EXIT_FAILURE: i32 = 1;
+
+#[derive(Debug)]
+pub enum MtpError {
+    /// No device matched the name
+    DeviceNotFound(String),
+    /// More than one device matched the name
+    AmbiguousDevice(String),
+    /// No storage matched the name
+    StorageNotFound(String),
+    /// More than one storage matched the name
+    AmbiguousStorage(String),
+    /// The file or folder does not exist
+    PathNotFound(String),
+    /// The destination is a hidden file or folder
+    HiddenDestination(String),
+    /// Neither the destination nor its parent folder exists
+    DestinationNotCreatable(String),
+    /// The device or the file system refused the operation
+    AccessDenied(String),
+    /// The device was disconnected
+    Disconnected(String),
+    /// The copied files do not match the source
+    VerificationFailed(String),
+    Io(std::io::Error),
+    /// An error reported by the WPD backend
+    Backend { hresult: i32, message: String },
+}
+
+impl MtpError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            MtpError::DeviceNotFound(_) => 3,
+            MtpError::AmbiguousDevice(_) => 4,
+            MtpError::StorageNotFound(_) => 5,
+            MtpError::AmbiguousStorage(_) => 6,
+            MtpError::PathNotFound(_) => 7,
+            MtpError::HiddenDestination(_) => 8,
+            MtpError::DestinationNotCreatable(_) => 9,
+            MtpError::AccessDenied(_) => 10,
+            MtpError::Disconnected(_) => 11,
+            MtpError::Io(_) => 12,
+            MtpError::Backend { .. } => 13,
+            MtpError::VerificationFailed(_) => 14,
+        }
+    }
+
+    // 把任意错误转换为 MtpError，无法分类的错误返回 None
+    pub fn classify(err: &(dyn std::error::Error + 'static)) -> Option<MtpError> {
+        if let Some(err) = err.downcast_ref::<MtpError>() {
+            return Some(err.duplicate());
+        }
+        if let Some(err) = err.downcast_ref::<std::io::Error>() {
+            // 包装在 io::Error 中的错误按原来的错误分类
+            if let Some(inner) = err.get_ref().and_then(|inner| MtpError::classify(inner)) {
+                return Some(inner);
+            }
+            return Some(MtpError::from(std::io::Error::new(err.kind(), err.to_string())));
+        }
+        if let Some(err) = err.downcast_ref::<ResponseError>() {
+            return Some(MtpError::from(err.clone()));
+        }
+        #[cfg(windows)]
+        if let Some(err) = err.downcast_ref::<windows::core::Error>() {
+            return Some(MtpError::from(err.clone()));
+        }
+        None
+    }
+
+    // std::io::Error 不能 clone，只保留 kind 和信息
+    fn duplicate(&self) -> MtpError {
+        match self {
+            MtpError::DeviceNotFound(s) => MtpError::DeviceNotFound(s.clone()),
+            MtpError::AmbiguousDevice(s) => MtpError::AmbiguousDevice(s.clone()),
+            MtpError::StorageNotFound(s) => MtpError::StorageNotFound(s.clone()),
+            MtpError::AmbiguousStorage(s) => MtpError::AmbiguousStorage(s.clone()),
+            MtpError::PathNotFound(s) => MtpError::PathNotFound(s.clone()),
+            MtpError::HiddenDestination(s) => MtpError::HiddenDestination(s.clone()),
+            MtpError::DestinationNotCreatable(s) => MtpError::DestinationNotCreatable(s.clone()),
+            MtpError::AccessDenied(s) => MtpError::AccessDenied(s.clone()),
+            MtpError::Disconnected(s) => MtpError::Disconnected(s.clone()),
+            MtpError::VerificationFailed(s) => MtpError::VerificationFailed(s.clone()),
+            MtpError::Io(err) => MtpError::Io(std::io::Error::new(err.kind(), err.to_string())),
+            MtpError::Backend { hresult, message } => MtpError::Backend {
+                hresult: *hresult,
+                message: message.clone(),
+            },
+        }
+    }
+}
+
+// 错误的退出码
+pub fn exit_code(err: &(dyn std::error::Error + 'static)) -> i32 {
+    MtpError::classify(err).map(|err| err.exit_code()).unwrap_or(EXIT_FAILURE)
+}
+
+impl fmt::Display for MtpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MtpError::DeviceNotFound(name) => write!(f, "device was not found: {}", name),
+            MtpError::AmbiguousDevice(name) => write!(f, "multiple devices were matched: {}", name),
+            MtpError::StorageNotFound(name) => write!(f, "storage was not found: {}", name),
+            MtpError::AmbiguousStorage(name) => write!(f, "multiple storages were matched: {}", name),
+            MtpError::PathNotFound(path) => write!(f, "path was not found: {}", path),
+            MtpError::HiddenDestination(path) => write!(f, "destination path is a hidden file or folder: {}", path),
+            MtpError::DestinationNotCreatable(path) => write!(f, "cannot create the destination path: {}", path),
+            MtpError::AccessDenied(message) => write!(f, "access denied: {}", message),
+            MtpError::Disconnected(message) => write!(f, "device was disconnected: {}", message),
+            MtpError::VerificationFailed(names) => write!(f, "checksum mismatch: {}", names),
+            MtpError::Io(err) => write!(f, "{}", err),
+            MtpError::Backend { hresult, message } => write!(f, "{} (HRESULT 0x{:08X})", message, hresult),
+        }
+    }
+}
+
+impl std::error::Error for MtpError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MtpError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for MtpError {
+    fn from(err: std::io::Error) -> Self {
+        use std::io::ErrorKind;
+        match err.kind() {
+            ErrorKind::PermissionDenied => MtpError::AccessDenied(err.to_string()),
+            ErrorKind::NotFound => MtpError::PathNotFound(err.to_string()),
+            // 连接中断
+            ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe
+            | ErrorKind::UnexpectedEof => MtpError::Disconnected(err.to_string()),
+            _ => MtpError::Io(err),
+        }
+    }
+}
+
+impl From<ResponseError> for MtpError {
+    fn from(err: ResponseError) -> Self {
+        match err.code {
+            RESPONSE_ACCESS_DENIED | RESPONSE_OBJECT_WRITE_PROTECTED | RESPONSE_STORE_READ_ONLY => {
+                MtpError::AccessDenied(err.to_string())
+            }
+            RESPONSE_INVALID_OBJECT_HANDLE | RESPONSE_INVALID_PARENT_OBJECT => MtpError::PathNotFound(err.to_string()),
+            RESPONSE_INVALID_STORAGE_ID => MtpError::StorageNotFound(err.to_string()),
+            RESPONSE_SESSION_NOT_OPEN => MtpError::Disconnected(err.to_string()),
+            code => MtpError::Backend {
+                hresult: code as i32,
+                message: err.to_string(),
+            },
+        }
+    }
+}
+
+// HRESULT_FROM_WIN32 的错误码
+#[cfg(windows)]
+const E_ACCESSDENIED: u32 = 0x80070005;
+#[cfg(windows)]
+const HRESULT_FILE_NOT_FOUND: u32 = 0x80070002;
+#[cfg(windows)]
+const HRESULT_PATH_NOT_FOUND: u32 = 0x80070003;
+#[cfg(windows)]
+const HRESULT_NOT_READY: u32 = 0x80070015;
+#[cfg(windows)]
+const HRESULT_GEN_FAILURE: u32 = 0x8007001F;
+#[cfg(windows)]
+const HRESULT_DEVICE_NOT_CONNECTED: u32 = 0x8007048F;
+#[cfg(windows)]
+const HRESULT_DEVICE_REMOVED: u32 = 0x80070651;
+
+#[cfg(windows)]
+impl From<windows::core::Error> for MtpError {
+    fn from(err: windows::core::Error) -> Self {
+        let hresult = err.code().0;
+        let message = err.message().to_string();
+        match hresult as u32 {
+            E_ACCESSDENIED => MtpError::AccessDenied(message),
+            HRESULT_FILE_NOT_FOUND | HRESULT_PATH_NOT_FOUND => MtpError::PathNotFound(message),
+            HRESULT_NOT_READY | HRESULT_GEN_FAILURE | HRESULT_DEVICE_NOT_CONNECTED | HRESULT_DEVICE_REMOVED => {
+                MtpError::Disconnected(message)
+            }
+            _ => MtpError::Backend { hresult, message },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let errors = [
+            MtpError::DeviceNotFound(String::new()),
+            MtpError::AmbiguousDevice(String::new()),
+            MtpError::StorageNotFound(String::new()),
+            MtpError::AmbiguousStorage(String::new()),
+            MtpError::PathNotFound(String::new()),
+            MtpError::HiddenDestination(String::new()),
+            MtpError::DestinationNotCreatable(String::new()),
+            MtpError::AccessDenied(String::new()),
+            MtpError::Disconnected(String::new()),
+            MtpError::VerificationFailed(String::new()),
+            MtpError::Io(std::io::Error::other("")),
+            MtpError::Backend { hresult: 0, message: String::new() },
+        ];
+        let mut codes: Vec<i32> = errors.iter().map(|err| err.exit_code()).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+        // 0 为成功，1 为其他错误，2 为参数错误 (clap)
+        assert!(codes.iter().all(|code| *code > 2));
+    }
+
+    #[test]
+    fn test_exit_code_of_boxed_errors() {
+        let err: Box<dyn std::error::Error> = MtpError::DeviceNotFound("Phone".to_string()).into();
+        assert_eq!(exit_code(err.as_ref()), 3);
+        assert_eq!(err.to_string(), "device was not found: Phone");
+
+        let err: Box<dyn std::error::Error> = std::io::Error::from(std::io::ErrorKind::PermissionDenied).into();
+        assert_eq!(exit_code(err.as_ref()), 10);
+        let err: Box<dyn std::error::Error> = std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into();
+        assert_eq!(exit_code(err.as_ref()), 11);
+        let err: Box<dyn std::error::Error> = std::io::Error::from(std::io::ErrorKind::WriteZero).into();
+        assert_eq!(exit_code(err.as_ref()), 12);
+
+        let err: Box<dyn std::error::Error> = Box::new(ResponseError {
+            operation: OPERATION_DELETE_OBJECT,
+            code: RESPONSE_OBJECT_WRITE_PROTECTED,
+        });
+        assert_eq!(exit_code(err.as_ref()), 10);
+
+        let err: Box<dyn std::error::Error> = "invalid device storage path format.".into();
+        assert_eq!(exit_code(err.as_ref()), EXIT_FAILURE);
+    }
+}
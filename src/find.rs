@@ -1,39 +1,25 @@
 use crate::common::path_matcher::{create_path_pattern_matcher, PathMatcher, PathMatchingState};
 use crate::list::{list_devices, list_device_storages};
 use crate::path::{DeviceStoragePath, SEPARATORS};
-use crate::wpd::device::{ContentObject, ContentObjectInfo, ContentObjectIterator, Device};
-use crate::wpd::manager::{DeviceInfo, Manager};
+use crate::backend::{ContentObject, ContentObjectInfo, ContentObjectIterator, DeviceInfo, DeviceOperate, PortableDeviceBackend};
+use crate::error::MtpError;
+
+// 找到的设备信息、设备实例和对象信息
+pub type FoundObject = (DeviceInfo, Box<dyn DeviceOperate>, ContentObjectInfo);
 
 // 查找设备存储
 // input: storage_path = "设备名:存储名"
 // output: 设备信息、设备实例和存储信息
-pub fn find_storage(manager: &Manager, storage_path: &DeviceStoragePath) -> Result<Option<(DeviceInfo, Device, ContentObjectInfo)>, Box<dyn std::error::Error>> {
+pub fn find_storage(backend: &dyn PortableDeviceBackend, storage_path: &DeviceStoragePath) -> Result<Option<FoundObject>, Box<dyn std::error::Error>> {
     log::trace!("find_device_storage: storage_path = {:?}", storage_path);
-    fn ensure_single_match<T>(
-        vec: Vec<T>,
-        entity_name: &str,
-        search_key: &str,
-    ) -> Result<T, Box<dyn std::error::Error>> {
-        match vec.len() {
-            0 => Err(format!("{} was not found: {}", entity_name, search_key).into()),
-            1 => Ok(vec.into_iter().next().unwrap()),
-            _ => Err(format!("multiple {} were matched: {}", entity_name, search_key).into()),
-        }
-    }
-    // 1. 找到设备
-    let device_info = ensure_single_match(
-        list_devices(manager, Some(&storage_path.device_name))?,
-        "device",
-        &storage_path.device_name,
-    )?;
+    // 1. 找到并打开设备
+    let (device_info, device) = find_device(backend, &storage_path.device_name)?;
 
-    // 2. 打开设备
-    let device = Device::open(&device_info)?;
-
-    // 3. 找到存储
+    // 2. 找到存储
     let storage_object = ensure_single_match(
-        list_device_storages(&device, Some(&storage_path.storage_name))?,
-        "storage",
+        list_device_storages(device.as_ref(), Some(&storage_path.storage_name))?,
+        MtpError::StorageNotFound,
+        MtpError::AmbiguousStorage,
         &format!("{}:{}", &storage_path.device_name, &storage_path.storage_name),
     )?;
 
@@ -46,17 +32,57 @@ pub fn find_storage(manager: &Manager, storage_path: &DeviceStoragePath) -> Resu
     Ok(Some((device_info, device, storage_object)))
 }
 
+// 查找并打开设备，device_name 可以包含通配符，但必须只匹配一个设备
+pub fn find_device(backend: &dyn PortableDeviceBackend, device_name: &str) -> Result<(DeviceInfo, Box<dyn DeviceOperate>), Box<dyn std::error::Error>> {
+    let device_info = ensure_single_match(
+        list_devices(backend, Some(device_name))?,
+        MtpError::DeviceNotFound,
+        MtpError::AmbiguousDevice,
+        device_name,
+    )?;
+    let device = backend.open_device(&device_info)?;
+    Ok((device_info, device))
+}
+
+fn ensure_single_match<T>(
+    vec: Vec<T>,
+    not_found: fn(String) -> MtpError,
+    ambiguous: fn(String) -> MtpError,
+    search_key: &str,
+) -> Result<T, Box<dyn std::error::Error>> {
+    match vec.len() {
+        0 => Err(not_found(search_key.to_string()).into()),
+        1 => Ok(vec.into_iter().next().unwrap()),
+        _ => Err(ambiguous(search_key.to_string()).into()),
+    }
+}
+
+// 在已经打开的设备上查找文件或文件夹，不使用 storage_path 中的设备名
+pub fn find_file_or_folder_in_device(
+    device: &dyn DeviceOperate,
+    device_info: &DeviceInfo,
+    storage_path: &DeviceStoragePath,
+) -> Result<Option<ContentObjectInfo>, Box<dyn std::error::Error>> {
+    let storage_object = ensure_single_match(
+        list_device_storages(device, Some(&storage_path.storage_name))?,
+        MtpError::StorageNotFound,
+        MtpError::AmbiguousStorage,
+        &format!("{}:{}", &device_info.name, &storage_path.storage_name),
+    )?;
+    Ok(find_device_storage_file_or_folder(device, device_info, &storage_object, &storage_path.path)?.map(|(info, _)| info))
+}
+
 // 查找文件或文件夹，
 // input: storage_path = "设备名:存储名:路径"
 // output: 设备信息、设备实例和存储信息
-pub fn find_file_or_folder(manager: &Manager, storage_path: &DeviceStoragePath) -> Result<Option<(DeviceInfo, Device, ContentObjectInfo)>, Box<dyn std::error::Error>> {
+pub fn find_file_or_folder(backend: &dyn PortableDeviceBackend, storage_path: &DeviceStoragePath) -> Result<Option<FoundObject>, Box<dyn std::error::Error>> {
     log::trace!("find_device_file_or_folder");
     // 尝试查找设备存储
-    if let Some((device_info, device, storage_object)) = find_storage(manager, storage_path)?{
+    if let Some((device_info, device, storage_object)) = find_storage(backend, storage_path)?{
         log::trace!("find_device_file_or_folder: storage found");
         // 尝试查找文件或文件夹
         match find_device_storage_file_or_folder(
-            &device,
+            device.as_ref(),
             &device_info,
             &storage_object,
             &storage_path.path,
@@ -78,7 +104,7 @@ pub fn find_file_or_folder(manager: &Manager, storage_path: &DeviceStoragePath)
 
 // 查询某个设备的某个storage的文件或文件夹，path：设备名:存储名:路径
 fn find_device_storage_file_or_folder(
-    device: &Device,
+    device: &dyn DeviceOperate,
     device_info: &DeviceInfo,
     storage_object: &ContentObjectInfo,
     path: &str,
@@ -99,7 +125,7 @@ fn find_device_storage_file_or_folder(
 // 获取storage的文件或文件夹
 //  recursive: 是否递归, callback: 回调函数,
 pub fn iterate_file_or_folder<F>(
-    device: &Device,
+    device: &dyn DeviceOperate,
     device_info: &DeviceInfo,
     storage_object: &ContentObjectInfo,
     path: &str,
@@ -140,11 +166,11 @@ pub fn iterate_file_or_folder<F>(
     }
 }
 
-fn get_object_iterator(
-    device: &Device,
+fn get_object_iterator<'d>(
+    device: &'d dyn DeviceOperate,
     content_object: &ContentObject,
     storage_path: &str,
-) -> Result<Option<ContentObjectIterator>, Box<dyn std::error::Error>> {
+) -> Result<Option<Box<dyn ContentObjectIterator + 'd>>, Box<dyn std::error::Error>> {
     match device.get_object_iterator(content_object) {
         Err(err) => {
             log::debug!("{}", err);
@@ -156,8 +182,8 @@ fn get_object_iterator(
 }
 
 fn iterate_file_or_folder_recursive<F>(
-    device: &Device,
-    mut content_object_iterator: ContentObjectIterator,
+    device: &dyn DeviceOperate,
+    mut content_object_iterator: Box<dyn ContentObjectIterator + '_>,
     path_matcher: &PathMatcher,
     base_path: String,
     callback: &mut F,
@@ -218,3 +244,108 @@ fn join_path(base_path: &str, sub_path: &str) -> String {
 
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    fn create_backend() -> MemoryBackend {
+        let backend = MemoryBackend::with_storage("Redmi K70", "内部存储设备");
+        backend.add_storage("Redmi K70", "SD card").unwrap();
+        backend.add_file("Redmi K70:内部存储设备:/Pictures/a.jpg", b"a").unwrap();
+        backend.add_file("Redmi K70:内部存储设备:/Pictures/2024/b.jpg", b"b").unwrap();
+        backend.add_file("Redmi K70:内部存储设备:/Pictures/2024/c.png", b"c").unwrap();
+        backend
+    }
+
+    fn collect_paths(backend: &MemoryBackend, path: &str, recursive: bool) -> Vec<String> {
+        let storage_path = DeviceStoragePath::from(path).unwrap();
+        let (device_info, device, storage_object) = find_storage(backend, &storage_path).unwrap().unwrap();
+        let mut paths = Vec::<String>::new();
+        iterate_file_or_folder(
+            device.as_ref(),
+            &device_info,
+            &storage_object,
+            &storage_path.path,
+            recursive,
+            |_, path| paths.push(path.to_string()),
+        ).unwrap();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_find_storage() {
+        let backend = create_backend();
+        let storage_path = DeviceStoragePath::from("Redmi K70:SD card:").unwrap();
+        let (device_info, _device, storage_object) = find_storage(&backend, &storage_path).unwrap().unwrap();
+        assert_eq!(device_info.name, "Redmi K70");
+        assert!(storage_object.is_storage());
+        assert_eq!(storage_object.name, "SD card");
+    }
+
+    #[test]
+    fn test_find_storage_with_several_devices() {
+        let backend = create_backend();
+        backend.add_device("Pixel 8");
+        backend.add_storage("Pixel 8", "SD card").unwrap();
+        // 按名称选择设备和存储，其他设备不影响查找
+        let storage_path = DeviceStoragePath::from("Pixel 8:SD card:").unwrap();
+        let (device_info, _device, storage_object) = find_storage(&backend, &storage_path).unwrap().unwrap();
+        assert_eq!(device_info.name, "Pixel 8");
+        assert_eq!(storage_object.name, "SD card");
+        let storage_path = DeviceStoragePath::from("Redmi K70:内部存储设备:").unwrap();
+        assert_eq!(find_storage(&backend, &storage_path).unwrap().unwrap().0.name, "Redmi K70");
+    }
+
+    #[test]
+    fn test_find_storage_errors() {
+        let backend = create_backend();
+        let not_found = DeviceStoragePath::from("Pixel:内部存储设备:").unwrap();
+        let err = find_storage(&backend, &not_found).err().unwrap();
+        assert!(matches!(err.downcast_ref::<MtpError>(), Some(MtpError::DeviceNotFound(_))));
+        let ambiguous = DeviceStoragePath::from("Redmi K70:*:").unwrap();
+        let err = find_storage(&backend, &ambiguous).err().unwrap();
+        assert!(matches!(err.downcast_ref::<MtpError>(), Some(MtpError::AmbiguousStorage(_))));
+    }
+
+    #[test]
+    fn test_find_file_or_folder() {
+        let backend = create_backend();
+        let storage_path = DeviceStoragePath::from("Redmi K70:内部存储设备:/Pictures/2024").unwrap();
+        let (_, _, object_info) = find_file_or_folder(&backend, &storage_path).unwrap().unwrap();
+        assert!(object_info.is_folder());
+        assert_eq!(object_info.name, "2024");
+
+        let storage_path = DeviceStoragePath::from("Redmi K70:内部存储设备:/Pictures/x.jpg").unwrap();
+        assert!(find_file_or_folder(&backend, &storage_path).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_iterate_file_or_folder_recursive() {
+        let backend = create_backend();
+        assert_eq!(
+            collect_paths(&backend, "Redmi K70:内部存储设备:/Pictures", true),
+            vec![
+                "Redmi K70:内部存储设备:\\Pictures",
+                "Redmi K70:内部存储设备:\\Pictures\\2024",
+                "Redmi K70:内部存储设备:\\Pictures\\2024\\b.jpg",
+                "Redmi K70:内部存储设备:\\Pictures\\2024\\c.png",
+                "Redmi K70:内部存储设备:\\Pictures\\a.jpg",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_iterate_file_or_folder_wildcard() {
+        let backend = create_backend();
+        assert_eq!(
+            collect_paths(&backend, "Redmi K70:内部存储设备:/Pictures/**/*.jpg", false),
+            vec![
+                "Redmi K70:内部存储设备:\\Pictures\\2024\\b.jpg",
+                "Redmi K70:内部存储设备:\\Pictures\\a.jpg",
+            ]
+        );
+    }
+}
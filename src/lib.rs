@@ -0,0 +1,66 @@
+mod find;
+mod list;
+#[cfg(windows)]
+mod wpd;
+pub mod backend;
+pub mod mtp;
+pub mod path;
+mod common;
+pub mod copy_operate;
+pub mod copy;
+pub mod remove;
+pub mod mkdir;
+pub mod rename;
+pub mod stat;
+pub mod output;
+pub mod error;
+pub mod checksum;
+pub mod session;
+
+pub use crate::common::timestamp::{TimeZone, Timestamp};
+pub use crate::copy_operate::CopyOptions;
+pub use crate::error::MtpError;
+pub use crate::list::{sort_entries, Entry, SortKey};
+pub use crate::session::Session;
+
+// 需要连接 "Redmi K70"，用 cargo test -- --ignored 运行
+#[cfg(all(test, windows))]
+mod tests {
+    use super::*;
+
+    const STORAGE: &str = "Redmi K70:内部存储设备:";
+
+    fn open_session() -> Session {
+        Session::open(Some("wpd"), TimeZone::UTC).unwrap()
+    }
+
+    #[test]
+    #[ignore = "needs a connected device"]
+    fn test_list_storages() {
+        let entries = open_session().list_storages().unwrap();
+        assert!(entries.iter().any(|entry| entry.device == "Redmi K70" && entry.info.is_storage()));
+    }
+
+    #[test]
+    #[ignore = "needs a connected device"]
+    fn test_stat_storage() {
+        let entry = open_session().stat(STORAGE).unwrap();
+        assert_eq!(entry.device, "Redmi K70");
+        assert!(entry.info.is_storage());
+    }
+
+    #[test]
+    #[ignore = "needs a connected device"]
+    fn test_stat_folder() {
+        let entry = open_session().stat(&format!("{}/Pictures", STORAGE)).unwrap();
+        assert!(entry.info.is_folder());
+        assert_eq!(entry.info.name, "Pictures");
+    }
+
+    #[test]
+    #[ignore = "needs a connected device"]
+    fn test_list_files() {
+        let entries = open_session().list(&format!("{}/Pictures", STORAGE), true).unwrap();
+        assert!(entries.iter().all(|entry| entry.path.starts_with("Redmi K70:内部存储设备:\\Pictures")));
+    }
+}
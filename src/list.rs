@@ -1,29 +1,32 @@
-use windows::core::Error;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::str::FromStr;
+use crate::backend::{ContentObjectInfo, DeviceInfo, DeviceOperate, PortableDeviceBackend};
+use crate::error::MtpError;
+use crate::common::filename::FileNamePattern;
 use crate::find::iterate_file_or_folder;
 use crate::path::DeviceStoragePath;
-use crate::wpd::device::{ContentObjectInfo, Device};
-use crate::wpd::manager::{DeviceInfo, Manager};
 
 
-// 列出设备
-pub fn list_devices(manager: &Manager, pattern: Option<&str>) -> Result<Vec<DeviceInfo>, Error> {
+// 列出设备, pattern 可以包含通配符
+pub fn list_devices(backend: &dyn PortableDeviceBackend, pattern: Option<&str>) -> Result<Vec<DeviceInfo>, Box<dyn std::error::Error>> {
+    let name_pattern = pattern.map(FileNamePattern::new);
 
-    let mut devices = Vec::<DeviceInfo>::new();
-
-    let mut iter = manager.get_device_iterator()?;
-    while let Some(device_info) = iter.next()? {
-        devices.push(device_info);
-    }
+    let devices = backend
+        .list_devices()?
+        .into_iter()
+        .filter(|device_info| name_pattern.as_ref().is_none_or(|p| p.matches(&device_info.name)))
+        .collect();
     Ok(devices)
 }
 
 // 获取设备对象
-fn get_device_object(device: &Device) -> Result<Option<ContentObjectInfo>, Box<dyn std::error::Error>> {
+pub fn get_device_object(device: &dyn DeviceOperate) -> Result<Option<ContentObjectInfo>, Box<dyn std::error::Error>> {
     let root = device.get_root_object();
     match device.get_object_iterator(&root) {
         Err(err) => {
             log::debug!("{}", err);
-            log::warn!("failed to get the device object: {}", &device.name);
+            log::warn!("failed to get the device object: {}", device.name());
         }
         Ok(mut iter) => {
             while let Some(obj) = iter.next()? {
@@ -39,9 +42,10 @@ fn get_device_object(device: &Device) -> Result<Option<ContentObjectInfo>, Box<d
     Ok(None)
 }
 
-// 列出某个设备的存储对象
-pub fn list_device_storages(device: &Device, pattern: Option<&str>) -> Result<Vec<ContentObjectInfo>, Box<dyn std::error::Error>> {
+// 列出某个设备的存储对象, pattern 可以包含通配符
+pub fn list_device_storages(device: &dyn DeviceOperate, pattern: Option<&str>) -> Result<Vec<ContentObjectInfo>, Box<dyn std::error::Error>> {
     log::trace!("device_find_storage_objects pattern={:?}", &pattern);
+    let name_pattern = pattern.map(FileNamePattern::new);
 
     let mut objects = Vec::<ContentObjectInfo>::new();
 
@@ -60,7 +64,7 @@ pub fn list_device_storages(device: &Device, pattern: Option<&str>) -> Result<Ve
                 log::trace!("  detected device object entry {:?}", &obj);
                 let info = device.get_object_info(obj)?;
                 log::trace!("   details {:?}", &info);
-                if info.is_storage(){
+                if info.is_storage() && name_pattern.as_ref().is_none_or(|p| p.matches(&info.name)) {
                     log::trace!("   --> storage object found");
                     objects.push(info);
                 }
@@ -70,21 +74,30 @@ pub fn list_device_storages(device: &Device, pattern: Option<&str>) -> Result<Ve
     Ok(objects)
 }
 
+/// A storage, file or folder found on a device.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub device: String,
+    pub storage: String,
+    /// The full path, e.g. "Redmi K70:内部存储设备:\Pictures"
+    pub path: String,
+    pub info: ContentObjectInfo,
+}
+
 // 列出所有设备的storages
-pub fn list_storages() -> Result<(), Box<dyn std::error::Error>> {
+pub fn list_storages(backend: &dyn PortableDeviceBackend) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
     log::trace!("COMMAND list-storages");
 
-    let manager = Manager::get_portable_device_manager().unwrap();
-    let device_info_vec = list_devices(&manager, None)?;
+    let device_info_vec = list_devices(backend, None)?;
 
-    let mut count = 0;
+    let mut entries = Vec::<Entry>::new();
     for device_info in device_info_vec {
-        match Device::open(&device_info) {
+        match backend.open_device(&device_info) {
             Err(err) => {
                 log::debug!("{}", err);
                 log::warn!("failed to open \"{}\" (skipped)", device_info.name);
             }
-            Ok(device) => match list_device_storages(&device, None) {
+            Ok(device) => match list_device_storages(device.as_ref(), None) {
                 Err(err) => {
                     log::debug!("{}", err);
                     log::warn!(
@@ -94,72 +107,233 @@ pub fn list_storages() -> Result<(), Box<dyn std::error::Error>> {
                 }
                 Ok(storage_object_vec) => {
                     for storage_object_info in storage_object_vec {
-                        count += 1;
-                        println!("{}:{}:", &device_info.name, &storage_object_info.name);
+                        entries.push(Entry {
+                            device: device_info.name.clone(),
+                            storage: storage_object_info.name.clone(),
+                            path: format!("{}:{}:", &device_info.name, &storage_object_info.name),
+                            info: storage_object_info,
+                        });
                     }
                 }
             },
         }
     }
-    if count == 0 {
-        println!("no storages were found.")
-    }
-    Ok(())
+    Ok(entries)
 }
 
-// 列出文件 path: Redmi K70:内部存储设备:/Pictures,recurse是否递归, detail是否显示详细信息
-pub fn list_files(path: String, recursive: bool, detail: bool) -> Result<(), Box<dyn std::error::Error>> {
+// 列出文件 path: Redmi K70:内部存储设备:/Pictures,recurse是否递归
+pub fn list_files(backend: &dyn PortableDeviceBackend, path: &str, recursive: bool) -> Result<Vec<Entry>, Box<dyn std::error::Error>> {
 
-    let storage_path = DeviceStoragePath::from(&path)?;
+    let storage_path = DeviceStoragePath::from(path)?;
 
-    let manager = Manager::get_portable_device_manager()?;
-    let device_info_vec = list_devices(&manager, Some(&storage_path.device_name))?;
+    let device_info_vec = list_devices(backend, Some(&storage_path.device_name))?;
 
-    if device_info_vec.len() == 0 {
-        return Err("No device matched.".into());
+    if device_info_vec.is_empty() {
+        return Err(MtpError::DeviceNotFound(storage_path.device_name.clone()).into());
     }
 
+    let mut entries = Vec::<Entry>::new();
     for device_info in device_info_vec {
-        let device = Device::open(&device_info)?;
-        let storage_object_vec = list_device_storages(&device, Some(&storage_path.storage_name))?;
-
-        let callback = if detail{
-            show_file_or_folder_with_details
-        } else {
-            show_file_or_folder_path_only
-        };
+        let device = backend.open_device(&device_info)?;
+        let storage_object_vec = list_device_storages(device.as_ref(), Some(&storage_path.storage_name))?;
 
         for storage_object_info in storage_object_vec {
             iterate_file_or_folder(
-                &device,
+                device.as_ref(),
                 &device_info,
                 &storage_object_info,
                 &storage_path.path,
                 recursive,
-                callback,
+                |info, path| {
+                    entries.push(Entry {
+                        device: device_info.name.clone(),
+                        storage: storage_object_info.name.clone(),
+                        path: path.to_string(),
+                        info: info.clone(),
+                    })
+                },
             )?;
         }
     }
-    Ok(())
+    Ok(entries)
+}
+
+/// The key to sort listed entries by.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SortKey {
+    /// Case-insensitive name
+    Name,
+    /// Largest first
+    Size,
+    /// Newest modification time first
+    Time,
+    /// Storages, folders, then files by extension
+    Type,
 }
 
+impl FromStr for SortKey {
+    type Err = String;
 
-fn show_file_or_folder_with_details(info: &ContentObjectInfo, path: &str){
-    println!(
-        "[{:<4}] {:<19} {:<19} {}",
-        if info.is_file() {
-            "FILE"
-        } else if info.is_folder() {
-            "DIR"
-        } else {
-            ""
-        },
-        if info.is_system { "S" } else { "-" },
-        if info.is_hidden { "H" } else { "-" },
-        path
-    );
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "name" => Ok(SortKey::Name),
+            "size" => Ok(SortKey::Size),
+            "time" => Ok(SortKey::Time),
+            "type" => Ok(SortKey::Type),
+            _ => Err(format!("unknown sort key: {} (name, size, time or type)", s)),
+        }
+    }
 }
 
-fn show_file_or_folder_path_only(_info: &ContentObjectInfo, path: &str){
-    println!("{}", path);
-}
\ No newline at end of file
+/// Sorts the entries of each folder by `key`, the folder contents stay under their folder.
+///
+/// Folders come before files when `directories_first` is set, also when `reverse` is set.
+pub fn sort_entries(entries: Vec<Entry>, key: SortKey, reverse: bool, directories_first: bool) -> Vec<Entry> {
+    // 按上层文件夹分组，分别排序后按树的顺序输出
+    let paths: Vec<String> = entries.iter().map(|entry| normalize_path(&entry.path)).collect();
+    let path_indices: HashMap<&str, usize> = paths.iter().enumerate().map(|(index, path)| (path.as_str(), index)).collect();
+    let mut children = HashMap::<Option<&str>, Vec<usize>>::new();
+    for (index, entry) in entries.iter().enumerate() {
+        let parent = DeviceStoragePath::from(&entry.path)
+            .ok()
+            .and_then(|path| path.parent())
+            .and_then(|parent| path_indices.get(parent.full_path().as_str()).copied())
+            .map(|parent_index| paths[parent_index].as_str());
+        children.entry(parent).or_default().push(index);
+    }
+    for indices in children.values_mut() {
+        indices.sort_by(|a, b| {
+            let (a, b) = (&entries[*a].info, &entries[*b].info);
+            let group = if directories_first { is_container(b).cmp(&is_container(a)) } else { Ordering::Equal };
+            let order = compare_entries(a, b, key);
+            group.then(if reverse { order.reverse() } else { order })
+        });
+    }
+
+    let mut order = Vec::with_capacity(entries.len());
+    let mut stack: Vec<usize> = children.get(&None).map(|roots| roots.iter().rev().copied().collect()).unwrap_or_default();
+    while let Some(index) = stack.pop() {
+        order.push(index);
+        if let Some(indices) = children.get(&Some(paths[index].as_str())) {
+            stack.extend(indices.iter().rev());
+        }
+    }
+    let mut entries: Vec<Option<Entry>> = entries.into_iter().map(Some).collect();
+    order.into_iter().filter_map(|index| entries[index].take()).collect()
+}
+
+fn normalize_path(path: &str) -> String {
+    DeviceStoragePath::from(path).map_or_else(|_| path.to_string(), |path| path.full_path())
+}
+
+fn is_container(info: &ContentObjectInfo) -> bool {
+    info.is_folder() || info.is_functional_object()
+}
+
+fn compare_entries(a: &ContentObjectInfo, b: &ContentObjectInfo, key: SortKey) -> Ordering {
+    let by_name = || a.name.to_lowercase().cmp(&b.name.to_lowercase()).then_with(|| a.name.cmp(&b.name));
+    match key {
+        SortKey::Name => by_name(),
+        SortKey::Size => b.data_size.cmp(&a.data_size).then_with(by_name),
+        SortKey::Time => b.time_modified.cmp(&a.time_modified).then_with(by_name),
+        SortKey::Type => type_rank(a)
+            .cmp(&type_rank(b))
+            .then_with(|| extension(&a.name).cmp(&extension(&b.name)))
+            .then_with(by_name),
+    }
+}
+
+fn type_rank(info: &ContentObjectInfo) -> u8 {
+    if info.is_functional_object() {
+        0
+    } else if info.is_folder() {
+        1
+    } else {
+        2
+    }
+}
+
+fn extension(name: &str) -> String {
+    match name.rfind('.') {
+        Some(index) if index > 0 => name[index + 1..].to_lowercase(),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    fn create_backend() -> MemoryBackend {
+        let backend = MemoryBackend::with_storage("Redmi K70", "内部存储设备");
+        backend.add_device("Pixel 8");
+        backend.add_storage("Pixel 8", "Internal shared storage").unwrap();
+        backend.add_file("Redmi K70:内部存储设备:/Pictures/a.jpg", b"a").unwrap();
+        backend
+    }
+
+    #[test]
+    fn test_list_devices_with_pattern() {
+        let backend = create_backend();
+        assert_eq!(list_devices(&backend, None).unwrap().len(), 2);
+        let devices = list_devices(&backend, Some("Pixel*")).unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].name, "Pixel 8");
+        assert!(list_devices(&backend, Some("iPhone")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_list_device_storages_with_pattern() {
+        let backend = create_backend();
+        let device_info = list_devices(&backend, Some("Redmi K70")).unwrap().remove(0);
+        let device = backend.open_device(&device_info).unwrap();
+        let storages = list_device_storages(device.as_ref(), None).unwrap();
+        assert_eq!(storages.len(), 1);
+        assert_eq!(storages[0].name, "内部存储设备");
+        assert!(list_device_storages(device.as_ref(), Some("SD*")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_list_storages() {
+        let backend = create_backend();
+        let paths: Vec<String> = list_storages(&backend).unwrap().into_iter().map(|e| e.path).collect();
+        assert_eq!(paths, vec!["Redmi K70:内部存储设备:", "Pixel 8:Internal shared storage:"]);
+    }
+
+    #[test]
+    fn test_list_files() {
+        let backend = create_backend();
+        let entries = list_files(&backend, "Redmi K70:内部存储设备:/Pictures", true).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].info.is_folder());
+        assert!(entries[1].info.is_file());
+        assert!(list_files(&backend, "iPhone:内部存储设备:/", false).is_err());
+    }
+
+    #[test]
+    fn test_sort_entries() {
+        let backend = create_backend();
+        backend.add_file("Redmi K70:内部存储设备:/b.txt", b"bbbb").unwrap();
+        backend.add_file("Redmi K70:内部存储设备:/C.jpg", b"cc").unwrap();
+        backend.add_file("Redmi K70:内部存储设备:/Pictures/d.png", b"ddd").unwrap();
+        backend
+            .update_object("Redmi K70:内部存储设备:/b.txt", |o| o.time_modified = Some(crate::Timestamp::from_unix_seconds(100)))
+            .unwrap();
+        let entries = list_files(&backend, "Redmi K70:内部存储设备:/", true).unwrap();
+        let sorted_names = |key: SortKey, reverse: bool, directories_first: bool| -> Vec<String> {
+            sort_entries(entries.clone(), key, reverse, directories_first).into_iter().map(|e| e.info.name).collect()
+        };
+        // 文件夹的内容排在文件夹后面
+        assert_eq!(sorted_names(SortKey::Name, false, false), vec!["内部存储设备", "b.txt", "C.jpg", "Pictures", "a.jpg", "d.png"]);
+        assert_eq!(sorted_names(SortKey::Name, true, true), vec!["内部存储设备", "Pictures", "d.png", "a.jpg", "C.jpg", "b.txt"]);
+        assert_eq!(sorted_names(SortKey::Size, false, false), vec!["内部存储设备", "b.txt", "C.jpg", "Pictures", "d.png", "a.jpg"]);
+        backend
+            .update_object("Redmi K70:内部存储设备:/C.jpg", |o| o.time_modified = Some(crate::Timestamp::from_unix_seconds(200)))
+            .unwrap();
+        let entries = list_files(&backend, "Redmi K70:内部存储设备:/", true).unwrap();
+        let names: Vec<String> = sort_entries(entries, SortKey::Time, false, false).into_iter().map(|e| e.info.name).collect();
+        assert_eq!(names, vec!["内部存储设备", "C.jpg", "b.txt", "Pictures", "a.jpg", "d.png"]);
+        assert_eq!(sorted_names(SortKey::Type, false, false), vec!["内部存储设备", "Pictures", "a.jpg", "d.png", "C.jpg", "b.txt"]);
+    }
+}
@@ -1,186 +1,296 @@
-mod find;
-mod list;
-mod wpd;
-pub mod path;
-mod common;
-pub mod copy_operate;
-pub mod copy;
-
 use std::error::Error;
-use clap::{Parser, Subcommand};
-use windows::Win32::System::Com::{COINIT_MULTITHREADED, CoInitializeEx};
-use crate::list::{list_files, list_storages};
+use std::path::PathBuf;
+use clap::{ArgMatches, Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use mtp_util::{error, mtp, sort_entries, CopyOptions, Session, SortKey, TimeZone};
+use mtp_util::checksum::ChecksumAlgorithm;
+use mtp_util::output::{write_entries, write_long_listing, OutputFormat};
+use mtp_util::copy_operate::filter::FilterRule;
+use mtp_util::remove::RemoveOptions;
+use mtp_util::copy_operate::{ComparePolicy, ConflictOverride, ConflictPolicy, VerifyFailureAction, DEFAULT_MTIME_TOLERANCE};
 
-#[derive(Debug)]
-pub struct Paths {
-    src: String,
-    dest: String,
-}
 #[derive(Subcommand)]
 enum Commands {
     #[clap(about = "List all device's storages ")]
     ListStorages {
+        #[command(flatten)]
+        output: ListOutput,
     },
-    #[clap(about = "List files in a storage")]
+    // -h 用于 --human-readable，帮助只有 --help
+    #[clap(about = "List files in a storage", disable_help_flag = true)]
     ListFiles {
         #[clap(value_parser, help ="The path to list files, e.g. \"<device>:<storage>:<path>\"")]
         path: String, //必填
         #[clap(short = 'r', long, help ="List files recursively")]
         recursive: bool,
-        #[clap(short = 'd', long,help ="Show file details")]
+        #[clap(short = 'd', long, short_alias = 'l', help ="Show the type, flags, size and modification date (text format only)")]
         detail: bool,
+        #[clap(short = 'h', long, help ="Show the sizes of --detail in K, M, G... units")]
+        human_readable: bool,
+        #[clap(long, value_name = "KEY", help ="Sort the entries of each folder by name, size (largest first), time (newest first) or type")]
+        sort: Option<SortKey>,
+        #[clap(long, help ="Reverse the sort order")]
+        reverse: bool,
+        #[clap(long, help ="List folders before files")]
+        dirs_first: bool,
+        #[clap(long, action = clap::ArgAction::Help, help ="Print help")]
+        help: Option<bool>,
+        #[command(flatten)]
+        output: ListOutput,
     },
     #[clap(about = "Copy files from source to destination")]
     Copy {
-        #[clap(value_parser,help ="The source path to copy from, e.g. \"<device>:<storage>:<path>\"")]
-        src: String,
-        #[clap(value_parser,help ="The destination path to copy to, e.g. \"<device>:<storage>:<path>\"")]
+        #[clap(value_parser, required = true, help ="The source paths to copy from, e.g. \"<device>:<storage>:<path>\", may contain '*', '?' and '**'")]
+        src: Vec<String>,
+        #[clap(value_parser,help ="The destination path to copy to, e.g. \"<device>:<storage>:<path>\", must be an existing folder when there are several sources")]
         dest: String,
         #[clap(short = 'r', long,help ="Copy files recursively")]
         recursive: bool,
         #[clap(short = 'm', long,help ="Mirror the source to the destination")]
         mirror: bool,
+        #[clap(long, num_args = 0..=1, require_equals = true, default_missing_value = "sha256", value_name = "ALGORITHM", help ="Verify the copied files with a checksum, sha256 (default), crc32 or blake3")]
+        verify: Option<ChecksumAlgorithm>,
+        #[clap(long, default_value = "report", value_name = "ACTION", help ="What to do when the verification fails, report, retry or delete")]
+        on_verify_failure: VerifyFailureAction,
+        #[clap(long, value_name = "FILE", help ="Record the copied files in a journal file")]
+        journal: Option<PathBuf>,
+        #[clap(long, requires = "journal", help ="Resume an interrupted copy, skip the files the journal records as copied")]
+        resume: bool,
+        #[clap(short = 'n', long, help ="Print what would be copied and deleted without changing anything")]
+        dry_run: bool,
+        #[clap(long, default_value = "size-mtime", value_name = "POLICY", help ="How to detect changed files, size, size-mtime (default), checksum or always")]
+        compare: ComparePolicy,
+        #[clap(long, default_value_t = DEFAULT_MTIME_TOLERANCE, value_name = "SECONDS", help ="Seconds the destination may be older than the source and still be up to date")]
+        mtime_tolerance: u64,
+        #[clap(long, help ="Do not set the times of the copied files to the times of the source files")]
+        no_preserve_times: bool,
+        #[clap(long, default_value = "overwrite", value_name = "POLICY", help ="What to do when a destination file exists and is not up to date, overwrite (default), skip, rename, newer, larger or ask")]
+        on_conflict: ConflictPolicy,
+        #[clap(long, value_name = "PATTERN=POLICY", help ="The conflict policy for the file names matching a pattern, e.g. \"*.db=skip\", can be repeated")]
+        on_conflict_for: Vec<ConflictOverride>,
+        #[clap(long, value_name = "PATTERN", help ="Copy the files and folders matching the pattern, can be repeated, the first matching --include or --exclude decides")]
+        include: Vec<String>,
+        #[clap(long, value_name = "PATTERN", help ="Do not copy the files and folders matching the pattern, e.g. \"*.tmp\", \"cache/\" or \"/DCIM/**/*.mp4\", can be repeated")]
+        exclude: Vec<String>,
+        #[clap(long, help ="Copy hidden files and folders")]
+        include_hidden: bool,
+        #[clap(long, help ="Copy system files and folders")]
+        include_system: bool,
+        #[clap(long, help ="Copy the matches of a wildcard source directly into the destination folder, without their folders")]
+        flatten: bool,
+    },
+    #[clap(about = "Move files from source to destination, the sources are deleted after their copies were verified")]
+    Move {
+        #[clap(value_parser, required = true, help ="The source paths to move, e.g. \"<device>:<storage>:<path>\", may contain '*', '?' and '**'")]
+        src: Vec<String>,
+        #[clap(value_parser, help ="The destination path to move to, must be an existing folder when there are several sources")]
+        dest: String,
+        #[clap(short = 'r', long, help ="Move folders recursively")]
+        recursive: bool,
+        #[clap(long, num_args = 0..=1, require_equals = true, default_missing_value = "sha256", value_name = "ALGORITHM", help ="Compare the checksums of the copies and the sources before deleting, sha256 (default), crc32 or blake3")]
+        verify: Option<ChecksumAlgorithm>,
+        #[clap(long, default_value = "overwrite", value_name = "POLICY", help ="What to do when a destination file exists and is not up to date, overwrite (default), skip, rename, newer, larger or ask")]
+        on_conflict: ConflictPolicy,
+        #[clap(long, help ="Print the actions without copying or deleting anything")]
+        dry_run: bool,
+    },
+    #[clap(about = "Remove files and folders")]
+    Rm {
+        #[clap(value_parser, required = true, help ="The paths to remove, e.g. \"<device>:<storage>:<path>\", may contain '*', '?' and '**'")]
+        paths: Vec<String>,
+        #[clap(short = 'r', long, help ="Remove folders with their contents")]
+        recursive: bool,
+        #[clap(long, help ="Print the files and folders without removing them")]
+        dry_run: bool,
+        #[clap(short = 'y', long, help ="Do not ask before removing a folder recursively")]
+        yes: bool,
+    },
+    #[clap(about = "Create folders on a device")]
+    Mkdir {
+        #[clap(value_parser, required = true, help ="The folders to create, e.g. \"<device>:<storage>:<path>\"")]
+        paths: Vec<String>,
+        #[clap(short = 'p', long, help ="Create the missing parent folders, no error if a folder exists")]
+        parents: bool,
+    },
+    #[clap(about = "Show every property of a device object, storage, file or folder, or of a local file")]
+    Stat {
+        #[clap(value_parser, help ="The path, e.g. \"<device>:<storage>:<path>\", \"<device>::\" for the device object or a local path")]
+        path: String,
+        #[clap(long, help ="Print the properties as JSON")]
+        json: bool,
+    },
+    #[clap(about = "Rename a file or folder on a device")]
+    Rename {
+        #[clap(value_parser, help ="The file or folder to rename, e.g. \"<device>:<storage>:<path>\"")]
+        path: String,
+        #[clap(value_parser, help ="The new name")]
+        new_name: String,
+    },
+    #[clap(about = "Move a file or folder on a device, copying and deleting it when the device cannot move it")]
+    Mv {
+        #[clap(value_parser, help ="The file or folder to move, e.g. \"<device>:<storage>:<path>\"")]
+        src: String,
+        #[clap(value_parser, help ="An existing folder to move into, or the new path")]
+        dest: String,
+    },
+    #[clap(about = "Serve a local folder as an MTP device over PTP/IP")]
+    Serve {
+        #[clap(long, help ="The folder to serve")]
+        root: PathBuf,
+        #[clap(long, default_value = "127.0.0.1:15740", help ="The address to listen on")]
+        listen: String,
+        #[clap(long, default_value = "mtp_util", help ="The device name shown to clients")]
+        name: String,
     },
 }
 
+// 列表命令共用的输出选项
+#[derive(Args)]
+struct ListOutput {
+    #[clap(long, default_value = "text", value_name = "FORMAT", help ="The output format, text (default), json, ndjson, csv or tsv")]
+    format: OutputFormat,
+    #[clap(long, help ="End each record with NUL instead of a newline")]
+    print0: bool,
+}
+
 #[derive(Parser)]
 #[command(name = "mtp_util")]
 struct Cli {
+    #[arg(long, global = true, env = "MTP_UTIL_BACKEND", help = "The device backend, \"wpd\" (default), \"emulated:<manifest>\" or \"ptpip://<host>[:<port>]\"")]
+    backend: Option<String>,
+    #[arg(long, global = true, env = "MTP_UTIL_TIME_ZONE", default_value = "UTC", value_name = "ZONE", help = "The time zone of the device dates without an offset, \"UTC\" (default) or an offset like \"+08:00\"")]
+    time_zone: TimeZone,
     #[command(subcommand)]
     command: Commands,
 }
 
 fn main() {
     env_logger::init();
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    if let Err(err) = run(&cli, &matches) {
+        eprintln!("Error: {}", err);
+        std::process::exit(error::exit_code(err.as_ref()));
+    }
+}
+
+fn run(cli: &Cli, matches: &ArgMatches) -> Result<(), Box<dyn Error>> {
+    // serve 不需要设备后端
+    if let Commands::Serve { root, listen, name } = &cli.command {
+        return serve(root, listen, name);
+    }
+    let session = Session::open(cli.backend.as_deref(), cli.time_zone)?;
     match &cli.command {
-        Commands::ListStorages { } => {
-            unsafe { CoInitializeEx(Some(std::ptr::null_mut()), COINIT_MULTITHREADED).ok().unwrap(); }
-            match list_storages() {
-                Ok(_) => {}
-                Err(err) => {
-                    println!("Error: {}", err);
-                }
+        Commands::ListStorages { output } => {
+            let entries = session.list_storages()?;
+            write_entries(&mut std::io::stdout(), &entries, output.format, output.print0, session.time_zone())?;
+            if entries.is_empty() && output.format == OutputFormat::Text && !output.print0 {
+                println!("no storages were found.")
             }
+            Ok(())
         }
-        Commands::ListFiles { path, recursive, detail } => {
-            unsafe { CoInitializeEx(Some(std::ptr::null_mut()), COINIT_MULTITHREADED).ok().unwrap(); }
-            match list_files(path.clone(),*recursive,*detail) {
-                Ok(_) => {}
-                Err(err) => {
-                    println!("Error: {}", err);
-                }
+        Commands::ListFiles { path, recursive, detail, human_readable, sort, reverse, dirs_first, output, .. } => {
+            let mut entries = session.list(path, *recursive)?;
+            if sort.is_some() || *reverse || *dirs_first {
+                entries = sort_entries(entries, sort.unwrap_or(SortKey::Name), *reverse, *dirs_first);
             }
+            if *detail && output.format == OutputFormat::Text {
+                return write_long_listing(&mut std::io::stdout(), &entries, *human_readable, output.print0, session.time_zone());
+            }
+            write_entries(&mut std::io::stdout(), &entries, output.format, output.print0, session.time_zone())
         }
-        Commands::Copy { src, dest, recursive, mirror } => {
-            unsafe { CoInitializeEx(Some(std::ptr::null_mut()), COINIT_MULTITHREADED).ok().unwrap(); }
-            let paths = Paths {
-                src: src.clone(),
-                dest: dest.clone(),
+        Commands::Copy { src, dest, recursive, mirror, verify, on_verify_failure, journal, resume, dry_run, compare, mtime_tolerance, no_preserve_times, on_conflict, on_conflict_for, include_hidden, include_system, flatten, .. } => {
+            let options = CopyOptions {
+                recursive: *recursive,
+                mirror: *mirror,
+                verify: *verify,
+                on_verify_failure: *on_verify_failure,
+                journal: journal.clone(),
+                resume: *resume,
+                dry_run: *dry_run,
+                compare: *compare,
+                mtime_tolerance: *mtime_tolerance,
+                preserve_times: !*no_preserve_times,
+                on_conflict: *on_conflict,
+                conflict_overrides: on_conflict_for.clone(),
+                filters: matches.subcommand_matches("copy").map(filter_rules).unwrap_or_default(),
+                include_hidden: *include_hidden,
+                include_system: *include_system,
+                flatten: *flatten,
+                move_sources: false,
             };
-            match copy::copy(&paths,  *recursive, *mirror) {
-                Ok(_) => {
-                    println!("Copy successfully.");
-                }
-                Err(err) => {
-                    println!("Error: {}", err);
-                }
+            let srcs: Vec<&str> = src.iter().map(String::as_str).collect();
+            session.copy_many(&srcs, dest, &options)?;
+            if !*dry_run {
+                println!("Copy successfully.");
             }
+            Ok(())
         }
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use windows::{core::Result, Win32::System::Threading::*,Win32::Devices::PortableDevices::*,Win32::System::Com::*};
-    use crate::find::{find_storage, find_file_or_folder};
-    use crate::list::{list_devices, list_device_storages, list_storages, list_files};
-    use crate::wpd::device::Device;
-    use crate::wpd::manager::{DeviceInfo, Manager};
-
-    static COUNTER: std::sync::RwLock<i32> = std::sync::RwLock::new(0);
-    #[test]
-    fn test_win_threading_pool() {
-        unsafe {
-            let work = CreateThreadpoolWork(Some(callback), None, None).unwrap();
-
-            for _ in 0..10 {
-                SubmitThreadpoolWork(work);
+        Commands::Move { src, dest, recursive, verify, on_conflict, dry_run } => {
+            let options = CopyOptions {
+                recursive: *recursive,
+                verify: *verify,
+                on_conflict: *on_conflict,
+                dry_run: *dry_run,
+                ..Default::default()
+            };
+            let srcs: Vec<&str> = src.iter().map(String::as_str).collect();
+            session.move_many(&srcs, dest, &options)?;
+            if !*dry_run {
+                println!("Move successfully.");
             }
-
-            WaitForThreadpoolWorkCallbacks(work, false);
+            Ok(())
         }
-
-        let counter = COUNTER.read().unwrap();
-        println!("counter: {}", *counter);
-    }
-
-    extern "system" fn callback(_: PTP_CALLBACK_INSTANCE, _: *mut std::ffi::c_void, _: PTP_WORK) {
-        let mut counter = COUNTER.write().unwrap();
-        *counter += 1;
-    }
-
-
-    #[test]
-    fn test_portable_devices() {
-        unsafe { CoInitializeEx(Some(std::ptr::null_mut()), COINIT_MULTITHREADED).ok().unwrap(); }
-        let manager = Manager::get_portable_device_manager().unwrap();
-        let vec = list_devices(&manager, None).unwrap();
-        let mut count = 0;
-        for device_info in vec {
-            match Device::open(&device_info) {
-                Err(err) => {
-                    log::debug!("{}", err);
-                    log::warn!("failed to open \"{}\" (skipped)", device_info.name);
-                }
-                Ok(device) => match list_device_storages(&device, None) {
-                    Err(err) => {
-                        log::debug!("{}", err);
-                        log::warn!(
-                        "failed to get storages from \"{}\" (skipped)",
-                        device_info.name
-                    );
-                    }
-                    Ok(storage_object_vec) => {
-                        for storage_object_info in storage_object_vec {
-                            count += 1;
-                            println!("{}:{}:", &device_info.name, &storage_object_info.name);
-                        }
-                    }
-                },
+        Commands::Rm { paths, recursive, dry_run, yes } => {
+            let options = RemoveOptions {
+                recursive: *recursive,
+                dry_run: *dry_run,
+                yes: *yes,
+            };
+            let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+            let summary = session.remove(&paths, &options)?;
+            if *dry_run {
+                println!("dry run, nothing was changed.");
             }
+            if !summary.refused.is_empty() {
+                let refused: Vec<&str> = summary.refused.iter().map(|(path, _)| path.as_str()).collect();
+                return Err(error::MtpError::AccessDenied(refused.join(", ")).into());
+            }
+            Ok(())
         }
+        Commands::Mkdir { paths, parents } => {
+            let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+            session.make_folders(&paths, *parents)?;
+            Ok(())
+        }
+        Commands::Stat { path, json } => {
+            let properties = session.properties(path)?;
+            if *json {
+                println!("{}", properties.to_json(session.time_zone())?);
+            } else {
+                print!("{}", properties.format(session.time_zone()));
+            }
+            Ok(())
+        }
+        Commands::Rename { path, new_name } => session.rename(path, new_name),
+        Commands::Mv { src, dest } => session.move_object(src, dest),
+        Commands::Serve { .. } => Ok(()),
     }
-    #[test]
-    fn test_get_storage() {
-        unsafe { CoInitializeEx(Some(std::ptr::null_mut()), COINIT_MULTITHREADED).ok().unwrap(); }
-        let manager = Manager::get_portable_device_manager().unwrap();
-        let storage_path = path::DeviceStoragePath::from("Redmi K70:内部存储设备:\\").unwrap();
-        let option = find_storage(&manager, &storage_path).unwrap();
-        let (device_info, device, storage_object) = option.unwrap();
-        println!("device_info: {:?}", device_info);
-        println!("storage_object: {:?}", storage_object);
-    }
-    #[test]
-    fn test_find_file_or_folder() {
-        unsafe { CoInitializeEx(Some(std::ptr::null_mut()), COINIT_MULTITHREADED).ok().unwrap(); }
-        let manager = Manager::get_portable_device_manager().unwrap();
-        let storage_path = path::DeviceStoragePath::from("Redmi K70:内部存储设备:/Pictures").unwrap();
-        let option = find_file_or_folder(&manager, &storage_path).unwrap();
-        let (device_info, device, content_object_info) = option.unwrap();
-        println!("device_info: {:?}", device_info);
-        println!("storage_object: {:?}", content_object_info);
-    }
-    #[test]
-    fn test_list_storages() {
-        unsafe { CoInitializeEx(Some(std::ptr::null_mut()), COINIT_MULTITHREADED).ok().unwrap(); }
-        list_storages();
-    }
+}
 
-    #[test]
-    fn test_list_files() {
-        unsafe { CoInitializeEx(Some(std::ptr::null_mut()), COINIT_MULTITHREADED).ok().unwrap(); }
-        list_files("Redmi K70:内部存储设备:/Pictures".to_string(), true, true).unwrap();
+// 按命令行中的顺序合并 --include 和 --exclude
+fn filter_rules(matches: &ArgMatches) -> Vec<FilterRule> {
+    let mut rules = Vec::<(usize, FilterRule)>::new();
+    for (id, rule) in [("include", FilterRule::Include as fn(String) -> FilterRule), ("exclude", FilterRule::Exclude)] {
+        if let (Some(indices), Some(patterns)) = (matches.indices_of(id), matches.get_many::<String>(id)) {
+            rules.extend(indices.zip(patterns).map(|(index, pattern)| (index, rule(pattern.clone()))));
+        }
     }
-}
\ No newline at end of file
+    rules.sort_by_key(|(index, _)| *index);
+    rules.into_iter().map(|(_, rule)| rule).collect()
+}
+
+fn serve(root: &std::path::Path, listen: &str, name: &str) -> Result<(), Box<dyn Error>> {
+    let listener = std::net::TcpListener::bind(listen)?;
+    println!("Serving {} on {}", root.display(), listener.local_addr()?);
+    mtp::responder::serve(listener, root, name)
+}
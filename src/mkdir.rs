@@ -0,0 +1,101 @@
+use crate::backend::PortableDeviceBackend;
+use crate::copy_operate::device_folder_imp::DeviceFolder;
+use crate::copy_operate::folder_operate::FolderOperate;
+use crate::error::MtpError;
+use crate::find::find_storage;
+use crate::path::{get_path_type, DeviceStoragePath, PathType, SEPARATORS};
+
+// 在设备存储上创建文件夹
+// parents 为 true 时创建缺少的上层文件夹，已存在的文件夹不是错误 (同 mkdir -p)
+
+/// Creates the folders at `paths`, returns the full paths of the created folders.
+pub fn make_folders(
+    backend: &dyn PortableDeviceBackend,
+    paths: &[&str],
+    parents: bool,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    log::trace!("command_mkdir paths={:?} parents={}", paths, parents);
+    let mut created = Vec::new();
+    for path in paths {
+        if get_path_type(path) != PathType::DeviceStorage {
+            return Err(format!("not a device path: {}", path).into());
+        }
+        let storage_path = DeviceStoragePath::from(path)?;
+        let (_, device, storage_object) = match find_storage(backend, &storage_path)? {
+            Some(found) => found,
+            None => return Err(MtpError::PathNotFound(path.to_string()).into()),
+        };
+        let components: Vec<&str> = storage_path.path.split(SEPARATORS).filter(|c| !c.is_empty()).collect();
+        if components.is_empty() {
+            return Err(format!("cannot create a storage: {}", path).into());
+        }
+        let mut folder = DeviceFolder::new(device.as_ref(), storage_object)?;
+        let base_path = format!("{}:{}:", storage_path.device_name, storage_path.storage_name);
+        make_folder_in(&mut folder, &base_path, &components, parents, &mut created)?;
+    }
+    Ok(created)
+}
+
+// 在 folder 下按 components 逐层打开或创建文件夹，base_path 为 folder 的完整路径
+fn make_folder_in(
+    folder: &mut impl FolderOperate,
+    base_path: &str,
+    components: &[&str],
+    parents: bool,
+    created: &mut Vec<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (name, rest) = match components.split_first() {
+        Some(split) => split,
+        None => return Ok(()),
+    };
+    let path = format!("{}\\{}", base_path.trim_end_matches('\\'), name);
+    match folder.get_file_info(name)? {
+        Some(info) if !info.is_folder => return Err(format!("not a folder: {}", path).into()),
+        Some(_) if rest.is_empty() && !parents => return Err(format!("already exists: {}", path).into()),
+        None if !rest.is_empty() && !parents => return Err(MtpError::PathNotFound(path).into()),
+        _ => (),
+    }
+    let mut sub_folder = folder.open_or_create_folder(name, |_| {}, |_| {
+        println!("create folder \"{}\"", path);
+        created.push(path.clone());
+    })?;
+    make_folder_in(sub_folder.as_mut(), &path, rest, parents, created)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    fn create_backend() -> MemoryBackend {
+        let backend = MemoryBackend::with_storage("Phone", "Internal");
+        backend.add_file("Phone:Internal:/Backups/notes.txt", b"notes").unwrap();
+        backend
+    }
+
+    #[test]
+    fn test_make_folders() -> Result<(), Box<dyn std::error::Error>> {
+        let backend = create_backend();
+        let created = make_folders(&backend, &["Phone:Internal:/Backups/2026/10", "Phone:Internal:/Music"], true)?;
+        assert_eq!(created, vec!["Phone:Internal:\\Backups\\2026", "Phone:Internal:\\Backups\\2026\\10", "Phone:Internal:\\Music"]);
+        assert_eq!(backend.list_names("Phone:Internal:/Backups/2026"), Some(vec!["10".to_string()]));
+
+        // 已存在的文件夹
+        assert!(make_folders(&backend, &["Phone:Internal:/Backups/2026"], true)?.is_empty());
+        assert!(make_folders(&backend, &["Phone:Internal:/Backups/2026"], false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_make_folders_errors() {
+        let backend = create_backend();
+        // 没有 -p 时上层文件夹必须存在
+        let err = make_folders(&backend, &["Phone:Internal:/Backups/2026/10"], false).err().unwrap();
+        assert_eq!(crate::error::exit_code(err.as_ref()), MtpError::PathNotFound(String::new()).exit_code());
+        assert_eq!(backend.list_names("Phone:Internal:/Backups"), Some(vec!["notes.txt".to_string()]));
+        // 路径中有文件
+        let err = make_folders(&backend, &["Phone:Internal:/Backups/notes.txt/a"], true).err().unwrap();
+        assert_eq!(err.to_string(), "not a folder: Phone:Internal:\\Backups\\notes.txt");
+        assert!(make_folders(&backend, &["Phone:Internal:/"], true).is_err());
+    }
+}
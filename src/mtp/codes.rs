@@ -0,0 +1,121 @@
+// PTP/MTP 的操作码、响应码、事件码和对象格式
+
+// 操作码
+pub const OPERATION_GET_DEVICE_INFO: u16 = 0x1001;
+pub const OPERATION_OPEN_SESSION: u16 = 0x1002;
+pub const OPERATION_CLOSE_SESSION: u16 = 0x1003;
+pub const OPERATION_GET_STORAGE_IDS: u16 = 0x1004;
+pub const OPERATION_GET_STORAGE_INFO: u16 = 0x1005;
+pub const OPERATION_GET_OBJECT_HANDLES: u16 = 0x1007;
+pub const OPERATION_GET_OBJECT_INFO: u16 = 0x1008;
+pub const OPERATION_GET_OBJECT: u16 = 0x1009;
+pub const OPERATION_DELETE_OBJECT: u16 = 0x100B;
+pub const OPERATION_SEND_OBJECT_INFO: u16 = 0x100C;
+pub const OPERATION_SEND_OBJECT: u16 = 0x100D;
+pub const OPERATION_MOVE_OBJECT: u16 = 0x1019;
+pub const OPERATION_GET_OBJECT_PROP_VALUE: u16 = 0x9803;
+pub const OPERATION_SET_OBJECT_PROP_VALUE: u16 = 0x9804;
+
+// 响应码
+pub const RESPONSE_OK: u16 = 0x2001;
+pub const RESPONSE_GENERAL_ERROR: u16 = 0x2002;
+pub const RESPONSE_SESSION_NOT_OPEN: u16 = 0x2003;
+pub const RESPONSE_INVALID_TRANSACTION_ID: u16 = 0x2004;
+pub const RESPONSE_OPERATION_NOT_SUPPORTED: u16 = 0x2005;
+pub const RESPONSE_PARAMETER_NOT_SUPPORTED: u16 = 0x2006;
+pub const RESPONSE_INCOMPLETE_TRANSFER: u16 = 0x2007;
+pub const RESPONSE_INVALID_STORAGE_ID: u16 = 0x2008;
+pub const RESPONSE_INVALID_OBJECT_HANDLE: u16 = 0x2009;
+pub const RESPONSE_STORE_FULL: u16 = 0x200C;
+pub const RESPONSE_OBJECT_WRITE_PROTECTED: u16 = 0x200D;
+pub const RESPONSE_STORE_READ_ONLY: u16 = 0x200E;
+pub const RESPONSE_ACCESS_DENIED: u16 = 0x200F;
+pub const RESPONSE_PARTIAL_DELETION: u16 = 0x2012;
+pub const RESPONSE_NO_VALID_OBJECT_INFO: u16 = 0x2015;
+pub const RESPONSE_INVALID_PARENT_OBJECT: u16 = 0x201A;
+pub const RESPONSE_INVALID_PARAMETER: u16 = 0x201D;
+pub const RESPONSE_SESSION_ALREADY_OPEN: u16 = 0x201E;
+pub const RESPONSE_INVALID_OBJECT_PROP_CODE: u16 = 0xA801;
+
+// 存储类型、文件系统类型和访问权限
+pub const STORAGE_TYPE_FIXED_RAM: u16 = 0x0003;
+pub const FILESYSTEM_GENERIC_HIERARCHICAL: u16 = 0x0002;
+pub const ACCESS_READ_WRITE: u16 = 0x0000;
+
+// 对象格式
+pub const FORMAT_UNDEFINED: u16 = 0x3000;
+pub const FORMAT_ASSOCIATION: u16 = 0x3001;
+
+// 对象属性
+pub const PROPERTY_OBJECT_SIZE: u16 = 0xDC04;
+pub const PROPERTY_OBJECT_FILE_NAME: u16 = 0xDC07;
+
+// 关联类型 (文件夹)
+pub const ASSOCIATION_GENERIC_FOLDER: u16 = 0x0001;
+
+// 保护状态
+pub const PROTECTION_NONE: u16 = 0x0000;
+pub const PROTECTION_READ_ONLY: u16 = 0x0001;
+
+// 特殊的 storage id 和 object handle
+pub const STORAGE_ALL: u32 = 0xFFFFFFFF;
+pub const FORMAT_ALL: u32 = 0x00000000;
+pub const PARENT_ROOT: u32 = 0xFFFFFFFF;
+pub const OBJECT_ALL: u32 = 0xFFFFFFFF;
+
+// ObjectInfo 中的大小为这个值时，对象不小于 4 GiB，实际大小为 ObjectSize 属性
+pub const OBJECT_SIZE_LARGE: u32 = 0xFFFFFFFF;
+
+// 响应码名称，用于错误信息
+pub fn response_code_name(code: u16) -> &'static str {
+    match code {
+        RESPONSE_OK => "OK",
+        RESPONSE_GENERAL_ERROR => "General_Error",
+        RESPONSE_SESSION_NOT_OPEN => "Session_Not_Open",
+        RESPONSE_INVALID_TRANSACTION_ID => "Invalid_TransactionID",
+        RESPONSE_OPERATION_NOT_SUPPORTED => "Operation_Not_Supported",
+        RESPONSE_PARAMETER_NOT_SUPPORTED => "Parameter_Not_Supported",
+        RESPONSE_INCOMPLETE_TRANSFER => "Incomplete_Transfer",
+        RESPONSE_INVALID_STORAGE_ID => "Invalid_StorageID",
+        RESPONSE_INVALID_OBJECT_HANDLE => "Invalid_ObjectHandle",
+        RESPONSE_STORE_FULL => "Store_Full",
+        RESPONSE_OBJECT_WRITE_PROTECTED => "Object_WriteProtected",
+        RESPONSE_STORE_READ_ONLY => "Store_Read_Only",
+        RESPONSE_ACCESS_DENIED => "Access_Denied",
+        RESPONSE_PARTIAL_DELETION => "Partial_Deletion",
+        RESPONSE_NO_VALID_OBJECT_INFO => "No_Valid_ObjectInfo",
+        RESPONSE_INVALID_PARENT_OBJECT => "Invalid_ParentObject",
+        RESPONSE_INVALID_PARAMETER => "Invalid_Parameter",
+        RESPONSE_SESSION_ALREADY_OPEN => "Session_Already_Open",
+        RESPONSE_INVALID_OBJECT_PROP_CODE => "Invalid_ObjectPropCode",
+        _ => "Unknown",
+    }
+}
+
+// 对象格式名称，用于显示对象属性
+pub fn format_code_name(code: u16) -> &'static str {
+    match code {
+        FORMAT_UNDEFINED => "Undefined",
+        FORMAT_ASSOCIATION => "Association",
+        0x3004 => "Text",
+        0x3005 => "HTML",
+        0x3008 => "WAV",
+        0x3009 => "MP3",
+        0x300A => "AVI",
+        0x300B => "MPEG",
+        0x3801 => "EXIF/JPEG",
+        0x3804 => "BMP",
+        0x3807 => "GIF",
+        0x3808 => "JFIF",
+        0x380B => "PNG",
+        0x380D => "TIFF",
+        0xB901 => "WMA",
+        0xB902 => "OGG",
+        0xB903 => "AAC",
+        0xB906 => "FLAC",
+        0xB981 => "WMV",
+        0xB982 => "MP4",
+        0xB984 => "3GP",
+        _ => "Unknown",
+    }
+}
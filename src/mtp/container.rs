@@ -0,0 +1,255 @@
+use std::fmt;
+
+// PTP/MTP 通用容器 (USB Still Image Class / PTP/IP 共用的数据格式)
+//
+// 结构 (little-endian):
+//   u32 container length (包含 12 字节头)
+//   u16 container type
+//   u16 operation / response / event code
+//   u32 transaction id
+//   payload (命令和响应为最多 5 个 u32 参数，数据容器为数据集)
+
+pub const HEADER_SIZE: usize = 12;
+pub const MAX_PARAMS: usize = 5;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ContainerType {
+    Command,
+    Data,
+    Response,
+    Event,
+}
+
+impl ContainerType {
+    pub fn to_u16(self) -> u16 {
+        match self {
+            ContainerType::Command => 1,
+            ContainerType::Data => 2,
+            ContainerType::Response => 3,
+            ContainerType::Event => 4,
+        }
+    }
+
+    pub fn from_u16(value: u16) -> Option<ContainerType> {
+        match value {
+            1 => Some(ContainerType::Command),
+            2 => Some(ContainerType::Data),
+            3 => Some(ContainerType::Response),
+            4 => Some(ContainerType::Event),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Container {
+    pub container_type: ContainerType,
+    pub code: u16,
+    pub transaction_id: u32,
+    pub payload: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContainerError {
+    // 数据长度不足
+    Truncated { expected: usize, actual: usize },
+    InvalidLength(u32),
+    InvalidType(u16),
+    TooManyParams(usize),
+    InvalidParams(usize),
+}
+
+impl fmt::Display for ContainerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ContainerError::Truncated { expected, actual } => {
+                write!(f, "truncated container: expected {} bytes, got {}", expected, actual)
+            }
+            ContainerError::InvalidLength(length) => write!(f, "invalid container length: {}", length),
+            ContainerError::InvalidType(container_type) => write!(f, "invalid container type: {}", container_type),
+            ContainerError::TooManyParams(count) => write!(f, "too many parameters: {}", count),
+            ContainerError::InvalidParams(size) => write!(f, "invalid parameter block size: {}", size),
+        }
+    }
+}
+
+impl std::error::Error for ContainerError {}
+
+impl Container {
+    // 命令容器
+    pub fn command(code: u16, transaction_id: u32, params: &[u32]) -> Result<Container, ContainerError> {
+        Container::with_params(ContainerType::Command, code, transaction_id, params)
+    }
+
+    // 响应容器
+    pub fn response(code: u16, transaction_id: u32, params: &[u32]) -> Result<Container, ContainerError> {
+        Container::with_params(ContainerType::Response, code, transaction_id, params)
+    }
+
+    // 数据容器，code 与对应的命令相同
+    pub fn data(code: u16, transaction_id: u32, payload: Vec<u8>) -> Container {
+        Container {
+            container_type: ContainerType::Data,
+            code,
+            transaction_id,
+            payload,
+        }
+    }
+
+    // 分块发送的数据容器的头部，数据超过 4 GiB 时长度为 0xFFFFFFFF
+    pub fn data_header(code: u16, transaction_id: u32, size: u64) -> [u8; HEADER_SIZE] {
+        let length = u32::try_from(HEADER_SIZE as u64 + size).unwrap_or(u32::MAX);
+        let mut header = [0u8; HEADER_SIZE];
+        header[0..4].copy_from_slice(&length.to_le_bytes());
+        header[4..6].copy_from_slice(&ContainerType::Data.to_u16().to_le_bytes());
+        header[6..8].copy_from_slice(&code.to_le_bytes());
+        header[8..12].copy_from_slice(&transaction_id.to_le_bytes());
+        header
+    }
+
+    fn with_params(container_type: ContainerType, code: u16, transaction_id: u32, params: &[u32]) -> Result<Container, ContainerError> {
+        if params.len() > MAX_PARAMS {
+            return Err(ContainerError::TooManyParams(params.len()));
+        }
+        let payload = params.iter().flat_map(|p| p.to_le_bytes()).collect();
+        Ok(Container {
+            container_type,
+            code,
+            transaction_id,
+            payload,
+        })
+    }
+
+    // 解析命令/响应/事件的参数
+    pub fn params(&self) -> Result<Vec<u32>, ContainerError> {
+        if !self.payload.len().is_multiple_of(4) || self.payload.len() > MAX_PARAMS * 4 {
+            return Err(ContainerError::InvalidParams(self.payload.len()));
+        }
+        Ok(self
+            .payload
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::<u8>::with_capacity(HEADER_SIZE + self.payload.len());
+        bytes.extend_from_slice(&((HEADER_SIZE + self.payload.len()) as u32).to_le_bytes());
+        bytes.extend_from_slice(&self.container_type.to_u16().to_le_bytes());
+        bytes.extend_from_slice(&self.code.to_le_bytes());
+        bytes.extend_from_slice(&self.transaction_id.to_le_bytes());
+        bytes.extend_from_slice(&self.payload);
+        bytes
+    }
+
+    // 解析一个完整的容器，返回容器和消耗的字节数
+    pub fn decode(bytes: &[u8]) -> Result<(Container, usize), ContainerError> {
+        if bytes.len() < HEADER_SIZE {
+            return Err(ContainerError::Truncated {
+                expected: HEADER_SIZE,
+                actual: bytes.len(),
+            });
+        }
+        let length = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        if (length as usize) < HEADER_SIZE {
+            return Err(ContainerError::InvalidLength(length));
+        }
+        if bytes.len() < length as usize {
+            return Err(ContainerError::Truncated {
+                expected: length as usize,
+                actual: bytes.len(),
+            });
+        }
+        let type_value = u16::from_le_bytes([bytes[4], bytes[5]]);
+        let container_type = ContainerType::from_u16(type_value).ok_or(ContainerError::InvalidType(type_value))?;
+        let code = u16::from_le_bytes([bytes[6], bytes[7]]);
+        let transaction_id = u32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]);
+        Ok((
+            Container {
+                container_type,
+                code,
+                transaction_id,
+                payload: bytes[HEADER_SIZE..length as usize].to_vec(),
+            },
+            length as usize,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_command() {
+        // OpenSession(session id = 1), transaction id 0
+        let container = Container::command(0x1002, 0, &[1]).unwrap();
+        assert_eq!(
+            container.encode(),
+            vec![0x10, 0, 0, 0, 0x01, 0x00, 0x02, 0x10, 0, 0, 0, 0, 0x01, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_decode_response() {
+        // OK 响应，带一个参数，后面多余的字节不会被消耗
+        let bytes = [0x10, 0, 0, 0, 0x03, 0x00, 0x01, 0x20, 0x05, 0, 0, 0, 0x2a, 0, 0, 0, 0xff];
+        let (container, size) = Container::decode(&bytes).unwrap();
+        assert_eq!(size, 16);
+        assert_eq!(container.container_type, ContainerType::Response);
+        assert_eq!(container.code, 0x2001);
+        assert_eq!(container.transaction_id, 5);
+        assert_eq!(container.params().unwrap(), vec![42]);
+    }
+
+    #[test]
+    fn test_decode_event_and_data() {
+        let event = Container {
+            container_type: ContainerType::Event,
+            code: 0x4002,
+            transaction_id: 0xffffffff,
+            payload: vec![7, 0, 0, 0],
+        };
+        let (decoded, _) = Container::decode(&event.encode()).unwrap();
+        assert_eq!(decoded, event);
+
+        let data = Container::data(0x1009, 3, b"hello".to_vec());
+        let (decoded, size) = Container::decode(&data.encode()).unwrap();
+        assert_eq!(size, 17);
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_data_header() {
+        let data = Container::data(0x100D, 3, b"hello".to_vec());
+        assert_eq!(Container::data_header(0x100D, 3, 5), data.encode()[..HEADER_SIZE]);
+        // 超过 4 GiB 的数据
+        let header = Container::data_header(0x100D, 3, 5 << 30);
+        assert_eq!(header[..4], [0xff, 0xff, 0xff, 0xff]);
+        assert_eq!(header[4..], data.encode()[4..HEADER_SIZE]);
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert_eq!(
+            Container::decode(&[1, 2, 3]),
+            Err(ContainerError::Truncated { expected: 12, actual: 3 })
+        );
+        assert_eq!(
+            Container::decode(&[8, 0, 0, 0, 1, 0, 1, 0x10, 0, 0, 0, 0]),
+            Err(ContainerError::InvalidLength(8))
+        );
+        assert_eq!(
+            Container::decode(&[12, 0, 0, 0, 9, 0, 1, 0x10, 0, 0, 0, 0]),
+            Err(ContainerError::InvalidType(9))
+        );
+        assert_eq!(
+            Container::decode(&[20, 0, 0, 0, 1, 0, 1, 0x10, 0, 0, 0, 0]),
+            Err(ContainerError::Truncated { expected: 20, actual: 12 })
+        );
+        assert_eq!(
+            Container::command(0x1001, 0, &[0; 6]),
+            Err(ContainerError::TooManyParams(6))
+        );
+    }
+}
@@ -0,0 +1,420 @@
+use std::fmt;
+use crate::mtp::codes::{FORMAT_ASSOCIATION, PROTECTION_NONE};
+
+// PTP 数据集的编码和解码
+// 所有整数都是 little-endian，字符串为 u8 字符数 (包括结尾的 0) + UTF-16LE
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatasetError {
+    pub message: String,
+}
+
+impl fmt::Display for DatasetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid dataset: {}", self.message)
+    }
+}
+
+impl std::error::Error for DatasetError {}
+
+fn dataset_error(message: &str) -> DatasetError {
+    DatasetError {
+        message: message.to_string(),
+    }
+}
+
+pub struct DataReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> DataReader<'a> {
+    pub fn new(data: &'a [u8]) -> DataReader<'a> {
+        DataReader { data, offset: 0 }
+    }
+
+    fn take(&mut self, size: usize) -> Result<&'a [u8], DatasetError> {
+        if self.data.len() - self.offset < size {
+            return Err(dataset_error("unexpected end of data"));
+        }
+        let bytes = &self.data[self.offset..self.offset + size];
+        self.offset += size;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, DatasetError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, DatasetError> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, DatasetError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, DatasetError> {
+        let bytes = self.take(8)?;
+        let mut array = [0u8; 8];
+        array.copy_from_slice(bytes);
+        Ok(u64::from_le_bytes(array))
+    }
+
+    pub fn read_string(&mut self) -> Result<String, DatasetError> {
+        let count = self.read_u8()? as usize;
+        if count == 0 {
+            return Ok(String::new());
+        }
+        let mut chars = Vec::<u16>::with_capacity(count);
+        for _ in 0..count {
+            chars.push(self.read_u16()?);
+        }
+        // 去掉结尾的 0
+        while chars.last() == Some(&0) {
+            chars.pop();
+        }
+        String::from_utf16(&chars).map_err(|_| dataset_error("invalid UTF-16 string"))
+    }
+
+    pub fn read_u16_array(&mut self) -> Result<Vec<u16>, DatasetError> {
+        let count = self.read_u32()? as usize;
+        let mut values = Vec::<u16>::new();
+        for _ in 0..count {
+            values.push(self.read_u16()?);
+        }
+        Ok(values)
+    }
+
+    pub fn read_u32_array(&mut self) -> Result<Vec<u32>, DatasetError> {
+        let count = self.read_u32()? as usize;
+        let mut values = Vec::<u32>::new();
+        for _ in 0..count {
+            values.push(self.read_u32()?);
+        }
+        Ok(values)
+    }
+}
+
+#[derive(Default)]
+pub struct DataWriter {
+    data: Vec<u8>,
+}
+
+impl DataWriter {
+    pub fn new() -> DataWriter {
+        DataWriter { data: Vec::new() }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+
+    // 字符串最多 255 个字符 (包括结尾的 0)，超出部分会被截断
+    pub fn write_string(&mut self, value: &str) {
+        if value.is_empty() {
+            self.write_u8(0);
+            return;
+        }
+        let mut chars: Vec<u16> = value.encode_utf16().take(254).collect();
+        chars.push(0);
+        self.write_u8(chars.len() as u8);
+        for c in chars {
+            self.write_u16(c);
+        }
+    }
+
+    pub fn write_u16_array(&mut self, values: &[u16]) {
+        self.write_u32(values.len() as u32);
+        for value in values {
+            self.write_u16(*value);
+        }
+    }
+
+    pub fn write_u32_array(&mut self, values: &[u32]) {
+        self.write_u32(values.len() as u32);
+        for value in values {
+            self.write_u32(*value);
+        }
+    }
+}
+
+/// DeviceInfo dataset
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MtpDeviceInfo {
+    pub standard_version: u16,
+    pub vendor_extension_id: u32,
+    pub vendor_extension_version: u16,
+    pub vendor_extension_desc: String,
+    pub functional_mode: u16,
+    pub operations_supported: Vec<u16>,
+    pub events_supported: Vec<u16>,
+    pub device_properties_supported: Vec<u16>,
+    pub capture_formats: Vec<u16>,
+    pub playback_formats: Vec<u16>,
+    pub manufacturer: String,
+    pub model: String,
+    pub device_version: String,
+    pub serial_number: String,
+}
+
+impl MtpDeviceInfo {
+    pub fn decode(data: &[u8]) -> Result<MtpDeviceInfo, DatasetError> {
+        let mut reader = DataReader::new(data);
+        Ok(MtpDeviceInfo {
+            standard_version: reader.read_u16()?,
+            vendor_extension_id: reader.read_u32()?,
+            vendor_extension_version: reader.read_u16()?,
+            vendor_extension_desc: reader.read_string()?,
+            functional_mode: reader.read_u16()?,
+            operations_supported: reader.read_u16_array()?,
+            events_supported: reader.read_u16_array()?,
+            device_properties_supported: reader.read_u16_array()?,
+            capture_formats: reader.read_u16_array()?,
+            playback_formats: reader.read_u16_array()?,
+            manufacturer: reader.read_string()?,
+            model: reader.read_string()?,
+            device_version: reader.read_string()?,
+            serial_number: reader.read_string()?,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = DataWriter::new();
+        writer.write_u16(self.standard_version);
+        writer.write_u32(self.vendor_extension_id);
+        writer.write_u16(self.vendor_extension_version);
+        writer.write_string(&self.vendor_extension_desc);
+        writer.write_u16(self.functional_mode);
+        writer.write_u16_array(&self.operations_supported);
+        writer.write_u16_array(&self.events_supported);
+        writer.write_u16_array(&self.device_properties_supported);
+        writer.write_u16_array(&self.capture_formats);
+        writer.write_u16_array(&self.playback_formats);
+        writer.write_string(&self.manufacturer);
+        writer.write_string(&self.model);
+        writer.write_string(&self.device_version);
+        writer.write_string(&self.serial_number);
+        writer.into_bytes()
+    }
+}
+
+/// StorageInfo dataset
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StorageInfo {
+    pub storage_type: u16,
+    pub filesystem_type: u16,
+    pub access_capability: u16,
+    pub max_capacity: u64,
+    pub free_space_in_bytes: u64,
+    pub free_space_in_objects: u32,
+    pub storage_description: String,
+    pub volume_identifier: String,
+}
+
+impl StorageInfo {
+    pub fn decode(data: &[u8]) -> Result<StorageInfo, DatasetError> {
+        let mut reader = DataReader::new(data);
+        Ok(StorageInfo {
+            storage_type: reader.read_u16()?,
+            filesystem_type: reader.read_u16()?,
+            access_capability: reader.read_u16()?,
+            max_capacity: reader.read_u64()?,
+            free_space_in_bytes: reader.read_u64()?,
+            free_space_in_objects: reader.read_u32()?,
+            storage_description: reader.read_string()?,
+            volume_identifier: reader.read_string()?,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = DataWriter::new();
+        writer.write_u16(self.storage_type);
+        writer.write_u16(self.filesystem_type);
+        writer.write_u16(self.access_capability);
+        writer.write_u64(self.max_capacity);
+        writer.write_u64(self.free_space_in_bytes);
+        writer.write_u32(self.free_space_in_objects);
+        writer.write_string(&self.storage_description);
+        writer.write_string(&self.volume_identifier);
+        writer.into_bytes()
+    }
+}
+
+/// ObjectInfo dataset
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectInfo {
+    pub storage_id: u32,
+    pub object_format: u16,
+    pub protection_status: u16,
+    /// 32 位大小，超过 4GB 的对象为 0xFFFFFFFF
+    pub object_compressed_size: u32,
+    pub thumb_format: u16,
+    pub thumb_compressed_size: u32,
+    pub thumb_pix_width: u32,
+    pub thumb_pix_height: u32,
+    pub image_pix_width: u32,
+    pub image_pix_height: u32,
+    pub image_bit_depth: u32,
+    pub parent_object: u32,
+    pub association_type: u16,
+    pub association_desc: u32,
+    pub sequence_number: u32,
+    pub filename: String,
+    /// "YYYYMMDDThhmmss" 格式，可能为空
+    pub date_created: String,
+    pub date_modified: String,
+    pub keywords: String,
+}
+
+impl ObjectInfo {
+    pub fn is_folder(&self) -> bool {
+        self.object_format == FORMAT_ASSOCIATION
+    }
+
+    pub fn can_delete(&self) -> bool {
+        self.protection_status == PROTECTION_NONE
+    }
+
+    pub fn decode(data: &[u8]) -> Result<ObjectInfo, DatasetError> {
+        let mut reader = DataReader::new(data);
+        Ok(ObjectInfo {
+            storage_id: reader.read_u32()?,
+            object_format: reader.read_u16()?,
+            protection_status: reader.read_u16()?,
+            object_compressed_size: reader.read_u32()?,
+            thumb_format: reader.read_u16()?,
+            thumb_compressed_size: reader.read_u32()?,
+            thumb_pix_width: reader.read_u32()?,
+            thumb_pix_height: reader.read_u32()?,
+            image_pix_width: reader.read_u32()?,
+            image_pix_height: reader.read_u32()?,
+            image_bit_depth: reader.read_u32()?,
+            parent_object: reader.read_u32()?,
+            association_type: reader.read_u16()?,
+            association_desc: reader.read_u32()?,
+            sequence_number: reader.read_u32()?,
+            filename: reader.read_string()?,
+            date_created: reader.read_string()?,
+            date_modified: reader.read_string()?,
+            keywords: reader.read_string()?,
+        })
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = DataWriter::new();
+        writer.write_u32(self.storage_id);
+        writer.write_u16(self.object_format);
+        writer.write_u16(self.protection_status);
+        writer.write_u32(self.object_compressed_size);
+        writer.write_u16(self.thumb_format);
+        writer.write_u32(self.thumb_compressed_size);
+        writer.write_u32(self.thumb_pix_width);
+        writer.write_u32(self.thumb_pix_height);
+        writer.write_u32(self.image_pix_width);
+        writer.write_u32(self.image_pix_height);
+        writer.write_u32(self.image_bit_depth);
+        writer.write_u32(self.parent_object);
+        writer.write_u16(self.association_type);
+        writer.write_u32(self.association_desc);
+        writer.write_u32(self.sequence_number);
+        writer.write_string(&self.filename);
+        writer.write_string(&self.date_created);
+        writer.write_string(&self.date_modified);
+        writer.write_string(&self.keywords);
+        writer.into_bytes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string() {
+        let mut writer = DataWriter::new();
+        writer.write_string("");
+        writer.write_string("ab");
+        writer.write_string("图片");
+        let bytes = writer.into_bytes();
+        assert_eq!(&bytes[0..8], &[0, 3, b'a', 0, b'b', 0, 0, 0]);
+
+        let mut reader = DataReader::new(&bytes);
+        assert_eq!(reader.read_string().unwrap(), "");
+        assert_eq!(reader.read_string().unwrap(), "ab");
+        assert_eq!(reader.read_string().unwrap(), "图片");
+        assert!(reader.read_u8().is_err());
+    }
+
+    #[test]
+    fn test_decode_captured_storage_info() {
+        // 从设备上抓取的 StorageInfo 数据集 (fixed RAM, hierarchical, "Internal")
+        let bytes: Vec<u8> = vec![
+            0x03, 0x00, 0x02, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00,
+            0xff, 0xff, 0xff, 0xff,
+            0x09, b'I', 0, b'n', 0, b't', 0, b'e', 0, b'r', 0, b'n', 0, b'a', 0, b'l', 0, 0, 0,
+            0x00,
+        ];
+        let info = StorageInfo::decode(&bytes).unwrap();
+        assert_eq!(info.storage_type, 3);
+        assert_eq!(info.filesystem_type, 2);
+        assert_eq!(info.max_capacity, 0x4000_0000);
+        assert_eq!(info.free_space_in_bytes, 0x1000_0000);
+        assert_eq!(info.storage_description, "Internal");
+        assert_eq!(info.volume_identifier, "");
+        assert_eq!(info.encode(), bytes);
+    }
+
+    #[test]
+    fn test_object_info_round_trip() {
+        let info = ObjectInfo {
+            storage_id: 0x00010001,
+            object_format: FORMAT_ASSOCIATION,
+            parent_object: 0xffffffff,
+            association_type: 1,
+            filename: "DCIM".to_string(),
+            date_modified: "20240102T030405".to_string(),
+            ..Default::default()
+        };
+        let decoded = ObjectInfo::decode(&info.encode()).unwrap();
+        assert_eq!(decoded, info);
+        assert!(decoded.is_folder());
+        assert!(decoded.can_delete());
+        assert!(ObjectInfo::decode(&info.encode()[..20]).is_err());
+    }
+
+    #[test]
+    fn test_device_info_round_trip() {
+        let info = MtpDeviceInfo {
+            standard_version: 100,
+            vendor_extension_id: 6,
+            vendor_extension_version: 100,
+            vendor_extension_desc: "microsoft.com: 1.0;".to_string(),
+            operations_supported: vec![0x1001, 0x1002],
+            manufacturer: "Xiaomi".to_string(),
+            model: "Redmi K70".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(MtpDeviceInfo::decode(&info.encode()).unwrap(), info);
+    }
+}
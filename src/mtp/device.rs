@@ -0,0 +1,629 @@
+use std::cell::RefCell;
+use std::io::Read;
+use crate::backend::{ContentObject, ContentObjectInfo, ContentObjectIterator, ContentType, DeviceOperate, FileWriter, FunctionalCategory};
+use crate::common::file_reader::FileReader;
+use crate::common::timestamp::{TimeZone, Timestamp};
+use crate::mtp::codes::*;
+use crate::mtp::dataset::ObjectInfo;
+use crate::mtp::session::{MtpSession, ResponseError};
+use crate::mtp::transport::Transport;
+
+// 对象id: 根对象 ""，设备对象 "DEVICE"，存储 "s<storage id>"，文件和文件夹 "o<object handle>"
+const ROOT_OBJECT_ID: &str = "";
+const DEVICE_OBJECT_ID: &str = "DEVICE";
+const BUFFER_SIZE: u32 = 32768;
+
+enum MtpObject {
+    Root,
+    Device,
+    Storage(u32),
+    Object(u32),
+}
+
+impl MtpObject {
+    fn parse(object: &ContentObject) -> Result<MtpObject, Box<dyn std::error::Error>> {
+        let id = object.id.as_str();
+        let invalid = || format!("invalid object id: {:?}", id);
+        match id {
+            ROOT_OBJECT_ID => Ok(MtpObject::Root),
+            DEVICE_OBJECT_ID => Ok(MtpObject::Device),
+            _ => {
+                let value = id.get(1..).and_then(|hex| u32::from_str_radix(hex, 16).ok()).ok_or_else(invalid)?;
+                match &id[..1] {
+                    "s" => Ok(MtpObject::Storage(value)),
+                    "o" => Ok(MtpObject::Object(value)),
+                    _ => Err(invalid().into()),
+                }
+            }
+        }
+    }
+
+    fn to_content_object(&self) -> ContentObject {
+        match self {
+            MtpObject::Root => ContentObject::new(ROOT_OBJECT_ID),
+            MtpObject::Device => ContentObject::new(DEVICE_OBJECT_ID),
+            MtpObject::Storage(storage_id) => ContentObject::new(&format!("s{:08X}", storage_id)),
+            MtpObject::Object(handle) => ContentObject::new(&format!("o{:08X}", handle)),
+        }
+    }
+}
+
+/// A device accessed through the native MTP implementation.
+pub struct MtpDevice {
+    name: String,
+    session: RefCell<MtpSession>,
+    // 没有时区的日期使用的时区
+    time_zone: TimeZone,
+}
+
+impl MtpDevice {
+    // 打开会话并读取设备信息，设备名称为型号
+    pub fn open(transport: Box<dyn Transport>) -> Result<MtpDevice, Box<dyn std::error::Error>> {
+        let mut session = MtpSession::open(transport, 1)?;
+        let device_info = session.get_device_info()?;
+        Ok(MtpDevice {
+            name: device_info.model,
+            session: RefCell::new(session),
+            time_zone: TimeZone::UTC,
+        })
+    }
+
+    /// Sets the time zone of the dates without an offset, the dates sent to the device use it too.
+    pub fn set_time_zone(&mut self, time_zone: TimeZone) {
+        self.time_zone = time_zone;
+    }
+
+    // 关闭会话
+    pub fn close(self) -> Result<(), Box<dyn std::error::Error>> {
+        self.session.into_inner().close()?;
+        Ok(())
+    }
+
+    // 获取新对象的 storage id 和 parent handle
+    fn resolve_parent(&self, parent: &ContentObject) -> Result<(u32, u32), Box<dyn std::error::Error>> {
+        match MtpObject::parse(parent)? {
+            MtpObject::Storage(storage_id) => Ok((storage_id, PARENT_ROOT)),
+            MtpObject::Object(handle) => {
+                let info = self.session.borrow_mut().get_object_info(handle)?;
+                if !info.is_folder() {
+                    return Err(format!("not a folder: {}", &info.filename).into());
+                }
+                Ok((info.storage_id, handle))
+            }
+            _ => Err(format!("cannot create objects in: {:?}", &parent.id).into()),
+        }
+    }
+}
+
+// 空字符串表示没有提供，无法解析的日期忽略
+fn optional_date(date: &str, time_zone: TimeZone) -> Option<Timestamp> {
+    if date.is_empty() {
+        None
+    } else {
+        Timestamp::parse_mtp(date, time_zone)
+    }
+}
+
+impl DeviceOperate for MtpDevice {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_root_object(&self) -> ContentObject {
+        ContentObject::new(ROOT_OBJECT_ID)
+    }
+
+    fn get_object_iterator(&self, parent: &ContentObject) -> Result<Box<dyn ContentObjectIterator + '_>, Box<dyn std::error::Error>> {
+        let mut objects = match MtpObject::parse(parent)? {
+            MtpObject::Root => vec![MtpObject::Device],
+            MtpObject::Device => self
+                .session
+                .borrow_mut()
+                .get_storage_ids()?
+                .into_iter()
+                .map(MtpObject::Storage)
+                .collect(),
+            MtpObject::Storage(storage_id) => self
+                .session
+                .borrow_mut()
+                .get_object_handles(storage_id, FORMAT_ALL, PARENT_ROOT)?
+                .into_iter()
+                .map(MtpObject::Object)
+                .collect(),
+            MtpObject::Object(handle) => self
+                .session
+                .borrow_mut()
+                .get_object_handles(STORAGE_ALL, FORMAT_ALL, handle)?
+                .into_iter()
+                .map(MtpObject::Object)
+                .collect::<Vec<_>>(),
+        };
+        objects.reverse(); // for moving item out by pop()
+        Ok(Box::new(MtpObjectIterator {
+            object_ids: objects.iter().map(|object| object.to_content_object()).collect(),
+        }))
+    }
+
+    fn get_object_info(&self, object: ContentObject) -> Result<ContentObjectInfo, Box<dyn std::error::Error>> {
+        let functional_object_info = |name: String, functional_object_category: FunctionalCategory, parent: Option<MtpObject>| ContentObjectInfo {
+            content_object: object.clone(),
+            name,
+            content_type: ContentType::FunctionalObject,
+            functional_object_category,
+            parent_id: parent.map(|parent| parent.to_content_object().id),
+            persistent_id: None,
+            format: None,
+            data_size: 0,
+            is_hidden: false,
+            is_system: false,
+            can_delete: false,
+            time_created: None,
+            time_modified: None,
+            time_authored: None,
+        };
+        match MtpObject::parse(&object)? {
+            MtpObject::Root => Ok(functional_object_info(String::new(), FunctionalCategory::Other, None)),
+            MtpObject::Device => Ok(functional_object_info(self.name.clone(), FunctionalCategory::Device, Some(MtpObject::Root))),
+            MtpObject::Storage(storage_id) => {
+                let storage_info = self.session.borrow_mut().get_storage_info(storage_id)?;
+                let name = if storage_info.storage_description.is_empty() {
+                    storage_info.volume_identifier
+                } else {
+                    storage_info.storage_description
+                };
+                Ok(functional_object_info(name, FunctionalCategory::Storage, Some(MtpObject::Device)))
+            }
+            MtpObject::Object(handle) => {
+                let info = self.session.borrow_mut().get_object_info(handle)?;
+                let data_size = if info.object_compressed_size == OBJECT_SIZE_LARGE && !info.is_folder() {
+                    self.session.borrow_mut().get_object_size(handle)?
+                } else {
+                    info.object_compressed_size as u64
+                };
+                // 存储根目录下的对象的 parent 为 0
+                let parent = match info.parent_object {
+                    0 | PARENT_ROOT => MtpObject::Storage(info.storage_id),
+                    parent => MtpObject::Object(parent),
+                };
+                Ok(ContentObjectInfo {
+                    content_object: object,
+                    content_type: if info.is_folder() { ContentType::Folder } else { ContentType::GenericFile },
+                    functional_object_category: FunctionalCategory::None,
+                    parent_id: Some(parent.to_content_object().id),
+                    persistent_id: None,
+                    format: Some(info.object_format),
+                    data_size,
+                    is_hidden: false,
+                    is_system: false,
+                    can_delete: info.can_delete(),
+                    name: info.filename,
+                    time_created: optional_date(&info.date_created, self.time_zone),
+                    time_modified: optional_date(&info.date_modified, self.time_zone),
+                    time_authored: None,
+                })
+            }
+        }
+    }
+
+    fn get_resoure(&self, object: &ContentObject) -> Result<Box<dyn FileReader + '_>, Box<dyn std::error::Error>> {
+        match MtpObject::parse(object)? {
+            MtpObject::Object(handle) => {
+                self.session.borrow_mut().begin_get_object(handle)?;
+                Ok(Box::new(MtpObjectReader {
+                    device: self,
+                    finished: false,
+                }))
+            }
+            _ => Err(format!("object has no data: {:?}", &object.id).into()),
+        }
+    }
+
+    fn create_file(
+        &self,
+        parent: &ContentObject,
+        name: &str,
+        size: u64,
+        created: &Option<Timestamp>,
+        modified: &Option<Timestamp>,
+    ) -> Result<Box<dyn FileWriter + '_>, Box<dyn std::error::Error>> {
+        let (storage_id, parent_handle) = self.resolve_parent(parent)?;
+        let info = ObjectInfo {
+            storage_id,
+            object_format: FORMAT_UNDEFINED,
+            protection_status: PROTECTION_NONE,
+            object_compressed_size: u32::try_from(size).unwrap_or(OBJECT_SIZE_LARGE),
+            parent_object: parent_handle,
+            filename: name.to_string(),
+            date_created: created.map(|time| time.to_mtp(self.time_zone)).unwrap_or_default(),
+            date_modified: modified.map(|time| time.to_mtp(self.time_zone)).unwrap_or_default(),
+            ..Default::default()
+        };
+        // SendObject 必须紧跟在 SendObjectInfo 之后，写入的数据直接发送
+        let mut session = self.session.borrow_mut();
+        let (_, _, handle) = session.send_object_info(storage_id, parent_handle, &info)?;
+        session.begin_send_object(size)?;
+        Ok(Box::new(MtpFileWriter {
+            device: self,
+            handle,
+            size,
+            written: 0,
+        }))
+    }
+
+    fn create_folder(&self, parent: &ContentObject, name: &str) -> Result<ContentObject, Box<dyn std::error::Error>> {
+        let (storage_id, parent_handle) = self.resolve_parent(parent)?;
+        let info = ObjectInfo {
+            storage_id,
+            object_format: FORMAT_ASSOCIATION,
+            protection_status: PROTECTION_NONE,
+            parent_object: parent_handle,
+            association_type: ASSOCIATION_GENERIC_FOLDER,
+            filename: name.to_string(),
+            ..Default::default()
+        };
+        let (_, _, handle) = self.session.borrow_mut().send_object_info(storage_id, parent_handle, &info)?;
+        Ok(MtpObject::Object(handle).to_content_object())
+    }
+
+    fn delete(&self, object: &ContentObject) -> Result<(), Box<dyn std::error::Error>> {
+        match MtpObject::parse(object)? {
+            MtpObject::Object(handle) => self.session.borrow_mut().delete_object(handle),
+            _ => Err(format!("object cannot be deleted: {:?}", &object.id).into()),
+        }
+    }
+
+    fn rename(&self, object: &ContentObject, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match MtpObject::parse(object)? {
+            MtpObject::Object(handle) => self.session.borrow_mut().set_object_prop_string(handle, PROPERTY_OBJECT_FILE_NAME, name),
+            _ => Err(format!("object cannot be renamed: {:?}", &object.id).into()),
+        }
+    }
+
+    fn move_object(&self, object: &ContentObject, parent: &ContentObject) -> Result<bool, Box<dyn std::error::Error>> {
+        let handle = match MtpObject::parse(object)? {
+            MtpObject::Object(handle) => handle,
+            _ => return Err(format!("object cannot be moved: {:?}", &object.id).into()),
+        };
+        let (storage_id, parent_handle) = self.resolve_parent(parent)?;
+        // MoveObject 使用 0 表示存储的根目录
+        let parent_handle = if parent_handle == PARENT_ROOT { 0 } else { parent_handle };
+        match self.session.borrow_mut().move_object(handle, storage_id, parent_handle) {
+            Ok(()) => Ok(true),
+            Err(err) => match err.downcast_ref::<ResponseError>() {
+                Some(response) if response.code == RESPONSE_OPERATION_NOT_SUPPORTED => Ok(false),
+                _ => Err(err),
+            },
+        }
+    }
+}
+
+pub struct MtpObjectIterator {
+    object_ids: Vec<ContentObject>,
+}
+
+impl ContentObjectIterator for MtpObjectIterator {
+    fn next(&mut self) -> Result<Option<ContentObject>, Box<dyn std::error::Error>> {
+        Ok(self.object_ids.pop())
+    }
+}
+
+// GetObject 的数据边读边从设备接收，不把整个对象放在内存中
+pub struct MtpObjectReader<'d> {
+    device: &'d MtpDevice,
+    // 数据已读完并收到了响应
+    finished: bool,
+}
+
+impl Read for MtpObjectReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.finished || buf.is_empty() {
+            return Ok(0);
+        }
+        let len = self.device.session.borrow_mut().read_data(buf).map_err(|err| match err.downcast::<std::io::Error>() {
+            Ok(err) => *err,
+            Err(err) => std::io::Error::other(err.to_string()),
+        })?;
+        self.finished = len == 0;
+        Ok(len)
+    }
+}
+
+impl FileReader for MtpObjectReader<'_> {
+    fn buffer_size(&self) -> u32 {
+        BUFFER_SIZE
+    }
+}
+
+// 没有读完就关闭时读完剩余的数据，会话才能执行后面的事务
+impl Drop for MtpObjectReader<'_> {
+    fn drop(&mut self) {
+        let mut buffer = vec![0u8; BUFFER_SIZE as usize];
+        while !self.finished {
+            if let Err(err) = self.read(&mut buffer) {
+                log::debug!("{}", err);
+                break;
+            }
+        }
+    }
+}
+
+// 写入的数据直接作为 SendObject 的数据发送，commit 时接收响应
+pub struct MtpFileWriter<'d> {
+    device: &'d MtpDevice,
+    handle: u32,
+    size: u64,
+    written: u64,
+}
+
+impl FileWriter for MtpFileWriter<'_> {
+    fn get_buffer_size(&self) -> u32 {
+        BUFFER_SIZE
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        if self.written + data.len() as u64 > self.size {
+            return Err(format!("size mismatch: more than {} bytes were written", self.size).into());
+        }
+        self.device.session.borrow_mut().write_data(data)?;
+        self.written += data.len() as u64;
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<ContentObject, Box<dyn std::error::Error>> {
+        if self.written != self.size {
+            return Err(format!(
+                "size mismatch: {} bytes were written, {} bytes were expected",
+                self.written, self.size
+            )
+            .into());
+        }
+        self.device.session.borrow_mut().end_data_phase()?;
+        Ok(MtpObject::Object(self.handle).to_content_object())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mtp::container::Container;
+    use crate::mtp::dataset::{DataWriter, MtpDeviceInfo, StorageInfo};
+    use crate::mtp::transport::ScriptedTransport;
+
+    // 脚本化的设备: 每个事务为 命令 -> [数据] -> 响应
+    struct Script {
+        transport: ScriptedTransport,
+        transaction_id: u32,
+    }
+
+    impl Script {
+        fn new() -> Script {
+            Script {
+                transport: ScriptedTransport::new(),
+                transaction_id: 0,
+            }
+        }
+
+        fn transaction(&mut self, operation: u16, params: &[u32], data_out: Option<Vec<u8>>, data_in: Option<Vec<u8>>, response: (u16, &[u32])) {
+            let tid = self.transaction_id;
+            self.transport.expect(&Container::command(operation, tid, params).unwrap());
+            if let Some(data) = data_out {
+                self.transport.expect(&Container::data(operation, tid, data));
+            }
+            if let Some(data) = data_in {
+                self.transport.reply(&Container::data(operation, tid, data));
+            }
+            self.transport.reply(&Container::response(response.0, tid, response.1).unwrap());
+            self.transaction_id += 1;
+        }
+
+        fn open_device(&mut self) -> MtpDevice {
+            self.transaction(OPERATION_OPEN_SESSION, &[1], None, None, (RESPONSE_OK, &[]));
+            let device_info = MtpDeviceInfo {
+                model: "Pixel".to_string(),
+                ..Default::default()
+            };
+            self.transaction(OPERATION_GET_DEVICE_INFO, &[], None, Some(device_info.encode()), (RESPONSE_OK, &[]));
+            MtpDevice::open(Box::new(self.transport.clone())).unwrap()
+        }
+    }
+
+    fn handles(values: &[u32]) -> Vec<u8> {
+        let mut writer = DataWriter::new();
+        writer.write_u32_array(values);
+        writer.into_bytes()
+    }
+
+    fn file_info(name: &str, size: u32) -> ObjectInfo {
+        ObjectInfo {
+            storage_id: 0x00010001,
+            object_format: FORMAT_UNDEFINED,
+            object_compressed_size: size,
+            parent_object: PARENT_ROOT,
+            filename: name.to_string(),
+            date_modified: "20240102T030405".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_browse() {
+        let mut script = Script::new();
+        let device = script.open_device();
+        assert_eq!(device.name(), "Pixel");
+
+        script.transaction(OPERATION_GET_STORAGE_IDS, &[], None, Some(handles(&[0x00010001])), (RESPONSE_OK, &[]));
+        let mut iter = device.get_object_iterator(&ContentObject::new(DEVICE_OBJECT_ID)).unwrap();
+        let storage = iter.next().unwrap().unwrap();
+        assert_eq!(storage.id, "s00010001");
+        assert!(iter.next().unwrap().is_none());
+        drop(iter);
+
+        let storage_info = StorageInfo {
+            storage_description: "Internal shared storage".to_string(),
+            ..Default::default()
+        };
+        script.transaction(OPERATION_GET_STORAGE_INFO, &[0x00010001], None, Some(storage_info.encode()), (RESPONSE_OK, &[]));
+        let info = device.get_object_info(storage.clone()).unwrap();
+        assert!(info.is_storage());
+        assert_eq!(info.name, "Internal shared storage");
+
+        script.transaction(OPERATION_GET_OBJECT_HANDLES, &[0x00010001, FORMAT_ALL, PARENT_ROOT], None, Some(handles(&[0x21, 0x22])), (RESPONSE_OK, &[]));
+        let mut iter = device.get_object_iterator(&storage).unwrap();
+        assert_eq!(iter.next().unwrap().unwrap().id, "o00000021");
+        let file = iter.next().unwrap().unwrap();
+        assert_eq!(file.id, "o00000022");
+
+        script.transaction(OPERATION_GET_OBJECT_INFO, &[0x22], None, Some(file_info("a.txt", 5).encode()), (RESPONSE_OK, &[]));
+        let info = device.get_object_info(file.clone()).unwrap();
+        assert!(info.is_file());
+        assert_eq!(info.name, "a.txt");
+        assert_eq!(info.data_size, 5);
+        assert_eq!(info.parent_id.as_deref(), Some("s00010001"));
+        assert_eq!(info.format, Some(FORMAT_UNDEFINED));
+        assert!(info.can_delete);
+        assert_eq!(info.time_created, None);
+        assert_eq!(info.time_modified, Some(Timestamp::from_unix_seconds(1704164645)));
+
+        script.transaction(OPERATION_GET_OBJECT, &[0x22], None, Some(b"hello".to_vec()), (RESPONSE_OK, &[]));
+        let mut reader = device.get_resoure(&file).unwrap();
+        let mut data = Vec::<u8>::new();
+        reader.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"hello");
+        drop(reader);
+        drop(iter);
+
+        script.transaction(OPERATION_CLOSE_SESSION, &[], None, None, (RESPONSE_OK, &[]));
+        device.close().unwrap();
+        assert_eq!(script.transport.remaining(), 0);
+    }
+
+    #[test]
+    fn test_create_and_delete() {
+        let mut script = Script::new();
+        let device = script.open_device();
+        let storage = ContentObject::new("s00010001");
+
+        let folder_info = ObjectInfo {
+            storage_id: 0x00010001,
+            object_format: FORMAT_ASSOCIATION,
+            parent_object: PARENT_ROOT,
+            association_type: ASSOCIATION_GENERIC_FOLDER,
+            filename: "DCIM".to_string(),
+            ..Default::default()
+        };
+        script.transaction(OPERATION_SEND_OBJECT_INFO, &[0x00010001, PARENT_ROOT], Some(folder_info.encode()), None, (RESPONSE_OK, &[0x00010001, PARENT_ROOT, 0x30]));
+        let folder = device.create_folder(&storage, "DCIM").unwrap();
+        assert_eq!(folder.id, "o00000030");
+
+        // 文件夹下创建文件时需要读取文件夹的 storage id
+        script.transaction(OPERATION_GET_OBJECT_INFO, &[0x30], None, Some(folder_info.encode()), (RESPONSE_OK, &[]));
+        let mut sent_info = file_info("a.txt", 5);
+        sent_info.parent_object = 0x30;
+        sent_info.date_modified = "20240102T030405Z".to_string();
+        script.transaction(OPERATION_SEND_OBJECT_INFO, &[0x00010001, 0x30], Some(sent_info.encode()), None, (RESPONSE_OK, &[0x00010001, 0x30, 0x31]));
+        script.transaction(OPERATION_SEND_OBJECT, &[], Some(b"hello".to_vec()), None, (RESPONSE_OK, &[]));
+        let mut writer = device.create_file(&folder, "a.txt", 5, &None, &Some(Timestamp::from_unix_seconds(1704164645))).unwrap();
+        writer.write(b"hel").unwrap();
+        writer.write(b"lo").unwrap();
+        assert_eq!(writer.commit().unwrap().id, "o00000031");
+        drop(writer);
+
+        script.transaction(OPERATION_DELETE_OBJECT, &[0x31, FORMAT_ALL], None, None, (RESPONSE_OBJECT_WRITE_PROTECTED, &[]));
+        assert!(device.delete(&ContentObject::new("o00000031")).is_err());
+        assert!(device.delete(&storage).is_err());
+        assert_eq!(script.transport.remaining(), 0);
+    }
+
+    #[test]
+    fn test_rename_and_move() {
+        let mut script = Script::new();
+        let device = script.open_device();
+        let file = ContentObject::new("o00000031");
+
+        let mut name = DataWriter::new();
+        name.write_string("b.txt");
+        script.transaction(OPERATION_SET_OBJECT_PROP_VALUE, &[0x31, PROPERTY_OBJECT_FILE_NAME as u32], Some(name.into_bytes()), None, (RESPONSE_OK, &[]));
+        device.rename(&file, "b.txt").unwrap();
+
+        // 存储的根目录为 0
+        script.transaction(OPERATION_MOVE_OBJECT, &[0x31, 0x00010001, 0], None, None, (RESPONSE_OK, &[]));
+        assert!(device.move_object(&file, &ContentObject::new("s00010001")).unwrap());
+        script.transaction(OPERATION_MOVE_OBJECT, &[0x31, 0x00010002, 0], None, None, (RESPONSE_OPERATION_NOT_SUPPORTED, &[]));
+        assert!(!device.move_object(&file, &ContentObject::new("s00010002")).unwrap());
+        script.transaction(OPERATION_MOVE_OBJECT, &[0x31, 0x00010002, 0], None, None, (RESPONSE_ACCESS_DENIED, &[]));
+        assert!(device.move_object(&file, &ContentObject::new("s00010002")).is_err());
+        assert!(device.rename(&ContentObject::new("s00010001"), "SD").is_err());
+        assert_eq!(script.transport.remaining(), 0);
+    }
+
+    #[test]
+    fn test_commit_size_mismatch() {
+        let mut script = Script::new();
+        let device = script.open_device();
+        let mut sent_info = file_info("a.txt", 5);
+        sent_info.date_modified = String::new();
+        script.transaction(OPERATION_SEND_OBJECT_INFO, &[0x00010001, PARENT_ROOT], Some(sent_info.encode()), None, (RESPONSE_OK, &[0x00010001, PARENT_ROOT, 0x31]));
+        script.transport.expect(&Container::command(OPERATION_SEND_OBJECT, script.transaction_id, &[]).unwrap());
+        script.transport.expect(&Container::data(OPERATION_SEND_OBJECT, script.transaction_id, b"hello".to_vec()));
+        let mut writer = device.create_file(&ContentObject::new("s00010001"), "a.txt", 5, &None, &None).unwrap();
+        writer.write(b"hel").unwrap();
+        assert!(writer.write(b"lo!").is_err());
+        assert!(writer.commit().is_err());
+        drop(writer);
+        // 数据没有发送完，不能开始其他事务
+        assert!(device.delete(&ContentObject::new("o00000031")).is_err());
+        assert_eq!(script.transport.remaining(), 0);
+    }
+
+    #[test]
+    fn test_large_file() {
+        let mut script = Script::new();
+        let device = script.open_device();
+        let size: u64 = 5 << 30;
+
+        // 不小于 4 GiB 的对象在 ObjectInfo 中的大小为 0xFFFFFFFF，数据分块发送
+        let mut sent_info = file_info("movie.mp4", OBJECT_SIZE_LARGE);
+        sent_info.date_modified = String::new();
+        script.transaction(OPERATION_SEND_OBJECT_INFO, &[0x00010001, PARENT_ROOT], Some(sent_info.encode()), None, (RESPONSE_OK, &[0x00010001, PARENT_ROOT, 0x40]));
+        let tid = script.transaction_id;
+        script
+            .transport
+            .expect(&Container::command(OPERATION_SEND_OBJECT, tid, &[]).unwrap())
+            .expect_data_size(OPERATION_SEND_OBJECT, tid, size)
+            .reply(&Container::response(RESPONSE_OK, tid, &[]).unwrap());
+        script.transaction_id += 1;
+        let mut writer = device.create_file(&ContentObject::new("s00010001"), "movie.mp4", size, &None, &None).unwrap();
+        let chunk = vec![0u8; 1 << 20];
+        for _ in 0..size / chunk.len() as u64 {
+            writer.write(&chunk).unwrap();
+        }
+        assert_eq!(writer.commit().unwrap().id, "o00000040");
+        drop(writer);
+
+        // 实际的大小从 ObjectSize 属性读取
+        let mut object_size = DataWriter::new();
+        object_size.write_u64(size);
+        script.transaction(OPERATION_GET_OBJECT_INFO, &[0x40], None, Some(sent_info.encode()), (RESPONSE_OK, &[]));
+        script.transaction(OPERATION_GET_OBJECT_PROP_VALUE, &[0x40, PROPERTY_OBJECT_SIZE as u32], None, Some(object_size.into_bytes()), (RESPONSE_OK, &[]));
+        assert_eq!(device.get_object_info(ContentObject::new("o00000040")).unwrap().data_size, size);
+        assert_eq!(script.transport.remaining(), 0);
+    }
+
+    #[test]
+    fn test_read_part_of_object() {
+        let mut script = Script::new();
+        let device = script.open_device();
+        let file = ContentObject::new("o00000022");
+
+        // 没有读完就关闭时读完剩余的数据，之后的事务正常执行
+        script.transaction(OPERATION_GET_OBJECT, &[0x22], None, Some(b"hello".to_vec()), (RESPONSE_OK, &[]));
+        let mut reader = device.get_resoure(&file).unwrap();
+        let mut buf = [0u8; 2];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"he");
+        drop(reader);
+        script.transaction(OPERATION_DELETE_OBJECT, &[0x22, FORMAT_ALL], None, None, (RESPONSE_OK, &[]));
+        device.delete(&file).unwrap();
+        assert_eq!(script.transport.remaining(), 0);
+    }
+}
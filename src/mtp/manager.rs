@@ -0,0 +1,120 @@
+use crate::backend::{DeviceInfo, DeviceOperate, PortableDeviceBackend};
+use crate::common::timestamp::TimeZone;
+use crate::mtp::device::MtpDevice;
+use crate::mtp::transport::Transport;
+
+// 建立到设备的连接，每次打开设备都会建立新的连接
+pub type Connector = Box<dyn Fn() -> Result<Box<dyn Transport>, Box<dyn std::error::Error>>>;
+
+/// A backend for devices accessed through the native MTP implementation.
+#[derive(Default)]
+pub struct MtpBackend {
+    connectors: Vec<Connector>,
+    time_zone: TimeZone,
+}
+
+impl MtpBackend {
+    pub fn new() -> MtpBackend {
+        MtpBackend::default()
+    }
+
+    pub fn add_connector(&mut self, connector: Connector) {
+        self.connectors.push(connector);
+    }
+
+    /// Sets the time zone of the dates on the devices.
+    pub fn set_time_zone(&mut self, time_zone: TimeZone) {
+        self.time_zone = time_zone;
+    }
+
+    fn connect(&self, info: &DeviceInfo) -> Result<Box<dyn Transport>, Box<dyn std::error::Error>> {
+        let connector = info
+            .id
+            .strip_prefix("mtp:")
+            .and_then(|s| s.parse::<usize>().ok())
+            .and_then(|index| self.connectors.get(index))
+            .ok_or_else(|| format!("failed to open device: {}", &info.name))?;
+        connector()
+    }
+}
+
+impl PortableDeviceBackend for MtpBackend {
+    fn list_devices(&self) -> Result<Vec<DeviceInfo>, Box<dyn std::error::Error>> {
+        let mut devices = Vec::<DeviceInfo>::new();
+        for (index, connector) in self.connectors.iter().enumerate() {
+            let device = match connector().and_then(MtpDevice::open) {
+                Ok(device) => device,
+                Err(err) => {
+                    log::debug!("{}", err);
+                    log::warn!("failed to connect to MTP device #{} (skipped)", index);
+                    continue;
+                }
+            };
+            devices.push(DeviceInfo {
+                id: format!("mtp:{}", index),
+                name: device.name().to_string(),
+            });
+            if let Err(err) = device.close() {
+                log::debug!("{}", err);
+            }
+        }
+        Ok(devices)
+    }
+
+    fn open_device(&self, info: &DeviceInfo) -> Result<Box<dyn DeviceOperate>, Box<dyn std::error::Error>> {
+        let mut device = MtpDevice::open(self.connect(info)?)?;
+        device.set_time_zone(self.time_zone);
+        Ok(Box::new(device))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mtp::codes::*;
+    use crate::mtp::container::Container;
+    use crate::mtp::dataset::MtpDeviceInfo;
+    use crate::mtp::transport::ScriptedTransport;
+
+    fn script_device(model: &str, close: bool) -> ScriptedTransport {
+        let transport = ScriptedTransport::new();
+        let device_info = MtpDeviceInfo {
+            model: model.to_string(),
+            ..Default::default()
+        };
+        transport
+            .expect(&Container::command(OPERATION_OPEN_SESSION, 0, &[1]).unwrap())
+            .reply(&Container::response(RESPONSE_OK, 0, &[]).unwrap())
+            .expect(&Container::command(OPERATION_GET_DEVICE_INFO, 1, &[]).unwrap())
+            .reply(&Container::data(OPERATION_GET_DEVICE_INFO, 1, device_info.encode()))
+            .reply(&Container::response(RESPONSE_OK, 1, &[]).unwrap());
+        if close {
+            transport
+                .expect(&Container::command(OPERATION_CLOSE_SESSION, 2, &[]).unwrap())
+                .reply(&Container::response(RESPONSE_OK, 2, &[]).unwrap());
+        }
+        transport
+    }
+
+    #[test]
+    fn test_list_and_open_devices() {
+        let phone = script_device("Phone", true);
+        let mut backend = MtpBackend::new();
+        let transport = phone.clone();
+        backend.add_connector(Box::new(move || Ok(Box::new(transport.clone()))));
+        backend.add_connector(Box::new(|| Err("device is not connected".into())));
+
+        let devices = backend.list_devices().unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].name, "Phone");
+        assert_eq!(phone.remaining(), 0);
+
+        let phone = script_device("Phone", false);
+        let transport = phone.clone();
+        let mut backend = MtpBackend::new();
+        backend.add_connector(Box::new(move || Ok(Box::new(transport.clone()))));
+        let device = backend.open_device(&devices[0]).unwrap();
+        assert_eq!(device.name(), "Phone");
+        assert_eq!(phone.remaining(), 0);
+    }
+}
@@ -0,0 +1,10 @@
+// 纯 Rust 实现的 MTP/PTP 协议层，不依赖 WPD
+pub mod codes;
+pub mod container;
+pub mod dataset;
+pub mod device;
+pub mod manager;
+pub mod ptpip;
+pub mod responder;
+pub mod session;
+pub mod transport;
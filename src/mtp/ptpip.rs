@@ -0,0 +1,663 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use crate::mtp::container::{Container, ContainerType};
+use crate::mtp::transport::Transport;
+
+// PTP/IP 传输 (CIPA DC-005)
+//
+// 每个连接有两个 TCP 通道: 命令/数据通道和事件通道。
+// 包结构 (little-endian): u32 包长度 (包含 8 字节头), u32 包类型, payload
+//
+// 建立连接:
+//   命令通道: Init_Command_Request (GUID, 名称, 版本) -> Init_Command_Ack (连接号, GUID, 名称, 版本)
+//   事件通道: Init_Event_Request (连接号) -> Init_Event_Ack
+// 事务:
+//   Operation_Request -> [Start_Data, Data..., End_Data] -> Operation_Response
+
+pub const DEFAULT_PORT: u16 = 15740;
+pub const URL_SCHEME: &str = "ptpip://";
+
+const PROTOCOL_VERSION: u32 = 0x00010000;
+const PACKET_HEADER_SIZE: usize = 8;
+// 一个包最大的大小，防止错误的长度导致分配过多的内存
+const MAX_PACKET_SIZE: usize = 64 * 1024 * 1024;
+// 发送数据时每个 Data 包的最大数据量
+const MAX_DATA_CHUNK: usize = 1024 * 1024;
+
+const PACKET_INIT_COMMAND_REQUEST: u32 = 1;
+const PACKET_INIT_COMMAND_ACK: u32 = 2;
+const PACKET_INIT_EVENT_REQUEST: u32 = 3;
+const PACKET_INIT_EVENT_ACK: u32 = 4;
+const PACKET_INIT_FAIL: u32 = 5;
+const PACKET_OPERATION_REQUEST: u32 = 6;
+const PACKET_OPERATION_RESPONSE: u32 = 7;
+const PACKET_EVENT: u32 = 8;
+const PACKET_START_DATA: u32 = 9;
+const PACKET_DATA: u32 = 10;
+const PACKET_CANCEL: u32 = 11;
+const PACKET_END_DATA: u32 = 12;
+
+// Operation_Request 的 DataPhaseInfo
+const DATA_PHASE_NONE_OR_IN: u32 = 1;
+const DATA_PHASE_OUT: u32 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Packet {
+    packet_type: u32,
+    payload: Vec<u8>,
+}
+
+// 正在分块接收的数据阶段
+struct DataIn {
+    transaction_id: u32,
+    // 0xFFFFFFFFFFFFFFFF 表示长度未知
+    total_size: u64,
+    received: u64,
+    // 当前的包 (包含 transaction id) 和已读取的位置
+    packet: Vec<u8>,
+    offset: usize,
+    // 已经收到 End_Data
+    ended: bool,
+}
+
+fn read_packet(stream: &mut impl Read) -> Result<Packet, Box<dyn std::error::Error>> {
+    let mut header = [0u8; PACKET_HEADER_SIZE];
+    stream.read_exact(&mut header)?;
+    let length = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let packet_type = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    if !(PACKET_HEADER_SIZE..=MAX_PACKET_SIZE).contains(&length) {
+        return Err(format!("invalid PTP/IP packet length: {}", length).into());
+    }
+    let mut payload = vec![0u8; length - PACKET_HEADER_SIZE];
+    stream.read_exact(&mut payload)?;
+    Ok(Packet { packet_type, payload })
+}
+
+fn write_packet(stream: &mut impl Write, packet_type: u32, payload: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut bytes = Vec::<u8>::with_capacity(PACKET_HEADER_SIZE + payload.len());
+    bytes.extend_from_slice(&((PACKET_HEADER_SIZE + payload.len()) as u32).to_le_bytes());
+    bytes.extend_from_slice(&packet_type.to_le_bytes());
+    bytes.extend_from_slice(payload);
+    stream.write_all(&bytes)?;
+    Ok(())
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> Result<u16, Box<dyn std::error::Error>> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| "PTP/IP packet is too short".into())
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, Box<dyn std::error::Error>> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| "PTP/IP packet is too short".into())
+}
+
+// 名称为以 0 结尾的 UTF-16LE 字符串
+fn encode_name(name: &str) -> Vec<u8> {
+    name.encode_utf16().chain(std::iter::once(0)).flat_map(|c| c.to_le_bytes()).collect()
+}
+
+// 解析名称，返回名称和消耗的字节数
+fn decode_name(bytes: &[u8]) -> Result<(String, usize), Box<dyn std::error::Error>> {
+    let mut chars = Vec::<u16>::new();
+    let mut offset = 0;
+    loop {
+        let c = u16_at(bytes, offset)?;
+        offset += 2;
+        if c == 0 {
+            break;
+        }
+        chars.push(c);
+    }
+    Ok((String::from_utf16(&chars)?, offset))
+}
+
+// Init_Command_Request 和 Init_Command_Ack 中的身份信息
+fn encode_identity(guid: &[u8; 16], name: &str) -> Vec<u8> {
+    let mut payload = guid.to_vec();
+    payload.extend_from_slice(&encode_name(name));
+    payload.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+    payload
+}
+
+fn decode_identity(bytes: &[u8]) -> Result<([u8; 16], String), Box<dyn std::error::Error>> {
+    let guid: [u8; 16] = bytes
+        .get(0..16)
+        .and_then(|b| b.try_into().ok())
+        .ok_or("PTP/IP packet is too short")?;
+    let (name, _) = decode_name(&bytes[16..])?;
+    Ok((guid, name))
+}
+
+// 生成本机的 GUID (进程号和当前时间)
+fn local_guid() -> [u8; 16] {
+    let mut guid = [0u8; 16];
+    guid[0..4].copy_from_slice(&std::process::id().to_le_bytes());
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or(0);
+    guid[4..12].copy_from_slice(&nanos.to_le_bytes());
+    guid
+}
+
+/// Parses `ptpip://host[:port]` into a socket address string.
+pub fn parse_url(url: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let address = url
+        .strip_prefix(URL_SCHEME)
+        .ok_or_else(|| format!("not a PTP/IP address: {}", url))?
+        .trim_end_matches('/');
+    if address.is_empty() {
+        return Err(format!("missing host: {}", url).into());
+    }
+    // 没有端口时使用默认端口，IPv6 地址需要用 [] 括起来
+    let has_port = match address.rfind(']') {
+        Some(bracket) => address[bracket..].contains(':'),
+        None => address.contains(':'),
+    };
+    if has_port {
+        Ok(address.to_string())
+    } else {
+        Ok(format!("{}:{}", address, DEFAULT_PORT))
+    }
+}
+
+/// A PTP/IP connection.
+///
+/// The same type is used on both ends: the initiator sends commands and data-out,
+/// the responder sends data-in and responses.
+pub struct PtpIpConnection {
+    command_stream: TcpStream,
+    // 事件通道必须保持打开，目前没有读取事件
+    event_stream: TcpStream,
+    connection_number: u32,
+    peer_guid: [u8; 16],
+    peer_name: String,
+    // 等待确定数据阶段的命令
+    pending_command: Option<Container>,
+    // 当前事务的操作码，用于组装数据容器
+    operation_code: u16,
+    // 正在分块发送的数据阶段的 transaction id 和剩余的字节数
+    data_out: Option<(u32, u64)>,
+    data_in: Option<DataIn>,
+}
+
+impl PtpIpConnection {
+    // 作为 initiator 连接到设备
+    pub fn connect(address: &str, name: &str) -> Result<PtpIpConnection, Box<dyn std::error::Error>> {
+        let socket_address = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| format!("failed to resolve: {}", address))?;
+
+        let mut command_stream = TcpStream::connect(socket_address)?;
+        command_stream.set_nodelay(true)?;
+        write_packet(&mut command_stream, PACKET_INIT_COMMAND_REQUEST, &encode_identity(&local_guid(), name))?;
+        let ack = read_packet(&mut command_stream)?;
+        match ack.packet_type {
+            PACKET_INIT_COMMAND_ACK => {}
+            PACKET_INIT_FAIL => {
+                return Err(format!("connection refused by {}: reason {}", address, u32_at(&ack.payload, 0)?).into());
+            }
+            packet_type => return Err(format!("unexpected PTP/IP packet: {}", packet_type).into()),
+        }
+        let connection_number = u32_at(&ack.payload, 0)?;
+        let (peer_guid, peer_name) = decode_identity(&ack.payload[4..])?;
+
+        let mut event_stream = TcpStream::connect(socket_address)?;
+        write_packet(&mut event_stream, PACKET_INIT_EVENT_REQUEST, &connection_number.to_le_bytes())?;
+        let ack = read_packet(&mut event_stream)?;
+        if ack.packet_type != PACKET_INIT_EVENT_ACK {
+            return Err(format!("event channel was refused by {}", address).into());
+        }
+
+        Ok(PtpIpConnection {
+            command_stream,
+            event_stream,
+            connection_number,
+            peer_guid,
+            peer_name,
+            pending_command: None,
+            operation_code: 0,
+            data_out: None,
+            data_in: None,
+        })
+    }
+
+    // 作为 responder 接受一个连接
+    pub fn accept(listener: &TcpListener, connection_number: u32, name: &str) -> Result<PtpIpConnection, Box<dyn std::error::Error>> {
+        let (mut command_stream, _) = listener.accept()?;
+        command_stream.set_nodelay(true)?;
+        let request = read_packet(&mut command_stream)?;
+        if request.packet_type != PACKET_INIT_COMMAND_REQUEST {
+            return Err(format!("unexpected PTP/IP packet: {}", request.packet_type).into());
+        }
+        let (peer_guid, peer_name) = decode_identity(&request.payload)?;
+        let mut ack = connection_number.to_le_bytes().to_vec();
+        ack.extend_from_slice(&encode_identity(&local_guid(), name));
+        write_packet(&mut command_stream, PACKET_INIT_COMMAND_ACK, &ack)?;
+
+        let (mut event_stream, _) = listener.accept()?;
+        let request = read_packet(&mut event_stream)?;
+        if request.packet_type != PACKET_INIT_EVENT_REQUEST || u32_at(&request.payload, 0)? != connection_number {
+            write_packet(&mut event_stream, PACKET_INIT_FAIL, &1u32.to_le_bytes())?;
+            return Err("invalid event channel request".into());
+        }
+        write_packet(&mut event_stream, PACKET_INIT_EVENT_ACK, &[])?;
+
+        Ok(PtpIpConnection {
+            command_stream,
+            event_stream,
+            connection_number,
+            peer_guid,
+            peer_name,
+            pending_command: None,
+            operation_code: 0,
+            data_out: None,
+            data_in: None,
+        })
+    }
+
+    pub fn connection_number(&self) -> u32 {
+        self.connection_number
+    }
+
+    pub fn peer_guid(&self) -> &[u8; 16] {
+        &self.peer_guid
+    }
+
+    // 对方的名称
+    pub fn peer_name(&self) -> &str {
+        &self.peer_name
+    }
+
+    fn flush_command(&mut self, data_phase: u32) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(command) = self.pending_command.take() {
+            let mut payload = data_phase.to_le_bytes().to_vec();
+            payload.extend_from_slice(&command.code.to_le_bytes());
+            payload.extend_from_slice(&command.transaction_id.to_le_bytes());
+            payload.extend_from_slice(&command.payload);
+            write_packet(&mut self.command_stream, PACKET_OPERATION_REQUEST, &payload)?;
+            self.operation_code = command.code;
+        }
+        Ok(())
+    }
+
+    // 读取 Start_Data，之后用 receive_data_chunk 读取 Data 和 End_Data 中的数据
+    fn start_data_in(&mut self, start: &Packet) -> Result<Container, Box<dyn std::error::Error>> {
+        let transaction_id = u32_at(&start.payload, 0)?;
+        let total_size = start
+            .payload
+            .get(4..12)
+            .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+            .ok_or("PTP/IP packet is too short")?;
+        self.data_in = Some(DataIn {
+            transaction_id,
+            total_size,
+            received: 0,
+            packet: Vec::new(),
+            offset: 0,
+            ended: false,
+        });
+        Ok(Container::data(self.operation_code, transaction_id, Vec::new()))
+    }
+}
+
+impl Transport for PtpIpConnection {
+    fn send(&mut self, container: &Container) -> Result<(), Box<dyn std::error::Error>> {
+        match container.container_type {
+            ContainerType::Command => {
+                // 要等到下一个容器才能确定是否有数据阶段
+                self.flush_command(DATA_PHASE_NONE_OR_IN)?;
+                self.pending_command = Some(container.clone());
+                Ok(())
+            }
+            ContainerType::Data => {
+                self.begin_send_data(container.code, container.transaction_id, container.payload.len() as u64)?;
+                self.send_data_chunk(&container.payload)
+            }
+            ContainerType::Response => {
+                let mut payload = container.code.to_le_bytes().to_vec();
+                payload.extend_from_slice(&container.transaction_id.to_le_bytes());
+                payload.extend_from_slice(&container.payload);
+                write_packet(&mut self.command_stream, PACKET_OPERATION_RESPONSE, &payload)
+            }
+            ContainerType::Event => {
+                let mut payload = container.code.to_le_bytes().to_vec();
+                payload.extend_from_slice(&container.transaction_id.to_le_bytes());
+                payload.extend_from_slice(&container.payload);
+                write_packet(&mut self.event_stream, PACKET_EVENT, &payload)
+            }
+        }
+    }
+
+    fn receive(&mut self) -> Result<Container, Box<dyn std::error::Error>> {
+        let mut container = self.begin_receive()?;
+        if container.container_type == ContainerType::Data {
+            let mut buffer = vec![0u8; MAX_DATA_CHUNK];
+            loop {
+                let len = self.receive_data_chunk(&mut buffer)?;
+                if len == 0 {
+                    break;
+                }
+                container.payload.extend_from_slice(&buffer[..len]);
+            }
+        }
+        Ok(container)
+    }
+
+    fn begin_send_data(&mut self, _code: u16, transaction_id: u32, size: u64) -> Result<(), Box<dyn std::error::Error>> {
+        self.flush_command(DATA_PHASE_OUT)?;
+        let mut start = transaction_id.to_le_bytes().to_vec();
+        start.extend_from_slice(&size.to_le_bytes());
+        write_packet(&mut self.command_stream, PACKET_START_DATA, &start)?;
+        if size == 0 {
+            write_packet(&mut self.command_stream, PACKET_END_DATA, &transaction_id.to_le_bytes())?;
+        } else {
+            self.data_out = Some((transaction_id, size));
+        }
+        Ok(())
+    }
+
+    fn send_data_chunk(&mut self, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let (transaction_id, remaining) = self.data_out.ok_or("no data phase in progress")?;
+        let remaining = remaining
+            .checked_sub(data.len() as u64)
+            .ok_or("more data than announced")?;
+        // 最后一块数据放在 End_Data 中
+        let mut chunks = data.chunks(MAX_DATA_CHUNK).peekable();
+        while let Some(chunk) = chunks.next() {
+            let packet_type = if remaining == 0 && chunks.peek().is_none() { PACKET_END_DATA } else { PACKET_DATA };
+            let mut payload = transaction_id.to_le_bytes().to_vec();
+            payload.extend_from_slice(chunk);
+            write_packet(&mut self.command_stream, packet_type, &payload)?;
+        }
+        self.data_out = if remaining == 0 { None } else { Some((transaction_id, remaining)) };
+        Ok(())
+    }
+
+    fn begin_receive(&mut self) -> Result<Container, Box<dyn std::error::Error>> {
+        self.flush_command(DATA_PHASE_NONE_OR_IN)?;
+        let packet = read_packet(&mut self.command_stream)?;
+        match packet.packet_type {
+            PACKET_OPERATION_REQUEST => {
+                // DataPhaseInfo 由 responder 根据操作码自行判断
+                let code = u16_at(&packet.payload, 4)?;
+                let transaction_id = u32_at(&packet.payload, 6)?;
+                self.operation_code = code;
+                Ok(Container {
+                    container_type: ContainerType::Command,
+                    code,
+                    transaction_id,
+                    payload: packet.payload[10..].to_vec(),
+                })
+            }
+            PACKET_OPERATION_RESPONSE => Ok(Container {
+                container_type: ContainerType::Response,
+                code: u16_at(&packet.payload, 0)?,
+                transaction_id: u32_at(&packet.payload, 2)?,
+                payload: packet.payload[6..].to_vec(),
+            }),
+            PACKET_START_DATA => self.start_data_in(&packet),
+            PACKET_CANCEL => Err("the transaction was cancelled".into()),
+            packet_type => Err(format!("unexpected PTP/IP packet: {}", packet_type).into()),
+        }
+    }
+
+    fn receive_data_chunk(&mut self, buf: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        let data_in = self.data_in.as_mut().ok_or("no data phase in progress")?;
+        while data_in.offset == data_in.packet.len() && !data_in.ended {
+            let packet = read_packet(&mut self.command_stream)?;
+            match packet.packet_type {
+                PACKET_DATA | PACKET_END_DATA => {
+                    if u32_at(&packet.payload, 0)? != data_in.transaction_id {
+                        return Err("unexpected transaction id in data packet".into());
+                    }
+                    data_in.ended = packet.packet_type == PACKET_END_DATA;
+                    data_in.received += (packet.payload.len() - 4) as u64;
+                    data_in.packet = packet.payload;
+                    data_in.offset = 4;
+                }
+                PACKET_CANCEL => return Err("the transaction was cancelled".into()),
+                packet_type => return Err(format!("unexpected PTP/IP packet: {}", packet_type).into()),
+            }
+        }
+
+        let len = std::cmp::min(buf.len(), data_in.packet.len() - data_in.offset);
+        buf[..len].copy_from_slice(&data_in.packet[data_in.offset..data_in.offset + len]);
+        data_in.offset += len;
+        if len == 0 {
+            let (received, total_size) = (data_in.received, data_in.total_size);
+            self.data_in = None;
+            // 0xFFFFFFFFFFFFFFFF 表示长度未知
+            if total_size != u64::MAX && total_size != received {
+                return Err(format!("data size mismatch: {} bytes received, {} bytes announced", received, total_size).into());
+            }
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::backend::PortableDeviceBackend;
+    use crate::copy::copy;
+    use crate::mtp::codes::*;
+    use crate::mtp::dataset::{DataWriter, MtpDeviceInfo, ObjectInfo, StorageInfo};
+    use crate::mtp::manager::MtpBackend;
+    use crate::copy_operate::CopyOptions;
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(parse_url("ptpip://192.168.1.10").unwrap(), "192.168.1.10:15740");
+        assert_eq!(parse_url("ptpip://camera.local:1234/").unwrap(), "camera.local:1234");
+        assert_eq!(parse_url("ptpip://[::1]").unwrap(), "[::1]:15740");
+        assert_eq!(parse_url("ptpip://[::1]:99").unwrap(), "[::1]:99");
+        assert!(parse_url("ptpip://").is_err());
+        assert!(parse_url("http://host").is_err());
+    }
+
+    #[test]
+    fn test_packet_framing() {
+        // 抓包: Operation_Request, OpenSession(1), transaction id 0, 无数据阶段
+        let bytes: Vec<u8> = vec![
+            0x16, 0, 0, 0, 0x06, 0, 0, 0,
+            0x01, 0, 0, 0, 0x02, 0x10, 0, 0, 0, 0, 0x01, 0, 0, 0,
+        ];
+        let packet = read_packet(&mut bytes.as_slice()).unwrap();
+        assert_eq!(packet.packet_type, PACKET_OPERATION_REQUEST);
+        assert_eq!(packet.payload.len(), 14);
+        let mut written = Vec::<u8>::new();
+        write_packet(&mut written, packet.packet_type, &packet.payload).unwrap();
+        assert_eq!(written, bytes);
+
+        assert!(read_packet(&mut [4u8, 0, 0, 0, 6, 0, 0, 0].as_slice()).is_err());
+        assert!(read_packet(&mut [0x10u8, 0, 0, 0, 6, 0].as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_name() {
+        let bytes = encode_name("相机");
+        assert_eq!(bytes.len(), 6);
+        assert_eq!(decode_name(&bytes).unwrap(), ("相机".to_string(), 6));
+        assert!(decode_name(&[b'a', 0]).is_err());
+    }
+
+    // 回环测试用的 responder，内存中保存一个存储的文件
+    fn serve_loopback(listener: TcpListener) {
+        let mut files: HashMap<u32, (String, Vec<u8>)> = HashMap::new();
+        files.insert(1, ("IMG_0001.JPG".to_string(), b"hello".to_vec()));
+        let mut connection_number = 0;
+        loop {
+            connection_number += 1;
+            let mut connection = match PtpIpConnection::accept(&listener, connection_number, "Loopback Camera") {
+                Ok(connection) => connection,
+                Err(_) => return,
+            };
+            assert_eq!(connection.peer_name(), "mtp_util");
+            let mut pending_info: Option<ObjectInfo> = None;
+            loop {
+                let command = match connection.receive() {
+                    Ok(command) => command,
+                    Err(_) => break,
+                };
+                let tid = command.transaction_id;
+                let params = command.params().unwrap();
+                let mut response_params = Vec::<u32>::new();
+                let data_in: Option<Vec<u8>> = match command.code {
+                    OPERATION_OPEN_SESSION | OPERATION_CLOSE_SESSION => None,
+                    OPERATION_GET_DEVICE_INFO => Some(MtpDeviceInfo {
+                        model: "Loopback Camera".to_string(),
+                        ..Default::default()
+                    }.encode()),
+                    OPERATION_GET_STORAGE_IDS => {
+                        let mut writer = DataWriter::new();
+                        writer.write_u32_array(&[0x00010001]);
+                        Some(writer.into_bytes())
+                    }
+                    OPERATION_GET_STORAGE_INFO => Some(StorageInfo {
+                        storage_description: "Card".to_string(),
+                        ..Default::default()
+                    }.encode()),
+                    OPERATION_GET_OBJECT_HANDLES => {
+                        let mut handles: Vec<u32> = if params[2] == PARENT_ROOT { files.keys().copied().collect() } else { vec![] };
+                        handles.sort();
+                        let mut writer = DataWriter::new();
+                        writer.write_u32_array(&handles);
+                        Some(writer.into_bytes())
+                    }
+                    OPERATION_GET_OBJECT_INFO => {
+                        let (name, data) = &files[&params[0]];
+                        Some(ObjectInfo {
+                            storage_id: 0x00010001,
+                            object_format: FORMAT_UNDEFINED,
+                            object_compressed_size: data.len() as u32,
+                            parent_object: 0,
+                            filename: name.clone(),
+                            ..Default::default()
+                        }.encode())
+                    }
+                    OPERATION_GET_OBJECT => Some(files[&params[0]].1.clone()),
+                    OPERATION_SEND_OBJECT_INFO => {
+                        let data = connection.receive().unwrap();
+                        assert_eq!(data.container_type, ContainerType::Data);
+                        pending_info = Some(ObjectInfo::decode(&data.payload).unwrap());
+                        response_params = vec![0x00010001, PARENT_ROOT, files.len() as u32 + 1];
+                        None
+                    }
+                    OPERATION_SEND_OBJECT => {
+                        let data = connection.receive().unwrap();
+                        let info = pending_info.take().unwrap();
+                        files.insert(files.len() as u32 + 1, (info.filename, data.payload));
+                        None
+                    }
+                    _ => {
+                        connection.send(&Container::response(RESPONSE_OPERATION_NOT_SUPPORTED, tid, &[]).unwrap()).unwrap();
+                        continue;
+                    }
+                };
+                if let Some(data) = data_in {
+                    connection.send(&Container::data(command.code, tid, data)).unwrap();
+                }
+                connection.send(&Container::response(RESPONSE_OK, tid, &response_params).unwrap()).unwrap();
+            }
+        }
+    }
+
+    fn loopback_backend() -> MtpBackend {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || serve_loopback(listener));
+        let mut backend = MtpBackend::new();
+        backend.add_connector(Box::new(move || Ok(Box::new(PtpIpConnection::connect(&address, "mtp_util")?))));
+        backend
+    }
+
+    #[test]
+    fn test_large_data_phase() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let data: Vec<u8> = (0..MAX_DATA_CHUNK * 2 + 10).map(|i| i as u8).collect();
+        let expected = data.clone();
+        let responder = std::thread::spawn(move || {
+            let mut connection = PtpIpConnection::accept(&listener, 7, "Responder").unwrap();
+            let command = connection.receive().unwrap();
+            assert_eq!(command.code, OPERATION_SEND_OBJECT);
+            let received = connection.receive().unwrap();
+            assert_eq!(received.code, OPERATION_SEND_OBJECT);
+            assert_eq!(received.payload, expected);
+            connection.send(&Container::response(RESPONSE_OK, command.transaction_id, &[]).unwrap()).unwrap();
+
+            let command = connection.receive().unwrap();
+            assert_eq!(command.code, OPERATION_GET_OBJECT);
+            connection.send(&Container::data(OPERATION_GET_OBJECT, command.transaction_id, expected)).unwrap();
+            connection.send(&Container::response(RESPONSE_OK, command.transaction_id, &[]).unwrap()).unwrap();
+        });
+
+        let mut connection = PtpIpConnection::connect(&address, "mtp_util").unwrap();
+        assert_eq!(connection.connection_number(), 7);
+        assert_eq!(connection.peer_name(), "Responder");
+
+        // 分块发送，块的大小与 Data 包的大小无关
+        connection.send(&Container::command(OPERATION_SEND_OBJECT, 3, &[]).unwrap()).unwrap();
+        connection.begin_send_data(OPERATION_SEND_OBJECT, 3, data.len() as u64).unwrap();
+        connection.send_data_chunk(&data[..10]).unwrap();
+        connection.send_data_chunk(&data[10..MAX_DATA_CHUNK * 2]).unwrap();
+        connection.send_data_chunk(&data[MAX_DATA_CHUNK * 2..]).unwrap();
+        assert!(connection.send_data_chunk(&data[..1]).is_err());
+        let response = connection.receive().unwrap();
+        assert_eq!(response.container_type, ContainerType::Response);
+        assert_eq!(response.code, RESPONSE_OK);
+
+        // 分块接收
+        connection.send(&Container::command(OPERATION_GET_OBJECT, 4, &[1]).unwrap()).unwrap();
+        let container = connection.begin_receive().unwrap();
+        assert_eq!(container.container_type, ContainerType::Data);
+        assert_eq!(container.code, OPERATION_GET_OBJECT);
+        let mut received = Vec::<u8>::new();
+        let mut buf = vec![0u8; 100000];
+        loop {
+            let len = connection.receive_data_chunk(&mut buf).unwrap();
+            if len == 0 {
+                break;
+            }
+            received.extend_from_slice(&buf[..len]);
+        }
+        assert_eq!(received, data);
+        assert_eq!(connection.receive().unwrap().code, RESPONSE_OK);
+        responder.join().unwrap();
+    }
+
+    #[test]
+    fn test_loopback_list_and_copy() -> Result<(), Box<dyn std::error::Error>> {
+        let backend = loopback_backend();
+        let devices = backend.list_devices()?;
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].name, "Loopback Camera");
+
+        // device -> local
+        let tempdir = tempfile::tempdir()?;
+        copy(&backend, "Loopback Camera:Card:/IMG_0001.JPG", tempdir.path().to_str().unwrap(), &CopyOptions::default())?;
+        assert_eq!(std::fs::read(tempdir.path().join("IMG_0001.JPG"))?, b"hello");
+
+        // local -> device
+        let src = tempdir.path().join("note.txt");
+        std::fs::write(&src, "note")?;
+        copy(&backend, src.to_str().unwrap(), "Loopback Camera:Card:/note.txt", &CopyOptions::default())?;
+
+        let device = backend.open_device(&devices[0])?;
+        let mut iter = device.get_object_iterator(&crate::backend::ContentObject::new("s00010001"))?;
+        let mut names = Vec::<String>::new();
+        while let Some(object) = iter.next()? {
+            names.push(device.get_object_info(object)?.name);
+        }
+        assert_eq!(names, vec!["IMG_0001.JPG", "note.txt"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,675 @@
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use crate::common::timestamp::{TimeZone, Timestamp};
+use crate::copy_operate::file_info::FileInfo;
+use crate::copy_operate::folder_operate::FolderOperate;
+use crate::copy_operate::local_folder_imp::LocalFolder;
+use crate::mtp::codes::*;
+use crate::mtp::container::{Container, ContainerType};
+use crate::mtp::dataset::{DataReader, DataWriter, MtpDeviceInfo, ObjectInfo, StorageInfo};
+use crate::mtp::ptpip::PtpIpConnection;
+use crate::mtp::transport::Transport;
+
+// MTP responder，把本地文件夹作为设备的一个存储提供给 MTP 客户端
+// 文件系统的操作使用 LocalFolder 和 FileInfo::from_metadata
+
+pub const STORAGE_ID: u32 = 0x00010001;
+
+const OPERATIONS_SUPPORTED: [u16; 14] = [
+    OPERATION_GET_DEVICE_INFO,
+    OPERATION_OPEN_SESSION,
+    OPERATION_CLOSE_SESSION,
+    OPERATION_GET_STORAGE_IDS,
+    OPERATION_GET_STORAGE_INFO,
+    OPERATION_GET_OBJECT_HANDLES,
+    OPERATION_GET_OBJECT_INFO,
+    OPERATION_GET_OBJECT,
+    OPERATION_DELETE_OBJECT,
+    OPERATION_SEND_OBJECT_INFO,
+    OPERATION_SEND_OBJECT,
+    OPERATION_MOVE_OBJECT,
+    OPERATION_GET_OBJECT_PROP_VALUE,
+    OPERATION_SET_OBJECT_PROP_VALUE,
+];
+
+// 操作的结果: 响应码，响应参数，返回给客户端的数据
+struct OperationResult {
+    code: u16,
+    params: Vec<u32>,
+    data: Option<Vec<u8>>,
+}
+
+impl OperationResult {
+    fn ok() -> OperationResult {
+        OperationResult::with_code(RESPONSE_OK)
+    }
+
+    fn with_code(code: u16) -> OperationResult {
+        OperationResult {
+            code,
+            params: Vec::new(),
+            data: None,
+        }
+    }
+
+    fn with_data(data: Vec<u8>) -> OperationResult {
+        OperationResult {
+            code: RESPONSE_OK,
+            params: Vec::new(),
+            data: Some(data),
+        }
+    }
+}
+
+// SendObjectInfo 之后等待 SendObject 的文件
+struct PendingObject {
+    handle: u32,
+    size: u64,
+    created: Option<Timestamp>,
+    modified: Option<Timestamp>,
+}
+
+// handle 和相对于根目录的路径的对应关系，所有连接共用
+#[derive(Default)]
+struct HandleTable {
+    // handle - 1 为下标
+    paths: Vec<PathBuf>,
+    handles: HashMap<PathBuf, u32>,
+}
+
+impl HandleTable {
+    // from 和它下面的对象移动到 to 之后更新路径，handle 不变
+    fn rename(&mut self, from: &Path, to: &Path) {
+        for (index, path) in self.paths.iter_mut().enumerate() {
+            let new_path = match path.strip_prefix(from) {
+                Ok(rest) if rest == Path::new("") => to.to_path_buf(),
+                Ok(rest) => to.join(rest),
+                Err(_) => continue,
+            };
+            self.handles.remove(path);
+            self.handles.insert(new_path.clone(), index as u32 + 1);
+            *path = new_path;
+        }
+    }
+}
+
+pub struct MtpResponder {
+    root: PathBuf,
+    name: String,
+    storage_name: String,
+    handle_table: Arc<Mutex<HandleTable>>,
+    session_id: Option<u32>,
+    pending_object: Option<PendingObject>,
+}
+
+impl MtpResponder {
+    pub fn new(root: &Path, name: &str) -> Result<MtpResponder, Box<dyn std::error::Error>> {
+        if !root.is_dir() {
+            return Err(format!("not a folder: {}", root.display()).into());
+        }
+        let storage_name = root
+            .canonicalize()?
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("Storage")
+            .to_string();
+        Ok(MtpResponder {
+            root: root.to_path_buf(),
+            name: name.to_string(),
+            storage_name,
+            handle_table: Arc::new(Mutex::new(HandleTable::default())),
+            session_id: None,
+            pending_object: None,
+        })
+    }
+
+    // 为新的连接创建 responder，共用 handle
+    pub fn new_connection(&self) -> MtpResponder {
+        MtpResponder {
+            root: self.root.clone(),
+            name: self.name.clone(),
+            storage_name: self.storage_name.clone(),
+            handle_table: self.handle_table.clone(),
+            session_id: None,
+            pending_object: None,
+        }
+    }
+
+    // 处理一个连接上的所有事务，直到连接断开
+    pub fn serve_connection(&mut self, transport: &mut dyn Transport) -> Result<(), Box<dyn std::error::Error>> {
+        let result = self.serve_transactions(transport);
+        self.session_id = None;
+        self.pending_object = None;
+        result
+    }
+
+    fn serve_transactions(&mut self, transport: &mut dyn Transport) -> Result<(), Box<dyn std::error::Error>> {
+        loop {
+            let command = match transport.receive() {
+                Ok(command) => command,
+                // 连接断开
+                Err(err) => {
+                    log::debug!("{}", err);
+                    return Ok(());
+                }
+            };
+            if command.container_type != ContainerType::Command {
+                return Err(format!("unexpected container: {:?}", command.container_type).into());
+            }
+            // 主机到设备的数据阶段
+            let data_out = match command.code {
+                OPERATION_SEND_OBJECT_INFO | OPERATION_SEND_OBJECT | OPERATION_SET_OBJECT_PROP_VALUE => {
+                    let data = transport.receive()?;
+                    if data.container_type != ContainerType::Data {
+                        return Err(format!("unexpected container: {:?}", data.container_type).into());
+                    }
+                    Some(data.payload)
+                }
+                _ => None,
+            };
+
+            let result = match command.params() {
+                Ok(params) => self.handle_operation(command.code, &params, data_out),
+                Err(_) => OperationResult::with_code(RESPONSE_INVALID_PARAMETER),
+            };
+            if result.code != RESPONSE_OK {
+                log::debug!("operation 0x{:04X} failed: {}", command.code, response_code_name(result.code));
+            }
+            if let Some(data) = result.data {
+                transport.send(&Container::data(command.code, command.transaction_id, data))?;
+            }
+            transport.send(&Container::response(result.code, command.transaction_id, &result.params)?)?;
+        }
+    }
+
+    fn handle_operation(&mut self, operation: u16, params: &[u32], data: Option<Vec<u8>>) -> OperationResult {
+        let param = |index: usize| params.get(index).copied().unwrap_or(0);
+        match operation {
+            OPERATION_GET_DEVICE_INFO => return OperationResult::with_data(self.device_info().encode()),
+            OPERATION_OPEN_SESSION => {
+                return match self.session_id {
+                    Some(session_id) => OperationResult {
+                        code: RESPONSE_SESSION_ALREADY_OPEN,
+                        params: vec![session_id],
+                        data: None,
+                    },
+                    None if param(0) == 0 => OperationResult::with_code(RESPONSE_INVALID_PARAMETER),
+                    None => {
+                        self.session_id = Some(param(0));
+                        OperationResult::ok()
+                    }
+                };
+            }
+            _ => {}
+        }
+        if self.session_id.is_none() {
+            return OperationResult::with_code(RESPONSE_SESSION_NOT_OPEN);
+        }
+
+        let result = match operation {
+            OPERATION_CLOSE_SESSION => {
+                self.session_id = None;
+                Ok(OperationResult::ok())
+            }
+            OPERATION_GET_STORAGE_IDS => {
+                let mut writer = DataWriter::new();
+                writer.write_u32_array(&[STORAGE_ID]);
+                Ok(OperationResult::with_data(writer.into_bytes()))
+            }
+            OPERATION_GET_STORAGE_INFO => self.check_storage(param(0)).map(|_| OperationResult::with_data(self.storage_info().encode())),
+            OPERATION_GET_OBJECT_HANDLES => self.get_object_handles(param(0), param(1), param(2)),
+            OPERATION_GET_OBJECT_INFO => self.get_object_info(param(0)),
+            OPERATION_GET_OBJECT => self.get_object(param(0)),
+            OPERATION_DELETE_OBJECT => self.delete_object(param(0)),
+            OPERATION_SEND_OBJECT_INFO => self.send_object_info(param(0), param(1), &data.unwrap_or_default()),
+            OPERATION_SEND_OBJECT => self.send_object(data.unwrap_or_default()),
+            OPERATION_MOVE_OBJECT => self.move_object(param(0), param(1), param(2)),
+            OPERATION_GET_OBJECT_PROP_VALUE => self.get_object_prop_value(param(0), param(1)),
+            OPERATION_SET_OBJECT_PROP_VALUE => self.set_object_prop_value(param(0), param(1), &data.unwrap_or_default()),
+            _ => Err(RESPONSE_OPERATION_NOT_SUPPORTED),
+        };
+        result.unwrap_or_else(OperationResult::with_code)
+    }
+
+    fn device_info(&self) -> MtpDeviceInfo {
+        MtpDeviceInfo {
+            standard_version: 100,
+            vendor_extension_id: 6,
+            vendor_extension_version: 100,
+            vendor_extension_desc: "microsoft.com: 1.0;".to_string(),
+            operations_supported: OPERATIONS_SUPPORTED.to_vec(),
+            manufacturer: "mtp_util".to_string(),
+            model: self.name.clone(),
+            device_version: env!("CARGO_PKG_VERSION").to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn storage_info(&self) -> StorageInfo {
+        StorageInfo {
+            storage_type: STORAGE_TYPE_FIXED_RAM,
+            filesystem_type: FILESYSTEM_GENERIC_HIERARCHICAL,
+            access_capability: ACCESS_READ_WRITE,
+            free_space_in_objects: 0xFFFFFFFF,
+            storage_description: self.storage_name.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn check_storage(&self, storage_id: u32) -> Result<(), u16> {
+        if storage_id == STORAGE_ID || storage_id == STORAGE_ALL {
+            Ok(())
+        } else {
+            Err(RESPONSE_INVALID_STORAGE_ID)
+        }
+    }
+
+    // 分配或查找 handle，同一个路径在 responder 的生命周期内 handle 不变
+    fn handle_of(&self, relative_path: &Path) -> u32 {
+        let mut table = self.handle_table.lock().unwrap();
+        if let Some(handle) = table.handles.get(relative_path) {
+            return *handle;
+        }
+        table.paths.push(relative_path.to_path_buf());
+        let handle = table.paths.len() as u32;
+        table.handles.insert(relative_path.to_path_buf(), handle);
+        handle
+    }
+
+    // handle 对应的相对路径，已经不存在的对象返回 Invalid_ObjectHandle
+    fn path_of(&self, handle: u32) -> Result<PathBuf, u16> {
+        let relative_path = match handle {
+            0 | OBJECT_ALL => None,
+            handle => self.handle_table.lock().unwrap().paths.get(handle as usize - 1).cloned(),
+        }
+        .ok_or(RESPONSE_INVALID_OBJECT_HANDLE)?;
+        if self.root.join(&relative_path).symlink_metadata().is_err() {
+            return Err(RESPONSE_INVALID_OBJECT_HANDLE);
+        }
+        Ok(relative_path)
+    }
+
+    // parent 为 0 或 PARENT_ROOT 时为根目录
+    fn folder_of(&self, parent: u32) -> Result<PathBuf, u16> {
+        if parent == 0 || parent == PARENT_ROOT {
+            return Ok(PathBuf::new());
+        }
+        let relative_path = self.path_of(parent).map_err(|_| RESPONSE_INVALID_PARENT_OBJECT)?;
+        if !self.root.join(&relative_path).is_dir() {
+            return Err(RESPONSE_INVALID_PARENT_OBJECT);
+        }
+        Ok(relative_path)
+    }
+
+    fn file_info(&self, relative_path: &Path) -> Result<FileInfo, u16> {
+        let name = relative_path.file_name().and_then(|name| name.to_str()).ok_or(RESPONSE_INVALID_OBJECT_HANDLE)?;
+        let metadata = self.root.join(relative_path).metadata().map_err(io_error_code)?;
+        FileInfo::from_metadata(&metadata, name).map_err(|_| RESPONSE_GENERAL_ERROR)
+    }
+
+    fn get_object_handles(&mut self, storage_id: u32, format: u32, parent: u32) -> Result<OperationResult, u16> {
+        self.check_storage(storage_id)?;
+        let folder = self.folder_of(parent)?;
+        let mut entries = Vec::<(String, bool)>::new();
+        for entry in self.root.join(&folder).read_dir().map_err(io_error_code)? {
+            let entry = entry.map_err(io_error_code)?;
+            let name = match entry.file_name().to_str() {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let file_info = match entry.metadata().ok().and_then(|metadata| FileInfo::from_metadata(&metadata, &name).ok()) {
+                Some(file_info) => file_info,
+                None => continue,
+            };
+            // MTP 没有隐藏和系统标志，不提供隐藏文件和系统文件
+            if file_info.is_hidden || file_info.is_system {
+                continue;
+            }
+            entries.push((name, file_info.is_folder));
+        }
+        entries.sort();
+
+        let mut handles = Vec::<u32>::new();
+        for (name, is_folder) in entries {
+            let object_format = if is_folder { FORMAT_ASSOCIATION } else { FORMAT_UNDEFINED };
+            if format != FORMAT_ALL && format != object_format as u32 {
+                continue;
+            }
+            handles.push(self.handle_of(&folder.join(name)));
+        }
+        let mut writer = DataWriter::new();
+        writer.write_u32_array(&handles);
+        Ok(OperationResult::with_data(writer.into_bytes()))
+    }
+
+    fn get_object_info(&mut self, handle: u32) -> Result<OperationResult, u16> {
+        let relative_path = self.path_of(handle)?;
+        let file_info = self.file_info(&relative_path)?;
+        let parent_object = match relative_path.parent() {
+            Some(parent) if parent != Path::new("") => self.handle_of(parent),
+            _ => 0,
+        };
+        let object_info = ObjectInfo {
+            storage_id: STORAGE_ID,
+            object_format: if file_info.is_folder { FORMAT_ASSOCIATION } else { FORMAT_UNDEFINED },
+            protection_status: if file_info.can_delete { PROTECTION_NONE } else { PROTECTION_READ_ONLY },
+            object_compressed_size: u32::try_from(file_info.data_size).unwrap_or(OBJECT_SIZE_LARGE),
+            parent_object,
+            association_type: if file_info.is_folder { ASSOCIATION_GENERIC_FOLDER } else { 0 },
+            filename: file_info.name,
+            date_created: file_info.time_created.map(|time| time.to_mtp(TimeZone::UTC)).unwrap_or_default(),
+            date_modified: file_info.time_modified.map(|time| time.to_mtp(TimeZone::UTC)).unwrap_or_default(),
+            ..Default::default()
+        };
+        Ok(OperationResult::with_data(object_info.encode()))
+    }
+
+    fn get_object(&mut self, handle: u32) -> Result<OperationResult, u16> {
+        let path = self.root.join(self.path_of(handle)?);
+        if path.is_dir() {
+            return Err(RESPONSE_INVALID_OBJECT_HANDLE);
+        }
+        let data = std::fs::read(path).map_err(io_error_code)?;
+        Ok(OperationResult::with_data(data))
+    }
+
+    fn delete_object(&mut self, handle: u32) -> Result<OperationResult, u16> {
+        if handle == OBJECT_ALL {
+            return Err(RESPONSE_PARAMETER_NOT_SUPPORTED);
+        }
+        let relative_path = self.path_of(handle)?;
+        if !self.file_info(&relative_path)?.can_delete {
+            return Err(RESPONSE_OBJECT_WRITE_PROTECTED);
+        }
+        let name = relative_path.file_name().and_then(|name| name.to_str()).ok_or(RESPONSE_INVALID_OBJECT_HANDLE)?;
+        let mut folder = LocalFolder::new(self.root.join(relative_path.parent().unwrap_or(Path::new(""))));
+        folder.delete_file_or_folder(name).map_err(boxed_error_code)?;
+        Ok(OperationResult::ok())
+    }
+
+    fn send_object_info(&mut self, storage_id: u32, parent: u32, data: &[u8]) -> Result<OperationResult, u16> {
+        self.pending_object = None;
+        if storage_id != STORAGE_ID && storage_id != 0 {
+            return Err(RESPONSE_INVALID_STORAGE_ID);
+        }
+        let folder = self.folder_of(parent)?;
+        let object_info = ObjectInfo::decode(data).map_err(|_| RESPONSE_INVALID_PARAMETER)?;
+        let name = object_info.filename.as_str();
+        check_name(name)?;
+
+        let relative_path = folder.join(name);
+        if object_info.is_folder() {
+            let mut parent_folder = LocalFolder::new(self.root.join(&folder));
+            parent_folder
+                .open_or_create_folder(name, |_| {}, |_| {})
+                .map_err(boxed_error_code)?;
+        } else if self.root.join(&relative_path).is_dir() {
+            return Err(RESPONSE_INVALID_PARAMETER);
+        }
+        let handle = self.handle_of(&relative_path);
+        if !object_info.is_folder() {
+            self.pending_object = Some(PendingObject {
+                handle,
+                size: object_info.object_compressed_size as u64,
+                // 没有时区的日期按 UTC 处理
+                created: Timestamp::parse_mtp(&object_info.date_created, TimeZone::UTC),
+                modified: Timestamp::parse_mtp(&object_info.date_modified, TimeZone::UTC),
+            });
+        }
+        Ok(OperationResult {
+            code: RESPONSE_OK,
+            params: vec![STORAGE_ID, if folder == Path::new("") { PARENT_ROOT } else { parent }, handle],
+            data: None,
+        })
+    }
+
+    fn send_object(&mut self, data: Vec<u8>) -> Result<OperationResult, u16> {
+        let pending_object = self.pending_object.take().ok_or(RESPONSE_NO_VALID_OBJECT_INFO)?;
+        // 不小于 4 GiB 的对象没有准确的大小
+        if pending_object.size != OBJECT_SIZE_LARGE as u64 && pending_object.size != data.len() as u64 {
+            return Err(RESPONSE_INCOMPLETE_TRANSFER);
+        }
+        let relative_path = self.handle_table.lock().unwrap().paths[pending_object.handle as usize - 1].clone();
+        let name = relative_path.file_name().and_then(|name| name.to_str()).ok_or(RESPONSE_GENERAL_ERROR)?;
+        let mut folder = LocalFolder::new(self.root.join(relative_path.parent().unwrap_or(Path::new(""))));
+        let size = data.len() as u64;
+        folder
+            .create_file(name, &mut data.as_slice(), size, &pending_object.created, &pending_object.modified)
+            .map_err(boxed_error_code)?;
+        Ok(OperationResult::ok())
+    }
+
+    // 只有一个存储，storage id 必须是这个存储
+    fn move_object(&mut self, handle: u32, storage_id: u32, parent: u32) -> Result<OperationResult, u16> {
+        if storage_id != STORAGE_ID {
+            return Err(RESPONSE_INVALID_STORAGE_ID);
+        }
+        let relative_path = self.path_of(handle)?;
+        let folder = self.folder_of(parent)?;
+        if folder.starts_with(&relative_path) {
+            return Err(RESPONSE_INVALID_PARENT_OBJECT);
+        }
+        let name = relative_path.file_name().ok_or(RESPONSE_INVALID_OBJECT_HANDLE)?;
+        self.rename_object(&relative_path, &folder.join(name))?;
+        Ok(OperationResult::ok())
+    }
+
+    // 只支持读取对象的大小，ObjectInfo 中的大小只有 32 位
+    fn get_object_prop_value(&mut self, handle: u32, property: u32) -> Result<OperationResult, u16> {
+        if property != PROPERTY_OBJECT_SIZE as u32 {
+            return Err(RESPONSE_INVALID_OBJECT_PROP_CODE);
+        }
+        let file_info = self.file_info(&self.path_of(handle)?)?;
+        let mut writer = DataWriter::new();
+        writer.write_u64(file_info.data_size);
+        Ok(OperationResult::with_data(writer.into_bytes()))
+    }
+
+    // 只支持修改文件名
+    fn set_object_prop_value(&mut self, handle: u32, property: u32, data: &[u8]) -> Result<OperationResult, u16> {
+        if property != PROPERTY_OBJECT_FILE_NAME as u32 {
+            return Err(RESPONSE_INVALID_OBJECT_PROP_CODE);
+        }
+        let relative_path = self.path_of(handle)?;
+        let name = DataReader::new(data).read_string().map_err(|_| RESPONSE_INVALID_PARAMETER)?;
+        check_name(&name)?;
+        self.rename_object(&relative_path, &relative_path.with_file_name(name))?;
+        Ok(OperationResult::ok())
+    }
+
+    // 重命名或移动对象，不覆盖已有的对象
+    fn rename_object(&mut self, from: &Path, to: &Path) -> Result<(), u16> {
+        if !self.file_info(from)?.can_delete {
+            return Err(RESPONSE_OBJECT_WRITE_PROTECTED);
+        }
+        if self.root.join(to).symlink_metadata().is_ok() {
+            return Err(RESPONSE_GENERAL_ERROR);
+        }
+        std::fs::rename(self.root.join(from), self.root.join(to)).map_err(io_error_code)?;
+        self.handle_table.lock().unwrap().rename(from, to);
+        Ok(())
+    }
+}
+
+fn check_name(name: &str) -> Result<(), u16> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(RESPONSE_INVALID_PARAMETER);
+    }
+    Ok(())
+}
+
+fn io_error_code(err: std::io::Error) -> u16 {
+    match err.kind() {
+        std::io::ErrorKind::NotFound => RESPONSE_INVALID_OBJECT_HANDLE,
+        std::io::ErrorKind::PermissionDenied => RESPONSE_ACCESS_DENIED,
+        _ => RESPONSE_GENERAL_ERROR,
+    }
+}
+
+fn boxed_error_code(err: Box<dyn std::error::Error>) -> u16 {
+    log::debug!("{}", err);
+    match err.downcast::<std::io::Error>() {
+        Ok(err) => io_error_code(*err),
+        Err(_) => RESPONSE_GENERAL_ERROR,
+    }
+}
+
+// 在 listener 上接受 PTP/IP 连接，每个连接在单独的线程中提供服务
+pub fn serve(listener: TcpListener, root: &Path, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let responder = MtpResponder::new(root, name)?;
+    let mut connection_number = 0u32;
+    loop {
+        connection_number = connection_number.wrapping_add(1).max(1);
+        let mut connection = match PtpIpConnection::accept(&listener, connection_number, name) {
+            Ok(connection) => connection,
+            Err(err) => {
+                log::warn!("failed to accept a connection: {}", err);
+                continue;
+            }
+        };
+        log::info!("connected: {}", connection.peer_name());
+        let mut connection_responder = responder.new_connection();
+        std::thread::spawn(move || {
+            if let Err(err) = connection_responder.serve_connection(&mut connection) {
+                log::warn!("connection closed: {}", err);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{ContentObject, PortableDeviceBackend};
+    use crate::copy::copy;
+    use crate::find::find_file_or_folder;
+    use crate::mtp::manager::MtpBackend;
+    use crate::mtp::transport::ScriptedTransport;
+    use crate::path::DeviceStoragePath;
+    use crate::copy_operate::CopyOptions;
+
+    fn start_responder(root: &Path) -> MtpBackend {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let root = root.to_path_buf();
+        std::thread::spawn(move || {
+            let _ = serve(listener, &root, "Test Device");
+        });
+        let mut backend = MtpBackend::new();
+        backend.add_connector(Box::new(move || Ok(Box::new(PtpIpConnection::connect(&address, "mtp_util")?))));
+        backend
+    }
+
+    #[test]
+    fn test_session_required() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let mut responder = MtpResponder::new(tempdir.path(), "Test Device").unwrap();
+        let transport = ScriptedTransport::new();
+        transport
+            .reply(&Container::command(OPERATION_GET_STORAGE_IDS, 1, &[]).unwrap())
+            .expect(&Container::response(RESPONSE_SESSION_NOT_OPEN, 1, &[]).unwrap())
+            .reply(&Container::command(OPERATION_OPEN_SESSION, 2, &[1]).unwrap())
+            .expect(&Container::response(RESPONSE_OK, 2, &[]).unwrap())
+            .reply(&Container::command(OPERATION_OPEN_SESSION, 3, &[2]).unwrap())
+            .expect(&Container::response(RESPONSE_SESSION_ALREADY_OPEN, 3, &[1]).unwrap())
+            .reply(&Container::command(OPERATION_SEND_OBJECT, 4, &[]).unwrap())
+            .reply(&Container::data(OPERATION_SEND_OBJECT, 4, vec![1, 2, 3]))
+            .expect(&Container::response(RESPONSE_NO_VALID_OBJECT_INFO, 4, &[]).unwrap())
+            .reply(&Container::command(OPERATION_GET_OBJECT_INFO, 5, &[99]).unwrap())
+            .expect(&Container::response(RESPONSE_INVALID_OBJECT_HANDLE, 5, &[]).unwrap());
+        responder.serve_connection(&mut transport.clone()).unwrap();
+        assert_eq!(transport.remaining(), 0);
+    }
+
+    #[test]
+    fn test_object_size_property() {
+        let tempdir = tempfile::tempdir().unwrap();
+        std::fs::write(tempdir.path().join("a.txt"), "hello").unwrap();
+        let mut responder = MtpResponder::new(tempdir.path(), "Test Device").unwrap();
+        let mut handles = DataWriter::new();
+        handles.write_u32_array(&[1]);
+        let mut size = DataWriter::new();
+        size.write_u64(5);
+        let transport = ScriptedTransport::new();
+        transport
+            .reply(&Container::command(OPERATION_OPEN_SESSION, 1, &[1]).unwrap())
+            .expect(&Container::response(RESPONSE_OK, 1, &[]).unwrap())
+            .reply(&Container::command(OPERATION_GET_OBJECT_HANDLES, 2, &[STORAGE_ID, FORMAT_ALL, PARENT_ROOT]).unwrap())
+            .expect(&Container::data(OPERATION_GET_OBJECT_HANDLES, 2, handles.into_bytes()))
+            .expect(&Container::response(RESPONSE_OK, 2, &[]).unwrap())
+            .reply(&Container::command(OPERATION_GET_OBJECT_PROP_VALUE, 3, &[1, PROPERTY_OBJECT_SIZE as u32]).unwrap())
+            .expect(&Container::data(OPERATION_GET_OBJECT_PROP_VALUE, 3, size.into_bytes()))
+            .expect(&Container::response(RESPONSE_OK, 3, &[]).unwrap())
+            .reply(&Container::command(OPERATION_GET_OBJECT_PROP_VALUE, 4, &[1, PROPERTY_OBJECT_FILE_NAME as u32]).unwrap())
+            .expect(&Container::response(RESPONSE_INVALID_OBJECT_PROP_CODE, 4, &[]).unwrap());
+        responder.serve_connection(&mut transport.clone()).unwrap();
+        assert_eq!(transport.remaining(), 0);
+    }
+
+    #[test]
+    fn test_serve_list_and_copy() -> Result<(), Box<dyn std::error::Error>> {
+        let tempdir = tempfile::tempdir()?;
+        let root = tempdir.path().join("Shared");
+        std::fs::create_dir_all(root.join("DCIM/Camera"))?;
+        std::fs::write(root.join("DCIM/Camera/IMG_0001.JPG"), "jpeg")?;
+        std::fs::write(root.join("DCIM/.hidden"), "hidden")?;
+        let backend = start_responder(&root);
+
+        let devices = backend.list_devices()?;
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].name, "Test Device");
+
+        let storage_path = DeviceStoragePath::from("Test Device:Shared:/DCIM/Camera/IMG_0001.JPG")?;
+        let (_, _, info) = find_file_or_folder(&backend, &storage_path)?.unwrap();
+        assert!(info.is_file());
+        assert_eq!(info.data_size, 4);
+        assert!(info.time_modified.is_some());
+        let storage_path = DeviceStoragePath::from("Test Device:Shared:/DCIM/.hidden")?;
+        assert!(find_file_or_folder(&backend, &storage_path)?.is_none());
+
+        // device -> local
+        let local = tempdir.path().join("local");
+        std::fs::create_dir(&local)?;
+        copy(&backend, "Test Device:Shared:/DCIM", local.to_str().unwrap(), &CopyOptions { recursive: true, ..Default::default() })?;
+        assert_eq!(std::fs::read(local.join("DCIM/Camera/IMG_0001.JPG"))?, b"jpeg");
+        assert!(!local.join("DCIM/.hidden").exists());
+
+        // local -> device
+        std::fs::create_dir_all(local.join("Music/Album"))?;
+        std::fs::write(local.join("Music/Album/song.mp3"), "mp3")?;
+        copy(&backend, local.join("Music").to_str().unwrap(), "Test Device:Shared:/", &CopyOptions { recursive: true, ..Default::default() })?;
+        assert_eq!(std::fs::read(root.join("Music/Album/song.mp3"))?, b"mp3");
+
+        // delete
+        let device = backend.open_device(&devices[0])?;
+        let storage_path = DeviceStoragePath::from("Test Device:Shared:/Music")?;
+        let (_, _, info) = find_file_or_folder(&backend, &storage_path)?.unwrap();
+        device.delete(&info.content_object)?;
+        assert!(!root.join("Music").exists());
+        assert!(device.delete(&ContentObject::new("s00010001")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_serve_rename_and_move() -> Result<(), Box<dyn std::error::Error>> {
+        let tempdir = tempfile::tempdir()?;
+        let root = tempdir.path().join("Shared");
+        std::fs::create_dir_all(root.join("DCIM/Camera"))?;
+        std::fs::create_dir_all(root.join("Pictures"))?;
+        std::fs::write(root.join("DCIM/Camera/IMG_0001.JPG"), "jpeg")?;
+        let backend = start_responder(&root);
+
+        crate::rename::rename(&backend, "Test Device:Shared:/DCIM/Camera", "Photos")?;
+        assert!(root.join("DCIM/Photos/IMG_0001.JPG").exists());
+        // 改名后子对象的 handle 仍然有效
+        crate::rename::move_object(&backend, "Test Device:Shared:/DCIM/Photos/IMG_0001.JPG", "Test Device:Shared:/Pictures/a.jpg")?;
+        assert_eq!(std::fs::read(root.join("Pictures/a.jpg"))?, b"jpeg");
+        assert!(!root.join("DCIM/Photos/IMG_0001.JPG").exists());
+        crate::rename::move_object(&backend, "Test Device:Shared:/DCIM", "Test Device:Shared:/Pictures")?;
+        assert!(root.join("Pictures/DCIM/Photos").is_dir());
+        assert!(crate::rename::move_object(&backend, "Test Device:Shared:/Pictures", "Test Device:Shared:/Pictures/DCIM").is_err());
+        Ok(())
+    }
+}
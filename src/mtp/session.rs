@@ -0,0 +1,395 @@
+use std::fmt;
+use crate::mtp::codes::*;
+use crate::mtp::container::{Container, ContainerType};
+use crate::mtp::dataset::{DataReader, DataWriter, MtpDeviceInfo, ObjectInfo, StorageInfo};
+use crate::mtp::transport::Transport;
+
+/// Error returned when the device answers an operation with a response code other than OK.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseError {
+    pub operation: u16,
+    pub code: u16,
+}
+
+impl fmt::Display for ResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "operation 0x{:04X} failed: {} (0x{:04X})",
+            self.operation,
+            response_code_name(self.code),
+            self.code
+        )
+    }
+}
+
+impl std::error::Error for ResponseError {}
+
+// 一次事务的结果
+pub struct TransactionResult {
+    pub params: Vec<u32>,
+    pub data: Option<Vec<u8>>,
+}
+
+// MTP 会话，负责分配 transaction id 并执行操作
+// 一个事务: 命令 -> [数据 (主机到设备或设备到主机)] -> 响应
+// 对象的数据分块传输，数据阶段结束前不能开始其他事务
+pub struct MtpSession {
+    transport: Box<dyn Transport>,
+    session_id: u32,
+    next_transaction_id: u32,
+    // 正在分块传输数据的操作码和 transaction id
+    data_phase: Option<(u16, u32)>,
+}
+
+impl MtpSession {
+    // 打开会话，OpenSession 的 transaction id 必须是 0
+    pub fn open(mut transport: Box<dyn Transport>, session_id: u32) -> Result<MtpSession, Box<dyn std::error::Error>> {
+        execute(transport.as_mut(), OPERATION_OPEN_SESSION, 0, &[session_id], None)?;
+        Ok(MtpSession {
+            transport,
+            session_id,
+            next_transaction_id: 1,
+            data_phase: None,
+        })
+    }
+
+    pub fn session_id(&self) -> u32 {
+        self.session_id
+    }
+
+    // 关闭会话，返回传输层
+    pub fn close(mut self) -> Result<Box<dyn Transport>, Box<dyn std::error::Error>> {
+        self.transaction(OPERATION_CLOSE_SESSION, &[], None)?;
+        Ok(self.transport)
+    }
+
+    pub fn transaction(
+        &mut self,
+        operation: u16,
+        params: &[u32],
+        data_out: Option<Vec<u8>>,
+    ) -> Result<TransactionResult, Box<dyn std::error::Error>> {
+        let transaction_id = self.begin_transaction()?;
+        execute(self.transport.as_mut(), operation, transaction_id, params, data_out)
+    }
+
+    // 分配 transaction id
+    fn begin_transaction(&mut self) -> Result<u32, Box<dyn std::error::Error>> {
+        if let Some((operation, _)) = self.data_phase {
+            return Err(format!("operation 0x{:04X} has not finished transferring data", operation).into());
+        }
+        let transaction_id = self.next_transaction_id;
+        // 0 和 0xFFFFFFFF 是保留值
+        self.next_transaction_id = match self.next_transaction_id.wrapping_add(1) {
+            0xFFFFFFFF => 1,
+            id => id,
+        };
+        Ok(transaction_id)
+    }
+
+    pub fn get_device_info(&mut self) -> Result<MtpDeviceInfo, Box<dyn std::error::Error>> {
+        let result = self.transaction(OPERATION_GET_DEVICE_INFO, &[], None)?;
+        Ok(MtpDeviceInfo::decode(&expect_data(OPERATION_GET_DEVICE_INFO, result.data)?)?)
+    }
+
+    pub fn get_storage_ids(&mut self) -> Result<Vec<u32>, Box<dyn std::error::Error>> {
+        let result = self.transaction(OPERATION_GET_STORAGE_IDS, &[], None)?;
+        let data = expect_data(OPERATION_GET_STORAGE_IDS, result.data)?;
+        Ok(crate::mtp::dataset::DataReader::new(&data).read_u32_array()?)
+    }
+
+    pub fn get_storage_info(&mut self, storage_id: u32) -> Result<StorageInfo, Box<dyn std::error::Error>> {
+        let result = self.transaction(OPERATION_GET_STORAGE_INFO, &[storage_id], None)?;
+        Ok(StorageInfo::decode(&expect_data(OPERATION_GET_STORAGE_INFO, result.data)?)?)
+    }
+
+    // parent 为 PARENT_ROOT 时列出存储根目录下的对象
+    pub fn get_object_handles(&mut self, storage_id: u32, format: u32, parent: u32) -> Result<Vec<u32>, Box<dyn std::error::Error>> {
+        let result = self.transaction(OPERATION_GET_OBJECT_HANDLES, &[storage_id, format, parent], None)?;
+        let data = expect_data(OPERATION_GET_OBJECT_HANDLES, result.data)?;
+        Ok(crate::mtp::dataset::DataReader::new(&data).read_u32_array()?)
+    }
+
+    pub fn get_object_info(&mut self, handle: u32) -> Result<ObjectInfo, Box<dyn std::error::Error>> {
+        let result = self.transaction(OPERATION_GET_OBJECT_INFO, &[handle], None)?;
+        Ok(ObjectInfo::decode(&expect_data(OPERATION_GET_OBJECT_INFO, result.data)?)?)
+    }
+
+    // ObjectInfo 中的大小只有 32 位，大小为 OBJECT_SIZE_LARGE 时用这个读取实际的大小
+    pub fn get_object_size(&mut self, handle: u32) -> Result<u64, Box<dyn std::error::Error>> {
+        let result = self.transaction(OPERATION_GET_OBJECT_PROP_VALUE, &[handle, PROPERTY_OBJECT_SIZE as u32], None)?;
+        Ok(DataReader::new(&expect_data(OPERATION_GET_OBJECT_PROP_VALUE, result.data)?).read_u64()?)
+    }
+
+    // 开始读取对象的数据，数据用 read_data 分块读取
+    pub fn begin_get_object(&mut self, handle: u32) -> Result<(), Box<dyn std::error::Error>> {
+        let transaction_id = self.begin_transaction()?;
+        self.transport.send(&Container::command(OPERATION_GET_OBJECT, transaction_id, &[handle])?)?;
+        let container = self.transport.begin_receive()?;
+        check_transaction_id(&container, transaction_id)?;
+        match container.container_type {
+            ContainerType::Data => {
+                self.data_phase = Some((OPERATION_GET_OBJECT, transaction_id));
+                Ok(())
+            }
+            ContainerType::Response => {
+                check_response(OPERATION_GET_OBJECT, &container)?;
+                Err(format!("operation 0x{:04X} returned no data", OPERATION_GET_OBJECT).into())
+            }
+            container_type => Err(format!("unexpected container: {:?}", container_type).into()),
+        }
+    }
+
+    // 读取数据阶段的数据，读完后接收响应并返回 0
+    pub fn read_data(&mut self, buf: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        if self.data_phase.is_none() {
+            return Err("no data phase in progress".into());
+        }
+        let len = self.transport.receive_data_chunk(buf)?;
+        if len == 0 {
+            self.end_data_phase()?;
+        }
+        Ok(len)
+    }
+
+    // 发送对象信息，返回 (storage id, parent handle, 新对象的 handle)
+    pub fn send_object_info(&mut self, storage_id: u32, parent: u32, info: &ObjectInfo) -> Result<(u32, u32, u32), Box<dyn std::error::Error>> {
+        let result = self.transaction(OPERATION_SEND_OBJECT_INFO, &[storage_id, parent], Some(info.encode()))?;
+        match result.params[..] {
+            [storage_id, parent, handle, ..] => Ok((storage_id, parent, handle)),
+            _ => Err("SendObjectInfo response has too few parameters".into()),
+        }
+    }
+
+    // 开始发送对象的数据，必须紧跟在 SendObjectInfo 之后
+    // size 为数据的总长度，数据用 write_data 分块发送，发送完后调用 end_data_phase
+    pub fn begin_send_object(&mut self, size: u64) -> Result<(), Box<dyn std::error::Error>> {
+        let transaction_id = self.begin_transaction()?;
+        self.transport.send(&Container::command(OPERATION_SEND_OBJECT, transaction_id, &[])?)?;
+        self.transport.begin_send_data(OPERATION_SEND_OBJECT, transaction_id, size)?;
+        self.data_phase = Some((OPERATION_SEND_OBJECT, transaction_id));
+        Ok(())
+    }
+
+    pub fn write_data(&mut self, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        if self.data_phase.is_none() {
+            return Err("no data phase in progress".into());
+        }
+        self.transport.send_data_chunk(data)
+    }
+
+    // 数据传输完后接收响应，返回响应的参数
+    pub fn end_data_phase(&mut self) -> Result<Vec<u32>, Box<dyn std::error::Error>> {
+        let (operation, transaction_id) = self.data_phase.take().ok_or("no data phase in progress")?;
+        let container = self.transport.receive()?;
+        check_transaction_id(&container, transaction_id)?;
+        match container.container_type {
+            ContainerType::Response => check_response(operation, &container),
+            container_type => Err(format!("unexpected container: {:?}", container_type).into()),
+        }
+    }
+
+    pub fn delete_object(&mut self, handle: u32) -> Result<(), Box<dyn std::error::Error>> {
+        self.transaction(OPERATION_DELETE_OBJECT, &[handle, FORMAT_ALL], None)?;
+        Ok(())
+    }
+
+    // 移动对象，parent 为 0 时移动到存储的根目录
+    pub fn move_object(&mut self, handle: u32, storage_id: u32, parent: u32) -> Result<(), Box<dyn std::error::Error>> {
+        self.transaction(OPERATION_MOVE_OBJECT, &[handle, storage_id, parent], None)?;
+        Ok(())
+    }
+
+    // 设置字符串类型的对象属性
+    pub fn set_object_prop_string(&mut self, handle: u32, property: u16, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut writer = DataWriter::new();
+        writer.write_string(value);
+        self.transaction(OPERATION_SET_OBJECT_PROP_VALUE, &[handle, property as u32], Some(writer.into_bytes()))?;
+        Ok(())
+    }
+}
+
+fn expect_data(operation: u16, data: Option<Vec<u8>>) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    data.ok_or_else(|| format!("operation 0x{:04X} returned no data", operation).into())
+}
+
+// 执行一个事务
+fn execute(
+    transport: &mut dyn Transport,
+    operation: u16,
+    transaction_id: u32,
+    params: &[u32],
+    data_out: Option<Vec<u8>>,
+) -> Result<TransactionResult, Box<dyn std::error::Error>> {
+    transport.send(&Container::command(operation, transaction_id, params)?)?;
+    if let Some(data) = data_out {
+        transport.send(&Container::data(operation, transaction_id, data))?;
+    }
+
+    let mut data_in = None;
+    loop {
+        let container = transport.receive()?;
+        check_transaction_id(&container, transaction_id)?;
+        match container.container_type {
+            ContainerType::Data if data_in.is_none() => data_in = Some(container.payload),
+            ContainerType::Response => {
+                return Ok(TransactionResult {
+                    params: check_response(operation, &container)?,
+                    data: data_in,
+                });
+            }
+            container_type => return Err(format!("unexpected container: {:?}", container_type).into()),
+        }
+    }
+}
+
+fn check_transaction_id(container: &Container, transaction_id: u32) -> Result<(), Box<dyn std::error::Error>> {
+    if container.transaction_id != transaction_id {
+        return Err(format!(
+            "unexpected transaction id: {} (expected {})",
+            container.transaction_id, transaction_id
+        )
+        .into());
+    }
+    Ok(())
+}
+
+// 响应码不是 OK 时返回 ResponseError，否则返回响应的参数
+fn check_response(operation: u16, response: &Container) -> Result<Vec<u32>, Box<dyn std::error::Error>> {
+    if response.code != RESPONSE_OK {
+        return Err(Box::new(ResponseError {
+            operation,
+            code: response.code,
+        }));
+    }
+    Ok(response.params()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mtp::transport::ScriptedTransport;
+
+    // OpenSession(1) 的命令和 OK 响应 (抓包)
+    const OPEN_SESSION_COMMAND: [u8; 16] = [0x10, 0, 0, 0, 0x01, 0, 0x02, 0x10, 0, 0, 0, 0, 0x01, 0, 0, 0];
+    const OPEN_SESSION_RESPONSE: [u8; 12] = [0x0c, 0, 0, 0, 0x03, 0, 0x01, 0x20, 0, 0, 0, 0];
+
+    fn open_session(transport: &ScriptedTransport) -> MtpSession {
+        transport.expect_bytes(&OPEN_SESSION_COMMAND).reply_bytes(&OPEN_SESSION_RESPONSE);
+        MtpSession::open(Box::new(transport.clone()), 1).unwrap()
+    }
+
+    #[test]
+    fn test_open_and_close_session() {
+        let transport = ScriptedTransport::new();
+        let session = open_session(&transport);
+        assert_eq!(session.session_id(), 1);
+
+        // CloseSession, transaction id 1
+        transport
+            .expect_bytes(&[0x0c, 0, 0, 0, 0x01, 0, 0x03, 0x10, 0x01, 0, 0, 0])
+            .reply_bytes(&[0x0c, 0, 0, 0, 0x03, 0, 0x01, 0x20, 0x01, 0, 0, 0]);
+        session.close().unwrap();
+        assert_eq!(transport.remaining(), 0);
+    }
+
+    #[test]
+    fn test_get_storage_ids() {
+        let transport = ScriptedTransport::new();
+        let mut session = open_session(&transport);
+        transport
+            .expect_bytes(&[0x0c, 0, 0, 0, 0x01, 0, 0x04, 0x10, 0x01, 0, 0, 0])
+            .reply_bytes(&[
+                0x18, 0, 0, 0, 0x02, 0, 0x04, 0x10, 0x01, 0, 0, 0,
+                0x02, 0, 0, 0, 0x01, 0, 0x01, 0, 0x01, 0, 0x02, 0,
+            ])
+            .reply_bytes(&[0x0c, 0, 0, 0, 0x03, 0, 0x01, 0x20, 0x01, 0, 0, 0]);
+        assert_eq!(session.get_storage_ids().unwrap(), vec![0x00010001, 0x00020001]);
+        assert_eq!(transport.remaining(), 0);
+    }
+
+    #[test]
+    fn test_send_object_info_and_object() {
+        let transport = ScriptedTransport::new();
+        let mut session = open_session(&transport);
+        let info = ObjectInfo {
+            storage_id: 0x00010001,
+            object_format: FORMAT_UNDEFINED,
+            object_compressed_size: 5,
+            parent_object: PARENT_ROOT,
+            filename: "a.txt".to_string(),
+            ..Default::default()
+        };
+        transport
+            .expect(&Container::command(OPERATION_SEND_OBJECT_INFO, 1, &[0x00010001, PARENT_ROOT]).unwrap())
+            .expect(&Container::data(OPERATION_SEND_OBJECT_INFO, 1, info.encode()))
+            .reply(&Container::response(RESPONSE_OK, 1, &[0x00010001, PARENT_ROOT, 0x20]).unwrap())
+            .expect(&Container::command(OPERATION_SEND_OBJECT, 2, &[]).unwrap())
+            .expect(&Container::data(OPERATION_SEND_OBJECT, 2, b"hello".to_vec()))
+            .reply(&Container::response(RESPONSE_OK, 2, &[]).unwrap());
+        assert_eq!(
+            session.send_object_info(0x00010001, PARENT_ROOT, &info).unwrap(),
+            (0x00010001, PARENT_ROOT, 0x20)
+        );
+        session.begin_send_object(5).unwrap();
+        session.write_data(b"hel").unwrap();
+        // 数据阶段结束前不能开始其他事务
+        assert!(session.delete_object(0x20).is_err());
+        session.write_data(b"lo").unwrap();
+        session.end_data_phase().unwrap();
+        assert_eq!(transport.remaining(), 0);
+    }
+
+    #[test]
+    fn test_get_object() {
+        let transport = ScriptedTransport::new();
+        let mut session = open_session(&transport);
+        transport
+            .expect(&Container::command(OPERATION_GET_OBJECT, 1, &[0x20]).unwrap())
+            .reply(&Container::data(OPERATION_GET_OBJECT, 1, b"hello".to_vec()))
+            .reply(&Container::response(RESPONSE_OK, 1, &[]).unwrap());
+        session.begin_get_object(0x20).unwrap();
+        let mut buf = [0u8; 3];
+        let mut data = Vec::<u8>::new();
+        loop {
+            let len = session.read_data(&mut buf).unwrap();
+            if len == 0 {
+                break;
+            }
+            data.extend_from_slice(&buf[..len]);
+        }
+        assert_eq!(data, b"hello");
+        assert_eq!(transport.remaining(), 0);
+        assert!(session.read_data(&mut buf).is_err());
+
+        transport
+            .expect(&Container::command(OPERATION_GET_OBJECT, 2, &[0x21]).unwrap())
+            .reply(&Container::response(RESPONSE_INVALID_OBJECT_HANDLE, 2, &[]).unwrap());
+        let err = session.begin_get_object(0x21).unwrap_err();
+        assert_eq!(err.downcast_ref::<ResponseError>().unwrap().code, RESPONSE_INVALID_OBJECT_HANDLE);
+    }
+
+    #[test]
+    fn test_error_response() {
+        let transport = ScriptedTransport::new();
+        let mut session = open_session(&transport);
+        transport
+            .expect(&Container::command(OPERATION_DELETE_OBJECT, 1, &[0x20, 0]).unwrap())
+            .reply(&Container::response(RESPONSE_OBJECT_WRITE_PROTECTED, 1, &[]).unwrap());
+        let err = session.delete_object(0x20).unwrap_err();
+        let response_error = err.downcast_ref::<ResponseError>().unwrap();
+        assert_eq!(response_error.code, RESPONSE_OBJECT_WRITE_PROTECTED);
+        assert_eq!(err.to_string(), "operation 0x100B failed: Object_WriteProtected (0x200D)");
+    }
+
+    #[test]
+    fn test_unexpected_transaction_id() {
+        let transport = ScriptedTransport::new();
+        let mut session = open_session(&transport);
+        transport
+            .expect(&Container::command(OPERATION_GET_OBJECT, 1, &[0x20]).unwrap())
+            .reply(&Container::response(RESPONSE_OK, 7, &[]).unwrap());
+        assert!(session.begin_get_object(0x20).is_err());
+    }
+}
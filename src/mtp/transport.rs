@@ -0,0 +1,171 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use crate::mtp::container::{Container, ContainerType, HEADER_SIZE};
+
+// 传输层接口，负责在主机和设备之间收发容器
+// USB bulk、PTP/IP 等传输方式都实现这个接口
+pub trait Transport {
+    // 发送一个容器 (命令或数据)
+    fn send(&mut self, container: &Container) -> Result<(), Box<dyn std::error::Error>>;
+    // 接收一个容器 (数据或响应)
+    fn receive(&mut self) -> Result<Container, Box<dyn std::error::Error>>;
+
+    // 分块发送数据阶段，大文件不需要全部放在内存中
+    // 先发送数据容器的头部，size 为数据的总长度，再用 send_data_chunk 发送全部数据
+    fn begin_send_data(&mut self, code: u16, transaction_id: u32, size: u64) -> Result<(), Box<dyn std::error::Error>>;
+    fn send_data_chunk(&mut self, data: &[u8]) -> Result<(), Box<dyn std::error::Error>>;
+    // 分块接收: 接收下一个容器，数据容器的 payload 为空，
+    // 数据用 receive_data_chunk 读取，读完时返回 0
+    fn begin_receive(&mut self) -> Result<Container, Box<dyn std::error::Error>>;
+    fn receive_data_chunk(&mut self, buf: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptStep {
+    // 期望主机发送的字节
+    Send(Vec<u8>),
+    // 期望主机分块发送的数据阶段，只检查头部和长度，数据不保存在内存中
+    SendData { code: u16, transaction_id: u32, size: u64 },
+    // 设备返回的字节
+    Receive(Vec<u8>),
+}
+
+// 正在分块发送的数据，只检查长度时没有期望的数据
+#[derive(Debug, Clone)]
+struct DataOut {
+    expected: Option<Vec<u8>>,
+    size: u64,
+    sent: u64,
+}
+
+/// A transport that replays a script of expected and returned byte sequences.
+///
+/// Clones share the same script, so a test can keep a clone to check that the script was consumed.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedTransport {
+    steps: Arc<Mutex<VecDeque<ScriptStep>>>,
+    sending: Option<DataOut>,
+    // 正在分块接收的数据和已读取的位置
+    receiving: Option<(Vec<u8>, usize)>,
+}
+
+impl ScriptedTransport {
+    pub fn new() -> ScriptedTransport {
+        ScriptedTransport::default()
+    }
+
+    pub fn expect_bytes(&self, bytes: &[u8]) -> &ScriptedTransport {
+        self.steps.lock().unwrap().push_back(ScriptStep::Send(bytes.to_vec()));
+        self
+    }
+
+    pub fn reply_bytes(&self, bytes: &[u8]) -> &ScriptedTransport {
+        self.steps.lock().unwrap().push_back(ScriptStep::Receive(bytes.to_vec()));
+        self
+    }
+
+    pub fn expect_data_size(&self, code: u16, transaction_id: u32, size: u64) -> &ScriptedTransport {
+        self.steps.lock().unwrap().push_back(ScriptStep::SendData { code, transaction_id, size });
+        self
+    }
+
+    pub fn expect(&self, container: &Container) -> &ScriptedTransport {
+        self.expect_bytes(&container.encode())
+    }
+
+    pub fn reply(&self, container: &Container) -> &ScriptedTransport {
+        self.reply_bytes(&container.encode())
+    }
+
+    // 剩余的步骤数
+    pub fn remaining(&self) -> usize {
+        self.steps.lock().unwrap().len()
+    }
+}
+
+impl Transport for ScriptedTransport {
+    fn send(&mut self, container: &Container) -> Result<(), Box<dyn std::error::Error>> {
+        if self.sending.is_some() {
+            return Err("the data phase is not complete".into());
+        }
+        let bytes = container.encode();
+        match self.steps.lock().unwrap().pop_front() {
+            Some(ScriptStep::Send(expected)) if expected == bytes => Ok(()),
+            Some(ScriptStep::Send(expected)) => {
+                Err(format!("unexpected container: sent {:02x?}, expected {:02x?}", bytes, expected).into())
+            }
+            Some(_) => Err(format!("unexpected send: {:02x?}", bytes).into()),
+            None => Err(format!("end of script: sent {:02x?}", bytes).into()),
+        }
+    }
+
+    fn receive(&mut self) -> Result<Container, Box<dyn std::error::Error>> {
+        match self.steps.lock().unwrap().pop_front() {
+            Some(ScriptStep::Receive(bytes)) => {
+                let (container, size) = Container::decode(&bytes)?;
+                if size != bytes.len() {
+                    return Err("trailing bytes after the container".into());
+                }
+                Ok(container)
+            }
+            Some(ScriptStep::Send(expected)) => {
+                Err(format!("unexpected receive: expected to send {:02x?}", expected).into())
+            }
+            Some(ScriptStep::SendData { .. }) => Err("unexpected receive: expected to send data".into()),
+            None => Err("end of script".into()),
+        }
+    }
+
+    fn begin_send_data(&mut self, code: u16, transaction_id: u32, size: u64) -> Result<(), Box<dyn std::error::Error>> {
+        let header = Container::data_header(code, transaction_id, size);
+        let expected = match self.steps.lock().unwrap().pop_front() {
+            Some(ScriptStep::Send(expected)) if expected.starts_with(&header) && (expected.len() - HEADER_SIZE) as u64 == size => {
+                Some(expected[HEADER_SIZE..].to_vec())
+            }
+            Some(ScriptStep::SendData { code: expected_code, transaction_id: expected_id, size: expected_size })
+                if (expected_code, expected_id, expected_size) == (code, transaction_id, size) => None,
+            step => return Err(format!("unexpected data phase: sent {:02x?}, expected {:02x?}", header, step).into()),
+        };
+        if size > 0 {
+            self.sending = Some(DataOut { expected, size, sent: 0 });
+        }
+        Ok(())
+    }
+
+    fn send_data_chunk(&mut self, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let sending = self.sending.as_mut().ok_or("no data phase in progress")?;
+        if sending.sent + data.len() as u64 > sending.size {
+            return Err(format!("more data than announced: {} bytes", sending.size).into());
+        }
+        if let Some(expected) = &sending.expected {
+            let offset = sending.sent as usize;
+            if expected[offset..offset + data.len()] != *data {
+                return Err(format!("unexpected data at offset {}: sent {:02x?}", offset, data).into());
+            }
+        }
+        sending.sent += data.len() as u64;
+        if sending.sent == sending.size {
+            self.sending = None;
+        }
+        Ok(())
+    }
+
+    fn begin_receive(&mut self) -> Result<Container, Box<dyn std::error::Error>> {
+        let mut container = self.receive()?;
+        if container.container_type == ContainerType::Data {
+            self.receiving = Some((std::mem::take(&mut container.payload), 0));
+        }
+        Ok(container)
+    }
+
+    fn receive_data_chunk(&mut self, buf: &mut [u8]) -> Result<usize, Box<dyn std::error::Error>> {
+        let (data, offset) = self.receiving.as_mut().ok_or("no data phase in progress")?;
+        let len = std::cmp::min(buf.len(), data.len() - *offset);
+        buf[..len].copy_from_slice(&data[*offset..*offset + len]);
+        *offset += len;
+        if len == 0 {
+            self.receiving = None;
+        }
+        Ok(len)
+    }
+}
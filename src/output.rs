@@ -0,0 +1,366 @@
+use std::io::Write;
+use std::str::FromStr;
+use serde::Serialize;
+use crate::backend::ContentObjectInfo;
+use crate::common::text_width::{display_width, pad_left, pad_right};
+use crate::common::timestamp::TimeZone;
+use crate::list::Entry;
+use crate::path::DeviceStoragePath;
+
+// 列表命令的输出格式，供脚本解析
+// json 输出一个数组，ndjson 每行一个对象，csv 和 tsv 第一行是列名
+// --print0 时记录以 NUL 结尾，文件名中有空格或换行也不会出错
+
+/// The output format of the listing commands.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// One path per line
+    #[default]
+    Text,
+    /// A JSON array of records
+    Json,
+    /// One JSON record per line
+    Ndjson,
+    Csv,
+    Tsv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            "ndjson" => Ok(OutputFormat::Ndjson),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            _ => Err(format!("unknown output format: {} (text, json, ndjson, csv or tsv)", s)),
+        }
+    }
+}
+
+/// A listed storage, file or folder as written by the machine-readable formats.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EntryRecord {
+    pub device: String,
+    pub storage: String,
+    /// The path in the storage, "\" for the storage itself
+    pub path: String,
+    pub name: String,
+    /// "storage", "folder", "file" or "other"
+    #[serde(rename = "type")]
+    pub object_type: &'static str,
+    pub size: u64,
+    pub hidden: bool,
+    pub system: bool,
+    pub can_delete: bool,
+    /// ISO 8601
+    pub created: Option<String>,
+    pub modified: Option<String>,
+}
+
+const CSV_HEADER: [&str; 11] = ["device", "storage", "path", "name", "type", "size", "hidden", "system", "can_delete", "created", "modified"];
+
+impl EntryRecord {
+    pub fn from_entry(entry: &Entry, time_zone: TimeZone) -> EntryRecord {
+        let info = &entry.info;
+        let path = DeviceStoragePath::from(&entry.path).map_or_else(|_| entry.path.clone(), |p| p.path);
+        EntryRecord {
+            device: entry.device.clone(),
+            storage: entry.storage.clone(),
+            path,
+            name: info.name.clone(),
+            object_type: object_type(info),
+            size: info.data_size,
+            hidden: info.is_hidden,
+            system: info.is_system,
+            can_delete: info.can_delete,
+            created: info.time_created.map(|time| time.to_iso(time_zone)),
+            modified: info.time_modified.map(|time| time.to_iso(time_zone)),
+        }
+    }
+
+    fn fields(&self) -> [String; 11] {
+        let date = |time: &Option<String>| time.clone().unwrap_or_default();
+        [
+            self.device.clone(),
+            self.storage.clone(),
+            self.path.clone(),
+            self.name.clone(),
+            self.object_type.to_string(),
+            self.size.to_string(),
+            self.hidden.to_string(),
+            self.system.to_string(),
+            self.can_delete.to_string(),
+            date(&self.created),
+            date(&self.modified),
+        ]
+    }
+}
+
+fn object_type(info: &ContentObjectInfo) -> &'static str {
+    if info.is_storage() {
+        "storage"
+    } else if info.is_folder() {
+        "folder"
+    } else if info.is_file() {
+        "file"
+    } else {
+        "other"
+    }
+}
+
+/// Writes `entries` in `format`, records end with NUL instead of a newline when `print0` is set.
+pub fn write_entries(
+    out: &mut dyn Write,
+    entries: &[Entry],
+    format: OutputFormat,
+    print0: bool,
+    time_zone: TimeZone,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let end = if print0 { "\0" } else { "\n" };
+    let records = entries.iter().map(|entry| EntryRecord::from_entry(entry, time_zone));
+    match format {
+        OutputFormat::Text => {
+            for entry in entries {
+                write!(out, "{}{}", entry.path, end)?;
+            }
+        }
+        OutputFormat::Json => {
+            if print0 {
+                return Err("--print0 cannot be used with --format json, use ndjson".into());
+            }
+            let records: Vec<EntryRecord> = records.collect();
+            writeln!(out, "{}", serde_json::to_string_pretty(&records)?)?;
+        }
+        OutputFormat::Ndjson => {
+            for record in records {
+                write!(out, "{}{}", serde_json::to_string(&record)?, end)?;
+            }
+        }
+        OutputFormat::Csv | OutputFormat::Tsv => {
+            let delimiter = if format == OutputFormat::Csv { ',' } else { '\t' };
+            write!(out, "{}{}", join_fields(CSV_HEADER.iter().copied(), delimiter), end)?;
+            for record in records {
+                let fields = record.fields();
+                write!(out, "{}{}", join_fields(fields.iter().map(String::as_str), delimiter), end)?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Writes `entries` as a long listing: path, type and flags, size and modification date.
+///
+/// The flags are `h` hidden, `s` system and `r` for objects the device does not allow to delete.
+/// Sizes are in K, M, G... units when `human` is set. The paths are padded by their display
+/// width, so names with CJK characters line up.
+pub fn write_long_listing(
+    out: &mut dyn Write,
+    entries: &[Entry],
+    human: bool,
+    print0: bool,
+    time_zone: TimeZone,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let end = if print0 { "\0" } else { "\n" };
+    let rows: Vec<[String; 4]> = entries
+        .iter()
+        .map(|entry| {
+            let info = &entry.info;
+            let mode = format!(
+                "{}{}{}{}",
+                match object_type(info) {
+                    "storage" => 's',
+                    "folder" => 'd',
+                    "file" => '-',
+                    _ => '?',
+                },
+                if info.is_hidden { 'h' } else { '-' },
+                if info.is_system { 's' } else { '-' },
+                if info.can_delete { '-' } else { 'r' },
+            );
+            let size = match (info.is_file(), human) {
+                (false, _) => "-".to_string(),
+                (true, false) => info.data_size.to_string(),
+                (true, true) => human_size(info.data_size),
+            };
+            let modified = info.time_modified.map_or_else(|| "-".to_string(), |time| time.format(time_zone));
+            [entry.path.clone(), mode, size, modified]
+        })
+        .collect();
+
+    // 按显示宽度对齐 (中日韩文字占两列)，最后一列不填充
+    let mut widths = [0usize; 3];
+    for row in &rows {
+        for (width, column) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(display_width(column));
+        }
+    }
+    for [path, mode, size, modified] in &rows {
+        write!(
+            out,
+            "{}  {}  {}  {}{}",
+            pad_right(path, widths[0]),
+            pad_right(mode, widths[1]),
+            pad_left(size, widths[2]),
+            modified,
+            end
+        )?;
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Formats `size` like `ls -h`, e.g. "512", "1.5K", "23M".
+pub fn human_size(size: u64) -> String {
+    const UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+    if size < 1024 {
+        return size.to_string();
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value < 9.95 {
+        format!("{:.1}{}", value, UNITS[unit])
+    } else {
+        format!("{:.0}{}", value, UNITS[unit])
+    }
+}
+
+// 字段包含分隔符、引号或换行时加引号，引号写两次 (RFC 4180，tsv 也一样)
+fn join_fields<'a>(fields: impl Iterator<Item = &'a str>, delimiter: char) -> String {
+    let fields: Vec<String> = fields
+        .map(|field| {
+            if field.contains([delimiter, '"', '\r', '\n']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+    fields.join(&delimiter.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+    use crate::backend::memory::MemoryBackend;
+    use crate::common::timestamp::Timestamp;
+    use crate::list::{list_files, list_storages};
+
+    fn create_entries() -> Vec<Entry> {
+        let backend = MemoryBackend::with_storage("Redmi K70", "内部存储设备");
+        backend.add_file("Redmi K70:内部存储设备:/My Pictures/a, \"b\".jpg", b"abc").unwrap();
+        backend
+            .update_object("Redmi K70:内部存储设备:/My Pictures/a, \"b\".jpg", |o| {
+                o.is_hidden = true;
+                o.time_modified = Some(Timestamp::from_unix_seconds(1627846261));
+            })
+            .unwrap();
+        let mut entries = list_storages(&backend).unwrap();
+        entries.extend(list_files(&backend, "Redmi K70:内部存储设备:/My Pictures", true).unwrap());
+        entries
+    }
+
+    fn write(format: OutputFormat, print0: bool) -> String {
+        let mut out = Vec::new();
+        write_entries(&mut out, &create_entries(), format, print0, "+08:00".parse().unwrap()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test_case("json", OutputFormat::Json)]
+    #[test_case("NDJSON", OutputFormat::Ndjson)]
+    #[test_case("tsv", OutputFormat::Tsv)]
+    fn test_parse_output_format(s: &str, format: OutputFormat) {
+        assert_eq!(s.parse::<OutputFormat>().unwrap(), format);
+    }
+
+    #[test_case(0, "0")]
+    #[test_case(1023, "1023")]
+    #[test_case(1536, "1.5K")]
+    #[test_case(10 * 1024, "10K")]
+    #[test_case(5 * 1024 * 1024 * 1024, "5.0G")]
+    fn test_human_size(size: u64, text: &str) {
+        assert_eq!(human_size(size), text);
+    }
+
+    #[test]
+    fn test_records() {
+        let time_zone = TimeZone::UTC;
+        let records: Vec<EntryRecord> = create_entries().iter().map(|e| EntryRecord::from_entry(e, time_zone)).collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!((records[0].object_type, records[0].path.as_str()), ("storage", "\\"));
+        assert_eq!((records[1].object_type, records[1].path.as_str()), ("folder", "\\My Pictures"));
+        let file = &records[2];
+        assert_eq!(file.device, "Redmi K70");
+        assert_eq!(file.storage, "内部存储设备");
+        assert_eq!(file.name, "a, \"b\".jpg");
+        assert_eq!((file.size, file.hidden, file.system), (3, true, false));
+        assert_eq!(file.modified.as_deref(), Some("2021-08-01T19:31:01Z"));
+    }
+
+    #[test]
+    fn test_write_text_and_json() {
+        assert_eq!(
+            write(OutputFormat::Text, true),
+            "Redmi K70:内部存储设备:\0Redmi K70:内部存储设备:\\My Pictures\0Redmi K70:内部存储设备:\\My Pictures\\a, \"b\".jpg\0"
+        );
+        let value: serde_json::Value = serde_json::from_str(&write(OutputFormat::Json, false)).unwrap();
+        assert_eq!(value[2]["type"], "file");
+        assert_eq!(value[2]["modified"], "2021-08-02T03:31:01+08:00");
+        assert_eq!(value[0]["created"], serde_json::Value::Null);
+
+        let lines: Vec<String> = write(OutputFormat::Ndjson, false).lines().map(str::to_string).collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&lines[1]).unwrap()["path"], "\\My Pictures");
+
+        let mut out = Vec::new();
+        assert!(write_entries(&mut out, &create_entries(), OutputFormat::Json, true, TimeZone::UTC).is_err());
+    }
+
+    #[test]
+    fn test_write_long_listing() {
+        let mut entries = create_entries();
+        entries[2].info.data_size = 1536;
+        let mut photo = entries[2].clone();
+        photo.path = "Redmi K70:内部存储设备:\\My Pictures\\照片.jpg".to_string();
+        photo.info.is_hidden = false;
+        photo.info.data_size = 20;
+        photo.info.time_modified = None;
+        entries.push(photo);
+        let mut out = Vec::new();
+        write_long_listing(&mut out, &entries, true, false, "+08:00".parse().unwrap()).unwrap();
+        // 路径列宽 46，"内部存储设备" 和 "照片" 每个字占两列
+        let expected: String = [
+            ("Redmi K70:内部存储设备:", 23, "s--r     -  -"),
+            ("Redmi K70:内部存储设备:\\My Pictures", 35, "d---     -  -"),
+            ("Redmi K70:内部存储设备:\\My Pictures\\a, \"b\".jpg", 46, "-h--  1.5K  2021-08-02 03:31:01"),
+            ("Redmi K70:内部存储设备:\\My Pictures\\照片.jpg", 44, "----    20  -"),
+        ]
+        .iter()
+        .map(|(path, width, rest)| format!("{}{}  {}\n", path, " ".repeat(46 - width), rest))
+        .collect();
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_write_csv_and_tsv() {
+        let csv = write(OutputFormat::Csv, false);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "device,storage,path,name,type,size,hidden,system,can_delete,created,modified");
+        assert_eq!(
+            lines[3],
+            "Redmi K70,内部存储设备,\"\\My Pictures\\a, \"\"b\"\".jpg\",\"a, \"\"b\"\".jpg\",file,3,true,false,true,,2021-08-02T03:31:01+08:00"
+        );
+        let tsv = write(OutputFormat::Tsv, true);
+        let records: Vec<&str> = tsv.split_terminator('\0').collect();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[1], "Redmi K70\t内部存储设备\t\\\t内部存储设备\tstorage\t0\tfalse\tfalse\tfalse\t\t");
+    }
+}
@@ -27,6 +27,7 @@ pub struct DeviceStoragePath {
 }
 
 impl DeviceStoragePath {
+    #[allow(clippy::len_zero)]
     pub fn from(path: &str) -> Result<DeviceStoragePath, Box<dyn std::error::Error>> {
         let mut path_sep: Vec<String> = path.split(':').map(|s| s.to_string()).collect();
         if path_sep.len() != 3 {
@@ -62,7 +63,7 @@ impl DeviceStoragePath {
         )
     }
 
-    pub fn file_name<'s>(&'s self) -> Option<&'s str> {
+    pub fn file_name(&self) -> Option<&str> {
         if self.path.ends_with('\\') {
             None
         }
@@ -0,0 +1,299 @@
+use std::io::{stderr, stdin, BufRead, Write};
+use std::path::{Path, PathBuf};
+use crate::backend::{ContentObjectInfo, DeviceOperate, PortableDeviceBackend};
+use crate::copy_operate::has_wildcard;
+use crate::copy_operate::wildcard::find_local_matches;
+use crate::error::MtpError;
+use crate::find::{find_storage, iterate_file_or_folder};
+use crate::path::{get_path_type, DeviceStoragePath, PathType};
+
+// 删除设备或本地的文件和文件夹
+// 先找到所有路径匹配的对象，有路径不存在时不删除任何对象
+// Device::delete 会递归删除，所以文件夹的内容逐个删除，不能删除的对象和它上层的文件夹都保留
+
+/// Options of a remove operation.
+#[derive(Debug, Clone, Default)]
+pub struct RemoveOptions {
+    /// Remove folders with their contents
+    pub recursive: bool,
+    /// Print the objects instead of removing them
+    pub dry_run: bool,
+    /// Do not ask before removing a folder recursively
+    pub yes: bool,
+}
+
+/// Results of a remove operation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RemoveSummary {
+    /// The removed files and folders, or the ones a dry run would remove
+    pub removed: Vec<String>,
+    /// The files and folders that were not removed, with the reasons
+    pub refused: Vec<(String, String)>,
+}
+
+enum Target {
+    // devices 中的序号、对象信息和完整路径
+    Device(usize, ContentObjectInfo, String),
+    Local(PathBuf),
+}
+
+pub fn remove(
+    backend: &dyn PortableDeviceBackend,
+    paths: &[&str],
+    options: &RemoveOptions,
+) -> Result<RemoveSummary, Box<dyn std::error::Error>> {
+    remove_with_input(backend, paths, options, &mut stdin().lock())
+}
+
+fn remove_with_input(
+    backend: &dyn PortableDeviceBackend,
+    paths: &[&str],
+    options: &RemoveOptions,
+    input: &mut impl BufRead,
+) -> Result<RemoveSummary, Box<dyn std::error::Error>> {
+    log::trace!("command_remove paths={:?} options={:?}", paths, options);
+
+    // 1. 找到所有要删除的对象
+    let mut devices = Vec::<Box<dyn DeviceOperate>>::new();
+    let mut targets = Vec::<Target>::new();
+    for path in paths {
+        let found = match get_path_type(path) {
+            PathType::DeviceStorage => find_device_targets(backend, path, &mut devices)?,
+            PathType::Local => find_local_targets(path)?,
+            PathType::Invalid => return Err(format!("invalid path: {}", path).into()),
+        };
+        if found.is_empty() {
+            return Err(MtpError::PathNotFound(path.to_string()).into());
+        }
+        targets.extend(found);
+    }
+
+    // 2. 逐个删除，文件夹需要 -r，递归删除前询问
+    let mut summary = RemoveSummary::default();
+    for target in &targets {
+        let (path, is_folder) = match target {
+            Target::Device(_, info, path) => (path.clone(), info.is_folder()),
+            Target::Local(path) => (path.display().to_string(), path.symlink_metadata()?.is_dir()),
+        };
+        if is_folder {
+            if !options.recursive {
+                refuse(&mut summary, &path, "is a folder, use -r");
+                continue;
+            }
+            if !options.yes && !options.dry_run && !confirm(input, &path)? {
+                refuse(&mut summary, &path, "not confirmed");
+                continue;
+            }
+        }
+        match target {
+            Target::Device(index, info, path) => {
+                remove_device_object(devices[*index].as_ref(), info, path, options, &mut summary)?;
+            }
+            Target::Local(path) => {
+                remove_local_path(path, options, &mut summary)?;
+            }
+        }
+    }
+    Ok(summary)
+}
+
+// 设备路径可以含有通配符
+fn find_device_targets(
+    backend: &dyn PortableDeviceBackend,
+    path: &str,
+    devices: &mut Vec<Box<dyn DeviceOperate>>,
+) -> Result<Vec<Target>, Box<dyn std::error::Error>> {
+    let storage_path = DeviceStoragePath::from(path)?;
+    let (device_info, device, storage_object) = match find_storage(backend, &storage_path)? {
+        Some(found) => found,
+        None => return Err(MtpError::PathNotFound(path.to_string()).into()),
+    };
+    let mut targets = Vec::new();
+    iterate_file_or_folder(device.as_ref(), &device_info, &storage_object, &storage_path.path, false, |info, path| {
+        targets.push(Target::Device(devices.len(), info.clone(), path.to_string()));
+    })?;
+    devices.push(device);
+    Ok(targets)
+}
+
+fn find_local_targets(path: &str) -> Result<Vec<Target>, Box<dyn std::error::Error>> {
+    if has_wildcard(path, PathType::Local)? {
+        return Ok(find_local_matches(path)?.into_iter().map(|m| Target::Local(m.path)).collect());
+    }
+    if Path::new(path).symlink_metadata().is_err() {
+        return Ok(Vec::new());
+    }
+    Ok(vec![Target::Local(PathBuf::from(path))])
+}
+
+// 删除设备上的对象，返回是否删除
+// 文件夹先删除内容，全部删除后才删除文件夹
+fn remove_device_object(
+    device: &dyn DeviceOperate,
+    info: &ContentObjectInfo,
+    path: &str,
+    options: &RemoveOptions,
+    summary: &mut RemoveSummary,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    if !info.is_file() && !info.is_folder() {
+        refuse(summary, path, "not a file or folder");
+        return Ok(false);
+    }
+    if !info.can_delete {
+        refuse(summary, path, "cannot be deleted");
+        return Ok(false);
+    }
+    if info.is_folder() {
+        // 删除前先读出所有的子对象
+        let mut children = Vec::new();
+        let mut iter = device.get_object_iterator(&info.content_object)?;
+        while let Some(object) = iter.next()? {
+            children.push(device.get_object_info(object)?);
+        }
+        let mut all_removed = true;
+        for child in &children {
+            let child_path = format!("{}\\{}", path.trim_end_matches('\\'), child.name);
+            all_removed &= remove_device_object(device, child, &child_path, options, summary)?;
+        }
+        if !all_removed {
+            refuse(summary, path, "not empty");
+            return Ok(false);
+        }
+    }
+    if !options.dry_run {
+        if let Err(err) = device.delete(&info.content_object) {
+            refuse(summary, path, &err.to_string());
+            return Ok(false);
+        }
+    }
+    report_remove(summary, path);
+    Ok(true)
+}
+
+fn remove_local_path(path: &Path, options: &RemoveOptions, summary: &mut RemoveSummary) -> Result<bool, Box<dyn std::error::Error>> {
+    let display_path = path.display().to_string();
+    // 不进入符号链接指向的文件夹
+    let is_dir = path.symlink_metadata()?.is_dir();
+    if is_dir {
+        let mut all_removed = true;
+        for entry in std::fs::read_dir(path)? {
+            all_removed &= remove_local_path(&entry?.path(), options, summary)?;
+        }
+        if !all_removed {
+            refuse(summary, &display_path, "not empty");
+            return Ok(false);
+        }
+    }
+    if !options.dry_run {
+        let result = if is_dir { std::fs::remove_dir(path) } else { std::fs::remove_file(path) };
+        if let Err(err) = result {
+            refuse(summary, &display_path, &err.to_string());
+            return Ok(false);
+        }
+    }
+    report_remove(summary, &display_path);
+    Ok(true)
+}
+
+// 询问是否递归删除文件夹，没有输入时不删除
+fn confirm(input: &mut impl BufRead, path: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    loop {
+        eprint!("remove \"{}\" and all its contents? [y/n] ", path);
+        stderr().flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            eprintln!();
+            return Ok(false);
+        }
+        match line.trim().to_lowercase().as_str() {
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => continue,
+        }
+    }
+}
+
+fn report_remove(summary: &mut RemoveSummary, path: &str) {
+    println!("remove \"{}\"", path);
+    summary.removed.push(path.to_string());
+}
+
+fn refuse(summary: &mut RemoveSummary, path: &str, reason: &str) {
+    eprintln!("cannot remove \"{}\" ({})", path, reason);
+    summary.refused.push((path.to_string(), reason.to_string()));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    fn create_backend() -> MemoryBackend {
+        let backend = MemoryBackend::with_storage("Phone", "Internal");
+        backend.add_file("Phone:Internal:/DCIM/a.jpg", b"a").unwrap();
+        backend.add_file("Phone:Internal:/DCIM/b.png", b"b").unwrap();
+        backend.add_file("Phone:Internal:/DCIM/sub/c.jpg", b"c").unwrap();
+        backend
+    }
+
+    fn remove_paths(backend: &MemoryBackend, paths: &[&str], options: &RemoveOptions, input: &str) -> RemoveSummary {
+        remove_with_input(backend, paths, options, &mut input.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_remove_device_files() {
+        let backend = create_backend();
+        let summary = remove_paths(&backend, &["Phone:Internal:/DCIM/*.jpg", "Phone:Internal:/DCIM/sub"], &RemoveOptions::default(), "");
+        assert_eq!(summary.removed, vec!["Phone:Internal:\\DCIM\\a.jpg"]);
+        assert_eq!(summary.refused, vec![("Phone:Internal:\\DCIM\\sub".to_string(), "is a folder, use -r".to_string())]);
+        assert_eq!(backend.list_names("Phone:Internal:/DCIM"), Some(vec!["b.png".to_string(), "sub".to_string()]));
+
+        // 有路径不存在时不删除任何对象
+        let err = remove_with_input(&backend, &["Phone:Internal:/DCIM/b.png", "Phone:Internal:/none"], &RemoveOptions::default(), &mut "".as_bytes()).err().unwrap();
+        assert_eq!(crate::error::exit_code(err.as_ref()), MtpError::PathNotFound(String::new()).exit_code());
+        assert!(backend.get_object("Phone:Internal:/DCIM/b.png").is_some());
+    }
+
+    #[test]
+    fn test_remove_device_folder_recursively() {
+        let backend = create_backend();
+        backend.update_object("Phone:Internal:/DCIM/sub/c.jpg", |o| o.can_delete = false).unwrap();
+        let options = RemoveOptions { recursive: true, ..Default::default() };
+
+        let summary = remove_paths(&backend, &["Phone:Internal:/DCIM"], &options, "n\n");
+        assert_eq!(summary.refused, vec![("Phone:Internal:\\DCIM".to_string(), "not confirmed".to_string())]);
+
+        let summary = remove_paths(&backend, &["Phone:Internal:/DCIM"], &RemoveOptions { dry_run: true, ..options.clone() }, "");
+        assert_eq!(summary.removed.len(), 2);
+        assert_eq!(backend.list_names("Phone:Internal:/DCIM").map(|names| names.len()), Some(3));
+
+        // 不能删除的文件和它上层的文件夹保留
+        let summary = remove_paths(&backend, &["Phone:Internal:/DCIM"], &options, "x\ny\n");
+        assert_eq!(
+            summary.refused.iter().map(|(path, _)| path.as_str()).collect::<Vec<_>>(),
+            vec!["Phone:Internal:\\DCIM\\sub\\c.jpg", "Phone:Internal:\\DCIM\\sub", "Phone:Internal:\\DCIM"]
+        );
+        assert_eq!(backend.list_names("Phone:Internal:/DCIM"), Some(vec!["sub".to_string()]));
+        assert!(backend.get_object("Phone:Internal:/DCIM/sub/c.jpg").is_some());
+    }
+
+    #[test]
+    fn test_remove_local() -> Result<(), Box<dyn std::error::Error>> {
+        let backend = create_backend();
+        let tempdir = tempfile::tempdir()?;
+        let folder = tempdir.path().join("folder");
+        std::fs::create_dir_all(folder.join("sub"))?;
+        std::fs::write(folder.join("a.tmp"), b"a")?;
+        std::fs::write(folder.join("sub").join("b.txt"), b"b")?;
+
+        let pattern = format!("{}/*.tmp", folder.display());
+        remove_paths(&backend, &[&pattern], &RemoveOptions::default(), "");
+        assert!(!folder.join("a.tmp").exists());
+
+        let options = RemoveOptions { recursive: true, yes: true, ..Default::default() };
+        let summary = remove_paths(&backend, &[folder.to_str().unwrap()], &options, "");
+        assert_eq!(summary.removed.len(), 3);
+        assert!(!folder.exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+use crate::backend::{ContentObjectInfo, DeviceInfo, DeviceOperate, PortableDeviceBackend};
+use crate::copy_operate::{has_wildcard, CopyOptions};
+use crate::error::MtpError;
+use crate::find::{find_file_or_folder, find_file_or_folder_in_device, FoundObject};
+use crate::list::list_devices;
+use crate::path::{get_path_type, DeviceStoragePath, PathType, SEPARATORS, WILDCARD_CHARACTERS};
+
+// 设备上的文件和文件夹的重命名和移动
+// 同一个设备上使用设备的移动操作，设备不支持或者移动到其他设备时复制后删除源
+
+/// Renames the file or folder at the device path `path` to `new_name`.
+pub fn rename(backend: &dyn PortableDeviceBackend, path: &str, new_name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    log::trace!("command_rename path={} new_name={}", path, new_name);
+    check_name(new_name)?;
+    let storage_path = parse_device_path(path)?;
+    let (device_info, device, info) = find_source(backend, &storage_path)?;
+    let new_path = child_path(&storage_path.parent().unwrap(), new_name);
+    check_not_exists(device.as_ref(), &device_info, &new_path, &info)?;
+    device.rename(&info.content_object, new_name)?;
+    println!("rename \"{}\" to \"{}\"", storage_path.full_path(), new_path.full_path());
+    Ok(())
+}
+
+/// Moves the file or folder `src` into the existing folder `dest`, or to the path `dest`.
+///
+/// Objects are copied and deleted when the device cannot move them or `dest` is on another device.
+pub fn move_object(backend: &dyn PortableDeviceBackend, src: &str, dest: &str) -> Result<(), Box<dyn std::error::Error>> {
+    log::trace!("command_mv src={} dest={}", src, dest);
+    let src_path = parse_device_path(src)?;
+    let dest_path = parse_device_path(dest)?;
+    let (device_info, device, info) = find_source(backend, &src_path)?;
+
+    // 其他设备上的路径
+    let same_device = matches!(list_devices(backend, Some(&dest_path.device_name))?.as_slice(), [dest_device] if dest_device.id == device_info.id);
+    if !same_device {
+        drop(device);
+        return move_by_copy(backend, src, dest);
+    }
+
+    // 目标是已有的文件夹时移动到文件夹下，否则移动到上层文件夹并改名
+    let (parent_path, name) = match find_file_or_folder_in_device(device.as_ref(), &device_info, &dest_path)? {
+        Some(dest_info) if dest_info.is_folder() || dest_info.is_storage() => (dest_path, info.name.clone()),
+        Some(dest_info) if dest_info.content_object != info.content_object => {
+            return Err(format!("already exists: {}", dest_path.full_path()).into());
+        }
+        _ => {
+            let name = dest_path.file_name().unwrap().to_string();
+            (dest_path.parent().unwrap(), name)
+        }
+    };
+    check_name(&name)?;
+    let parent = match find_file_or_folder_in_device(device.as_ref(), &device_info, &parent_path)? {
+        Some(parent) if parent.is_folder() || parent.is_storage() => parent,
+        Some(_) => return Err(format!("not a folder: {}", parent_path.full_path()).into()),
+        None => return Err(MtpError::PathNotFound(parent_path.full_path()).into()),
+    };
+    check_not_inside(device.as_ref(), &device_info, &parent_path, &parent, &info)?;
+    let new_path = child_path(&parent_path, &name);
+    check_not_exists(device.as_ref(), &device_info, &new_path, &info)?;
+
+    let src_parent_path = src_path.parent().unwrap();
+    let src_parent = find_file_or_folder_in_device(device.as_ref(), &device_info, &src_parent_path)?;
+    if src_parent.is_some_and(|src_parent| src_parent.content_object == parent.content_object) {
+        // 同一个文件夹中只需要改名
+        if name != info.name {
+            device.rename(&info.content_object, &name)?;
+        }
+    } else {
+        // 目标文件夹中已有和源同名的对象时不能先移动再改名
+        let moved_path = child_path(&parent_path, &info.name);
+        let can_move = name == info.name || find_file_or_folder_in_device(device.as_ref(), &device_info, &moved_path)?.is_none();
+        if !can_move || !device.move_object(&info.content_object, &parent.content_object)? {
+            log::info!("cannot move \"{}\" on the device, copying it", src_path.full_path());
+            drop(device);
+            return move_by_copy(backend, src, &new_path.full_path());
+        }
+        if name != info.name {
+            // 移动后对象 id 可能改变，重新查找
+            let moved = find_file_or_folder_in_device(device.as_ref(), &device_info, &moved_path)?
+                .ok_or_else(|| MtpError::PathNotFound(moved_path.full_path()))?;
+            device.rename(&moved.content_object, &name)?;
+        }
+    }
+    println!("move \"{}\" to \"{}\"", src_path.full_path(), new_path.full_path());
+    Ok(())
+}
+
+// 复制所有文件，包括隐藏和系统文件，源没有完全删除时返回错误
+fn move_by_copy(backend: &dyn PortableDeviceBackend, src: &str, dest: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let options = CopyOptions {
+        recursive: true,
+        filters: Vec::new(),
+        include_hidden: true,
+        include_system: true,
+        ..Default::default()
+    };
+    crate::copy::move_many(backend, &[src], dest, &options)?;
+    let src_path = DeviceStoragePath::from(src)?;
+    if find_file_or_folder(backend, &src_path)?.is_some() {
+        return Err(format!("the source was copied but could not be deleted completely: {}", src_path.full_path()).into());
+    }
+    Ok(())
+}
+
+// 只支持不含通配符的设备路径
+fn parse_device_path(path: &str) -> Result<DeviceStoragePath, Box<dyn std::error::Error>> {
+    if get_path_type(path) != PathType::DeviceStorage {
+        return Err(format!("not a device path: {}", path).into());
+    }
+    if has_wildcard(path, PathType::DeviceStorage)? {
+        return Err(format!("wildcards are not supported: {}", path).into());
+    }
+    DeviceStoragePath::from(path)
+}
+
+fn find_source(
+    backend: &dyn PortableDeviceBackend,
+    storage_path: &DeviceStoragePath,
+) -> Result<FoundObject, Box<dyn std::error::Error>> {
+    match find_file_or_folder(backend, storage_path)? {
+        Some((_, _, info)) if !info.is_file() && !info.is_folder() => {
+            Err(format!("not a file or folder: {}", storage_path.full_path()).into())
+        }
+        Some(found) => Ok(found),
+        None => Err(MtpError::PathNotFound(storage_path.full_path()).into()),
+    }
+}
+
+fn check_name(name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(SEPARATORS) || name.contains(':') || name.contains(WILDCARD_CHARACTERS) {
+        return Err(format!("invalid name: {:?}", name).into());
+    }
+    Ok(())
+}
+
+// 路径上已有其他对象时返回错误，只有大小写不同的同一个对象不是错误
+fn check_not_exists(
+    device: &dyn DeviceOperate,
+    device_info: &DeviceInfo,
+    path: &DeviceStoragePath,
+    info: &ContentObjectInfo,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match find_file_or_folder_in_device(device, device_info, path)? {
+        Some(found) if found.content_object != info.content_object => Err(format!("already exists: {}", path.full_path()).into()),
+        _ => Ok(()),
+    }
+}
+
+// 文件夹不能移动到自己或自己的子文件夹下，设备不支持移动时复制会不断地创建嵌套的文件夹
+fn check_not_inside(
+    device: &dyn DeviceOperate,
+    device_info: &DeviceInfo,
+    parent_path: &DeviceStoragePath,
+    parent: &ContentObjectInfo,
+    info: &ContentObjectInfo,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !info.is_folder() {
+        return Ok(());
+    }
+    let mut inside = parent.content_object == info.content_object;
+    let mut ancestor_path = parent_path.parent();
+    while let (false, Some(path)) = (inside, ancestor_path) {
+        inside = find_file_or_folder_in_device(device, device_info, &path)?.is_some_and(|found| found.content_object == info.content_object);
+        ancestor_path = path.parent();
+    }
+    if inside {
+        return Err(format!("cannot move a folder into itself: {}", parent_path.full_path()).into());
+    }
+    Ok(())
+}
+
+fn child_path(parent: &DeviceStoragePath, name: &str) -> DeviceStoragePath {
+    DeviceStoragePath {
+        device_name: parent.device_name.clone(),
+        storage_name: parent.storage_name.clone(),
+        path: format!("{}\\{}", parent.path.trim_end_matches('\\'), name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    fn create_backend() -> MemoryBackend {
+        let backend = MemoryBackend::with_storage("Phone", "Internal");
+        backend.add_storage("Phone", "SD card").unwrap();
+        backend.add_file("Phone:Internal:/DCIM/a.jpg", b"a").unwrap();
+        backend.add_file("Phone:Internal:/DCIM/b.jpg", b"b").unwrap();
+        backend.add_folder("Phone:Internal:/Pictures").unwrap();
+        backend
+    }
+
+    #[test]
+    fn test_rename() {
+        let backend = create_backend();
+        let parent_id = backend.get_object("Phone:Internal:/DCIM/a.jpg").unwrap().parent_id;
+        rename(&backend, "Phone:Internal:/DCIM/a.jpg", "c.jpg").unwrap();
+        assert_eq!(backend.list_names("Phone:Internal:/DCIM"), Some(vec!["b.jpg".to_string(), "c.jpg".to_string()]));
+        assert_eq!(backend.get_object("Phone:Internal:/DCIM/c.jpg").unwrap().parent_id, parent_id);
+
+        assert_eq!(rename(&backend, "Phone:Internal:/DCIM/c.jpg", "b.jpg").err().unwrap().to_string(), "already exists: Phone:Internal:\\DCIM\\b.jpg");
+        assert!(rename(&backend, "Phone:Internal:/DCIM/c.jpg", "x/y.jpg").is_err());
+        assert!(rename(&backend, "Phone:Internal:/", "Storage").is_err());
+        let err = rename(&backend, "Phone:Internal:/DCIM/none.jpg", "d.jpg").err().unwrap();
+        assert_eq!(crate::error::exit_code(err.as_ref()), MtpError::PathNotFound(String::new()).exit_code());
+    }
+
+    #[test]
+    fn test_move_object() {
+        let backend = create_backend();
+        // 移动到已有的文件夹下
+        move_object(&backend, "Phone:Internal:/DCIM/a.jpg", "Phone:Internal:/Pictures").unwrap();
+        assert_eq!(backend.list_names("Phone:Internal:/Pictures"), Some(vec!["a.jpg".to_string()]));
+        // 移动并改名
+        move_object(&backend, "Phone:Internal:/DCIM/b.jpg", "Phone:SD card:/b2.jpg").unwrap();
+        assert_eq!(backend.list_names("Phone:SD card:/"), Some(vec!["b2.jpg".to_string()]));
+        assert_eq!(backend.get_object("Phone:SD card:/b2.jpg").unwrap().data, b"b");
+        // 同一个文件夹中改名
+        move_object(&backend, "Phone:Internal:/Pictures/a.jpg", "Phone:Internal:/Pictures/c.jpg").unwrap();
+        assert_eq!(backend.list_names("Phone:Internal:/Pictures"), Some(vec!["c.jpg".to_string()]));
+
+        assert!(move_object(&backend, "Phone:Internal:/Pictures", "Phone:Internal:/Pictures/sub").is_err());
+        assert!(move_object(&backend, "Phone:Internal:/Pictures/c.jpg", "Phone:SD card:/b2.jpg").is_err());
+        assert!(move_object(&backend, "Phone:Internal:/Pictures/c.jpg", "Phone:Internal:/none/c.jpg").is_err());
+        assert!(backend.get_object("Phone:Internal:/Pictures/c.jpg").is_some());
+    }
+
+    #[test]
+    fn test_move_folder_into_itself() {
+        let backend = create_backend();
+        backend.add_folder("Phone:Internal:/Pictures/sub").unwrap();
+        // 设备不支持移动时也不能复制到自己的子文件夹下
+        backend.disable_move();
+        let err = move_object(&backend, "Phone:Internal:/Pictures", "Phone:Internal:/Pictures/sub").err().unwrap();
+        assert_eq!(err.to_string(), "cannot move a folder into itself: Phone:Internal:\\Pictures\\sub");
+        assert!(move_object(&backend, "Phone:Internal:/Pictures", "Phone:Internal:/Pictures/sub/new").is_err());
+        assert!(move_object(&backend, "Phone:Internal:/Pictures", "Phone:Internal:/Pictures").is_err());
+        assert_eq!(backend.list_names("Phone:Internal:/Pictures"), Some(vec!["sub".to_string()]));
+        assert_eq!(backend.list_names("Phone:Internal:/Pictures/sub"), Some(Vec::new()));
+
+        // 复制到其他文件夹
+        move_object(&backend, "Phone:Internal:/Pictures", "Phone:Internal:/DCIM").unwrap();
+        assert_eq!(backend.list_names("Phone:Internal:/DCIM/Pictures"), Some(vec!["sub".to_string()]));
+        assert!(backend.get_object("Phone:Internal:/Pictures").is_none());
+    }
+
+    #[test]
+    fn test_move_object_to_another_device() {
+        let backend = create_backend();
+        backend.add_device("Tablet");
+        backend.add_storage("Tablet", "Internal").unwrap();
+        move_object(&backend, "Phone:Internal:/DCIM", "Tablet:Internal:/Photos").unwrap();
+        assert_eq!(backend.list_names("Tablet:Internal:/Photos"), Some(vec!["a.jpg".to_string(), "b.jpg".to_string()]));
+        assert!(backend.get_object("Phone:Internal:/DCIM").is_none());
+    }
+
+    #[test]
+    fn test_move_object_by_copy_includes_hidden_files() {
+        let backend = create_backend();
+        backend.add_device("Tablet");
+        backend.add_storage("Tablet", "Internal").unwrap();
+        backend.add_file("Phone:Internal:/DCIM/.thumbnails/t.jpg", b"t").unwrap();
+        backend.update_object("Phone:Internal:/DCIM/.thumbnails", |o| o.is_hidden = true).unwrap();
+        backend.update_object("Phone:Internal:/DCIM/b.jpg", |o| o.is_system = true).unwrap();
+        move_object(&backend, "Phone:Internal:/DCIM", "Tablet:Internal:/Photos").unwrap();
+        assert_eq!(
+            backend.list_names("Tablet:Internal:/Photos"),
+            Some(vec![".thumbnails".to_string(), "a.jpg".to_string(), "b.jpg".to_string()])
+        );
+        assert_eq!(backend.get_object("Tablet:Internal:/Photos/.thumbnails/t.jpg").unwrap().data, b"t");
+        assert!(backend.get_object("Phone:Internal:/DCIM").is_none());
+
+        // 源中不能删除的文件留下时返回错误
+        backend.update_object("Phone:Internal:/Pictures", |o| o.can_delete = false).unwrap();
+        assert!(move_object(&backend, "Phone:Internal:/Pictures", "Tablet:Internal:/Pictures").is_err());
+    }
+}
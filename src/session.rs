@@ -0,0 +1,249 @@
+use std::error::Error;
+use std::path::Path;
+use crate::backend::PortableDeviceBackend;
+use crate::common::timestamp::TimeZone;
+use crate::copy_operate::CopyOptions;
+use crate::error::MtpError;
+use crate::find::find_file_or_folder;
+use crate::list::{list_files, list_storages, Entry};
+use crate::mtp;
+use crate::path::DeviceStoragePath;
+use crate::remove::{RemoveOptions, RemoveSummary};
+use crate::stat::ObjectProperties;
+
+/// A connection to a device backend.
+///
+/// Paths are written as `"<device>:<storage>:<path>"`, device and storage names may contain wildcards.
+pub struct Session {
+    backend: Box<dyn PortableDeviceBackend>,
+    time_zone: TimeZone,
+    // COM 必须在后端释放之后才能反初始化，所以放在 backend 后面
+    #[cfg(windows)]
+    _com: Option<ComGuard>,
+}
+
+impl Session {
+    /// Opens a backend, `spec` is "wpd" (default), "emulated:<manifest>" or "ptpip://<host>[:<port>]".
+    ///
+    /// Device dates without an offset are read and written in `time_zone`.
+    pub fn open(spec: Option<&str>, time_zone: TimeZone) -> Result<Session, Box<dyn Error>> {
+        let session = match spec.unwrap_or("wpd") {
+            "wpd" => Session::open_wpd(time_zone)?,
+            spec if spec.starts_with(mtp::ptpip::URL_SCHEME) => {
+                let address = mtp::ptpip::parse_url(spec)?;
+                let mut backend = mtp::manager::MtpBackend::new();
+                backend.add_connector(Box::new(move || {
+                    Ok(Box::new(mtp::ptpip::PtpIpConnection::connect(&address, "mtp_util")?))
+                }));
+                backend.set_time_zone(time_zone);
+                Session::new(Box::new(backend))
+            }
+            spec => match spec.strip_prefix("emulated:") {
+                Some(manifest) if !manifest.is_empty() => {
+                    let mut backend = crate::backend::emulated::EmulatedBackend::from_manifest(Path::new(manifest))?;
+                    backend.set_time_zone(time_zone);
+                    Session::new(Box::new(backend))
+                }
+                _ => return Err(format!("unknown backend: {}", spec).into()),
+            },
+        };
+        Ok(Session { time_zone, ..session })
+    }
+
+    /// Creates a session over an existing backend.
+    pub fn new(backend: Box<dyn PortableDeviceBackend>) -> Session {
+        Session {
+            backend,
+            time_zone: TimeZone::UTC,
+            #[cfg(windows)]
+            _com: None,
+        }
+    }
+
+    #[cfg(windows)]
+    fn open_wpd(time_zone: TimeZone) -> Result<Session, Box<dyn Error>> {
+        // Manager 需要在 COM 初始化之后创建
+        let com = ComGuard::new()?;
+        let mut manager = crate::wpd::manager::Manager::get_portable_device_manager()?;
+        manager.set_time_zone(time_zone);
+        Ok(Session {
+            backend: Box::new(manager),
+            time_zone,
+            _com: Some(com),
+        })
+    }
+
+    #[cfg(not(windows))]
+    fn open_wpd(#[allow(unused_variables)] time_zone: TimeZone) -> Result<Session, Box<dyn Error>> {
+        Err("the WPD backend is only available on Windows.".into())
+    }
+
+    pub fn backend(&self) -> &dyn PortableDeviceBackend {
+        self.backend.as_ref()
+    }
+
+    /// The time zone of the device dates, also used to show the times.
+    pub fn time_zone(&self) -> TimeZone {
+        self.time_zone
+    }
+
+    /// Lists the storages of all devices.
+    pub fn list_storages(&self) -> Result<Vec<Entry>, Box<dyn Error>> {
+        list_storages(self.backend())
+    }
+
+    /// Lists the files and folders matching `path`.
+    pub fn list(&self, path: &str, recursive: bool) -> Result<Vec<Entry>, Box<dyn Error>> {
+        list_files(self.backend(), path, recursive)
+    }
+
+    /// Returns the storage, file or folder at `path`.
+    pub fn stat(&self, path: &str) -> Result<Entry, Box<dyn Error>> {
+        let storage_path = DeviceStoragePath::from(path)?;
+        match find_file_or_folder(self.backend(), &storage_path)? {
+            Some((device_info, _, info)) => Ok(Entry {
+                device: device_info.name,
+                storage: storage_path.storage_name.clone(),
+                path: storage_path.full_path(),
+                info,
+            }),
+            None => Err(MtpError::PathNotFound(path.to_string()).into()),
+        }
+    }
+
+    /// Returns every known property of a device object, storage, file or folder, or of a local file.
+    ///
+    /// `"<device>::"` is the device object.
+    pub fn properties(&self, path: &str) -> Result<ObjectProperties, Box<dyn Error>> {
+        crate::stat::stat(self.backend(), path)
+    }
+
+    /// Copies between a device and the local file system, or between devices.
+    pub fn copy(&self, src: &str, dest: &str, options: &CopyOptions) -> Result<(), Box<dyn Error>> {
+        crate::copy::copy(self.backend(), src, dest, options)
+    }
+
+    /// Copies several sources into the existing folder `dest`.
+    pub fn copy_many(&self, srcs: &[&str], dest: &str, options: &CopyOptions) -> Result<(), Box<dyn Error>> {
+        crate::copy::copy_many(self.backend(), srcs, dest, options)
+    }
+
+    /// Copies the sources into `dest`, then deletes the sources whose copies were verified.
+    pub fn move_many(&self, srcs: &[&str], dest: &str, options: &CopyOptions) -> Result<(), Box<dyn Error>> {
+        crate::copy::move_many(self.backend(), srcs, dest, options)
+    }
+
+    /// Removes the files and folders matching `paths` on devices or in the local file system.
+    pub fn remove(&self, paths: &[&str], options: &RemoveOptions) -> Result<RemoveSummary, Box<dyn Error>> {
+        crate::remove::remove(self.backend(), paths, options)
+    }
+
+    /// Creates folders, with `parents` the missing parent folders too, returns the created folders.
+    pub fn make_folders(&self, paths: &[&str], parents: bool) -> Result<Vec<String>, Box<dyn Error>> {
+        crate::mkdir::make_folders(self.backend(), paths, parents)
+    }
+
+    /// Renames a file or folder on a device.
+    pub fn rename(&self, path: &str, new_name: &str) -> Result<(), Box<dyn Error>> {
+        crate::rename::rename(self.backend(), path, new_name)
+    }
+
+    /// Moves a file or folder into the existing folder `dest`, or to the path `dest`.
+    pub fn move_object(&self, src: &str, dest: &str) -> Result<(), Box<dyn Error>> {
+        crate::rename::move_object(self.backend(), src, dest)
+    }
+
+    /// Deletes a file, or a folder with its contents.
+    pub fn delete(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        let storage_path = DeviceStoragePath::from(path)?;
+        match find_file_or_folder(self.backend(), &storage_path)? {
+            Some((_, device, info)) if info.is_file() || info.is_folder() => device.delete(&info.content_object),
+            Some(_) => Err(format!("cannot delete a storage: {}", path).into()),
+            None => Err(MtpError::PathNotFound(path.to_string()).into()),
+        }
+    }
+}
+
+// 初始化 COM，释放时反初始化
+#[cfg(windows)]
+struct ComGuard;
+
+#[cfg(windows)]
+impl ComGuard {
+    fn new() -> Result<ComGuard, Box<dyn Error>> {
+        use windows::Win32::System::Com::{COINIT_MULTITHREADED, CoInitializeEx};
+        unsafe { CoInitializeEx(Some(std::ptr::null_mut()), COINIT_MULTITHREADED).ok()?; }
+        Ok(ComGuard)
+    }
+}
+
+#[cfg(windows)]
+impl Drop for ComGuard {
+    fn drop(&mut self) {
+        unsafe { windows::Win32::System::Com::CoUninitialize(); }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    fn create_session() -> (MemoryBackend, Session) {
+        let backend = MemoryBackend::with_storage("Phone", "Internal");
+        backend.add_file("Phone:Internal:/DCIM/a.jpg", b"aaa").unwrap();
+        backend.add_file("Phone:Internal:/DCIM/sub/b.jpg", b"b").unwrap();
+        (backend.clone(), Session::new(Box::new(backend)))
+    }
+
+    #[test]
+    fn test_open() {
+        let utc = TimeZone::UTC;
+        assert!(Session::open(Some("unknown"), utc).is_err());
+        assert!(Session::open(Some("emulated:"), utc).is_err());
+        assert!(Session::open(Some("emulated:/nonexistent/manifest.json"), utc).is_err());
+        assert!(Session::open(Some("ptpip://"), utc).is_err());
+        assert!(Session::open(Some("ptpip://127.0.0.1:1"), utc).is_ok());
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let manifest_path = tempdir.path().join("manifest.json");
+        std::fs::write(&manifest_path, r#"{ "devices": [ { "name": "Phone" } ] }"#).unwrap();
+        let time_zone = "+08:00".parse().unwrap();
+        let session = Session::open(Some(&format!("emulated:{}", manifest_path.display())), time_zone).unwrap();
+        assert_eq!(session.backend().list_devices().unwrap()[0].name, "Phone");
+        assert_eq!(session.time_zone(), time_zone);
+    }
+
+    #[test]
+    fn test_list_and_stat() -> Result<(), Box<dyn Error>> {
+        let (_, session) = create_session();
+        let storages = session.list_storages()?;
+        assert_eq!(storages.len(), 1);
+        assert_eq!(storages[0].path, "Phone:Internal:");
+
+        let names: Vec<String> = session.list("Phone:Internal:/DCIM", true)?.into_iter().map(|e| e.info.name).collect();
+        assert_eq!(names, vec!["DCIM", "a.jpg", "sub", "b.jpg"]);
+
+        let entry = session.stat("Phone:Internal:/DCIM/a.jpg")?;
+        assert_eq!(entry.path, "Phone:Internal:\\DCIM\\a.jpg");
+        assert!(entry.info.is_file());
+        assert_eq!(entry.info.data_size, 3);
+        let err = session.stat("Phone:Internal:/DCIM/none.jpg").err().unwrap();
+        assert!(matches!(err.downcast_ref::<MtpError>(), Some(MtpError::PathNotFound(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_and_delete() -> Result<(), Box<dyn Error>> {
+        let (backend, session) = create_session();
+        let tempdir = tempfile::tempdir()?;
+        session.copy("Phone:Internal:/DCIM", tempdir.path().to_str().unwrap(), &CopyOptions { recursive: true, ..Default::default() })?;
+        assert_eq!(std::fs::read(tempdir.path().join("DCIM").join("sub").join("b.jpg"))?, b"b");
+
+        session.delete("Phone:Internal:/DCIM/sub")?;
+        assert_eq!(backend.list_names("Phone:Internal:/DCIM"), Some(vec!["a.jpg".to_string()]));
+        assert!(session.delete("Phone:Internal:/DCIM/sub").is_err());
+        assert!(session.delete("Phone:Internal:").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+use std::path::Path;
+use serde::Serialize;
+use crate::backend::{ContentObjectInfo, ContentType, FunctionalCategory, PortableDeviceBackend};
+use crate::common::timestamp::{TimeZone, Timestamp};
+use crate::copy_operate::file_info::FileInfo;
+use crate::error::MtpError;
+use crate::find::{find_device, find_file_or_folder};
+use crate::list::get_device_object;
+use crate::mtp::codes::format_code_name;
+use crate::path::{get_path_type, DeviceStoragePath, PathType};
+
+// 显示设备对象、存储、文件夹、文件或本地文件的所有属性
+// "<设备名>::" 表示设备对象，本地文件没有对象 id 和格式
+
+/// Every known property of a device object, storage, file or folder, or of a local file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectProperties {
+    /// The full path, "<device>::" for a device object
+    pub path: String,
+    pub name: String,
+    /// Object id on the device (None for local files)
+    pub object_id: Option<String>,
+    pub parent_id: Option<String>,
+    pub persistent_id: Option<String>,
+    /// MTP object format code
+    pub format: Option<u16>,
+    pub content_type: ContentType,
+    pub functional_category: FunctionalCategory,
+    pub size: u64,
+    pub is_hidden: bool,
+    pub is_system: bool,
+    pub can_delete: bool,
+    pub time_created: Option<Timestamp>,
+    pub time_modified: Option<Timestamp>,
+    pub time_authored: Option<Timestamp>,
+}
+
+impl ObjectProperties {
+    pub fn from_content_object_info(path: &str, info: ContentObjectInfo) -> ObjectProperties {
+        ObjectProperties {
+            path: path.to_string(),
+            name: info.name,
+            object_id: Some(info.content_object.id),
+            parent_id: info.parent_id,
+            persistent_id: info.persistent_id,
+            format: info.format,
+            content_type: info.content_type,
+            functional_category: info.functional_object_category,
+            size: info.data_size,
+            is_hidden: info.is_hidden,
+            is_system: info.is_system,
+            can_delete: info.can_delete,
+            time_created: info.time_created,
+            time_modified: info.time_modified,
+            time_authored: info.time_authored,
+        }
+    }
+
+    pub fn from_file_info(path: &str, info: FileInfo) -> ObjectProperties {
+        ObjectProperties {
+            path: path.to_string(),
+            name: info.name,
+            object_id: None,
+            parent_id: None,
+            persistent_id: None,
+            format: None,
+            content_type: if info.is_folder { ContentType::Folder } else { ContentType::GenericFile },
+            functional_category: FunctionalCategory::None,
+            size: info.data_size,
+            is_hidden: info.is_hidden,
+            is_system: info.is_system,
+            can_delete: info.can_delete,
+            time_created: info.time_created,
+            time_modified: info.time_modified,
+            time_authored: None,
+        }
+    }
+
+    /// The properties as "label: value" lines, dates in `time_zone`.
+    pub fn format(&self, time_zone: TimeZone) -> String {
+        let text = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+        let date = |time: &Option<Timestamp>| time.map_or_else(|| "-".to_string(), |time| time.format(time_zone));
+        let flag = |value: bool| if value { "yes" } else { "no" };
+        let format = self.format.map(|code| format!("0x{:04X} ({})", code, format_code_name(code)));
+        let lines = [
+            ("Path", self.path.clone()),
+            ("Name", self.name.clone()),
+            ("Object ID", text(&self.object_id)),
+            ("Parent ID", text(&self.parent_id)),
+            ("Persistent ID", text(&self.persistent_id)),
+            ("Format", text(&format)),
+            ("Content type", self.content_type.name().to_string()),
+            ("Category", self.functional_category.name().unwrap_or("-").to_string()),
+            ("Size", self.size.to_string()),
+            ("Hidden", flag(self.is_hidden).to_string()),
+            ("System", flag(self.is_system).to_string()),
+            ("Can delete", flag(self.can_delete).to_string()),
+            ("Created", date(&self.time_created)),
+            ("Modified", date(&self.time_modified)),
+            ("Authored", date(&self.time_authored)),
+        ];
+        lines.iter().map(|(label, value)| format!("{:<15}{}\n", format!("{}:", label), value)).collect()
+    }
+
+    /// The properties as a pretty-printed JSON object, dates as ISO 8601 in `time_zone`.
+    pub fn to_json(&self, time_zone: TimeZone) -> Result<String, Box<dyn std::error::Error>> {
+        let date = |time: &Option<Timestamp>| time.map(|time| time.to_iso(time_zone));
+        let json = PropertiesJson {
+            path: &self.path,
+            name: &self.name,
+            object_id: self.object_id.as_deref(),
+            parent_id: self.parent_id.as_deref(),
+            persistent_id: self.persistent_id.as_deref(),
+            format: self.format.map(|code| format!("0x{:04X}", code)),
+            format_name: self.format.map(format_code_name),
+            content_type: self.content_type.name(),
+            functional_category: self.functional_category.name(),
+            size: self.size,
+            hidden: self.is_hidden,
+            system: self.is_system,
+            can_delete: self.can_delete,
+            created: date(&self.time_created),
+            modified: date(&self.time_modified),
+            authored: date(&self.time_authored),
+        };
+        Ok(serde_json::to_string_pretty(&json)?)
+    }
+}
+
+// JSON 输出，字段按显示的顺序排列
+#[derive(Serialize)]
+struct PropertiesJson<'a> {
+    path: &'a str,
+    name: &'a str,
+    object_id: Option<&'a str>,
+    parent_id: Option<&'a str>,
+    persistent_id: Option<&'a str>,
+    format: Option<String>,
+    format_name: Option<&'static str>,
+    content_type: &'static str,
+    functional_category: Option<&'static str>,
+    size: u64,
+    hidden: bool,
+    system: bool,
+    can_delete: bool,
+    created: Option<String>,
+    modified: Option<String>,
+    authored: Option<String>,
+}
+
+/// Returns the properties of the device object, storage, file or folder at `path`, or of a local file.
+pub fn stat(backend: &dyn PortableDeviceBackend, path: &str) -> Result<ObjectProperties, Box<dyn std::error::Error>> {
+    log::trace!("command_stat path={}", path);
+    match get_path_type(path) {
+        PathType::DeviceStorage => stat_device_path(backend, path),
+        PathType::Local => stat_local_path(path),
+        PathType::Invalid => Err(format!("invalid path: {}", path).into()),
+    }
+}
+
+fn stat_device_path(backend: &dyn PortableDeviceBackend, path: &str) -> Result<ObjectProperties, Box<dyn std::error::Error>> {
+    let storage_path = DeviceStoragePath::from(path)?;
+    if storage_path.storage_name.is_empty() {
+        let (device_info, device) = find_device(backend, &storage_path.device_name)?;
+        let info = get_device_object(device.as_ref())?
+            .ok_or_else(|| format!("the device object was not found: {}", &device_info.name))?;
+        return Ok(ObjectProperties::from_content_object_info(&format!("{}::", device_info.name), info));
+    }
+    match find_file_or_folder(backend, &storage_path)? {
+        Some((_, _, info)) => Ok(ObjectProperties::from_content_object_info(&storage_path.full_path(), info)),
+        None => Err(MtpError::PathNotFound(path.to_string()).into()),
+    }
+}
+
+fn stat_local_path(path: &str) -> Result<ObjectProperties, Box<dyn std::error::Error>> {
+    let local_path = Path::new(path);
+    let metadata = match local_path.metadata() {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Err(MtpError::PathNotFound(path.to_string()).into()),
+        Err(err) => return Err(err.into()),
+    };
+    let name = local_path.file_name().and_then(|name| name.to_str()).unwrap_or(path);
+    Ok(ObjectProperties::from_file_info(path, FileInfo::from_metadata(&metadata, name)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::memory::MemoryBackend;
+
+    fn create_backend() -> MemoryBackend {
+        let backend = MemoryBackend::with_storage("Phone", "Internal");
+        backend.add_file("Phone:Internal:/DCIM/a.jpg", b"abc").unwrap();
+        backend
+            .update_object("Phone:Internal:/DCIM/a.jpg", |o| {
+                o.is_hidden = true;
+                o.time_modified = Some(Timestamp::from_unix_seconds(1627846261));
+            })
+            .unwrap();
+        backend
+    }
+
+    #[test]
+    fn test_stat_device_objects() -> Result<(), Box<dyn std::error::Error>> {
+        let backend = create_backend();
+        let file = stat(&backend, "Phone:Internal:/DCIM/a.jpg")?;
+        let folder = stat(&backend, "Phone:Internal:/DCIM")?;
+        assert_eq!(file.path, "Phone:Internal:\\DCIM\\a.jpg");
+        assert_eq!(file.content_type, ContentType::GenericFile);
+        assert_eq!(file.parent_id, folder.object_id);
+        assert_eq!(file.size, 3);
+        assert!(file.is_hidden);
+
+        let storage = stat(&backend, "Phone:Internal:")?;
+        assert_eq!(storage.functional_category, FunctionalCategory::Storage);
+        assert_eq!(folder.parent_id, storage.object_id);
+        let device = stat(&backend, "Phone::")?;
+        assert_eq!(device.path, "Phone::");
+        assert_eq!(device.functional_category, FunctionalCategory::Device);
+        assert_eq!(storage.parent_id, device.object_id);
+
+        let err = stat(&backend, "Phone:Internal:/none").err().unwrap();
+        assert_eq!(crate::error::exit_code(err.as_ref()), MtpError::PathNotFound(String::new()).exit_code());
+        Ok(())
+    }
+
+    #[test]
+    fn test_stat_local_file() -> Result<(), Box<dyn std::error::Error>> {
+        let backend = create_backend();
+        let tempdir = tempfile::tempdir()?;
+        let path = tempdir.path().join("a.txt");
+        std::fs::write(&path, b"hello")?;
+        let properties = stat(&backend, path.to_str().unwrap())?;
+        assert_eq!(properties.name, "a.txt");
+        assert_eq!(properties.size, 5);
+        assert_eq!(properties.object_id, None);
+        assert!(properties.time_modified.is_some());
+        assert!(stat(&backend, tempdir.path().join("none").to_str().unwrap()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_format_and_json() -> Result<(), Box<dyn std::error::Error>> {
+        let backend = create_backend();
+        let mut properties = stat(&backend, "Phone:Internal:/DCIM/a.jpg")?;
+        properties.format = Some(0x3801);
+        let time_zone: TimeZone = "+08:00".parse()?;
+
+        let text = properties.format(time_zone);
+        assert!(text.contains("Format:        0x3801 (EXIF/JPEG)\n"));
+        assert!(text.contains("Hidden:        yes\n"));
+        assert!(text.contains("Modified:      2021-08-02 03:31:01\n"));
+        assert!(text.contains("Authored:      -\n"));
+
+        let value: serde_json::Value = serde_json::from_str(&properties.to_json(time_zone)?)?;
+        assert_eq!(value["format_name"], "EXIF/JPEG");
+        assert_eq!(value["content_type"], "file");
+        assert_eq!(value["functional_category"], serde_json::Value::Null);
+        assert_eq!(value["modified"], "2021-08-02T03:31:01+08:00");
+        assert_eq!(value["size"], 3);
+        Ok(())
+    }
+}
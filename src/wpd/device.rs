@@ -1,91 +1,60 @@
-use std::fmt::Debug;
 use windows::core::{Error, GUID, PWSTR, PROPVARIANT as propvar, PCWSTR};
 use windows::core::imp::{PROPVARIANT};
-use windows::Win32::Devices::PortableDevices::{IEnumPortableDeviceObjectIDs, IPortableDevice, IPortableDeviceContent, IPortableDeviceKeyCollection, IPortableDeviceProperties, IPortableDevicePropVariantCollection, IPortableDeviceResources, IPortableDeviceValues, PORTABLE_DEVICE_DELETE_WITH_RECURSION, PortableDevice, PortableDeviceKeyCollection, PortableDevicePropVariantCollection, PortableDeviceValues, WPD_CONTENT_TYPE_FOLDER, WPD_CONTENT_TYPE_FUNCTIONAL_OBJECT, WPD_CONTENT_TYPE_GENERIC_FILE, WPD_FUNCTIONAL_CATEGORY_DEVICE, WPD_FUNCTIONAL_CATEGORY_STORAGE, WPD_FUNCTIONAL_OBJECT_CATEGORY, WPD_OBJECT_CAN_DELETE, WPD_OBJECT_CONTENT_TYPE, WPD_OBJECT_DATE_CREATED, WPD_OBJECT_DATE_MODIFIED, WPD_OBJECT_FORMAT, WPD_OBJECT_FORMAT_ALL, WPD_OBJECT_ISHIDDEN, WPD_OBJECT_ISSYSTEM, WPD_OBJECT_NAME, WPD_OBJECT_ORIGINAL_FILE_NAME, WPD_OBJECT_PARENT_ID, WPD_OBJECT_SIZE, WPD_RESOURCE_DEFAULT};
-use windows::Win32::Foundation::S_OK;
-use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_ALL, IStream};
-use crate::wpd::manager::DeviceInfo;
+use windows::Win32::Devices::PortableDevices::{IEnumPortableDeviceObjectIDs, IPortableDevice, IPortableDeviceContent, IPortableDeviceKeyCollection, IPortableDeviceProperties, IPortableDevicePropVariantCollection, IPortableDeviceResources, IPortableDeviceValues, PORTABLE_DEVICE_DELETE_WITH_RECURSION, PortableDevice, PortableDeviceKeyCollection, PortableDevicePropVariantCollection, PortableDeviceValues, WPD_CONTENT_TYPE_FOLDER, WPD_CONTENT_TYPE_FUNCTIONAL_OBJECT, WPD_CONTENT_TYPE_GENERIC_FILE, WPD_FUNCTIONAL_CATEGORY_DEVICE, WPD_FUNCTIONAL_CATEGORY_STORAGE, WPD_FUNCTIONAL_OBJECT_CATEGORY, WPD_OBJECT_CAN_DELETE, WPD_OBJECT_CONTENT_TYPE, WPD_OBJECT_DATE_AUTHORED, WPD_OBJECT_DATE_CREATED, WPD_OBJECT_DATE_MODIFIED, WPD_OBJECT_FORMAT, WPD_OBJECT_FORMAT_ALL, WPD_OBJECT_ISHIDDEN, WPD_OBJECT_ISSYSTEM, WPD_OBJECT_NAME, WPD_OBJECT_ORIGINAL_FILE_NAME, WPD_OBJECT_PARENT_ID, WPD_OBJECT_PERSISTENT_UNIQUE_ID, WPD_OBJECT_SIZE, WPD_RESOURCE_DEFAULT};
+use windows::Win32::Foundation::{E_NOTIMPL, ERROR_NOT_SUPPORTED, S_OK};
+use windows::Win32::System::Com::{CoCreateInstance, CoTaskMemAlloc, CLSCTX_ALL, IStream};
+use crate::backend::{ContentObject, ContentObjectInfo, ContentObjectIterator, ContentType, DeviceInfo, DeviceOperate, FileWriter, FunctionalCategory};
+use crate::common::file_reader::FileReader;
+use crate::common::timestamp::{TimeZone, Timestamp};
 use crate::wpd::resource_stream::{ResourceReader, ResourceWriter};
 
-#[derive(Debug)]
-pub struct ContentObject {
-    pub id: PWSTR,
+// 字符串转换成以0结尾的 UTF-16 缓冲区，用于传递 PCWSTR
+pub(crate) fn to_wide(s: &str) -> Vec<u16> {
+    let mut buf: Vec<u16> = s.encode_utf16().collect();
+    buf.push(0);
+    buf
 }
 
-impl ContentObject {
-    pub fn new(id: PWSTR) -> ContentObject {
-        ContentObject { id }
+// 从 WPD 返回的 PWSTR 中读取字符串
+pub(crate) fn from_pwstr(s: PWSTR) -> Result<String, Box<dyn std::error::Error>> {
+    if s.is_null() {
+        return Ok(String::new());
     }
+    Ok(unsafe { s.to_string()? })
 }
 
-impl Clone for ContentObject {
-    fn clone(&self) -> Self {
-        ContentObject {
-            id: self.id.clone(),
-        }
+fn content_type_from_guid(guid: &GUID) -> ContentType {
+    if *guid == WPD_CONTENT_TYPE_FUNCTIONAL_OBJECT {
+        ContentType::FunctionalObject
+    } else if *guid == WPD_CONTENT_TYPE_FOLDER {
+        ContentType::Folder
+    } else if *guid == WPD_CONTENT_TYPE_GENERIC_FILE {
+        ContentType::GenericFile
+    } else {
+        ContentType::Other
     }
 }
 
-// 对象详情信息
-#[derive(Debug)]
-pub struct ContentObjectInfo {
-    pub content_object: ContentObject,
-    /// Name to display
-    pub name: String,
-    /// Content type GUID
-    content_type: GUID,
-    /// 如果device获取storage，则为零。
-    functional_object_category: GUID,
-    /// Size of the resource data
-    pub data_size: u64,
-    /// Hidden flag
-    pub is_hidden: bool,
-    /// System flag
-    pub is_system: bool,
-    /// Whether the object can be deleted
-    pub can_delete: bool,
-    /// Time created (or None if not provided)
-    pub time_created: Option<String>,
-    /// Time modified (or None if not provided)
-    pub time_modified: Option<String>,
-}
-
-impl Clone for ContentObjectInfo {
-    fn clone(&self) -> Self {
-        ContentObjectInfo {
-            content_object: self.content_object.clone(),
-            name: self.name.clone(),
-            content_type: self.content_type.clone(),
-            functional_object_category: self.functional_object_category.clone(),
-            data_size: self.data_size,
-            is_hidden: self.is_hidden,
-            is_system: self.is_system,
-            can_delete: self.can_delete,
-            time_created: self.time_created.clone(),
-            time_modified: self.time_modified.clone(),
-        }
+// WPD 的对象格式 GUID 为 {XXXX0000-AE6C-4804-98BA-C57B46965FE7}，XXXX 是 MTP 的格式码
+fn format_code_from_guid(guid: &GUID) -> Option<u16> {
+    let is_mtp_format = guid.data1 & 0xFFFF == 0
+        && guid.data2 == 0xAE6C
+        && guid.data3 == 0x4804
+        && guid.data4 == [0x98, 0xBA, 0xC5, 0x7B, 0x46, 0x96, 0x5F, 0xE7];
+    if is_mtp_format {
+        Some((guid.data1 >> 16) as u16)
+    } else {
+        None
     }
 }
 
-impl ContentObjectInfo {
-    pub fn is_functional_object(&self) -> bool {
-        self.content_type == WPD_CONTENT_TYPE_FUNCTIONAL_OBJECT
-    }
-
-    pub fn is_device(&self) -> bool {
-        self.functional_object_category == WPD_FUNCTIONAL_CATEGORY_DEVICE
-    }
-
-    pub fn is_storage(&self) -> bool {
-        self.functional_object_category == WPD_FUNCTIONAL_CATEGORY_STORAGE
-    }
-
-    pub fn is_folder(&self) -> bool {
-        self.content_type == WPD_CONTENT_TYPE_FOLDER
-    }
-
-    pub fn is_file(&self) -> bool {
-        !self.is_functional_object() && !self.is_folder()
+fn functional_category_from_guid(guid: &GUID) -> FunctionalCategory {
+    if *guid == WPD_FUNCTIONAL_CATEGORY_DEVICE {
+        FunctionalCategory::Device
+    } else if *guid == WPD_FUNCTIONAL_CATEGORY_STORAGE {
+        FunctionalCategory::Storage
+    } else {
+        FunctionalCategory::Other
     }
 }
 
@@ -95,6 +64,8 @@ pub struct Device {
     properties: IPortableDeviceProperties,
     resources: IPortableDeviceResources,
     pub name: String,
+    // WPD 的日期没有时区，按这个时区解释
+    time_zone: TimeZone,
 }
 
 impl Device {
@@ -110,8 +81,9 @@ impl Device {
             CoCreateInstance(&PortableDeviceValues, None, CLSCTX_ALL)?
         };
 
+        let id_buf = to_wide(&info.id);
         unsafe {
-            device.Open(info.id.clone(), &values)?;
+            device.Open(PCWSTR(id_buf.as_ptr()), &values)?;
         }
         // 获取device的内容、属性和资源
         let content = unsafe { device.Content()? };
@@ -124,27 +96,40 @@ impl Device {
             properties,
             resources,
             name: info.name.clone(),
+            time_zone: TimeZone::UTC,
         })
     }
 
-    pub fn get_root_object(&self) -> ContentObject {
-        ContentObject::new(PWSTR(vec![0].as_mut_ptr()))
+    /// Sets the time zone of the dates on the device.
+    pub fn set_time_zone(&mut self, time_zone: TimeZone) {
+        self.time_zone = time_zone;
+    }
+}
+
+impl DeviceOperate for Device {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_root_object(&self) -> ContentObject {
+        ContentObject::new("")
     }
 
     // 获取parent对象下的所有对象的迭代器
-    pub fn get_object_iterator(&self, parent: &ContentObject) -> Result<ContentObjectIterator, Error> {
+    fn get_object_iterator(&self, parent: &ContentObject) -> Result<Box<dyn ContentObjectIterator + '_>, Box<dyn std::error::Error>> {
+        let parent_id_buf = to_wide(&parent.id);
         let enum_object_ids = unsafe {
             self.content.EnumObjects(
                     0,
-                    parent.id.clone(),
+                    PCWSTR(parent_id_buf.as_ptr()),
                     None,
                 )?
         };
 
-        Ok(ContentObjectIterator::new(enum_object_ids))
+        Ok(Box::new(EnumObjectIterator::new(enum_object_ids)))
     }
     // 获取对象信息，对象包括是device、storages、文件夹、文件。
-    pub fn get_object_info(&self, object: ContentObject) -> Result<ContentObjectInfo, Error> {
+    fn get_object_info(&self, object: ContentObject) -> Result<ContentObjectInfo, Box<dyn std::error::Error>> {
         let key_collection: IPortableDeviceKeyCollection = unsafe { CoCreateInstance(&PortableDeviceKeyCollection, None, CLSCTX_ALL)? };
 
         unsafe {
@@ -159,32 +144,52 @@ impl Device {
                 &WPD_OBJECT_CAN_DELETE,
                 &WPD_OBJECT_DATE_CREATED,
                 &WPD_OBJECT_DATE_MODIFIED,
+                &WPD_OBJECT_DATE_AUTHORED,
+                &WPD_OBJECT_PARENT_ID,
+                &WPD_OBJECT_PERSISTENT_UNIQUE_ID,
+                &WPD_OBJECT_FORMAT,
             ] {
                 key_collection.Add(key)?;
             }
         }
         // 获取对象的属性值，上述key_collection中的属性值
-        let values = unsafe { self.properties.GetValues(object.id.clone(), &key_collection)? };
+        let object_id_buf = to_wide(&object.id);
+        let values = unsafe { self.properties.GetValues(PCWSTR(object_id_buf.as_ptr()), &key_collection)? };
         // 从属性值中提取对象名称、对象类型、对象大小、是否隐藏、是否系统、是否可删除、创建时间、修改时间
         let name = unsafe { values.GetStringValue(&WPD_OBJECT_NAME)?.to_string()? };
         let content_type = unsafe { values.GetGuidValue(&WPD_OBJECT_CONTENT_TYPE)? };
+        // 有的设备不提供这些属性，根对象 ("") 的 parent 为空字符串，设备对象的 parent 是根对象
+        let (parent_id, persistent_id, format) = unsafe {
+            (
+                values.GetStringValue(&WPD_OBJECT_PARENT_ID).ok().and_then(|x| x.to_string().ok()).filter(|x| !x.is_empty() || !object.id.is_empty()),
+                values.GetStringValue(&WPD_OBJECT_PERSISTENT_UNIQUE_ID).ok().and_then(|x| x.to_string().ok()),
+                values.GetGuidValue(&WPD_OBJECT_FORMAT).ok().and_then(|x| format_code_from_guid(&x)),
+            )
+        };
 
-        let (mut object_orig_name, mut data_size, mut is_hidden, mut is_system, mut can_delete) = (None, 0, false, false, true);
-        let mut functional_object_category = GUID::zeroed();
-        let (mut time_created, mut time_modified) = (None, None);
+        let (mut data_size, mut is_hidden, mut is_system, mut can_delete) = (0, false, false, true);
+        let mut functional_object_category = FunctionalCategory::None;
+        let (mut time_created, mut time_modified, mut time_authored) = (None, None, None);
         // 根据内容类型处理属性值
         // 如果是device、storages 可以获取FUNCTIONAL_OBJECT GUID
         // 如果是文件夹、文件获取文件名称、文件大小、是否隐藏、是否系统、是否可删除、创建时间、修改时间
         unsafe {
             if content_type == WPD_CONTENT_TYPE_FUNCTIONAL_OBJECT {
-                functional_object_category = values.GetGuidValue(&WPD_FUNCTIONAL_OBJECT_CATEGORY)?;
+                functional_object_category = functional_category_from_guid(&values.GetGuidValue(&WPD_FUNCTIONAL_OBJECT_CATEGORY)?);
             } else {
-                object_orig_name = values.GetStringValue(&WPD_OBJECT_ORIGINAL_FILE_NAME)?.to_string().ok();
                 is_hidden = values.GetBoolValue(&WPD_OBJECT_ISHIDDEN).is_ok_and(|x| x.as_bool());
                 is_system = values.GetBoolValue(&WPD_OBJECT_ISSYSTEM).is_ok_and(|x| x.as_bool());
                 can_delete = values.GetBoolValue(&WPD_OBJECT_CAN_DELETE).is_ok_and(|x| x.as_bool());
-                time_created = values.GetStringValue(&WPD_OBJECT_DATE_CREATED).iter().find_map(|x| x.to_string().ok());
-                time_modified = values.GetStringValue(&WPD_OBJECT_DATE_MODIFIED).iter().find_map(|x| x.to_string().ok());
+                // 日期格式为 "YYYY/MM/DD:HH:MM:SS.mmm"
+                time_created = values.GetStringValue(&WPD_OBJECT_DATE_CREATED).iter()
+                    .find_map(|x| x.to_string().ok())
+                    .and_then(|x| Timestamp::parse_wpd(&x, self.time_zone));
+                time_modified = values.GetStringValue(&WPD_OBJECT_DATE_MODIFIED).iter()
+                    .find_map(|x| x.to_string().ok())
+                    .and_then(|x| Timestamp::parse_wpd(&x, self.time_zone));
+                time_authored = values.GetStringValue(&WPD_OBJECT_DATE_AUTHORED).iter()
+                    .find_map(|x| x.to_string().ok())
+                    .and_then(|x| Timestamp::parse_wpd(&x, self.time_zone));
 
                 if content_type != WPD_CONTENT_TYPE_FOLDER {
                     data_size = values.GetUnsignedLargeIntegerValue(&WPD_OBJECT_SIZE)?;
@@ -195,26 +200,31 @@ impl Device {
         Ok(ContentObjectInfo {
             content_object: object,
             name,
-            content_type,
+            content_type: content_type_from_guid(&content_type),
             functional_object_category,
+            parent_id,
+            persistent_id,
+            format,
             data_size,
             is_hidden,
             is_system,
             can_delete,
             time_created,
             time_modified,
+            time_authored,
         })
     }
 
 
-    pub fn get_resoure(&self, object: &ContentObject) -> Result<ResourceReader, Error> {
+    fn get_resoure(&self, object: &ContentObject) -> Result<Box<dyn FileReader + '_>, Box<dyn std::error::Error>> {
         const STGM_READ: u32 = 0;
         let mut buff_size: u32 = 0;
         let mut stream_receptor: Option<IStream> = None;
+        let object_id_buf = to_wide(&object.id);
         unsafe {
             self.resources
                 .GetStream(
-                    object.id.clone(),
+                    PCWSTR(object_id_buf.as_ptr()),
                     &WPD_RESOURCE_DEFAULT,
                     STGM_READ,
                     &mut buff_size,
@@ -222,25 +232,24 @@ impl Device {
                 )?;
         }
         let stream = stream_receptor.unwrap();
-        Ok(ResourceReader::new(stream, buff_size))
+        Ok(Box::new(ResourceReader::new(stream, buff_size)))
     }
     // 创建文件,parent为父文件夹对象，name为文件名称，size为文件大小，created为创建时间，modified为修改时间
-    // todo 创建时间和修改时间暂时不支持
-    pub fn create_file(
+    fn create_file(
         &self,
         parent: &ContentObject,
         name: &str,
         size: u64,
-        created: &Option<String>,
-        modified: &Option<String>,
-    ) -> Result<ResourceWriter, Error> {
+        created: &Option<Timestamp>,
+        modified: &Option<Timestamp>,
+    ) -> Result<Box<dyn FileWriter + '_>, Box<dyn std::error::Error>> {
         let values: IPortableDeviceValues = unsafe { CoCreateInstance(&PortableDeviceValues, None, CLSCTX_ALL)? };
-        let mut name_buf: Vec<u16>  = name.encode_utf16().collect();
-        name_buf.push(0);
+        let parent_id_buf = to_wide(&parent.id);
+        let name_buf = to_wide(name);
 
         unsafe {
             values
-                .SetStringValue(&WPD_OBJECT_PARENT_ID, parent.id.clone())?;
+                .SetStringValue(&WPD_OBJECT_PARENT_ID, PCWSTR(parent_id_buf.as_ptr()))?;
             values
                 .SetStringValue(&WPD_OBJECT_NAME, PCWSTR(name_buf.as_ptr()))?;
             values
@@ -251,25 +260,16 @@ impl Device {
                 .SetGuidValue(&WPD_OBJECT_CONTENT_TYPE, &WPD_CONTENT_TYPE_GENERIC_FILE)?;
             values
                 .SetUnsignedLargeIntegerValue(&WPD_OBJECT_SIZE, size)?;
+            // 日期属性的类型是 VT_DATE
+            for (key, time) in [(&WPD_OBJECT_DATE_CREATED, created), (&WPD_OBJECT_DATE_MODIFIED, modified)] {
+                if let Some(time) = time {
+                    let mut var: PROPVARIANT = core::mem::zeroed();
+                    var.Anonymous.Anonymous.vt = 7; // VT_DATE
+                    var.Anonymous.Anonymous.Anonymous.date = time.to_ole_automation_date(self.time_zone);
+                    values.SetValue(key, &propvar::from_raw(var))?;
+                }
+            }
         }
-        // if let Some(&created_dt) = created.as_ref() {
-        //     let dt = format_datetime(&created_dt);
-        //     let mut dt_buf = WStrBuf::from(&dt, true);
-        //     unsafe {
-        //         values
-        //             .SetStringValue(&WPD_OBJECT_DATE_CREATED, dt_buf.as_pwstr())
-        //             .ok()?;
-        //     }
-        // }
-        // if let Some(&modified_dt) = modified.as_ref() {
-        //     let dt = format_datetime(&modified_dt);
-        //     let mut dt_buf = WStrBuf::from(&dt, true);
-        //     unsafe {
-        //         values
-        //             .SetStringValue(&WPD_OBJECT_DATE_MODIFIED, dt_buf.as_pwstr())
-        //             .ok()?;
-        //     }
-        // }
 
         let mut stream_receptor: Option<IStream> = None;
         let mut buffer_size: u32 = 0;
@@ -286,18 +286,17 @@ impl Device {
 
         let stream = stream_receptor.unwrap();
 
-        Ok(ResourceWriter::new(stream, buffer_size))
+        Ok(Box::new(ResourceWriter::new(stream, buffer_size)))
     }
     // 创建文件夹,parent为父文件夹对象，name为文件夹名称
-    pub fn create_folder(&self, parent: &ContentObject, name: &str) -> Result<ContentObject, Error> {
+    fn create_folder(&self, parent: &ContentObject, name: &str) -> Result<ContentObject, Box<dyn std::error::Error>> {
         let values: IPortableDeviceValues = unsafe { CoCreateInstance(&PortableDeviceValues, None, CLSCTX_ALL)? };
-        // name 转成 pwstr
-        let mut name_buf:Vec<u16> = name.encode_utf16().collect();
-        name_buf.push(0);
+        let parent_id_buf = to_wide(&parent.id);
+        let name_buf = to_wide(name);
 
         unsafe {
             values
-                .SetStringValue(&WPD_OBJECT_PARENT_ID, parent.id.clone())?;
+                .SetStringValue(&WPD_OBJECT_PARENT_ID, PCWSTR(parent_id_buf.as_ptr()))?;
             values
                 .SetStringValue(&WPD_OBJECT_NAME, PCWSTR(name_buf.as_ptr()))?;
             values
@@ -311,29 +310,76 @@ impl Device {
             self.content
                 .CreateObjectWithPropertiesOnly(&values, &mut object_id)?;
         }
-        let content_object = ContentObject::new(object_id);
+        let content_object = ContentObject::new(&from_pwstr(object_id)?);
 
         Ok(content_object)
     }
 
-    pub fn delete(&self, object: &ContentObject) -> Result<(), Error> {
+    fn delete(&self, object: &ContentObject) -> Result<(), Box<dyn std::error::Error>> {
+        let collection = object_id_collection(object)?;
         unsafe {
-            let mut str = object.id.clone();
-            let collection: IPortableDevicePropVariantCollection = CoCreateInstance(&PortableDevicePropVariantCollection, None, CLSCTX_ALL)?;
-            let mut var: PROPVARIANT = core::mem::zeroed();
-            var.Anonymous.Anonymous.vt = 31; // VT_LPWSTR
-            var.Anonymous.Anonymous.Anonymous.pwszVal = str.0;
-            let propvar = propvar::from_raw(var);
-            collection.Add(&propvar)?;
             self.content.Delete(
                 PORTABLE_DEVICE_DELETE_WITH_RECURSION.0 as u32,
                 &collection,
                 std::ptr::null_mut(),
             )?;
         }
-        println!("delete object: {:?}", object.id);
+        log::trace!("delete object: {:?}", object.id);
+        Ok(())
+    }
+
+    fn rename(&self, object: &ContentObject, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let values: IPortableDeviceValues = unsafe { CoCreateInstance(&PortableDeviceValues, None, CLSCTX_ALL)? };
+        let object_id_buf = to_wide(&object.id);
+        let name_buf = to_wide(name);
+        unsafe {
+            values
+                .SetStringValue(&WPD_OBJECT_NAME, PCWSTR(name_buf.as_ptr()))?;
+            values
+                .SetStringValue(&WPD_OBJECT_ORIGINAL_FILE_NAME, PCWSTR(name_buf.as_ptr()))?;
+            let results = self.properties.SetValues(PCWSTR(object_id_buf.as_ptr()), &values)?;
+            // 有的设备只能修改其中一个属性，都失败时才是错误
+            let name_result = results.GetErrorValue(&WPD_OBJECT_NAME).unwrap_or_else(|err| err.code());
+            let file_name_result = results.GetErrorValue(&WPD_OBJECT_ORIGINAL_FILE_NAME).unwrap_or_else(|err| err.code());
+            if name_result.is_err() && file_name_result.is_err() {
+                return Err(Error::from(file_name_result).into());
+            }
+        }
         Ok(())
     }
+
+    fn move_object(&self, object: &ContentObject, parent: &ContentObject) -> Result<bool, Box<dyn std::error::Error>> {
+        let collection = object_id_collection(object)?;
+        let parent_id_buf = to_wide(&parent.id);
+        let result = unsafe { self.content.Move(&collection, PCWSTR(parent_id_buf.as_ptr()), std::ptr::null_mut()) };
+        match result {
+            Ok(()) => Ok(true),
+            // 设备不支持移动
+            Err(err) if err.code() == E_NOTIMPL || err.code() == ERROR_NOT_SUPPORTED.to_hresult() => Ok(false),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+// 只有一个对象 id 的集合，用于 Delete 和 Move
+fn object_id_collection(object: &ContentObject) -> Result<IPortableDevicePropVariantCollection, Box<dyn std::error::Error>> {
+    unsafe {
+        // PROPVARIANT 释放时会调用 CoTaskMemFree，所以字符串必须用 CoTaskMemAlloc 分配
+        let id_buf = to_wide(&object.id);
+        let id_ptr = CoTaskMemAlloc(id_buf.len() * std::mem::size_of::<u16>()) as *mut u16;
+        if id_ptr.is_null() {
+            return Err("failed to allocate memory.".into());
+        }
+        std::ptr::copy_nonoverlapping(id_buf.as_ptr(), id_ptr, id_buf.len());
+
+        let collection: IPortableDevicePropVariantCollection = CoCreateInstance(&PortableDevicePropVariantCollection, None, CLSCTX_ALL)?;
+        let mut var: PROPVARIANT = core::mem::zeroed();
+        var.Anonymous.Anonymous.vt = 31; // VT_LPWSTR
+        var.Anonymous.Anonymous.Anonymous.pwszVal = id_ptr;
+        let propvar = propvar::from_raw(var);
+        collection.Add(&propvar)?;
+        Ok(collection)
+    }
 }
 
 impl Drop for Device {
@@ -346,25 +392,27 @@ impl Drop for Device {
 }
 
 
-pub struct ContentObjectIterator {
+pub struct EnumObjectIterator {
     enum_object_ids: IEnumPortableDeviceObjectIDs,
     object_ids: Option<Vec<PWSTR>>,
     completed: bool,
 }
 
-impl ContentObjectIterator {
-    fn new(enum_object_ids: IEnumPortableDeviceObjectIDs) -> ContentObjectIterator {
-        ContentObjectIterator {
+impl EnumObjectIterator {
+    fn new(enum_object_ids: IEnumPortableDeviceObjectIDs) -> EnumObjectIterator {
+        EnumObjectIterator {
             enum_object_ids,
             object_ids: None,
             completed: false,
         }
     }
+}
 
-    pub fn next(&mut self) -> Result<Option<ContentObject>, Error> {
+impl ContentObjectIterator for EnumObjectIterator {
+    fn next(&mut self) -> Result<Option<ContentObject>, Box<dyn std::error::Error>> {
         if let Some(object_ids_ref) = self.object_ids.as_mut() {
             if let Some(id) = object_ids_ref.pop() {
-                return Ok(Some(ContentObject::new(id)));
+                return Ok(Some(ContentObject::new(&from_pwstr(id)?)));
             }
         }
         //
@@ -392,7 +440,7 @@ impl ContentObjectIterator {
         let mut object_ids_vec = object_ids
             .iter()
             .take(read as usize)
-            .map(|id| id.clone())
+            .copied()
             .collect::<Vec<PWSTR>>();
         object_ids_vec.reverse(); // for moving item out by pop()
         self.object_ids = Some(object_ids_vec);
@@ -421,14 +469,11 @@ mod tests {
         let manager = Manager::get_portable_device_manager().unwrap();
         let storage_path = path::DeviceStoragePath::from("Redmi K70:内部存储设备:/Pictures").unwrap();
         let option = find_file_or_folder(&manager, &storage_path).unwrap();
-        let (device_info, device, content_object_info) = option.unwrap();
-        println!("device_info: {:?}", device_info);
-        println!("storage_object: {:?}", content_object_info);
+        let (_, device, content_object_info) = option.unwrap();
 
         let folder_name = "New Folder";
         let result = device.create_folder(&content_object_info.content_object, folder_name);
         assert!(result.is_ok());
-        println!("create folder: {:?}", result.unwrap());
     }
 
     #[test]
@@ -439,9 +484,7 @@ mod tests {
         let path = format!("Redmi K70:内部存储设备:/Pictures/{}", folder_name);
         let storage_path = path::DeviceStoragePath::from(path.as_str()).unwrap();
         let option = find_file_or_folder(&manager, &storage_path).unwrap();
-        let (device_info, device, content_object_info) = option.unwrap();
-        println!("device_info: {:?}", device_info);
-        println!("storage_object: {:?}", content_object_info);
+        let (_, device, content_object_info) = option.unwrap();
         let result = device.delete(&content_object_info.content_object);
         assert!(result.is_ok());
     }
@@ -452,9 +495,7 @@ mod tests {
         let manager = Manager::get_portable_device_manager().unwrap();
         let storage_path = path::DeviceStoragePath::from("Redmi K70:内部存储设备:/Pictures/").unwrap();
         let option = find_file_or_folder(&manager, &storage_path).unwrap();
-        let (device_info, device, content_object_info) = option.unwrap();
-        println!("device_info: {:?}", device_info);
-        println!("storage_object: {:?}", content_object_info);
+        let (_, device, content_object_info) = option.unwrap();
 
         let file_name = "New File.txt";
         let file_size = 1024;
@@ -467,7 +508,6 @@ mod tests {
         let write_result = writer.write(&buffer);
         assert!(write_result.is_ok());
         let commit_result = writer.commit();
-        println!("create file: {:?}", commit_result);
         assert!(commit_result.is_ok());
         device.delete(&commit_result.unwrap()).unwrap();
     }
@@ -1,16 +1,13 @@
-use std::fmt::Debug;
 use windows::core::{Error, PWSTR};
 use windows::Win32::Devices::PortableDevices::{IPortableDeviceManager, PortableDeviceManager};
 use windows::Win32::System::Com::{CoCreateInstance, CLSCTX_ALL};
+use crate::backend::{DeviceInfo, DeviceOperate, PortableDeviceBackend};
+use crate::common::timestamp::TimeZone;
+use crate::wpd::device::{from_pwstr, Device};
 
 pub struct Manager {
     manager: IPortableDeviceManager,
-}
-
-#[derive(Debug)]
-pub struct DeviceInfo {
-    pub id: PWSTR,
-    pub name: String,
+    time_zone: TimeZone,
 }
 
 impl Manager {
@@ -20,7 +17,12 @@ impl Manager {
             None,
             CLSCTX_ALL,
         )?};
-        Ok(Manager { manager })
+        Ok(Manager { manager, time_zone: TimeZone::UTC })
+    }
+
+    /// Sets the time zone of the dates on the devices.
+    pub fn set_time_zone(&mut self, time_zone: TimeZone) {
+        self.time_zone = time_zone;
     }
 
     pub fn get_device_iterator<'a>(&'a self) -> Result<DeviceInfoIterator<'a>, Error> {
@@ -65,7 +67,7 @@ impl<'a> DeviceInfoIterator<'a> {
         }
     }
 
-    pub fn next(&mut self) -> Result<Option<DeviceInfo>, Error> {
+    pub fn next(&mut self) -> Result<Option<DeviceInfo>, Box<dyn std::error::Error>> {
         let device_id = match self.device_ids.pop() {
             Some(id) => id,
             None => return Ok(None),
@@ -95,15 +97,31 @@ impl<'a> DeviceInfoIterator<'a> {
                 )
                 .ok();
             name_buf.set_len(name_buf_len as usize);
-            name = String::from_utf16_lossy(&name_buf);
+            name = String::from_utf16_lossy(&name_buf).trim_end_matches('\0').to_string();
         }
 
 
 
         Ok(Some(DeviceInfo {
-            id: device_id,
+            id: from_pwstr(device_id)?,
             name,
         }))
     }
 }
 
+impl PortableDeviceBackend for Manager {
+    fn list_devices(&self) -> Result<Vec<DeviceInfo>, Box<dyn std::error::Error>> {
+        let mut devices = Vec::<DeviceInfo>::new();
+        let mut iter = self.get_device_iterator()?;
+        while let Some(device_info) = iter.next()? {
+            devices.push(device_info);
+        }
+        Ok(devices)
+    }
+
+    fn open_device(&self, info: &DeviceInfo) -> Result<Box<dyn DeviceOperate>, Box<dyn std::error::Error>> {
+        let mut device = Device::open(info)?;
+        device.set_time_zone(self.time_zone);
+        Ok(Box::new(device))
+    }
+}
@@ -1,61 +1,46 @@
-use windows::core::{Error, Interface};
+use windows::core::Interface;
 use windows::Win32::Devices::PortableDevices::IPortableDeviceDataStream;
 use windows::Win32::System::Com::{IStream, STGC_DEFAULT};
+use crate::backend::{ContentObject, FileWriter};
 use crate::common::file_reader::FileReader;
-use super::{device::ContentObject};
+use super::device::from_pwstr;
 
 // wpd 文件数据流读取器
 
 pub struct ResourceReader {
     stream: IStream,
-    buffer: Vec<u8>,
+    buff_size: u32,
 }
 
-impl FileReader for ResourceReader {
-    fn buffer_size(&self) -> u32 {
-        self.buffer.len() as u32
+impl ResourceReader {
+    pub fn new(stream: IStream, buff_size: u32) -> ResourceReader {
+        ResourceReader { stream, buff_size }
     }
 
-    fn seek(&mut self, max_size: u32) -> Result<Option<&[u8]>, Box<dyn std::error::Error>> {
-        Ok(self.next(max_size)?)
+    pub fn get_optimized_buffer_size(&self) -> u32 {
+        self.buff_size
     }
 }
 
-impl ResourceReader {
-    pub fn new(stream: IStream, buff_size: u32) -> ResourceReader {
-        let mut buffer = Vec::<u8>::with_capacity(buff_size as usize);
-        buffer.resize(buff_size as usize, 0);
-        ResourceReader { stream, buffer }
-    }
-
-    pub fn next(&mut self, max_size: u32) -> Result<Option<&[u8]>, Error> {
-        let available_buffer_size = std::cmp::min(self.buffer.len() as u32, max_size);
-        let read: Option<*mut u32> = None;
+impl std::io::Read for ResourceReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // IStream::Read 一次最多读取 u32::MAX 字节
+        let len = std::cmp::min(buf.len(), u32::MAX as usize) as u32;
+        let mut read: u32 = 0;
+        let pcbread = &mut read as *mut u32;
         unsafe {
-            self.stream.Read(
-                self.buffer.as_mut_ptr().cast(),
-                available_buffer_size,
-                read,
-            )
-                .ok()?;
-        }
-        match read {
-            None => {
-                return Ok(None);
-            }
-            Some(v) => unsafe {
-                let read = *v;
-                if read == 0 {
-                    Ok(None)
-                } else {
-                    Ok(Some(&self.buffer[..read as usize]))
-                }
-            }
+            self.stream
+                .Read(buf.as_mut_ptr().cast(), len, Some(pcbread))
+                .ok()
+                .map_err(std::io::Error::other)?;
         }
+        Ok(read as usize)
     }
+}
 
-    pub fn get_optimized_buffer_size(&self) -> u32 {
-        self.buffer.len() as u32
+impl FileReader for ResourceReader {
+    fn buffer_size(&self) -> u32 {
+        self.buff_size
     }
 }
 
@@ -76,12 +61,14 @@ impl ResourceWriter {
             committed: false,
         }
     }
+}
 
-    pub fn get_buffer_size(&self) -> u32 {
+impl FileWriter for ResourceWriter {
+    fn get_buffer_size(&self) -> u32 {
         self.buff_size
     }
 
-    pub fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+    fn write(&mut self, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
         let data_len = data.len() as u32;
         let mut data_offset: u32 = 0;
         while data_offset < data_len {
@@ -97,13 +84,12 @@ impl ResourceWriter {
                     )
                     .ok()?;
             }
-            ;
             data_offset += bytes_written;
         }
         Ok(())
     }
 
-    pub fn commit(&mut self) -> Result<ContentObject, Error> {
+    fn commit(&mut self) -> Result<ContentObject, Box<dyn std::error::Error>> {
         self.committed = true;
         unsafe {
             self.stream.Commit(STGC_DEFAULT)?;
@@ -113,6 +99,6 @@ impl ResourceWriter {
 
         let object_id = unsafe{data_stream.GetObjectID()?};
 
-        Ok(ContentObject::new(object_id))
+        Ok(ContentObject::new(&from_pwstr(object_id)?))
     }
 }